@@ -0,0 +1,78 @@
+//! Throughput comparison: [`NaiveDecomposer`] versus [`ApproxDecomposer`]
+//! on a random palette.
+//!
+//! ```text
+//! cargo run --release --example approx_speed -- [COLOURS]
+//! ```
+//!
+//! Both decompose the same random convex mixes of a palette of `COLOURS`
+//! random colours (20 by default), so every query is inside the gamut.
+//! The largest difference between the colours their weights reproduce is
+//! printed alongside the timings; the approximation only changes which
+//! tetrahedron a colour is mixed from, not the colour.
+
+use epd_dither::decompose::Decomposer;
+use epd_dither::decompose::approx::ApproxDecomposer;
+use epd_dither::decompose::naive::NaiveDecomposer;
+use epd_dither::noise::Pcg32;
+use nalgebra::Point3;
+use std::time::Instant;
+
+const QUERIES: usize = 2000;
+
+fn reconstruct(points: &[Point3<f32>], weights: &[f32]) -> Point3<f32> {
+    points
+        .iter()
+        .zip(weights)
+        .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * *w)
+}
+
+/// Decompose every query, returning the weights and the seconds taken.
+fn time(
+    decomposer: &dyn Decomposer<f32, Input = Point3<f32>>,
+    queries: &[Point3<f32>],
+) -> (Vec<f32>, f64) {
+    let size = decomposer.palette_size();
+    let mut weights = vec![0.0; size * queries.len()];
+    let start = Instant::now();
+    for (query, out) in queries.iter().zip(weights.chunks_exact_mut(size)) {
+        decomposer.decompose_into(query, out);
+    }
+    (weights, start.elapsed().as_secs_f64())
+}
+
+fn main() {
+    let colours: usize = std::env::args()
+        .nth(1)
+        .map_or(20, |arg| arg.parse().expect("COLOURS must be a number"));
+    let mut rng = Pcg32::new(20, 0);
+    let points: Vec<Point3<f32>> = (0..colours)
+        .map(|_| Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()))
+        .collect();
+    let queries: Vec<Point3<f32>> = (0..QUERIES)
+        .map(|_| {
+            let mix: Vec<f32> = (0..colours).map(|_| rng.next_f32().powi(4)).collect();
+            let sum: f32 = mix.iter().sum();
+            let weights: Vec<f32> = mix.iter().map(|w| w / sum).collect();
+            reconstruct(&points, &weights)
+        })
+        .collect();
+
+    let naive = NaiveDecomposer::new(&points).expect("palette is not degenerate");
+    let approx = ApproxDecomposer::new(&points).expect("palette is not degenerate");
+    let (exact_weights, exact_time) = time(&naive, &queries);
+    let (approx_weights, approx_time) = time(&approx, &queries);
+    let max_error = exact_weights
+        .chunks_exact(colours)
+        .zip(approx_weights.chunks_exact(colours))
+        .map(|(e, a)| (reconstruct(&points, a) - reconstruct(&points, e)).norm())
+        .fold(0.0, f32::max);
+
+    println!("{QUERIES} queries against {colours} colours:");
+    println!("  naive:  {:8.2} ms", exact_time * 1e3);
+    println!("  approx: {:8.2} ms", approx_time * 1e3);
+    println!(
+        "  speedup {:.1}x, largest colour difference {max_error:.2e}",
+        exact_time / approx_time
+    );
+}
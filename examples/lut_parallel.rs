@@ -0,0 +1,110 @@
+//! Throughput comparison: serial ordered dithering versus
+//! [`dither_image_lut_parallel`] over a 33³ lookup table.
+//!
+//! ```text
+//! cargo run --release --features rayon --example lut_parallel -- \
+//!     [IMAGE] [--strategy STRATEGY] [--threads N] [--chunk-rows M]
+//! ```
+//!
+//! Both use interleaved gradient noise and no error diffusion, and
+//! decompose with `--strategy` (octahedron-closest by default; see
+//! [`DecomposeStrategy::LONG_HELP`] for which suit ordered dithering).
+//! The LUT build is timed separately since it is paid once per palette.
+//! `--threads` runs the LUT path on a pool of that many threads instead of
+//! the rayon global pool, and `--chunk-rows` sets the rows per task; see
+//! [`ParallelOptions`]. The serial octahedron run, like any error
+//! diffusion, ignores both.
+//!
+//! Only the LUT path scales with the number of cores.
+
+use clap::Parser;
+use epd_dither::decompose::Decomposer;
+use epd_dither::decompose::lut::{DEFAULT_LUT_RESOLUTION, LutDecomposer};
+use epd_dither::dither::diffusion_matrix::NO_DIFFUSE;
+use epd_dither::dither::parallel::{ParallelOptions, dither_image_lut_parallel_with};
+use epd_dither::dither::{DecomposeStrategy, ImageCombinedRW};
+use epd_dither::image::palette_image::{PaletteImage, VerifiedPalette};
+use epd_dither::noise::{NoiseSource, interleaved_gradient_noise};
+use epd_dither::palette::SPECTRA6;
+use epd_dither::registry::{FactoryOptions, decompose_ditherer, decomposer_for};
+use image::Rgb;
+use nalgebra::Point3;
+use std::time::Instant;
+
+#[derive(Parser)]
+struct Args {
+    #[arg(default_value = "docs/lena_original.png")]
+    image: String,
+    #[arg(long, value_name = "STRATEGY", long_help = DecomposeStrategy::LONG_HELP, default_value = "octahedron-closest")]
+    strategy: DecomposeStrategy,
+    /// Threads for the LUT path; defaults to the rayon global pool.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    threads: Option<u16>,
+    /// Rows per parallel task.
+    #[arg(long, value_name = "M", value_parser = clap::value_parser!(u16).range(1..))]
+    chunk_rows: Option<u16>,
+}
+
+fn main() {
+    let args = Args::parse();
+    let input = image::open(&args.image).unwrap().into_rgb32f();
+    let (width, height) = input.dimensions();
+    let palette: Vec<Rgb<u8>> = SPECTRA6.iter().map(|&c| Rgb(c)).collect();
+
+    let start = Instant::now();
+    let ditherer = decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
+        args.strategy,
+        NoiseSource::InterleavedGradient,
+        &palette,
+        NO_DIFFUSE,
+    )
+    .unwrap();
+    let writer = PaletteImage::new(
+        width,
+        height,
+        VerifiedPalette::new(palette.clone()).unwrap(),
+    );
+    let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+    ditherer.dyn_dither_into(&mut inout);
+    println!("serial {}: {:?}", args.strategy, start.elapsed());
+
+    let start = Instant::now();
+    let decomposer =
+        decomposer_for::<Rgb<f32>, Rgb<u8>>(args.strategy, &palette, &FactoryOptions::default())
+            .unwrap();
+    let lut = LutDecomposer::new(&PointInput(decomposer), DEFAULT_LUT_RESOLUTION).unwrap();
+    println!(
+        "LUT build ({DEFAULT_LUT_RESOLUTION}³): {:?}",
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let noise = |x, y| interleaved_gradient_noise(x as f32, y as f32);
+    let options = ParallelOptions {
+        threads: args.threads.map(usize::from),
+        chunk_rows: args.chunk_rows.map(usize::from),
+    };
+    let indices = dither_image_lut_parallel_with(&lut, noise, &inout.reader, options).unwrap();
+    println!(
+        "parallel LUT: {:?} ({} threads)",
+        start.elapsed(),
+        options.threads.unwrap_or_else(rayon::current_num_threads)
+    );
+    assert_eq!(indices.len(), (width * height) as usize);
+}
+
+/// Feeds the LUT's grid points to a decomposer taking RGB pixels.
+struct PointInput<D>(D);
+
+impl<D: Decomposer<f32, Input = Rgb<f32>>> Decomposer<f32> for PointInput<D> {
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.0.palette_size()
+    }
+
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        self.0
+            .decompose_into(&Rgb([input.x, input.y, input.z]), out)
+    }
+}
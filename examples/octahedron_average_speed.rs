@@ -0,0 +1,106 @@
+//! Throughput of the octahedron `average` strategy against averaging all
+//! three axes' projections for every input.
+//!
+//! ```text
+//! cargo run --release --example octahedron_average_speed
+//! ```
+//!
+//! Near a hull face the three axes decompose a colour alike, so `average`
+//! projects onto the first axis alone there. The full average is built
+//! from the `axis:0`..`axis:2` decomposers. Two query sets are timed,
+//! both inside the Spectra 6 hull, where `average` does average: random
+//! colours, and saturated mixes just inside the faces, the colours the
+//! shortcut targets. The largest weight difference is printed alongside
+//! the timings.
+
+use epd_dither::decompose::octahedron::{OctahedronDecomposer, OctahedronDecomposerAxisStrategy};
+use epd_dither::decompose::{Decomposer, DecomposerInputColor};
+use epd_dither::noise::Pcg32;
+use epd_dither::palette::SPECTRA6;
+use nalgebra::Point3;
+use std::time::Instant;
+
+const QUERIES: usize = 200_000;
+
+/// Decompose every query with `decompose`, returning the weights and the
+/// seconds taken.
+fn time(queries: &[Point3<f32>], decompose: impl Fn(&Point3<f32>, &mut [f32])) -> (Vec<f32>, f64) {
+    let mut weights = vec![0.0; 6 * queries.len()];
+    let start = Instant::now();
+    for (query, out) in queries.iter().zip(weights.chunks_exact_mut(6)) {
+        decompose(query, out);
+    }
+    (weights, start.elapsed().as_secs_f64())
+}
+
+fn compare(name: &str, colours: &[Point3<f32>; 6], queries: &[Point3<f32>]) {
+    let decomposer = |strategy| {
+        OctahedronDecomposer::new(colours)
+            .expect("Spectra 6 is an octahedron")
+            .with_strategy(strategy)
+    };
+    let average = decomposer(OctahedronDecomposerAxisStrategy::Average);
+    let axes = [0, 1, 2].map(|axis| decomposer(OctahedronDecomposerAxisStrategy::Axis(axis)));
+    let (fast, fast_time) = time(queries, |query, out| average.decompose_into(query, out));
+    let (full, full_time) = time(queries, |query, out| {
+        let mut sum = [0.0; 6];
+        let mut weights = [0.0; 6];
+        for axis in &axes {
+            axis.decompose_into(query, &mut weights);
+            for (s, w) in sum.iter_mut().zip(weights) {
+                *s += w / 3.0;
+            }
+        }
+        out.copy_from_slice(&sum);
+    });
+    let max_difference = fast
+        .iter()
+        .zip(&full)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f32::max);
+
+    println!("{} {name} queries:", queries.len());
+    println!("  all three axes: {:8.2} ms", full_time * 1e3);
+    println!("  average:        {:8.2} ms", fast_time * 1e3);
+    println!(
+        "  speedup {:.1}x, largest weight difference {max_difference:.2e}",
+        full_time / fast_time
+    );
+}
+
+fn main() {
+    let colours = SPECTRA6.map(|c| c.to_point());
+    let centroid = Point3::from(
+        colours
+            .iter()
+            .map(|c| c.coords)
+            .sum::<nalgebra::Vector3<f32>>()
+            / 6.0,
+    );
+    let hull = OctahedronDecomposer::new(&colours).expect("Spectra 6 is an octahedron");
+    let mut rng = Pcg32::new(200, 0);
+    let inside: Vec<Point3<f32>> =
+        core::iter::repeat_with(|| Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()))
+            .filter(|query| hull.confidence(query) > 0.0)
+            .take(QUERIES)
+            .collect();
+    // Random mixes of a face's three colours, pulled slightly inside.
+    let faces = [(0, 2, 4), (1, 3, 5), (0, 3, 4), (1, 2, 5)];
+    let near_faces: Vec<Point3<f32>> = (0..QUERIES)
+        .map(|i| {
+            let (a, b, c) = faces[i % faces.len()];
+            let (u, v) = (rng.next_f32(), rng.next_f32());
+            let (u, v) = if u + v > 1.0 {
+                (1.0 - u, 1.0 - v)
+            } else {
+                (u, v)
+            };
+            let on_face =
+                colours[a].coords * u + colours[b].coords * v + colours[c].coords * (1.0 - u - v);
+            Point3::from(on_face * 0.997 + centroid.coords * 0.003)
+        })
+        .collect();
+
+    compare("in-gamut", &colours, &inside);
+    compare("near-face", &colours, &near_faces);
+}
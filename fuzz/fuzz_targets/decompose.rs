@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// See `epd_dither::fuzz` for the input layout and what is checked.
+fuzz_target!(|data: &[u8]| {
+    if let Err(failure) = epd_dither::fuzz::check(data) {
+        panic!("{failure}");
+    }
+});
@@ -1,3 +1,4 @@
+use crate::bytes::{ByteReader, ByteWriter};
 use nalgebra::base::{Scalar, Vector2, Vector3};
 use nalgebra::geometry::Point3;
 use nalgebra::{ClosedAddAssign, ClosedDivAssign, ClosedMulAssign, ClosedSubAssign, ComplexField};
@@ -23,6 +24,9 @@ impl<
 > LineProjector<T>
 {
     pub fn new(vertices: [Point3<T>; 2]) -> Option<Self> {
+        if super::find_duplicate(&vertices).is_some() {
+            return None;
+        }
         let [a, b] = vertices;
         let direction = b - &a;
         let origin = a;
@@ -56,20 +60,71 @@ impl<
         &self.origin + (&self.direction * barycentric_coords[1].clone())
     }
 
-    // Returns closest barycentric coordinate on line (first retval)
-    // If it was clipped (e.g. fell outside the two vertices) (second retval)
-    // And optionally, if it has already been calculated
-    // Second retval is None is the projection was on the line,
+    // Returns closest barycentric coordinate on line (first retval).
+    // Second retval is None if the projection was on the line,
     // or Some(distance_squared) if it was clipped to the endpoints.
     // distance_squared is the squared euclidian distance from pt to the clipped point
-    pub fn clipping_project(&self, pt: &Point3<T>) -> (Vector2<T>, bool) {
+    pub fn clipping_project(&self, pt: &Point3<T>) -> (Vector2<T>, Option<T::RealField>) {
         let ret = self.project(pt);
         if ret[0] < zero() {
-            (Vector2::new(zero(), one()), true)
+            let end = &self.origin + &self.direction;
+            (Vector2::new(zero(), one()), Some((pt - end).norm_squared()))
         } else if ret[1] < zero() {
-            (Vector2::new(one(), zero()), true)
+            (
+                Vector2::new(one(), zero()),
+                Some((pt - &self.origin).norm_squared()),
+            )
         } else {
-            (ret, false)
+            (ret, None)
+        }
+    }
+}
+
+impl LineProjector<f32> {
+    pub(crate) const SERIALIZED_LEN: usize = 3 * 3 * 4;
+
+    pub(crate) fn write_bytes(&self, writer: &mut ByteWriter) {
+        writer.f32s(self.origin.coords.as_slice());
+        writer.f32s(self.direction.as_slice());
+        writer.f32s(self.direction_div_length_squared.as_slice());
+    }
+
+    /// Rejects a zero direction, which [`new`](Self::new) never builds.
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let origin = Point3::from(reader.f32s::<3>()?);
+        let direction = Vector3::from(reader.f32s::<3>()?);
+        let direction_div_length_squared = Vector3::from(reader.f32s::<3>()?);
+        if direction.norm_squared() == 0.0 {
+            return None;
         }
+        Some(Self {
+            origin,
+            direction,
+            direction_div_length_squared,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipped_distance_matches_manual() {
+        let line =
+            LineProjector::new([Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0)]).unwrap();
+        let beyond = Point3::new(3.0, 2.0, 0.0);
+        let (barycentric, distance_sq) = line.clipping_project(&beyond);
+        assert_eq!(barycentric, Vector2::new(0.0, 1.0));
+        let manual = (beyond - line.bary_to_point(&barycentric)).norm_squared();
+        assert_eq!(distance_sq, Some(manual));
+
+        let before = Point3::new(-1.0, 0.0, 1.0);
+        let (barycentric, distance_sq) = line.clipping_project(&before);
+        assert_eq!(barycentric, Vector2::new(1.0, 0.0));
+        assert_eq!(distance_sq, Some(2.0));
+
+        let (_, distance_sq) = line.clipping_project(&Point3::new(0.5, 1.0, 0.0));
+        assert_eq!(distance_sq, None);
     }
 }
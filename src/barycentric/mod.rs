@@ -1,4 +1,106 @@
+//! Barycentric projectors for the simplices (and the octahedron) the
+//! decomposers are built from.
+//!
+//! Containment checks compare barycentric coordinates against
+//! `-epsilon` rather than exactly against zero, so a point that lies on a
+//! face or edge shared by two cells (and picks up a rounding-sized
+//! negative coordinate in one of them) is classified the same way every
+//! time. Coordinates accepted this way are clamped to zero and
+//! renormalised, so callers still see non-negative weights summing to one.
+
 pub mod line;
 pub mod octahedron;
 pub mod tetrahedron;
 pub mod triangle;
+
+use nalgebra::base::allocator::Allocator;
+use nalgebra::base::{DefaultAllocator, Dim, OVector, Scalar};
+use nalgebra::geometry::Point3;
+use nalgebra::{ClosedAddAssign, ClosedDivAssign, ComplexField};
+use num_traits::zero;
+
+/// Default containment tolerance, in barycentric units. Well above `f32`
+/// rounding for colour-space coordinates in `[0, 1]`, and far below any
+/// weight that makes a visible difference.
+pub const DEFAULT_EPSILON: f64 = 1e-5;
+
+pub(crate) fn default_epsilon<T: ComplexField>() -> T {
+    nalgebra::convert(DEFAULT_EPSILON)
+}
+
+/// Distance, in colour-space units, below which two vertices count as the
+/// same colour. Well below the `1/255` step between 8-bit colours.
+pub const DUPLICATE_DISTANCE: f64 = 1e-4;
+
+/// The first pair of indices `[i, j]`, `i < j`, of `points` closer together
+/// than [`DUPLICATE_DISTANCE`]. Projectors and decomposers reject such
+/// vertices: nearly coincident ones make a cell that's degenerate or
+/// numerically meaningless rather than one that's just small.
+pub fn find_duplicate<T: Scalar + ComplexField>(points: &[Point3<T>]) -> Option<[usize; 2]> {
+    let limit: T::RealField = nalgebra::convert(DUPLICATE_DISTANCE * DUPLICATE_DISTANCE);
+    (0..points.len()).find_map(|j| {
+        (0..j)
+            .find(|&i| (&points[j] - &points[i]).norm_squared() < limit)
+            .map(|i| [i, j])
+    })
+}
+
+/// Why a simplex projector couldn't be built; see
+/// [`TriangleProjector::new_checked`](triangle::TriangleProjector::new_checked)
+/// and
+/// [`TetrahedronProjector::new_checked`](tetrahedron::TetrahedronProjector::new_checked).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimplexError {
+    /// Vertices `indices` (positions in the vertex array) are closer
+    /// together than [`DUPLICATE_DISTANCE`].
+    CoincidentVertices { indices: [usize; 2] },
+    /// Every vertex lies on one line.
+    Collinear,
+    /// The vertices span no area (triangle) or volume (tetrahedron) though
+    /// they don't all lie on a line, e.g. four coplanar vertices, or the
+    /// projection can't be inverted, e.g. for non-finite coordinates.
+    Degenerate,
+}
+
+impl core::fmt::Display for SimplexError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::CoincidentVertices { indices: [a, b] } => {
+                write!(f, "vertices {a} and {b} coincide")
+            }
+            Self::Collinear => f.write_str("vertices are collinear"),
+            Self::Degenerate => f.write_str("simplex is degenerate"),
+        }
+    }
+}
+
+impl core::error::Error for SimplexError {}
+
+/// True iff every coordinate is at least `-epsilon`.
+pub fn is_inside<T, D>(barycentric: &OVector<T, D>, epsilon: &T) -> bool
+where
+    T: Scalar + ComplexField + PartialOrd,
+    D: Dim,
+    DefaultAllocator: Allocator<D>,
+{
+    barycentric.iter().all(|c| *c >= -epsilon.clone())
+}
+
+/// Clamp negative coordinates to zero and rescale to sum to one (left as
+/// is if nothing positive remains).
+pub fn clamp_normalize<T, D>(barycentric: &mut OVector<T, D>)
+where
+    T: Scalar + ComplexField + ClosedAddAssign + ClosedDivAssign + PartialOrd,
+    D: Dim,
+    DefaultAllocator: Allocator<D>,
+{
+    for coord in barycentric.iter_mut() {
+        if *coord < zero() {
+            *coord = zero();
+        }
+    }
+    let sum = barycentric.sum();
+    if sum > zero() {
+        *barycentric /= sum;
+    }
+}
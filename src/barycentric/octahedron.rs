@@ -1,6 +1,8 @@
 use crate::barycentric::line::LineProjector;
 use crate::barycentric::tetrahedron::TetrahedronProjector;
 use crate::barycentric::triangle::TriangleProjector;
+use crate::barycentric::{clamp_normalize, default_epsilon, is_inside};
+use crate::bytes::{ByteReader, ByteWriter};
 use nalgebra::base::{Matrix3, Scalar, Vector2, Vector3, Vector4, Vector6};
 use nalgebra::geometry::Point3;
 use nalgebra::{ClosedAddAssign, ClosedDivAssign, ClosedMulAssign, ClosedSubAssign, ComplexField};
@@ -13,18 +15,20 @@ pub struct OctahedronProjector<T: Scalar + ComplexField> {
      * Projector for each of the 4 wedges. Each wedge goes from north to south, subsequently from a
      * to b, b to c, c to d, d to a.
      */
-    wedges: [TetrahedronProjector<T>; 4],
+    pub(crate) wedges: [TetrahedronProjector<T>; 4],
     /*
      * Projector for each of the 8 faces. Each face goes from pole -> a -> b, pole -> b -> c, etc.
      * First 4 faces go from north (first) pole, second 4 from south (second) pole.
      */
-    faces: [TriangleProjector<T>; 8],
+    pub(crate) faces: [TriangleProjector<T>; 8],
     /*
      * Projector for each of the edges from from or to a pole. First 4 go from north (first) pole to
      * each equatorial vertex, second 4 go from south (second) pole to each equatorial vertex, last
      * 4 go between the equatorial vertices (e.g. a->b, b->c, c->d, d->a).
      */
-    edges: [LineProjector<T>; 12],
+    pub(crate) edges: [LineProjector<T>; 12],
+    // Containment tolerance, see [`crate::barycentric`].
+    pub(crate) epsilon: T,
 }
 
 impl<T> OctahedronProjector<T>
@@ -44,6 +48,9 @@ where
          * Vertex input ordering should be the two opposing poles first, then the other vertices in
          * cyclical order
          */
+        if super::find_duplicate(&vertices).is_some() {
+            return None;
+        }
         let wedges: [TetrahedronProjector<T>; 4] =
             crate::array_util::opt_array_transpose(core::array::from_fn(|i| {
                 TetrahedronProjector::new([
@@ -86,9 +93,17 @@ where
             wedges,
             faces,
             edges,
+            epsilon: default_epsilon(),
         })
     }
 
+    /// Set the containment tolerance (default
+    /// [`DEFAULT_EPSILON`](crate::barycentric::DEFAULT_EPSILON)).
+    pub fn with_epsilon(mut self, epsilon: T) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
     fn wedge_barycentric_local_to_global(index: usize, local: Vector4<T>) -> Vector6<T> {
         // Wedge from north (0), south (1), a (2 + index), b (2 + ((index+1)%4)
         let [north, south, a, b] = local.into();
@@ -128,16 +143,37 @@ where
         ret
     }
 
+    /// Project `pt` onto the octahedron, returning barycentric weights over
+    /// the six vertices (in the order given to [`new`](Self::new)) and
+    /// whether `pt` lies inside.
+    ///
+    /// Guarantees:
+    ///
+    /// * The weights are non-negative (up to rounding for inside points)
+    ///   and sum to one, with at most four of them non-zero: those of the
+    ///   wedge, face or edge the point was resolved to.
+    /// * Inside (`true`): the weights reconstruct `pt` exactly. Points on
+    ///   the boundary count as inside, as do points within the containment
+    ///   epsilon (see [`with_epsilon`](Self::with_epsilon)) of a wedge or
+    ///   in the rounding gap between two wedges; for those, negative
+    ///   coordinates are clamped to zero and renormalised. Wedges are
+    ///   tried in a fixed order, so a point on a face shared by two wedges
+    ///   always resolves to the same one.
+    /// * Outside (`false`): the weights describe the closest point on the
+    ///   surface, found on a single face or, failing that, on the closest
+    ///   edge (clipped to its endpoints, so far-away points may snap to a
+    ///   vertex).
     pub fn project(&self, pt: &Point3<T>) -> (Vector6<T>, bool) {
         let mut edges_to_check: [bool; 12] = [false; 12];
         let mut best: Option<(Vector6<T>, T)> = None;
         for (wedge_index, wedge) in self.wedges.iter().enumerate() {
             // Wedge from north (0), south (1), a (2 + wedge_index), b (2 + ((wedge_index+1)%4)
-            let barycentric_local: Vector4<T> = wedge.project(pt);
+            let mut barycentric_local: Vector4<T> = wedge.project(pt);
             let barycentric_local_min = barycentric_local.min();
-            if barycentric_local_min >= zero() {
-                // Point lies in this wedge, so convert to global barycentric coordinates and we're
-                // off to the races!
+            if is_inside(&barycentric_local, &self.epsilon) {
+                // Point lies in this wedge (or on its boundary, within epsilon), so convert to
+                // global barycentric coordinates and we're off to the races!
+                clamp_normalize(&mut barycentric_local);
                 return (
                     Self::wedge_barycentric_local_to_global(wedge_index, barycentric_local),
                     true,
@@ -165,8 +201,9 @@ where
                     let other_pole = 1 - pole;
                     let face_index = (other_pole * 4) + wedge_index;
                     let face = &self.faces[face_index];
-                    let barycentric_local = face.project(pt).0;
-                    if barycentric_local.min() >= zero() {
+                    let mut barycentric_local = face.project(pt).0;
+                    if is_inside(&barycentric_local, &self.epsilon) {
+                        clamp_normalize(&mut barycentric_local);
                         // Point lies outside the octahedron, but projects cleanly onto this face,
                         // meaning the point on this face is the closest to it! (as long as all faces are
                         // convex)
@@ -207,8 +244,9 @@ where
                 });
         let edges_projected = edges_to_check.map(|edge_index| {
             let edge = &self.edges[edge_index];
-            let (barycentric_local, _) = edge.clipping_project(pt);
-            let distance_sq = (edge.bary_to_point(&barycentric_local) - pt).norm_squared();
+            let (barycentric_local, clipped_distance_sq) = edge.clipping_project(pt);
+            let distance_sq = clipped_distance_sq
+                .unwrap_or_else(|| (edge.bary_to_point(&barycentric_local) - pt).norm_squared());
             (edge_index, barycentric_local, distance_sq)
         });
         let closest_edge = edges_projected.reduce(|a, b| if b.2 < a.2 { b } else { a });
@@ -226,17 +264,7 @@ where
             // wedges. Take the barycentric coordinates for the wedge with the highest minimum
             // barycentric coordinate (e.g. closest to being inside), and normalize it.
             let mut best = best.map(|(best, _)| best).unwrap_or(num_traits::zero());
-            // Set all coordinates <0 to 0
-            for coord in best.iter_mut() {
-                if *coord < zero() {
-                    *coord = zero()
-                }
-            }
-            // Normalize such that the sum will be 1 again
-            let sum = best.sum();
-            if sum > zero() {
-                best /= sum;
-            }
+            clamp_normalize(&mut best);
             (best, true)
         }
     }
@@ -343,3 +371,218 @@ where
         }
     }
 }
+
+impl OctahedronProjector<f32> {
+    pub(crate) const SERIALIZED_LEN: usize = 4 * TetrahedronProjector::<f32>::SERIALIZED_LEN
+        + 8 * TriangleProjector::<f32>::SERIALIZED_LEN
+        + 12 * LineProjector::<f32>::SERIALIZED_LEN
+        + 4;
+
+    pub(crate) fn write_bytes(&self, writer: &mut ByteWriter) {
+        self.wedges
+            .iter()
+            .for_each(|wedge| wedge.write_bytes(writer));
+        self.faces.iter().for_each(|face| face.write_bytes(writer));
+        self.edges.iter().for_each(|edge| edge.write_bytes(writer));
+        writer.f32s(&[self.epsilon]);
+    }
+
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let wedges = crate::array_util::opt_array_transpose(core::array::from_fn(|_| {
+            TetrahedronProjector::read_bytes(reader)
+        }))?;
+        let faces = crate::array_util::opt_array_transpose(core::array::from_fn(|_| {
+            TriangleProjector::read_bytes(reader)
+        }))?;
+        let edges = crate::array_util::opt_array_transpose(core::array::from_fn(|_| {
+            LineProjector::read_bytes(reader)
+        }))?;
+        let [epsilon] = reader.f32s::<1>()?;
+        Some(Self {
+            wedges,
+            faces,
+            edges,
+            epsilon,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Regular octahedron: poles on z, equator counter-clockwise from +x.
+    fn projector() -> OctahedronProjector<f32> {
+        OctahedronProjector::new([
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-1.0, 0.0, 0.0),
+            Point3::new(0.0, -1.0, 0.0),
+        ])
+        .unwrap()
+    }
+
+    fn assert_weights(actual: Vector6<f32>, expected: [f32; 6]) {
+        assert!(
+            (actual - Vector6::from(expected)).amax() < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn vertices_are_one_hot() {
+        let projector = projector();
+        let vertices = [
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-1.0, 0.0, 0.0),
+            Point3::new(0.0, -1.0, 0.0),
+        ];
+        for (index, vertex) in vertices.iter().enumerate() {
+            let (weights, inside) = projector.project(vertex);
+            assert!(inside);
+            assert_weights(weights, core::array::from_fn(|i| (i == index) as u8 as f32));
+        }
+    }
+
+    #[test]
+    fn edge_midpoints_split_evenly() {
+        let projector = projector();
+        let (weights, inside) = projector.project(&Point3::new(0.5, 0.0, 0.5));
+        assert!(inside);
+        assert_weights(weights, [0.5, 0.0, 0.5, 0.0, 0.0, 0.0]);
+        let (weights, inside) = projector.project(&Point3::new(0.5, 0.5, 0.0));
+        assert!(inside);
+        assert_weights(weights, [0.0, 0.0, 0.5, 0.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn centroid_is_inside_and_normalized() {
+        let (weights, inside) = projector().project(&Point3::origin());
+        assert!(inside);
+        assert!((weights.sum() - 1.0).abs() < 1e-5);
+        assert!(weights.min() >= 0.0);
+    }
+
+    #[test]
+    fn outside_points_project_to_surface() {
+        let projector = projector();
+        // Straight out from the north/+x/+y face lands on its centre.
+        let (weights, inside) = projector.project(&Point3::new(1.0, 1.0, 1.0));
+        assert!(!inside);
+        assert_weights(weights, [1.0 / 3.0, 0.0, 1.0 / 3.0, 1.0 / 3.0, 0.0, 0.0]);
+        // Far beyond a vertex clips to that vertex.
+        let (weights, inside) = projector.project(&Point3::new(3.0, 0.0, 0.0));
+        assert!(!inside);
+        assert_weights(weights, [0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn shared_wedge_face_is_assigned_stably() {
+        let projector = projector();
+        // On the internal face north-south-(+y), shared by two wedges.
+        let on_face = Point3::new(0.0, 0.3, 0.2);
+        let (weights, inside) = projector.project(&on_face);
+        assert!(inside);
+        assert!(weights.min() >= 0.0);
+        assert_weights(weights, [0.45, 0.25, 0.0, 0.3, 0.0, 0.0]);
+        for dx in [1e-7, -1e-7] {
+            let (nudged, inside) = projector.project(&(on_face + Vector3::new(dx, 0.0, 0.0)));
+            assert!(inside);
+            assert_weights(nudged, [0.45, 0.25, 0.0, 0.3, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn epsilon_accepts_points_just_outside() {
+        let just_outside = Point3::new(1.0 + 1e-6, 0.0, 0.0);
+        let (weights, inside) = projector().project(&just_outside);
+        assert!(inside);
+        assert_weights(weights, [0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        let (_, inside) = projector().with_epsilon(0.0).project(&just_outside);
+        assert!(!inside);
+    }
+
+    /// Spectra 6 inks as RGB points, in palette order K, W, Y, R, B, G.
+    /// That is already projector order: the black/white poles, then the
+    /// equator Y → R → B → G, with complementary inks two apart.
+    fn spectra6() -> [Point3<f32>; 6] {
+        crate::palette::SPECTRA6.map(|c| crate::decompose::DecomposerInputColor::to_point(&c))
+    }
+
+    fn mix(weights: [f32; 6]) -> Point3<f32> {
+        spectra6()
+            .iter()
+            .zip(weights)
+            .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * w)
+    }
+
+    #[test]
+    fn spectra6_opposites_pair_complementary_inks() {
+        let mut pairs = OctahedronProjector::find_opposites(&spectra6())
+            .unwrap()
+            .map(|(a, b)| (a.min(b), a.max(b)));
+        pairs.sort();
+        // K–W, Y–B, R–G.
+        assert_eq!(pairs, [(0, 1), (2, 4), (3, 5)]);
+        for (a, b) in pairs {
+            assert!(OctahedronProjector::are_valid_poles([a, b], &spectra6()));
+        }
+        assert!(!OctahedronProjector::are_valid_poles([0, 2], &spectra6()));
+    }
+
+    #[test]
+    fn spectra6_vertices_are_one_hot() {
+        let projector = OctahedronProjector::new(spectra6()).unwrap();
+        for (index, vertex) in spectra6().iter().enumerate() {
+            let (weights, inside) = projector.project(vertex);
+            assert!(inside);
+            assert_weights(weights, core::array::from_fn(|i| (i == index) as u8 as f32));
+        }
+    }
+
+    #[test]
+    fn spectra6_mixes_recover_their_weights() {
+        let projector = OctahedronProjector::new(spectra6()).unwrap();
+        let third = 1.0 / 3.0;
+        for expected in [
+            // Midpoint of the black/white axis.
+            [0.5, 0.5, 0.0, 0.0, 0.0, 0.0],
+            // Midpoint of the black–red edge.
+            [0.5, 0.0, 0.0, 0.5, 0.0, 0.0],
+            // Centre of the black/yellow/red face.
+            [third, 0.0, third, third, 0.0, 0.0],
+            // Strictly inside the K/W/Y/R wedge.
+            [0.4, 0.2, 0.3, 0.1, 0.0, 0.0],
+            // Strictly inside the K/W/B/G wedge.
+            [0.1, 0.3, 0.0, 0.0, 0.25, 0.35],
+            // On the internal face K/W/R shared by two wedges.
+            [0.2, 0.3, 0.0, 0.5, 0.0, 0.0],
+        ] {
+            let (weights, inside) = projector.project(&mix(expected));
+            assert!(inside, "{expected:?}");
+            assert_weights(weights, expected);
+        }
+    }
+
+    #[test]
+    fn spectra6_outside_point_lands_on_nearest_face() {
+        let projector = OctahedronProjector::new(spectra6()).unwrap();
+        let [k, w, y, r, ..] = spectra6();
+        let third = 1.0 / 3.0;
+        let centre = mix([third, 0.0, third, third, 0.0, 0.0]);
+        // Outward normal of the black/yellow/red face: away from white,
+        // the opposite pole.
+        let mut normal = (y - k).cross(&(r - k)).normalize();
+        if normal.dot(&(w - k)) > 0.0 {
+            normal = -normal;
+        }
+        let (weights, inside) = projector.project(&(centre + normal * 0.05));
+        assert!(!inside);
+        assert_weights(weights, [third, 0.0, third, third, 0.0, 0.0]);
+    }
+}
@@ -1,17 +1,26 @@
+use crate::barycentric::SimplexError;
+use crate::bytes::{ByteReader, ByteWriter};
 use nalgebra::base::{Matrix4, Scalar, Vector3, Vector4};
 use nalgebra::geometry::Point3;
 use nalgebra::{ClosedAddAssign, ClosedDivAssign, ClosedMulAssign, ComplexField};
 use num_traits::identities::{One, Zero};
+use num_traits::zero;
 
 pub struct TetrahedronProjector<T: Scalar> {
-    to_barycentric: Matrix4<T>,
+    pub(crate) to_barycentric: Matrix4<T>,
     from_barycentric: Matrix4<T>,
 }
 
 impl<T: Scalar + ComplexField + ClosedMulAssign + ClosedAddAssign + ClosedDivAssign + Zero + One>
     TetrahedronProjector<T>
 {
+    /// [`new_checked`](Self::new_checked), without the reason.
     pub fn new(vertices: [Point3<T>; 4]) -> Option<Self> {
+        Self::new_checked(vertices).ok()
+    }
+
+    /// Projector onto the tetrahedron `vertices`, or why there is none.
+    pub fn new_checked(vertices: [Point3<T>; 4]) -> Result<Self, SimplexError> {
         // Method used:
         // Create a matrix from barycentric coordinates to [x,y,z,1]
         // of the following form:
@@ -19,10 +28,33 @@ impl<T: Scalar + ComplexField + ClosedMulAssign + ClosedAddAssign + ClosedDivAss
         // [ y1 y2 y3 y4 ]
         // [ z1 z2 z3 z4 ]
         // [ 1  1  1  1  ]
+        if let Some(indices) = super::find_duplicate(&vertices) {
+            return Err(SimplexError::CoincidentVertices { indices });
+        }
         let from_barycentric: Matrix4<T> =
-            Matrix4::from_columns(&vertices.map(|x| x.to_homogeneous()));
-        let to_barycentric: Matrix4<T> = from_barycentric.clone().try_inverse()?;
-        Some(TetrahedronProjector {
+            Matrix4::from_columns(&vertices.each_ref().map(|x| x.to_homogeneous()));
+        let Some(to_barycentric) = from_barycentric.clone().try_inverse() else {
+            // Collinear iff every edge from the first vertex is parallel
+            // to the longest of them.
+            let [a, b, c, d] = &vertices;
+            let edges = [b - a, c - a, d - a];
+            let longest = edges.iter().fold(&edges[0], |longest, edge| {
+                if edge.norm_squared() > longest.norm_squared() {
+                    edge
+                } else {
+                    longest
+                }
+            });
+            let collinear = edges
+                .iter()
+                .all(|edge| edge.cross(longest).try_normalize(zero()).is_none());
+            return Err(if collinear {
+                SimplexError::Collinear
+            } else {
+                SimplexError::Degenerate
+            });
+        };
+        Ok(TetrahedronProjector {
             to_barycentric,
             from_barycentric,
         })
@@ -37,3 +69,64 @@ impl<T: Scalar + ComplexField + ClosedMulAssign + ClosedAddAssign + ClosedDivAss
             .unwrap_or(Point3::from(num_traits::zero::<Vector3<T>>()))
     }
 }
+
+impl TetrahedronProjector<f32> {
+    pub(crate) const SERIALIZED_LEN: usize = 2 * 16 * 4;
+
+    pub(crate) fn write_bytes(&self, writer: &mut ByteWriter) {
+        writer.f32s(self.to_barycentric.as_slice());
+        writer.f32s(self.from_barycentric.as_slice());
+    }
+
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        Some(Self {
+            to_barycentric: Matrix4::from_column_slice(&reader.f32s::<16>()?),
+            from_barycentric: Matrix4::from_column_slice(&reader.f32s::<16>()?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_checked_names_the_degeneracy() {
+        let p = |x, y, z| Point3::new(x, y, z);
+        let origin = p(0.0, 0.0, 0.0);
+        assert!(
+            TetrahedronProjector::new_checked([
+                origin,
+                p(1.0, 0.0, 0.0),
+                p(0.0, 1.0, 0.0),
+                p(0.0, 0.0, 1.0)
+            ])
+            .is_ok()
+        );
+        assert_eq!(
+            TetrahedronProjector::new_checked([origin, p(1.0, 0.0, 0.0), p(0.0, 1.0, 0.0), origin])
+                .err(),
+            Some(SimplexError::CoincidentVertices { indices: [0, 3] })
+        );
+        assert_eq!(
+            TetrahedronProjector::new_checked([
+                origin,
+                p(0.25, 0.25, 0.25),
+                p(0.5, 0.5, 0.5),
+                p(1.0, 1.0, 1.0)
+            ])
+            .err(),
+            Some(SimplexError::Collinear)
+        );
+        assert_eq!(
+            TetrahedronProjector::new_checked([
+                origin,
+                p(1.0, 0.0, 0.0),
+                p(0.0, 1.0, 0.0),
+                p(1.0, 1.0, 0.0)
+            ])
+            .err(),
+            Some(SimplexError::Degenerate)
+        );
+    }
+}
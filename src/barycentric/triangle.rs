@@ -5,12 +5,17 @@ use num_traits::identities::{One, Zero};
 use num_traits::{one, zero};
 
 use crate::barycentric::line::LineProjector;
+use crate::barycentric::{SimplexError, clamp_normalize, default_epsilon, is_inside};
+use crate::bytes::{ByteReader, ByteWriter};
 
 pub struct TriangleProjector<T: Scalar + ComplexField> {
-    v1: Point3<T>,
+    pub(crate) v1: Point3<T>,
     // Matrix to project from a point (as Vector3) to t (distance to plane), u, and v. w can be
     // calculated through w = 1 - u - v
-    project_matrix: Matrix3<T>,
+    pub(crate) project_matrix: Matrix3<T>,
+    // v2 - v1 and v3 - v1, to map barycentric coordinates back to a point.
+    v1_to_v2: Vector3<T>,
+    v1_to_v3: Vector3<T>,
 }
 
 impl<T> TriangleProjector<T>
@@ -25,19 +30,31 @@ where
         + One
         + PartialOrd,
 {
+    /// [`new_checked`](Self::new_checked), without the reason.
     pub fn new(vertices: [Point3<T>; 3]) -> Option<Self> {
+        Self::new_checked(vertices).ok()
+    }
+
+    /// Projector onto the triangle `vertices`, or why there is none.
+    pub fn new_checked(vertices: [Point3<T>; 3]) -> Result<Self, SimplexError> {
         // Method used:
         // Moeller-Trumbore intersection algorithm
         // https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
 
         // Triangle plane defined as
         // P = w*v1 + u*v2 + v * v3
+        if let Some(indices) = super::find_duplicate(&vertices) {
+            return Err(SimplexError::CoincidentVertices { indices });
+        }
         let [v1, v2, v3] = vertices;
 
         let v1_to_v2: Vector3<T> = v2 - &v1;
         let v1_to_v3: Vector3<T> = v3 - &v1;
         let normal: Vector3<T> = v1_to_v2.cross(&v1_to_v3);
-        let normal: Vector3<T> = normal.try_normalize(zero())?; // Normalize (e.g. ensure length is 1)
+        // Normalize (e.g. ensure length is 1)
+        let normal: Vector3<T> = normal
+            .try_normalize(zero())
+            .ok_or(SimplexError::Collinear)?;
         // normal, such that the t component is
         // equal to the distance to the plane.
         let neg_normal: Vector3<T> = zero::<Vector3<T>>() - normal;
@@ -49,9 +66,14 @@ where
         premul.set_column(2, &v1_to_v3);
 
         // Matrix such that [t,u,v] = (P - v1) * project_matrix
-        let project_matrix: Matrix3<T> = premul.try_inverse()?;
+        let project_matrix: Matrix3<T> = premul.try_inverse().ok_or(SimplexError::Degenerate)?;
 
-        Some(TriangleProjector { v1, project_matrix })
+        Ok(TriangleProjector {
+            v1,
+            project_matrix,
+            v1_to_v2,
+            v1_to_v3,
+        })
     }
 
     /**
@@ -68,12 +90,46 @@ where
         // P = w*v1 + u*v2 + v * v3
         (Vector3::new(w, u, v), t)
     }
+
+    /// Point in the triangle's plane with barycentric coordinates
+    /// `(w, u, v)` as returned by [`project`](Self::project); `w` is taken
+    /// to be `1 - u - v`.
+    pub fn bary_to_point(&self, barycentric_coords: &Vector3<T>) -> Point3<T> {
+        &self.v1
+            + &self.v1_to_v2 * barycentric_coords[1].clone()
+            + &self.v1_to_v3 * barycentric_coords[2].clone()
+    }
+}
+
+impl TriangleProjector<f32> {
+    pub(crate) const SERIALIZED_LEN: usize = (3 + 9) * 4;
+
+    pub(crate) fn write_bytes(&self, writer: &mut ByteWriter) {
+        writer.f32s(self.v1.coords.as_slice());
+        writer.f32s(self.project_matrix.as_slice());
+    }
+
+    /// The edges aren't stored; they are the last two columns of the
+    /// inverse of the projection matrix.
+    pub(crate) fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let v1 = Point3::from(reader.f32s::<3>()?);
+        let project_matrix = Matrix3::from_column_slice(&reader.f32s::<9>()?);
+        let premul = project_matrix.try_inverse()?;
+        Some(Self {
+            v1,
+            project_matrix,
+            v1_to_v2: premul.column(1).into_owned(),
+            v1_to_v3: premul.column(2).into_owned(),
+        })
+    }
 }
 
 pub struct ClippingTriangleProjector<T: Scalar + ComplexField> {
     vertices: Matrix3<T>, // Each column is a vertex, such that vertices * barycentric == point
     lines: [LineProjector<T>; 3], // Line x is the line from vertex[(x+1)%3] to vertices[(x+2)%3]
     normal_project: TriangleProjector<T>,
+    // Containment tolerance, see [`crate::barycentric`].
+    epsilon: T,
 }
 impl<T> ClippingTriangleProjector<T>
 where
@@ -98,9 +154,17 @@ where
             vertices,
             lines,
             normal_project,
+            epsilon: default_epsilon(),
         })
     }
 
+    /// Set the containment tolerance (default
+    /// [`DEFAULT_EPSILON`](crate::barycentric::DEFAULT_EPSILON)).
+    pub fn with_epsilon(mut self, epsilon: T) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
     pub fn project(&self, pt: &Point3<T>) -> (Vector3<T>, T) {
         self.normal_project.project(pt)
     }
@@ -112,8 +176,10 @@ where
     // Returns barycentric coordinates, if it was clipped, and (if already calculated) distance^2
     pub fn clipping_project(&self, pt: &Point3<T>) -> (Vector3<T>, bool, Option<T::RealField>) {
         let barycentric: Vector3<T> = self.project(pt).0;
-        if barycentric.min() >= zero() {
+        if is_inside(&barycentric, &self.epsilon) {
             // Inside the triangle, no need to clip, hurrah!
+            let mut barycentric = barycentric;
+            clamp_normalize(&mut barycentric);
             return (barycentric, false, None);
         }
         let best_barycentric: Vector3<T> = barycentric
@@ -128,28 +194,50 @@ where
                 // If the barycentric coordinate for a point is negative,
                 // this means the point is behind the opposing line
                 // Find the closest point on that line.
-                let (line_barycentric, line_clipped) = self.lines[index].clipping_project(pt);
+                let (line_barycentric, line_distance_sq) = self.lines[index].clipping_project(pt);
                 let mut candidate_barycentric: Vector3<T> = zero();
                 candidate_barycentric[(index + 1) % 3] = line_barycentric[0].clone();
                 candidate_barycentric[(index + 2) % 3] = line_barycentric[1].clone();
-                if !line_clipped {
-                    // The point projected on the line fell cleanly between the endpoints,
-                    // which indicates that this is indeed the closest point on the triangle.
-                    // We can return it right away, no need to keep looking.
-                    return (candidate_barycentric, true, None);
-                } else {
+                if let Some(candidate_distance_sq) = line_distance_sq {
                     // The point projected on the line fell outside the line, so was clipped to one
                     // of the endpoints. This doesn't garantuee that this is the best point, so
                     // save it and keep looking
-                    let candidate_distance_sq =
-                        (self.bary_to_point(&candidate_barycentric) - pt).norm_squared();
                     if candidate_distance_sq < best_distance_sq {
                         best_distance_sq = candidate_distance_sq;
                         best_barycentric = candidate_barycentric;
                     }
+                } else {
+                    // The point projected on the line fell cleanly between the endpoints,
+                    // which indicates that this is indeed the closest point on the triangle.
+                    // We can return it right away, no need to keep looking.
+                    return (candidate_barycentric, true, None);
                 }
             }
         }
         (best_barycentric, true, Some(best_distance_sq))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_checked_names_the_degeneracy() {
+        let p = |x, y, z| Point3::new(x, y, z);
+        assert!(
+            TriangleProjector::new_checked([p(0.0, 0.0, 0.0), p(1.0, 0.0, 0.0), p(0.0, 1.0, 0.0)])
+                .is_ok()
+        );
+        assert_eq!(
+            TriangleProjector::new_checked([p(0.0, 0.0, 0.0), p(1.0, 0.0, 0.0), p(1.0, 0.0, 0.0)])
+                .err(),
+            Some(SimplexError::CoincidentVertices { indices: [1, 2] })
+        );
+        assert_eq!(
+            TriangleProjector::new_checked([p(0.0, 0.0, 0.0), p(0.5, 0.5, 0.0), p(1.0, 1.0, 0.0)])
+                .err(),
+            Some(SimplexError::Collinear)
+        );
+    }
+}
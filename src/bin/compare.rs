@@ -0,0 +1,48 @@
+use clap::Parser;
+use epd_dither::metrics::{pixel_diff_count, psnr, ssim, worm_score};
+use image::RgbImage;
+
+/// Compare two dither outputs of the same original: PSNR and SSIM of each
+/// against the original, each one's worm score, and how many pixels differ
+/// between them.
+#[derive(Parser)]
+#[command(name = "compare")]
+struct Args {
+    /// Undithered source image.
+    #[arg()]
+    original: String,
+    #[arg()]
+    first: String,
+    #[arg()]
+    second: String,
+}
+
+fn open_rgb(path: &str) -> Result<RgbImage, String> {
+    image::open(path)
+        .map(|image| image.to_rgb8())
+        .map_err(|e| format!("opening `{path}`: {e}"))
+}
+
+fn main() -> Result<(), String> {
+    let args = Args::parse();
+    let original = open_rgb(&args.original)?;
+    let first = open_rgb(&args.first)?;
+    let second = open_rgb(&args.second)?;
+    for (path, image) in [(&args.first, &first), (&args.second, &second)] {
+        let (Some(psnr), Some(ssim)) = (psnr(&original, image), ssim(&original, image)) else {
+            return Err(format!("`{path}` is not the size of `{}`", args.original));
+        };
+        println!(
+            "{path}: PSNR {psnr:.2} dB, SSIM {ssim:.4}, worm score {:.4}",
+            worm_score(image)
+        );
+    }
+    let differing = pixel_diff_count(&first, &second)
+        .ok_or_else(|| format!("`{}` and `{}` differ in size", args.first, args.second))?;
+    let total = first.width() as usize * first.height() as usize;
+    println!(
+        "Differing pixels: {differing} of {total} ({:.2}%)",
+        differing as f64 * 100.0 / total.max(1) as f64
+    );
+    Ok(())
+}
@@ -1,47 +1,1309 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use epd_dither::Palette;
-use epd_dither::dither::diffusion_matrix::DiffuseMethod;
-use epd_dither::dither::{DecomposeStrategy, DynDitherer, ImageCombinedRW};
-use epd_dither::registry::decompose_ditherer;
+use epd_dither::colorspace::{
+    AlphaHandling, AlphaMode, HsvAdjustment, ToneMap, black_point_compensate, linear_to_srgb,
+    srgb_to_linear, tonemap,
+};
+use epd_dither::config::{
+    CONFIG_PNG_KEYWORD, DiffusionCoefficients, DiffusionSetting, DitherConfig,
+};
+use epd_dither::decompose::bias::InkBias;
+use epd_dither::decompose::naive::NaiveDecomposerStrategy;
+use epd_dither::decompose::octahedron::OctahedronDecomposerAxisStrategy;
+use epd_dither::decompose::subtractive::MixingModel;
+use epd_dither::decompose::{DecomposerInputColor, assert_valid};
+use epd_dither::dither::barrier::DiffuseMask;
+use epd_dither::dither::checkpoint::Checkpoint;
+use epd_dither::dither::density::{DEFAULT_DENSITY_RADIUS, DensityLimitSpec, limit_density};
+use epd_dither::dither::diffuse::{EdgeMode, diffuse_dither_with_edges};
+use epd_dither::dither::diffusion_matrix::{
+    AdaptiveDiffusion, DEFAULT_ADAPTIVE_THRESHOLD, DEFAULT_ADAPTIVE_TILE, DiffuseMethod,
+    DiffusionMatrix, ScanOrder,
+};
+use epd_dither::dither::mask::InkMask;
+use epd_dither::dither::previous::{DEFAULT_PREVIOUS_TOLERANCE, PreviousFrame};
+use epd_dither::dither::rgb::ErrorSpace;
+use epd_dither::dither::subpixel::{SubpixelLayout, SubpixelStrategy, SubpixelWriter};
+use epd_dither::dither::tiles::Region;
+use epd_dither::dither::usage::palette_usage;
+use epd_dither::dither::{
+    DecomposeStrategy, DynResumableDitherer, ImageCombinedRW, ImageReader, ImageSize, ImageWriter,
+};
+use epd_dither::image::adapter::DynamicImageIo;
+use epd_dither::image::axes::axis_map;
+use epd_dither::image::brightness::{
+    DEFAULT_BRIGHTNESS_ITERATIONS, match_brightness, mean_luma, mean_palette_luma,
+};
+use epd_dither::image::palette_image::{OutputFormat, PaletteImage, PngFormat, VerifiedPalette};
+use epd_dither::image::passes::multi_pass_until;
+use epd_dither::image::sidecar::Sidecar;
+use epd_dither::image::weights::encode_weights_tiff;
+use epd_dither::metrics::worm_score;
 use epd_dither::noise::NoiseSource;
-use epd_dither::image::palette_image::{PaletteImage, VerifiedPalette};
-use image::Rgb;
+use epd_dither::palette::{analyze, index_of, nearest_index, parse_hex_color, strip_comment};
+use epd_dither::preset::Preset;
+use epd_dither::registry::{
+    FactoryError, FactoryOptions, decompose_ditherer_with, decomposer_for, octahedron_axis_for,
+    tiled_ditherer_with,
+};
+use image::{Rgb, Rgb32FImage, RgbImage};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
-#[command(name = "dither")]
+#[command(name = "dither", args_conflicts_with_subcommands = true)]
 struct Args {
-    #[arg()]
-    input_file: String,
-    #[arg()]
-    output_file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(required_unless_present = "analyze_palette")]
+    input_file: Option<String>,
+    #[arg(required_unless_present_any = ["stats", "analyze_palette"])]
+    output_file: Option<String>,
     #[arg(long, value_name="NOISE", long_help=NoiseSource::LONG_HELP, default_value = "ign")]
     noise: NoiseSource,
-    #[arg(long, value_name = "STRATEGY", long_help = DecomposeStrategy::LONG_HELP, default_value = "octahedron-closest")]
-    strategy: DecomposeStrategy,
+    /// Ordered-dither strength: 1 dithers fully, 0 posterizes (each
+    /// pixel takes the ink where its cumulative weights cross one half,
+    /// not necessarily the largest one), values between reduce grain.
+    #[arg(long, value_name = "0..1", default_value_t = 1.0, value_parser = parse_noise_amplitude)]
+    noise_amplitude: f32,
+    /// Let the noise pick evenly between inks whose decomposition weights
+    /// are within M of the largest, e.g. `0.1` to dither a 47/53 blend as
+    /// an even 50/50 texture. Shifts the local mix by up to M/2; 0 picks
+    /// in proportion to the weights.
+    #[arg(long, value_name = "M", default_value_t = 0.0, value_parser = parse_tie_margin, conflicts_with = "lab_diffusion")]
+    tie_margin: f32,
+    /// Shift the noise pattern by OX,OY pixels, e.g. to line it up with a
+    /// panel origin when dithering one tile of a larger image.
+    #[arg(long, value_name = "OX,OY", default_value = "0,0", value_parser = parse_noise_offset)]
+    noise_offset: [usize; 2],
+    /// Sample the noise half a tile further right on odd rows, to break
+    /// up faint horizontal banding where rows of a blue-noise or
+    /// `file:` tile line up. Noise without a tile is unaffected.
+    #[arg(long)]
+    noise_row_phase: bool,
+    /// Dither pixels whose chroma (largest minus smallest RGB channel, in
+    /// 0..1) is at most this with only the darkest and brightest inks,
+    /// keeping grays free of coloured speckle. RGB strategies only.
+    #[arg(long, value_name = "0..1", value_parser = parse_noise_amplitude)]
+    neutral_lock: Option<f32>,
+    /// Use these three pairs of dither-palette indices as the octahedron's
+    /// axes, e.g. `--axes 0,1 2,3 4,5`, instead of finding opposite inks
+    /// automatically, which can misfire on measured palettes. Each pair
+    /// must be roughly opposite. Octahedron strategies only.
+    #[arg(long, value_name = "A,B", num_args = 3, action = clap::ArgAction::Set, value_parser = parse_axis_pair)]
+    axes: Vec<(usize, usize)>,
+    #[arg(long, value_name = "PRESET", long_help = Preset::LONG_HELP)]
+    preset: Option<Preset>,
+    /// Decomposition strategy; the default depends on the dither palette.
+    #[arg(long, value_name = "STRATEGY", long_help = DecomposeStrategy::LONG_HELP)]
+    strategy: Option<DecomposeStrategy>,
     #[arg(long, value_name = "DIFFUSE", long_help = DiffuseMethod::LONG_HELP, default_value = "floyd-steinberg")]
     diffuse: DiffuseMethod,
-    #[arg(long, value_name = "DITHER_PALETTE", long_help = Palette::LONG_HELP, default_value = "spectra6")]
-    dither_palette: Palette,
-    #[arg(long, value_name = "OUTPUT_PALETTE", long_help = Palette::LONG_HELP, default_value = "spectra6")]
-    output_palette: Palette,
+    /// Dither a downscaled preview with each fixed diffusion kernel and
+    /// use the one with the lowest worm score (see
+    /// `epd_dither::metrics::worm_score`) instead of `--diffuse`.
+    #[arg(long)]
+    pick_diffusion: bool,
+    #[arg(long, value_name = "EDGES", long_help = EdgeMode::LONG_HELP, default_value = "drop")]
+    edges: EdgeMode,
+    #[arg(long, value_name = "SCAN", long_help = ScanOrder::LONG_HELP, default_value = "auto")]
+    scan: ScanOrder,
+    /// Diffuse colour error in CIELAB rather than per-ink weight error.
+    /// Inks are still mixed by the decomposer in RGB; only the error that
+    /// carries to neighbours is measured in Lab. RGB strategies only.
+    #[arg(long)]
+    lab_diffusion: bool,
+    /// `rgb` diffuses classic per-channel RGB error and emits the nearest
+    /// palette colour, bypassing the decomposer: `--noise`, `--mixing`,
+    /// ink bias and gamma, masks and `--prev` don't apply. RGB strategies
+    /// only.
+    #[arg(long, value_name = "SPACE", long_help = ErrorSpace::LONG_HELP, default_value = "decomposed", conflicts_with = "lab_diffusion")]
+    error_space: ErrorSpace,
+    /// Pick each pixel's ink together with the next pixel's, minimising
+    /// the error over both instead of at each pixel alone. Deterministic:
+    /// `--noise`, `--noise-amplitude` and `--index-order-seed` don't
+    /// apply, nor does the `dominant-texture` pick. Two to three times
+    /// as slow.
+    #[arg(long, conflicts_with_all = ["lab_diffusion", "subpixel"])]
+    lookahead: bool,
+    /// Diffuse less of the error left by colours near or outside the
+    /// edge of the palette's gamut, in proportion to how deep inside it
+    /// they lie, so out-of-gamut regions don't smear error into their
+    /// surroundings. Octahedron and naive strategies; the others diffuse
+    /// all their error as before.
+    #[arg(long, conflicts_with_all = ["lab_diffusion", "subpixel"])]
+    confidence_weighting: bool,
+    #[arg(long, value_name = "DITHER_PALETTE", long_help = palette_long_help(), default_value = "spectra6")]
+    dither_palette: PaletteArg,
+    #[arg(long, value_name = "OUTPUT_PALETTE", long_help = palette_long_help(), default_value = "spectra6")]
+    output_palette: PaletteArg,
+    /// Dither regions of the image against their own palettes, e.g. for
+    /// a panel tiled from modules with different ink measurements. Each
+    /// line of FILE is `X,Y,WIDTH,HEIGHT PALETTE`, with PALETTE in the
+    /// `--dither-palette` form and listing the same inks in the same
+    /// order; `#` at the start of a line or after whitespace starts a
+    /// comment, unless it begins a `#RRGGBB` colour followed by whitespace
+    /// or the end of the line, so list colours as `000000,FFFFFF` rather
+    /// than `#000000,#FFFFFF`. Pixels outside every
+    /// region use `--dither-palette`, which `--weights-dir`,
+    /// `--pick-diffusion` and the recorded configuration keep using
+    /// throughout.
+    #[arg(long, value_name = "FILE", conflicts_with = "lab_diffusion")]
+    tiles: Option<String>,
+    /// Penalise naive-strategy mixes spanning far-apart inks (e.g. black
+    /// and yellow for a dark green) by this much per unit of RGB distance,
+    /// preferring tighter ink clusters. 0 disables it. Naive strategies
+    /// only.
+    #[arg(long, value_name = "K", default_value_t = 0.0, value_parser = parse_compactness)]
+    compactness: f32,
+    /// Mix at most this many inks per pixel, for panels that can't blend
+    /// more at one location: wider decompositions are re-projected onto
+    /// their top N inks. RGB strategies only.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..))]
+    max_inks: Option<u8>,
+    /// Dither palette index for input pixels with NaN or infinite samples;
+    /// defaults to the darkest palette entry.
+    #[arg(long, value_name = "INDEX")]
+    non_finite_fallback: Option<usize>,
+    /// Re-dither with a small input gain so the output's mean luminance
+    /// matches the input's, compensating for brightness drift where the
+    /// image clips to the palette's gamut. Runs the dither up to three
+    /// times.
+    #[arg(long)]
+    match_brightness: bool,
+    /// Dither N times, each pass adding part of the accumulated blurred
+    /// residual of the previous ones (input minus output) to the input to
+    /// correct local tone drift. Each pass costs about one full dither and
+    /// the improvement is usually small (see
+    /// `epd_dither::image::passes`); 1 is a plain dither.
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    passes: u16,
+    /// Stop the passes of `--passes` early once the blurred RMS error of
+    /// a pass (input minus output, in `[0, 1]`) drops below E, and report
+    /// how many ran.
+    #[arg(long, value_name = "E", requires = "passes")]
+    max_error: Option<f32>,
+    /// Dither for a panel whose pixels are split into COLUMNSxROWS
+    /// subpixel cells of one ink each, e.g. `3x1` for vertical stripes:
+    /// each pixel's cells are shared out between inks by its decomposition
+    /// weights (see `epd_dither::dither::subpixel`), and the output has
+    /// one pixel per cell. Noise, the pick mode and the ink-order seed
+    /// don't apply; `adaptive` diffusion classifies the input pixels, one
+    /// kernel per pixel's cells.
+    #[arg(
+        long,
+        value_name = "LAYOUT",
+        conflicts_with_all = [
+            "tiles",
+            "prev",
+            "ink_mask",
+            "diffuse_mask",
+            "passes",
+            "match_brightness",
+            "lab_diffusion"
+        ]
+    )]
+    subpixel: Option<SubpixelLayout>,
+    #[arg(long, value_name = "MIXING", long_help = MixingModel::LONG_HELP, default_value = "additive")]
+    mixing: MixingModel,
+    /// Rotate hue by H degrees and scale saturation and value by S and V,
+    /// e.g. `-20,1.2,1` to pull greens toward the green ink. Applied to
+    /// the decoded sRGB input before anything but `--tonemap`, so
+    /// `--mixing` and every other option see the adjusted colours.
+    #[arg(long, value_name = "H,S,V")]
+    hsv: Option<HsvAdjustment>,
+    /// Black-point compensation: rescale the input so its black maps to
+    /// the darkest dither-palette entry instead of clipping to it, keeping
+    /// shadow detail at the cost of lifted, ink-tinted shadows. Applied
+    /// after `--hsv`.
+    #[arg(long)]
+    bpc: bool,
+    /// Colour that transparent input is composited over (in linear light)
+    /// before anything else; ignored for inputs without alpha.
+    #[arg(long, value_name = "#RRGGBB", default_value = "#FFFFFF", value_parser = parse_color)]
+    background: [u8; 3],
+    #[arg(long, value_name = "ALPHA", long_help = AlphaMode::LONG_HELP, default_value = "straight")]
+    alpha: AlphaMode,
+    #[arg(long, value_name = "MODE", long_help = AlphaHandling::LONG_HELP, default_value = "composite")]
+    alpha_mode: AlphaHandling,
+    /// Dither palette index for the transparent pixels of `--alpha-mode
+    /// mask`; defaults to the entry nearest `--background`.
+    #[arg(long, value_name = "INDEX", requires = "alpha_mode")]
+    transparent_index: Option<usize>,
+    #[arg(long, value_name = "OPERATOR", long_help = ToneMap::LONG_HELP, default_value = "none")]
+    tonemap: ToneMap,
+    /// Scale the decomposition weight of a dither-palette entry before
+    /// picking, e.g. `2:0.8` to use less of entry 2. Repeatable. Trades
+    /// colour accuracy for control over ink usage.
+    #[arg(long, value_name = "INDEX:FACTOR")]
+    ink_bias: Vec<InkBias>,
+    /// Measured inks with a response exponent each, replacing
+    /// `--dither-palette`. Each line of FILE is `RRGGBB GAMMA` (or
+    /// `#RRGGBB GAMMA`), one ink per line in palette order; `#` at the
+    /// start of a line or after whitespace starts a comment, unless it
+    /// begins a colour. GAMMA models an ink whose share `w` of the pixels
+    /// reads as `w^GAMMA` of its full colour, so values above 1 give
+    /// sparse ink a larger share; 1 is linear. See
+    /// `epd_dither::decompose::response`.
+    #[arg(long, value_name = "FILE", conflicts_with = "dither_palette")]
+    calibration: Option<String>,
+    /// Walk palette indices in a per-pixel shuffled order (seeded by this
+    /// value) when picking with noise, so ties don't favour low indices.
+    #[arg(long, value_name = "SEED")]
+    index_order_seed: Option<u64>,
+    /// Image currently shown on the panel. Pixels keep their previous
+    /// colour wherever that is nearly as good, reducing refresh flicker.
+    #[arg(long, value_name = "PATH")]
+    prev: Option<String>,
+    /// Scale dither-palette weights per pixel, like a spatially varying
+    /// `--ink-bias`. `INDEX:PATH` reads a grayscale image whose luma (0
+    /// black to 1 white) scales entry INDEX; a bare PATH reads a TIFF with
+    /// one channel per palette entry, e.g. a `--weights-tiff` export. Float
+    /// TIFFs may exceed 1 to use an ink more. Where a factor is 0 the ink
+    /// is never used. Must match the input's size.
+    #[arg(long, value_name = "[INDEX:]PATH", conflicts_with = "lab_diffusion")]
+    ink_mask: Option<String>,
+    /// Keep error diffusion out of the black areas of this image, e.g. a
+    /// solid UI panel next to a photo: pixels with luma below one half
+    /// neither receive nor pass on error, and their neighbours treat them
+    /// as past the image edge (see `--edges`). Must match the input's size.
+    #[arg(long, value_name = "PATH", conflicts_with = "lab_diffusion")]
+    diffuse_mask: Option<String>,
+    /// How much decomposition weight (0..1) a pixel may give up to keep
+    /// its `--prev` colour.
+    #[arg(long, value_name = "TOLERANCE", default_value_t = DEFAULT_PREVIOUS_TOLERANCE)]
+    prev_tolerance: f32,
+    /// Write one grayscale image per dither-palette entry into this
+    /// directory, showing that entry's decomposition weight (before noise
+    /// and diffusion) across the input.
+    #[arg(long, value_name = "DIR")]
+    weights_dir: Option<String>,
+    /// Write the decomposition weights (before noise and diffusion) to
+    /// this file as a float TIFF with one channel per dither-palette
+    /// entry, in palette order.
+    #[arg(long, value_name = "FILE")]
+    weights_tiff: Option<String>,
+    /// Write an image to this file colouring each input pixel by the
+    /// octahedron axis its decomposition uses (red, green, blue for axes
+    /// 0, 1, 2; gray for `octahedron-average`), to see where the choice
+    /// switches. Needs an octahedron strategy.
+    #[arg(long, value_name = "FILE")]
+    axis_map: Option<String>,
+    /// Check every input pixel's decomposition weights (non-negative,
+    /// summing to one; see `epd_dither::decompose::assert_valid`) before
+    /// dithering, and exit with an error if any fail. For developing
+    /// decomposers.
+    #[arg(long)]
+    check_decompositions: bool,
+    /// Cap the local density of a dither-palette entry, named by index,
+    /// `#RRGGBB` colour or ink name (the entry nearest that ink), e.g.
+    /// `red:0.25` or `3:0.25`. Repeatable. Applied as a heuristic
+    /// post-pass after dithering.
+    #[arg(long, value_name = "INK:FRACTION")]
+    max_density: Vec<DensityLimitSpec>,
+    /// Window radius (in pixels) used by `--max-density`.
+    #[arg(long, value_name = "RADIUS", default_value_t = DEFAULT_DENSITY_RADIUS)]
+    density_radius: usize,
+    /// Decode the written PNG and check every pixel is an output-palette
+    /// colour; exit with an error otherwise. PNG formats only.
+    #[arg(long)]
+    verify: bool,
+    /// Dither as usual, but print how many pixels use each output-palette
+    /// entry instead of writing an image.
+    #[arg(long)]
+    stats: bool,
+    /// Print every setting that affects the output, with diffusion as its
+    /// coefficients (or adaptive tile size and threshold) and `white`
+    /// noise with a fixed seed, in a form that parses back to the same
+    /// configuration.
+    #[arg(long)]
+    print_config: bool,
+    /// Print the dither palette's gamut volume (as a share of the RGB
+    /// cube), its closest pair of colours, and whether and how it pairs
+    /// into an octahedron, then exit without dithering. See
+    /// `epd_dither::palette::analyze`.
+    #[arg(long)]
+    analyze_palette: bool,
+    /// Store the `--print-config` text in the output PNG as an `iTXt`
+    /// chunk with keyword "epd-dither config". PNG formats only.
+    #[arg(long)]
+    embed_config: bool,
+    /// Also write a JSON summary of the dither to FILE: mean and maximum
+    /// blurred reconstruction error, pixels per dither-palette entry, the
+    /// share of input pixels inside the palette's gamut and the full
+    /// configuration. See `epd_dither::image::sidecar` for the schema.
+    #[arg(long, value_name = "FILE", conflicts_with = "subpixel")]
+    sidecar: Option<String>,
+    /// Save the dither's progress to PATH every 30 seconds, so an
+    /// interrupted run over a large image can be continued with
+    /// `--resume`. The file is removed once the dither finishes. Palettes
+    /// of up to 256 entries. Only the output rows done and the pending
+    /// error are saved: the whole image is still decoded and held in
+    /// memory, so this doesn't help images too large to fit.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["passes", "match_brightness", "subpixel"])]
+    checkpoint: Option<String>,
+    /// Continue from the `--checkpoint` file instead of starting over.
+    /// The input, settings and the files they name must be the same as
+    /// the interrupted run's; pass `--noise white:<seed>` with the seed it
+    /// printed for `white` noise.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+    #[arg(long, value_name = "FORMAT", long_help = OutputFormat::LONG_HELP, default_value = "packed-png")]
+    format: OutputFormat,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check this build by dithering a built-in gradient.
+    ///
+    /// Runs every strategy, diffusion and noise combination over a
+    /// gradient of each test palette's inks and checks that every output
+    /// pixel is a palette colour, no weights are NaN and the mean luma
+    /// stays close to the input's. Prints one PASS/FAIL line per
+    /// combination and a summary; exits non-zero if any check fails.
+    Selftest,
+}
+
+/// [`Palette::NAMES_HELP`] plus the custom colour-list form.
+fn palette_long_help() -> String {
+    format!(
+        concat!(
+            "Built-in palette name, or a comma-separated list of #RRGGBB colours\n",
+            "for panels with other ink counts (e.g. `#000000,#FFFFFF,#FF0000,#FFFF00`).\n",
+            "Octahedron strategies need exactly six colours; naive works with any.\n",
+            "`file:<PATH>` loads an Adobe swatch file (.ase or .aco), in file order.\n\n",
+            "Built-in palettes:\n{}",
+        ),
+        Palette::NAMES_HELP
+    )
+}
+
+/// `--dither-palette` / `--output-palette`: a built-in [`Palette`] or an
+/// explicit colour list of any length.
+#[derive(Clone)]
+enum PaletteArg {
+    Builtin(Palette),
+    Custom(Vec<[u8; 3]>),
+}
+
+impl PaletteArg {
+    fn as_rgb_slice(&self) -> &[[u8; 3]] {
+        match self {
+            Self::Builtin(palette) => palette.as_rgb_slice(),
+            Self::Custom(colors) => colors,
+        }
+    }
+}
+
+impl std::str::FromStr for PaletteArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(palette) = s.parse::<Palette>() {
+            return Ok(Self::Builtin(palette));
+        }
+        if let Some(path) = s.strip_prefix("file:") {
+            return load_swatch_file(path).map(Self::Custom);
+        }
+        s.split(',')
+            .map(|c| {
+                parse_hex_color(c.trim()).ok_or_else(|| format!("invalid palette or colour `{c}`"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self::Custom)
+    }
+}
+
+/// A `--tiles` region and its dither palette.
+type Tile = (Region, Vec<Rgb<u8>>);
+
+/// Read a `--tiles` file; every palette must have `palette_len` entries.
+fn load_tiles(path: &str, palette_len: usize) -> Result<Vec<Tile>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("reading `{path}`: {e}"))?;
+    let mut tiles = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let context = |e: &dyn std::fmt::Display| format!("`{path}` line {}: {e}", number + 1);
+        let (region, palette) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| context(&"expected `X,Y,WIDTH,HEIGHT PALETTE`"))?;
+        let region: Region = region.parse().map_err(|e| context(&e))?;
+        let palette: PaletteArg = palette.trim().parse().map_err(|e: String| context(&e))?;
+        let palette = palette.as_rgb_slice();
+        if palette.len() != palette_len {
+            return Err(context(&format!(
+                "palette has {} colours but the dither palette has {palette_len}",
+                palette.len()
+            )));
+        }
+        tiles.push((region, palette.iter().map(|&c| Rgb(c)).collect()));
+    }
+    Ok(tiles)
+}
+
+/// Read a `--calibration` file into the dither palette and the inks'
+/// response exponents.
+fn load_calibration(path: &str) -> Result<(Vec<[u8; 3]>, Vec<f32>), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("reading `{path}`: {e}"))?;
+    let (mut palette, mut gammas) = (Vec::new(), Vec::new());
+    for (number, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let context = |e: &dyn std::fmt::Display| format!("`{path}` line {}: {e}", number + 1);
+        let (color, gamma) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| context(&"expected `RRGGBB GAMMA`"))?;
+        let color = parse_hex_color(color).ok_or_else(|| context(&"invalid colour"))?;
+        let gamma = match gamma.trim().parse::<f32>() {
+            Ok(gamma) if gamma.is_finite() && gamma > 0.0 => gamma,
+            _ => return Err(context(&"gamma must be a positive number")),
+        };
+        palette.push(color);
+        gammas.push(gamma);
+    }
+    if palette.is_empty() {
+        return Err(format!("`{path}` contains no colours"));
+    }
+    Ok((palette, gammas))
+}
+
+/// Read a `.ase` / `.aco` swatch file, picking the parser by extension.
+fn load_swatch_file(path: &str) -> Result<Vec<[u8; 3]>, String> {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let parse = match extension.as_deref() {
+        Some("ase") => epd_dither::palette::from_ase,
+        Some("aco") => epd_dither::palette::from_aco,
+        _ => {
+            return Err(format!(
+                "unknown swatch file type `{path}` (expected .ase or .aco)"
+            ));
+        }
+    };
+    let bytes = std::fs::read(path).map_err(|e| format!("reading `{path}`: {e}"))?;
+    let colors = parse(&bytes).map_err(|e| format!("`{path}`: {e}"))?;
+    if colors.is_empty() {
+        return Err(format!("`{path}` contains no colours"));
+    }
+    Ok(colors)
+}
+
+/// Number of progress lines printed over a whole dither.
+const PROGRESS_STEPS: usize = 20;
+
+/// Pass-through image wrapper that counts written pixels and prints a
+/// percentage and ETA each time another `1 / PROGRESS_STEPS` of the image
+/// is done. The per-pixel cost is one increment and one comparison.
+struct Progress<I> {
+    inner: I,
+    written: usize,
+    next_report: usize,
+    total: usize,
+    start: Instant,
+}
+
+impl<I: ImageSize> Progress<I> {
+    fn new(inner: I) -> Self {
+        let total = inner.width() * inner.height();
+        Self {
+            inner,
+            written: 0,
+            next_report: total.div_ceil(PROGRESS_STEPS),
+            total,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<I> Progress<I> {
+    fn report(&mut self) {
+        let elapsed = self.start.elapsed();
+        let fraction = self.written as f64 / self.total as f64;
+        let remaining = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+        println!(
+            "Dithering: {:3.0}% (elapsed {:.1?}, ETA {:.1?})",
+            fraction * 100.0,
+            elapsed,
+            Duration::from_secs_f64(remaining)
+        );
+        let step = self.total.div_ceil(PROGRESS_STEPS);
+        self.next_report = (self.written + step).min(self.total);
+    }
+}
+
+impl<I: ImageSize> ImageSize for Progress<I> {
+    fn width(&self) -> usize {
+        self.inner.width()
+    }
+    fn height(&self) -> usize {
+        self.inner.height()
+    }
+}
+
+impl<I: ImageReader<T>, T> ImageReader<T> for Progress<I> {
+    fn get_pixel(&self, x: usize, y: usize) -> T {
+        self.inner.get_pixel(x, y)
+    }
+}
+
+impl<I: ImageWriter<T>, T> ImageWriter<T> for Progress<I> {
+    fn put_pixel(&mut self, x: usize, y: usize, pixel: T) {
+        self.inner.put_pixel(x, y, pixel);
+        self.written += 1;
+        if self.written == self.next_report {
+            self.report();
+        }
+    }
+}
+
+/// Rows dithered between checks whether a checkpoint is due.
+const CHECKPOINT_ROWS: usize = 64;
+
+/// Time between `--checkpoint` saves.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Write `checkpoint` to `path` through a temporary file, so an
+/// interruption mid-write leaves the previous checkpoint intact.
+fn write_checkpoint(path: &str, checkpoint: &Checkpoint) {
+    let temporary = format!("{path}.tmp");
+    std::fs::write(&temporary, checkpoint.to_bytes()).unwrap();
+    std::fs::rename(&temporary, path).unwrap();
+}
+
+/// FNV-1a over `bytes`, continuing from `hash`.
+fn fnv1a(hash: u64, bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// What a `--checkpoint` must match to be resumed: the configuration
+/// text, then hashes of the prepared input and of the files it names by
+/// path, which it can't tell apart when only their contents changed.
+fn checkpoint_fingerprint(config: &DitherConfig, input: &Rgb32FImage) -> String {
+    const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+    let mut fingerprint = config.to_string();
+    let pixels = input.iter().flat_map(|c| c.to_le_bytes());
+    fingerprint += &format!("input={:016x}\n", fnv1a(FNV_OFFSET, pixels));
+    // `--ink-mask` is `INDEX:PATH` or a plain path; see `load_ink_mask`.
+    let ink_mask = config.ink_mask.as_deref().map(|spec| {
+        spec.split_once(':')
+            .filter(|(index, _)| index.parse::<usize>().is_ok())
+            .map_or(spec, |(_, path)| path)
+    });
+    for path in [
+        config.prev.as_deref(),
+        ink_mask,
+        config.diffuse_mask.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        // Unreadable files fail the run later anyway.
+        let bytes = std::fs::read(path).unwrap_or_default();
+        fingerprint += &format!("file:{path}={:016x}\n", fnv1a(FNV_OFFSET, bytes));
+    }
+    fingerprint
+}
+
+/// Decompose every pixel of `input` and write `weight_<index>.png` per
+/// palette entry into `dir`, mapping weight 0..1 to black..white.
+fn write_weight_images(
+    dir: &str,
+    input: &image::Rgb32FImage,
+    decomposer: &dyn epd_dither::Decomposer<f32, Input = Rgb<f32>>,
+) {
+    std::fs::create_dir_all(dir).unwrap();
+    let palette_size = decomposer.palette_size();
+    let mut layers: Vec<image::GrayImage> = (0..palette_size)
+        .map(|_| image::GrayImage::new(input.width(), input.height()))
+        .collect();
+    let mut weights = vec![0.0; palette_size];
+    for (x, y, pixel) in input.enumerate_pixels() {
+        decomposer.decompose_into(pixel, &mut weights);
+        for (layer, weight) in layers.iter_mut().zip(&weights) {
+            let level = (weight.clamp(0.0, 1.0) * 255.0).round() as u8;
+            layer.put_pixel(x, y, image::Luma([level]));
+        }
+    }
+    for (index, layer) in layers.iter().enumerate() {
+        layer
+            .save(std::path::Path::new(dir).join(format!("weight_{index}.png")))
+            .unwrap();
+    }
+}
+
+/// Decompose every finite pixel of `input` and check the weights with
+/// [`assert_valid`], without reconstruction since pixels may be out of
+/// gamut. Prints the first failure and returns the number of failing
+/// pixels.
+fn check_decompositions(
+    input: &image::Rgb32FImage,
+    decomposer: &dyn epd_dither::Decomposer<f32, Input = Rgb<f32>>,
+    palette: &[Rgb<u8>],
+) -> usize {
+    let points: Vec<_> = palette.iter().map(|c| c.to_point()).collect();
+    let mut weights = vec![0.0; decomposer.palette_size()];
+    let mut failures = 0;
+    for (x, y, pixel) in input.enumerate_pixels() {
+        if !pixel.is_finite() {
+            continue;
+        }
+        decomposer.decompose_into(pixel, &mut weights);
+        if let Err(e) = assert_valid(&weights, &points, None, DECOMPOSITION_TOLERANCE) {
+            if failures == 0 {
+                eprintln!("Invalid decomposition at ({x}, {y}) of {:?}: {e}", pixel.0);
+            }
+            failures += 1;
+        }
+    }
+    failures
+}
+
+/// Allowed deviation of the weight sum in `--check-decompositions`.
+const DECOMPOSITION_TOLERANCE: f32 = 1e-4;
+
+/// Decode `png_bytes` and return the first pixel whose colour is not in
+/// `palette`, as `(x, y, colour)`.
+fn find_non_palette_pixel(
+    png_bytes: &[u8],
+    palette: &[Rgb<u8>],
+) -> Result<Option<(u32, u32, Rgb<u8>)>, String> {
+    let Ok(decoded) = image::load_from_memory(png_bytes) else {
+        return Err("the written PNG does not decode".into());
+    };
+    Ok(decoded
+        .into_rgb8()
+        .enumerate_pixels()
+        .find(|(_, _, pixel)| !palette.contains(pixel))
+        .map(|(x, y, pixel)| (x, y, *pixel)))
+}
+
+/// Load `path` as a [`PreviousFrame`], mapping each pixel to the nearest
+/// `palette` colour.
+fn load_previous_frame(
+    path: &str,
+    palette: &[Rgb<u8>],
+    tolerance: f32,
+) -> Result<PreviousFrame, String> {
+    let image = image::ImageReader::open(path)
+        .map_err(|e| format!("reading `{path}`: {e}"))?
+        .decode()
+        .map_err(|e| format!("decoding `{path}`: {e}"))?
+        .into_rgb8();
+    let palette: Vec<[u8; 3]> = palette.iter().map(|color| color.0).collect();
+    let indices = image
+        .pixels()
+        .map(|pixel| {
+            index_of(pixel.0, &palette).unwrap_or_else(|| nearest_index(pixel.0, &palette))
+        })
+        .collect();
+    let frame = PreviousFrame::new(image.width() as usize, image.height() as usize, indices)
+        .ok_or_else(|| format!("`{path}` is malformed"))?;
+    Ok(frame.with_tolerance(tolerance))
+}
+
+/// Read a `--diffuse-mask`: pixels with luma below one half are masked out.
+fn load_diffuse_mask(path: &str) -> Result<DiffuseMask, String> {
+    let luma = image::ImageReader::open(path)
+        .map_err(|e| format!("reading `{path}`: {e}"))?
+        .decode()
+        .map_err(|e| format!("decoding `{path}`: {e}"))?
+        .to_luma32f();
+    let (width, height) = (luma.width() as usize, luma.height() as usize);
+    let diffuses = luma.into_raw().into_iter().map(|l| l >= 0.5).collect();
+    DiffuseMask::new(width, height, diffuses).ok_or_else(|| format!("`{path}` is malformed"))
+}
+
+/// Read an `--ink-mask` for a `palette_len`-entry dither palette.
+fn load_ink_mask(spec: &str, palette_len: usize) -> Result<InkMask, String> {
+    let single = spec
+        .split_once(':')
+        .and_then(|(index, path)| Some((index.parse::<usize>().ok()?, path)));
+    if let Some((index, path)) = single {
+        if index >= palette_len {
+            return Err(format!(
+                "ink mask index {index} is past the {palette_len}-colour dither palette"
+            ));
+        }
+        let luma = image::ImageReader::open(path)
+            .map_err(|e| format!("reading `{path}`: {e}"))?
+            .decode()
+            .map_err(|e| format!("decoding `{path}`: {e}"))?
+            .to_luma32f();
+        let (width, height) = (luma.width() as usize, luma.height() as usize);
+        return InkMask::new(width, height, vec![index], luma.into_raw())
+            .ok_or_else(|| format!("`{path}` has negative or non-finite values"));
+    }
+    let path = spec;
+    let file = std::fs::File::open(path).map_err(|e| format!("reading `{path}`: {e}"))?;
+    let context = |e: tiff::TiffError| format!("decoding `{path}`: {e}");
+    let mut decoder =
+        tiff::decoder::Decoder::new(std::io::BufReader::new(file)).map_err(context)?;
+    let (width, height) = decoder.dimensions().map_err(context)?;
+    let factors: Vec<f32> = match decoder.read_image().map_err(context)? {
+        tiff::decoder::DecodingResult::U8(values) => {
+            values.into_iter().map(|v| v as f32 / 255.0).collect()
+        }
+        tiff::decoder::DecodingResult::U16(values) => {
+            values.into_iter().map(|v| v as f32 / 65535.0).collect()
+        }
+        tiff::decoder::DecodingResult::F32(values) => values,
+        _ => return Err(format!("`{path}`: expected 8-bit, 16-bit or float samples")),
+    };
+    let pixels = width as usize * height as usize;
+    if factors.len() != pixels * palette_len {
+        return Err(format!(
+            "`{path}` has {} channels but the dither palette has {palette_len} colours",
+            factors.len() / pixels.max(1)
+        ));
+    }
+    InkMask::new(
+        width as usize,
+        height as usize,
+        (0..palette_len).collect(),
+        factors,
+    )
+    .ok_or_else(|| format!("`{path}` has negative or non-finite values"))
+}
+
+fn parse_color(s: &str) -> Result<[u8; 3], String> {
+    parse_hex_color(s).ok_or_else(|| format!("invalid colour `{s}`, expected #RRGGBB"))
+}
+
+fn parse_noise_amplitude(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(amplitude) if (0.0..=1.0).contains(&amplitude) => Ok(amplitude),
+        _ => Err(format!("`{s}` is not a number in [0, 1]")),
+    }
+}
+
+/// Decomposition weights lie in [0, 1], so a margin of 1 already ties
+/// every positive weight.
+fn parse_tie_margin(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(margin) if (0.0..=1.0).contains(&margin) => Ok(margin),
+        _ => Err(format!(
+            "`{s}` is not a tie margin: expected a weight difference in [0, 1]"
+        )),
+    }
+}
+
+fn parse_compactness(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(compactness) if compactness.is_finite() && compactness >= 0.0 => Ok(compactness),
+        _ => Err(format!("`{s}` is not a finite number >= 0")),
+    }
+}
+
+fn parse_noise_offset(s: &str) -> Result<[usize; 2], String> {
+    s.split_once(',')
+        .and_then(|(x, y)| Some([x.trim().parse().ok()?, y.trim().parse().ok()?]))
+        .ok_or_else(|| format!("invalid offset `{s}`, expected OX,OY"))
+}
+
+fn parse_axis_pair(s: &str) -> Result<(usize, usize), String> {
+    s.split_once(',')
+        .and_then(|(a, b)| Some((a.trim().parse().ok()?, b.trim().parse().ok()?)))
+        .ok_or_else(|| format!("invalid axis `{s}`, expected A,B"))
+}
+
+/// Print the `--analyze-palette` report on `palette`.
+fn print_palette_report(palette: &[[u8; 3]]) {
+    let report = analyze(palette);
+    println!("Palette analysis:");
+    println!(
+        "  hull volume: {:.4} ({:.1}% of the RGB cube)",
+        report.hull_volume,
+        report.hull_volume * 100.0
+    );
+    match report.closest_pair {
+        Some([a, b]) => println!(
+            "  closest colours: {a} and {b}, distance {:.4}",
+            report.min_distance
+        ),
+        None => println!("  closest colours: none"),
+    }
+    match report.opposites {
+        Some(pairs) => {
+            let pairs: Vec<String> = pairs.iter().map(|(a, b)| format!("{a},{b}")).collect();
+            println!("  opposite pairs: {}", pairs.join(" "));
+        }
+        None => println!("  opposite pairs: none found"),
+    }
+    println!(
+        "  octahedral: {}",
+        if report.is_octahedral { "yes" } else { "no" }
+    );
+}
+
+/// Print the `--stats` histogram of palette index usage in `image`.
+fn print_usage(image: &PaletteImage) {
+    let (width, height) = (image.width(), image.height());
+    let indices = (0..height).flat_map(|y| (0..width).map(move |x| image.get_pixel(x, y)));
+    let counts = palette_usage(indices, image.palette.palette.len());
+    let total = (width * height).max(1) as f64;
+    for (index, (count, color)) in counts.iter().zip(&image.palette.palette).enumerate() {
+        println!(
+            "{index:>3} #{:02X}{:02X}{:02X}: {count:>9} ({:5.1}%)",
+            color[0],
+            color[1],
+            color[2],
+            *count as f64 * 100.0 / total
+        );
+    }
+}
+
+/// Strategy used when `--strategy` is omitted: grayscale for all-gray
+/// palettes, the octahedron for six inks, and the naive decomposer (which
+/// handles any count, e.g. 4-ink BWRY) otherwise.
+fn default_strategy(palette: &[[u8; 3]]) -> DecomposeStrategy {
+    if palette.iter().all(|&c| Rgb(c).is_grayscale()) {
+        DecomposeStrategy::GrayOffsetBlend(0.0)
+    } else if palette.len() == 6 {
+        DecomposeStrategy::Octahedron(OctahedronDecomposerAxisStrategy::Closest)
+    } else {
+        DecomposeStrategy::Naive(NaiveDecomposerStrategy::FavorMix)
+    }
+}
+
+/// Set `preset`'s strategy, diffusion and noise where they weren't given
+/// on the command line.
+fn apply_preset(args: &mut Args, matches: &ArgMatches, preset: Preset) {
+    let explicit = |id| matches.value_source(id) != Some(ValueSource::DefaultValue);
+    (args.strategy, args.diffuse, args.noise) = preset.resolve(
+        args.strategy,
+        explicit("diffuse").then_some(args.diffuse),
+        explicit("noise").then(|| args.noise.clone()),
+    );
+}
+
+/// Long side of the `--pick-diffusion` preview, in pixels.
+const PICK_DIFFUSION_PREVIEW: u32 = 256;
+/// Kernels `--pick-diffusion` chooses between.
+const PICK_DIFFUSION_METHODS: &[DiffuseMethod] = &[
+    DiffuseMethod::FloydSteinberg,
+    DiffuseMethod::JarvisJudiceAndNinke,
+    DiffuseMethod::Atkinson,
+    DiffuseMethod::Sierra,
+    DiffuseMethod::SierraLite,
+];
+
+/// `--pick-diffusion`: print each kernel's worm score on a preview of
+/// `input` and return the lowest-scoring kernel.
+fn pick_diffusion(
+    input: &Rgb32FImage,
+    strategy: DecomposeStrategy,
+    noise: &NoiseSource,
+    palette: &[Rgb<u8>],
+    options: &FactoryOptions,
+) -> DiffuseMethod {
+    let scale = PICK_DIFFUSION_PREVIEW as f32 / input.width().max(input.height()).max(1) as f32;
+    let preview = if scale < 1.0 {
+        image::imageops::resize(
+            input,
+            ((input.width() as f32 * scale).round() as u32).max(1),
+            ((input.height() as f32 * scale).round() as u32).max(1),
+            image::imageops::FilterType::Triangle,
+        )
+    } else {
+        input.clone()
+    };
+    // The previous frame and the mask are sized for the full image.
+    let options = FactoryOptions {
+        previous: None,
+        mask: None,
+        ..options.clone()
+    };
+    let mut best = (DiffuseMethod::FloydSteinberg, f32::INFINITY);
+    for &method in PICK_DIFFUSION_METHODS {
+        // Every candidate is a fixed kernel.
+        let Some(matrix) = method.try_to_matrix() else {
+            continue;
+        };
+        let ditherer = decompose_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+            strategy,
+            noise.clone(),
+            palette,
+            matrix,
+            &options,
+        )
+        .unwrap_or_else(exit_with);
+        let writer = PaletteImage::new(
+            preview.width(),
+            preview.height(),
+            VerifiedPalette::new(palette.to_vec()).unwrap(),
+        );
+        let mut inout = ImageCombinedRW::new(preview.clone(), writer).unwrap();
+        ditherer.dyn_dither_into(&mut inout);
+        let rendered = RgbImage::from_fn(preview.width(), preview.height(), |x, y| {
+            palette[ImageReader::<usize>::get_pixel(&inout.writer, x as usize, y as usize)]
+        });
+        let score = worm_score(&rendered);
+        println!("  {method}: worm score {score:.4}");
+        if score < best.1 {
+            best = (method, score);
+        }
+    }
+    best.0
+}
+
+/// Palettes `selftest` covers, each with the strategies that accept it.
+const SELFTEST_CASES: &[(&str, &[&str])] = &[
+    (
+        "spectra6",
+        &[
+            "octahedron-closest",
+            "octahedron-furthest",
+            "octahedron-blended",
+            "naive-mix",
+            "naive-dominant",
+            "naive-blend",
+            "naive-closest",
+            "dominant-texture",
+        ],
+    ),
+    ("bwry", &["naive-mix", "naive-dominant"]),
+    (
+        "grayscale4",
+        &["grayscale", "gray-pure-spread:0.5", "gray-offset-blend:0.5"],
+    ),
+];
+const SELFTEST_DIFFUSIONS: &[&str] = &[
+    "none",
+    "floyd-steinberg",
+    "jarvis-judice-and-ninke",
+    "atkinson",
+];
+const SELFTEST_NOISES: &[&str] = &["none", "bayer:3", "ign", "white:1"];
+/// Largest `selftest` difference between input and output mean luma, for
+/// runs with noise or diffusion; plain posterization is exempt.
+const SELFTEST_LUMA_TOLERANCE: f32 = 0.05;
+
+/// `selftest` input: blends of neighbouring palette entries across, towards
+/// a dark-to-light ramp of the palette downwards. Every pixel is a convex
+/// mix of inks, so a faithful dither preserves its mean luma.
+fn selftest_gradient(palette: &[[u8; 3]]) -> Rgb32FImage {
+    let (width, height) = (64, 48);
+    let points: Vec<_> = palette.iter().map(|c| c.to_point()).collect();
+    let by_luma = |a: &&[u8; 3], b: &&[u8; 3]| a.brightness().total_cmp(&b.brightness());
+    let darkest = palette
+        .iter()
+        .min_by(by_luma)
+        .map_or([0; 3], |c| *c)
+        .to_point();
+    let lightest = palette
+        .iter()
+        .max_by(by_luma)
+        .map_or([0; 3], |c| *c)
+        .to_point();
+    Rgb32FImage::from_fn(width, height, |x, y| {
+        let along = x as f32 / width as f32 * points.len() as f32;
+        let (a, t) = (along as usize % points.len(), along.fract());
+        let across = points[a] + (points[(a + 1) % points.len()] - points[a]) * t;
+        let ramp = darkest + (lightest - darkest) * (y as f32 / (height - 1) as f32);
+        Rgb((across + (ramp - across) * 0.5).coords.into())
+    })
+}
+
+/// Problems with one `selftest` combination, empty if it passed.
+fn selftest_run(
+    palette: &[[u8; 3]],
+    input: &Rgb32FImage,
+    strategy: DecomposeStrategy,
+    diffuse: DiffuseMethod,
+    noise: NoiseSource,
+) -> Vec<String> {
+    let palette_rgb: Vec<Rgb<u8>> = palette.iter().map(|&c| Rgb(c)).collect();
+    let options = FactoryOptions::default();
+    let mut problems = Vec::new();
+    let decomposer = match decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options) {
+        Ok(decomposer) => decomposer,
+        Err(e) => return vec![format!("no decomposer: {e}")],
+    };
+    let mut weights = vec![0.0; palette.len()];
+    if input.pixels().any(|p| {
+        decomposer.decompose_into(p, &mut weights);
+        !weights.iter().all(|w| w.is_finite())
+    }) {
+        problems.push("non-finite decomposition weights".into());
+    }
+    let Some(matrix) = diffuse.try_to_matrix() else {
+        return vec![format!("{diffuse} has no fixed kernel")];
+    };
+    let ditherer = match decompose_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+        strategy,
+        noise.clone(),
+        &palette_rgb,
+        matrix,
+        &options,
+    ) {
+        Ok(ditherer) => ditherer,
+        Err(e) => return vec![format!("no ditherer: {e}")],
+    };
+    let writer = PaletteImage::new(
+        input.width(),
+        input.height(),
+        VerifiedPalette::new(palette_rgb.clone()).unwrap(),
+    );
+    let mut inout = ImageCombinedRW::new(input.clone(), writer).unwrap();
+    ditherer.dyn_dither_into(&mut inout);
+    let output = inout.writer;
+    if let Err(e) = output.verify() {
+        problems.push(e.to_string());
+    }
+    let indices = (0..output.height()).flat_map(|y| (0..output.width()).map(move |x| (x, y)));
+    let counted: usize = palette_usage(indices.map(|(x, y)| output.get_pixel(x, y)), palette.len())
+        .iter()
+        .sum();
+    if counted != output.width() * output.height() {
+        problems.push(format!(
+            "{} pixels outside the palette",
+            output.width() * output.height() - counted
+        ));
+    }
+    match output.encode(PngFormat::Packed, &[]) {
+        Ok(png) => match find_non_palette_pixel(&png, &palette_rgb) {
+            Ok(Some((x, y, _))) => {
+                problems.push(format!("PNG pixel ({x}, {y}) is not a palette colour"))
+            }
+            Ok(None) => {}
+            Err(e) => problems.push(e),
+        },
+        Err(e) => problems.push(format!("PNG encoding failed: {e}")),
+    }
+    let (target, achieved) = (mean_luma(input), mean_palette_luma(&output, &palette_rgb));
+    if !achieved.is_finite() {
+        problems.push("mean luma is not finite".into());
+    } else if (diffuse != DiffuseMethod::None || noise != NoiseSource::None)
+        && (achieved - target).abs() > SELFTEST_LUMA_TOLERANCE
+    {
+        problems.push(format!("mean luma {achieved:.3}, input {target:.3}"));
+    }
+    problems
+}
+
+/// The `selftest` subcommand.
+fn selftest() -> ExitCode {
+    let (mut passed, mut failed) = (0, 0);
+    for (palette, strategies) in SELFTEST_CASES {
+        let colors = palette.parse::<Palette>().unwrap().as_rgb_slice();
+        let input = selftest_gradient(colors);
+        for strategy in *strategies {
+            for diffuse in SELFTEST_DIFFUSIONS {
+                for noise in SELFTEST_NOISES {
+                    let problems = selftest_run(
+                        colors,
+                        &input,
+                        strategy.parse().unwrap(),
+                        diffuse.parse().unwrap(),
+                        noise.parse().unwrap(),
+                    );
+                    let name = format!("{palette} {strategy} {diffuse} {noise}");
+                    if problems.is_empty() {
+                        passed += 1;
+                        println!("PASS {name}");
+                    } else {
+                        failed += 1;
+                        println!("FAIL {name}: {}", problems.join("; "));
+                    }
+                }
+            }
+        }
+    }
+    println!("{passed} passed, {failed} failed");
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// `--diffuse adaptive`: classify the tiles of `image` by luma variance.
+fn adaptive_diffusion<I>(image: &I) -> AdaptiveDiffusion
+where
+    I: ImageSize + ImageReader<Rgb<f32>> + ?Sized,
+{
+    AdaptiveDiffusion::from_luma(
+        image.width(),
+        image.height(),
+        DEFAULT_ADAPTIVE_TILE,
+        DEFAULT_ADAPTIVE_THRESHOLD,
+        |x, y| image.get_pixel(x, y).brightness(),
+    )
+}
+
+/// [`decompose_ditherer_with`], or [`tiled_ditherer_with`] given `--tiles`.
+fn build_ditherer<T>(
+    strategy: DecomposeStrategy,
+    noise: &NoiseSource,
+    palette: &[Rgb<u8>],
+    tiles: Option<&[Tile]>,
+    matrix: impl DiffusionMatrix + Send + Sync + 'static,
+    options: &FactoryOptions,
+) -> Box<dyn DynResumableDitherer<T> + Send + Sync>
+where
+    T: ImageSize + ImageReader<Rgb<f32>> + ImageWriter<usize> + ?Sized + 'static,
+{
+    match tiles {
+        Some(tiles) => tiled_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+            strategy,
+            noise.clone(),
+            palette,
+            tiles,
+            matrix,
+            options,
+        ),
+        None => decompose_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+            strategy,
+            noise.clone(),
+            palette,
+            matrix,
+            options,
+        ),
+    }
+    .unwrap_or_else(exit_with)
 }
 
-fn main() {
-    let args = Args::parse();
+/// Report a factory error (e.g. a palette listing one colour twice) and
+/// exit.
+fn exit_with<T>(error: FactoryError) -> T {
+    eprintln!("{error}");
+    std::process::exit(1)
+}
+
+fn main() -> ExitCode {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(preset) = args.preset {
+        apply_preset(&mut args, &matches, preset);
+    }
+    if let Some(Command::Selftest) = args.command {
+        return selftest();
+    }
+    let (dither_palette, ink_gamma) = match args.calibration.as_deref() {
+        Some(path) => load_calibration(path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }),
+        None => (args.dither_palette.as_rgb_slice().to_vec(), Vec::new()),
+    };
+    let dither_palette = dither_palette.as_slice();
+    if args.analyze_palette {
+        print_palette_report(dither_palette);
+        return ExitCode::SUCCESS;
+    }
+    let Some(input_file) = &args.input_file else {
+        unreachable!("clap requires an input file without a subcommand or --analyze-palette");
+    };
+    if !matches!(args.format, OutputFormat::Png(_)) && (args.verify || args.embed_config) {
+        eprintln!("--verify and --embed-config need a PNG --format");
+        return ExitCode::FAILURE;
+    }
+    let mut max_density = Vec::with_capacity(args.max_density.len());
+    for spec in &args.max_density {
+        let Some(limit) = spec.resolve(dither_palette) else {
+            eprintln!("--max-density {spec}: no such dither-palette entry");
+            return ExitCode::FAILURE;
+        };
+        max_density.push(limit);
+    }
+    if args.transparent_index.is_some() && args.alpha_mode != AlphaHandling::Mask {
+        eprintln!("--transparent-index only applies to --alpha-mode mask");
+        return ExitCode::FAILURE;
+    }
     println!("Opening image");
-    let input = image::ImageReader::open(&args.input_file)
+    let decoded = image::ImageReader::open(input_file)
         .unwrap()
         .decode()
-        .unwrap()
-        .into_rgb32f();
+        .unwrap();
+    // Float formats store linear light; integer ones are sRGB-encoded.
+    let linear_input = matches!(
+        decoded.color(),
+        image::ColorType::Rgb32F | image::ColorType::Rgba32F
+    );
+    let background = args.background.map(|c| c as f32 / 255.0);
+    let alpha_mask = match args.alpha_mode {
+        AlphaHandling::Mask => DynamicImageIo::alpha_mask(&decoded),
+        AlphaHandling::Composite | AlphaHandling::Ignore => None,
+    };
+    let mut input = match args.alpha_mode {
+        AlphaHandling::Ignore => DynamicImageIo::new(decoded),
+        AlphaHandling::Composite | AlphaHandling::Mask => {
+            DynamicImageIo::composited(decoded, background, args.alpha)
+        }
+    }
+    .into_inner();
+    if args.tonemap != ToneMap::None {
+        for pixel in input.pixels_mut() {
+            let linear = if linear_input {
+                pixel.0
+            } else {
+                pixel.0.map(srgb_to_linear)
+            };
+            pixel.0 = tonemap(linear, args.tonemap).map(linear_to_srgb);
+        }
+    }
+    if let Some(hsv) = &args.hsv {
+        for pixel in input.pixels_mut() {
+            pixel.0 = hsv.apply(pixel.0);
+        }
+    }
+    if args.bpc {
+        let panel_black = dither_palette
+            .iter()
+            .min_by(|a, b| a.brightness().total_cmp(&b.brightness()))
+            .map_or([0; 3], |c| *c)
+            .to_point();
+        for pixel in input.pixels_mut() {
+            pixel.0 = black_point_compensate(pixel.0, panel_black.into());
+        }
+    }
     println!("Opened image");
+    let non_finite = input
+        .pixels()
+        .filter(|p| !p.0.iter().all(|c| c.is_finite()))
+        .count();
 
-    let dither_palette = args.dither_palette.as_rgb_slice();
     println!("Dither palette used:");
     for color in dither_palette {
         println!("  #{:02X}{:02X}{:02X},", color[0], color[1], color[2]);
     }
 
+    let strategy = args
+        .strategy
+        .unwrap_or_else(|| default_strategy(dither_palette));
+    // Fix the seed up front so the recorded configuration reproduces it.
+    let noise = match args.noise.clone() {
+        NoiseSource::White => NoiseSource::WhiteSeeded(rand::random()),
+        noise => noise,
+    };
+
+    // The dither's indices are written into the output palette verbatim,
+    // so a size mismatch would emit indices the output palette lacks.
+    if dither_palette.len() != args.output_palette.as_rgb_slice().len() {
+        eprintln!(
+            "Dither palette has {} colours but output palette has {}",
+            dither_palette.len(),
+            args.output_palette.as_rgb_slice().len()
+        );
+        std::process::exit(1);
+    }
+    let tiles = args.tiles.as_deref().map(|path| {
+        load_tiles(path, dither_palette.len()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+    });
+
     let output_width = input.width();
     let output_height = input.height();
     let output_palette: Vec<Rgb<u8>> = args
@@ -55,19 +1317,486 @@ fn main() {
         output_height,
         VerifiedPalette::new(output_palette).unwrap(),
     );
-    let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+    let mut inout = Progress::new(ImageCombinedRW::new(input, writer).unwrap());
 
     let palette_rgb: Vec<Rgb<u8>> = dither_palette.iter().map(|&c| Rgb(c)).collect();
-    let ditherer: Box<dyn DynDitherer<_>> = decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
-        args.strategy,
-        args.noise,
-        &palette_rgb,
-        args.diffuse.to_matrix(),
-    )
-    .unwrap();
-    ditherer.dyn_dither_into(&mut inout);
+    let previous = args.prev.as_deref().map(|path| {
+        load_previous_frame(
+            path,
+            &inout.inner.writer.palette.palette,
+            args.prev_tolerance,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+    });
+    if let Some(previous) = &previous
+        && (previous.width() != output_width as usize
+            || previous.height() != output_height as usize)
+    {
+        eprintln!(
+            "Previous frame is {}x{} but input is {output_width}x{output_height}",
+            previous.width(),
+            previous.height()
+        );
+        std::process::exit(1);
+    }
+    let mask = args.ink_mask.as_deref().map(|spec| {
+        load_ink_mask(spec, dither_palette.len()).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+    });
+    if let Some(mask) = &mask
+        && (mask.width() != output_width as usize || mask.height() != output_height as usize)
+    {
+        eprintln!(
+            "Ink mask is {}x{} but input is {output_width}x{output_height}",
+            mask.width(),
+            mask.height()
+        );
+        std::process::exit(1);
+    }
+    let diffuse_mask = args.diffuse_mask.as_deref().map(|path| {
+        load_diffuse_mask(path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        })
+    });
+    let diffuse_mask = match alpha_mask {
+        Some(_) if diffuse_mask.is_some() => {
+            eprintln!("--alpha-mode mask can't be combined with --diffuse-mask");
+            std::process::exit(1);
+        }
+        Some(_)
+            if args.subpixel.is_some()
+                || args.lab_diffusion
+                || args.error_space == ErrorSpace::Rgb =>
+        {
+            eprintln!(
+                "--alpha-mode mask doesn't apply to --subpixel, --lab-diffusion or --error-space rgb"
+            );
+            std::process::exit(1);
+        }
+        Some(mask) => {
+            let index = match args.transparent_index {
+                Some(index) if index >= dither_palette.len() => {
+                    eprintln!("--transparent-index {index}: no such dither-palette entry");
+                    std::process::exit(1);
+                }
+                Some(index) => index,
+                None => nearest_index(args.background, dither_palette),
+            };
+            Some(mask.with_fill(Some(index)))
+        }
+        None => diffuse_mask,
+    };
+    if let Some(mask) = &diffuse_mask
+        && (mask.width() != output_width as usize || mask.height() != output_height as usize)
+    {
+        eprintln!(
+            "Diffuse mask is {}x{} but input is {output_width}x{output_height}",
+            mask.width(),
+            mask.height()
+        );
+        std::process::exit(1);
+    }
+    let options = FactoryOptions {
+        mixing: args.mixing,
+        ink_bias: args.ink_bias.clone(),
+        ink_gamma,
+        index_order_seed: args.index_order_seed,
+        previous,
+        mask,
+        diffuse_mask,
+        noise_amplitude: args.noise_amplitude,
+        tie_margin: args.tie_margin,
+        noise_offset: args.noise_offset,
+        noise_row_phase: args.noise_row_phase,
+        compactness: args.compactness,
+        max_inks: args.max_inks.map(usize::from),
+        neutral_lock: args.neutral_lock,
+        non_finite_fallback: args.non_finite_fallback,
+        edges: args.edges,
+        scan: args.scan,
+        lab_diffusion: args.lab_diffusion,
+        error_space: args.error_space,
+        lookahead: args.lookahead,
+        confidence_weighting: args.confidence_weighting,
+        octahedron_axes: args.axes.as_slice().try_into().ok(),
+        ..Default::default()
+    };
+    if non_finite > 0 {
+        match args.non_finite_fallback {
+            Some(index) => {
+                println!("Replacing {non_finite} non-finite pixels with palette entry {index}")
+            }
+            None => {
+                println!("Replacing {non_finite} non-finite pixels with the darkest palette entry")
+            }
+        }
+    }
+    if let Some(dir) = &args.weights_dir {
+        let decomposer = decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options)
+            .unwrap_or_else(exit_with);
+        write_weight_images(dir, &inout.inner.reader, decomposer.as_ref());
+        println!("Wrote decomposition weights to {dir}");
+    }
+    if let Some(path) = &args.weights_tiff {
+        let decomposer = decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options)
+            .unwrap_or_else(exit_with);
+        let tiff = encode_weights_tiff(&inout.inner.reader, decomposer.as_ref()).unwrap();
+        std::fs::write(path, tiff).unwrap();
+        println!("Wrote decomposition weights to {path}");
+    }
+    if let Some(path) = &args.axis_map {
+        let axis = octahedron_axis_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options)
+            .unwrap_or_else(exit_with);
+        axis_map(&inout.inner.reader, axis).save(path).unwrap();
+        println!("Wrote axis map to {path}");
+    }
+    if args.check_decompositions {
+        let decomposer = decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options)
+            .unwrap_or_else(exit_with);
+        let failures = check_decompositions(&inout.inner.reader, decomposer.as_ref(), &palette_rgb);
+        if failures > 0 {
+            eprintln!("{failures} pixels have invalid decompositions");
+            return ExitCode::FAILURE;
+        }
+        println!("All decompositions are valid");
+    }
+    let diffuse = if args.pick_diffusion {
+        println!("Scoring diffusion kernels on a preview:");
+        let diffuse = pick_diffusion(
+            &inout.inner.reader,
+            strategy,
+            &noise,
+            &palette_rgb,
+            &options,
+        );
+        println!("Using {diffuse}");
+        diffuse
+    } else {
+        args.diffuse
+    };
+    let (diffusion, ditherer): (_, Box<dyn DynResumableDitherer<_>>) = match diffuse.try_to_matrix()
+    {
+        Some(matrix) => (
+            DiffusionSetting::Fixed(DiffusionCoefficients::of(&matrix)),
+            build_ditherer(
+                strategy,
+                &noise,
+                &palette_rgb,
+                tiles.as_deref(),
+                matrix,
+                &options,
+            ),
+        ),
+        None => {
+            let matrix = adaptive_diffusion(&inout.inner);
+            (
+                DiffusionSetting::Adaptive {
+                    tile_size: DEFAULT_ADAPTIVE_TILE,
+                    threshold: DEFAULT_ADAPTIVE_THRESHOLD,
+                },
+                build_ditherer(
+                    strategy,
+                    &noise,
+                    &palette_rgb,
+                    tiles.as_deref(),
+                    matrix,
+                    &options,
+                ),
+            )
+        }
+    };
+    let dither_config = DitherConfig {
+        palette: dither_palette.to_vec(),
+        output_palette: args.output_palette.as_rgb_slice().to_vec(),
+        ink_gamma: options.ink_gamma.clone(),
+        strategy,
+        mixing: args.mixing,
+        axes: options.octahedron_axes,
+        compactness: args.compactness,
+        max_inks: options.max_inks,
+        neutral_lock: args.neutral_lock,
+        ink_bias: args.ink_bias.clone(),
+        tiles: tiles
+            .iter()
+            .flatten()
+            .map(|(region, palette)| (*region, palette.iter().map(|c| c.0).collect()))
+            .collect(),
+        diffusion,
+        edges: args.edges,
+        scan: args.scan,
+        error_space: args.error_space,
+        lab_diffusion: args.lab_diffusion,
+        confidence_weighting: args.confidence_weighting,
+        lookahead: args.lookahead,
+        noise,
+        noise_amplitude: args.noise_amplitude,
+        tie_margin: args.tie_margin,
+        noise_offset: args.noise_offset,
+        noise_row_phase: args.noise_row_phase,
+        index_order_seed: args.index_order_seed,
+        non_finite_fallback: args.non_finite_fallback,
+        alpha_mode: args.alpha_mode,
+        alpha: args.alpha,
+        background: args.background,
+        transparent_index: args.transparent_index,
+        tonemap: args.tonemap,
+        hsv: args.hsv,
+        bpc: args.bpc,
+        prev: args.prev.clone(),
+        prev_tolerance: args.prev_tolerance,
+        ink_mask: args.ink_mask.clone(),
+        diffuse_mask: args.diffuse_mask.clone(),
+        passes: args.passes,
+        max_error: args.max_error,
+        match_brightness: args.match_brightness,
+        subpixel: args.subpixel,
+        max_density: max_density.clone(),
+        density_radius: args.density_radius,
+    };
+    let config = dither_config.to_string();
+    if args.print_config {
+        print!("Configuration:\n{config}");
+    }
+    let mut inout = if args.match_brightness || args.passes > 1 {
+        let ImageCombinedRW {
+            reader: input,
+            writer,
+        } = inout.inner;
+        let dither_once = |image| {
+            let writer = PaletteImage::new(output_width, output_height, writer.palette.clone());
+            let mut inout = Progress::new(ImageCombinedRW::new(image, writer).unwrap());
+            ditherer.dyn_dither_into(&mut inout);
+            inout.inner.writer
+        };
+        let passes = usize::from(args.passes);
+        let mut passes_run = None;
+        let mut dither = |image: Rgb32FImage| {
+            let result =
+                multi_pass_until(&image, &palette_rgb, passes, args.max_error, dither_once);
+            passes_run = result.error.map(|error| (result.passes, error));
+            result.output
+        };
+        let output = if args.match_brightness {
+            let matched =
+                match_brightness(&input, &palette_rgb, DEFAULT_BRIGHTNESS_ITERATIONS, dither);
+            println!(
+                "Matched brightness: mean luma {:.4} (input {:.4}) at gain {:.4}",
+                matched.achieved, matched.target, matched.gain
+            );
+            matched.output
+        } else {
+            dither(input.clone())
+        };
+        if let Some((run, error)) = passes_run {
+            println!("Ran {run} of {passes} passes: blurred RMS error {error:.4}");
+        }
+        ImageCombinedRW::new(input, output).unwrap()
+    } else if let Some(layout) = args.subpixel {
+        let decomposer = decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options)
+            .unwrap_or_else(exit_with);
+        let ImageCombinedRW {
+            reader: input,
+            writer,
+        } = inout.inner;
+        let scaled = |size: u32, factor: usize| {
+            u32::try_from(factor)
+                .ok()
+                .and_then(|factor| size.checked_mul(factor))
+        };
+        let (Some(width), Some(height)) = (
+            scaled(output_width, layout.columns),
+            scaled(output_height, layout.rows),
+        ) else {
+            eprintln!("--subpixel {layout} makes the output too large");
+            return ExitCode::FAILURE;
+        };
+        let cells = PaletteImage::new(width, height, writer.palette);
+        let Some(writer) = SubpixelWriter::new(cells, layout) else {
+            eprintln!("--subpixel {layout} has too many cells per pixel");
+            return ExitCode::FAILURE;
+        };
+        // As the factory resolves it for whole-pixel dithering.
+        let fallback = match args.non_finite_fallback {
+            Some(index) if index >= palette_rgb.len() => {
+                return exit_with(FactoryError::FallbackOutOfRange);
+            }
+            Some(index) => index,
+            None => (0..palette_rgb.len())
+                .min_by(|&a, &b| {
+                    palette_rgb[a]
+                        .brightness()
+                        .total_cmp(&palette_rgb[b].brightness())
+                })
+                .unwrap_or(0),
+        };
+        let Some(subpixel) = SubpixelStrategy::new(decomposer.as_ref(), layout) else {
+            eprintln!("--subpixel {layout} needs a non-empty palette");
+            return ExitCode::FAILURE;
+        };
+        let subpixel = subpixel.with_fallback(fallback);
+        let (fixed, adaptive);
+        let matrix: &dyn DiffusionMatrix = match diffuse.try_to_matrix() {
+            Some(matrix) => {
+                fixed = matrix;
+                &fixed
+            }
+            None => {
+                adaptive = adaptive_diffusion(&input);
+                &adaptive
+            }
+        };
+        let mut inout = Progress::new(ImageCombinedRW::new(input, writer).unwrap());
+        diffuse_dither_with_edges(
+            &subpixel,
+            matrix,
+            &mut inout,
+            args.scan.is_serpentine(matrix),
+            args.edges,
+        );
+        let ImageCombinedRW { reader, writer } = inout.inner;
+        // The sizes differ, so `ImageCombinedRW::new` would refuse them.
+        ImageCombinedRW {
+            reader,
+            writer: writer.inner,
+        }
+    } else if let Some(path) = &args.checkpoint {
+        let (width, height) = (inout.width(), inout.height());
+        let fingerprint = checkpoint_fingerprint(&dither_config, &inout.inner.reader);
+        let (mut row, mut errors) = if args.resume {
+            let checkpoint = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Checkpoint::from_bytes(&bytes).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| {
+                    eprintln!("Cannot resume from {path}: {e}");
+                    std::process::exit(1);
+                });
+            if (checkpoint.width, checkpoint.height) != (width, height)
+                || checkpoint.config != fingerprint
+            {
+                eprintln!(
+                    "Cannot resume from {path}: it was made with another input or configuration \
+                     (for white noise, pass the seed printed then as --noise white:<seed>)"
+                );
+                std::process::exit(1);
+            }
+            checkpoint.restore(&mut inout);
+            println!("Resuming at row {} of {height}", checkpoint.next_row);
+            (checkpoint.next_row, checkpoint.errors)
+        } else {
+            (0, Vec::new())
+        };
+        let mut saved = Instant::now();
+        while row < height {
+            let end = (row + CHECKPOINT_ROWS).min(height);
+            ditherer
+                .dyn_dither_rows_into(&mut inout, row..end, &mut errors)
+                .unwrap_or_else(|e| {
+                    eprintln!("Cannot resume from {path}: {e}");
+                    std::process::exit(1);
+                });
+            row = end;
+            if row < height && saved.elapsed() >= CHECKPOINT_INTERVAL {
+                let checkpoint = Checkpoint::capture(
+                    &inout.inner.writer,
+                    width,
+                    height,
+                    row,
+                    errors.clone(),
+                    fingerprint.clone(),
+                )
+                .unwrap_or_else(|| {
+                    eprintln!("--checkpoint needs an output palette of at most 256 entries");
+                    std::process::exit(1);
+                });
+                write_checkpoint(path, &checkpoint);
+                saved = Instant::now();
+            }
+        }
+        // Already gone if the run finished before the first save.
+        let _ = std::fs::remove_file(path);
+        inout.inner
+    } else {
+        ditherer.dyn_dither_into(&mut inout);
+        inout.inner
+    };
+    if !max_density.is_empty() {
+        let palette_points: Vec<_> = palette_rgb.iter().map(|c| c.to_point()).collect();
+        limit_density(
+            &mut inout.writer,
+            &palette_points,
+            &max_density,
+            args.density_radius,
+        );
+    }
 
-    let png_bytes = inout.writer.to_png().unwrap();
-    std::fs::write(&args.output_file, png_bytes).unwrap();
+    if let Err(e) = inout.writer.verify() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    if let Some(path) = &args.sidecar {
+        let decomposer = decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &options)
+            .unwrap_or_else(exit_with);
+        let sidecar = Sidecar::measure(
+            &inout.reader,
+            &inout.writer,
+            &palette_rgb,
+            decomposer.as_ref(),
+            dither_config,
+        )
+        .unwrap();
+        std::fs::write(path, sidecar.to_string()).unwrap();
+    }
+    if args.stats {
+        print_usage(&inout.writer);
+        return ExitCode::SUCCESS;
+    }
+    let text: &[(&str, &str)] = if args.embed_config {
+        &[(CONFIG_PNG_KEYWORD, &config)]
+    } else {
+        &[]
+    };
+    let png_format = match args.format {
+        OutputFormat::Png(format) => format,
+        OutputFormat::ReTerminalE1002 => {
+            let buffer = match inout.writer.to_reterminal_e1002() {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+            };
+            if let Some(output_file) = &args.output_file {
+                std::fs::write(output_file, buffer).unwrap();
+            }
+            println!("Done");
+            return ExitCode::SUCCESS;
+        }
+    };
+    let png_bytes = inout.writer.encode(png_format, text).unwrap();
+    if args.verify {
+        let found = find_non_palette_pixel(&png_bytes, &inout.writer.palette.palette)
+            .unwrap_or_else(|e| {
+                eprintln!("Verification failed: {e}");
+                std::process::exit(1)
+            });
+        if let Some((x, y, pixel)) = found {
+            eprintln!(
+                "Verification failed: pixel ({x}, {y}) is #{:02X}{:02X}{:02X}, not an output-palette colour",
+                pixel[0], pixel[1], pixel[2]
+            );
+            std::process::exit(1);
+        }
+        println!("Verified: every pixel is an output-palette colour");
+    }
+    if let Some(output_file) = &args.output_file {
+        std::fs::write(output_file, png_bytes).unwrap();
+    }
     println!("Done");
+    ExitCode::SUCCESS
 }
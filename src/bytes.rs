@@ -0,0 +1,65 @@
+//! Little-endian cursor helpers for the fixed-layout blobs produced by
+//! e.g. [`OctahedronDecomposer::to_bytes`](crate::decompose::octahedron::OctahedronDecomposer::to_bytes).
+
+/// Writes into a caller-sized buffer. Writes past the end are dropped;
+/// callers size the buffer from the matching `SERIALIZED_LEN` constant.
+pub(crate) struct ByteWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> ByteWriter<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        if let Some(slot) = self.buf.get_mut(self.pos..self.pos + bytes.len()) {
+            slot.copy_from_slice(bytes);
+        }
+        self.pos += bytes.len();
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    pub(crate) fn f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.bytes(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Reads back what [`ByteWriter`] wrote. Every getter returns `None` once
+/// the input runs out; [`f32s`](Self::f32s) also rejects non-finite values.
+pub(crate) struct ByteReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub(crate) fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (head, rest) = self.buf.split_first_chunk::<N>()?;
+        self.buf = rest;
+        Some(*head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.bytes::<1>().map(|[value]| value)
+    }
+
+    pub(crate) fn f32s<const N: usize>(&mut self) -> Option<[f32; N]> {
+        let values = crate::array_util::opt_array_transpose(core::array::from_fn(|_| {
+            self.bytes::<4>().map(f32::from_le_bytes)
+        }))?;
+        values.iter().all(|v| v.is_finite()).then_some(values)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
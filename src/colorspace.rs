@@ -0,0 +1,629 @@
+//! Per-pixel colour adjustments applied to the input before decomposition,
+//! alpha compositing onto a background, tone mapping for high-dynamic-range
+//! input, and the CIELAB conversion used for Lab error diffusion.
+//!
+//! The HSV adjustments are artistic controls, not calibration: a hue
+//! rotation or a saturation boost changes *which* colour gets dithered,
+//! e.g. to push greens toward the panel's green ink. All RGB channels are
+//! sRGB-encoded values in `[0, 1]`, the same space the RGB decomposers'
+//! inputs come from.
+
+use nalgebra::ComplexField;
+use num_traits::Euclid;
+
+/// D65 reference white in XYZ, with `Y = 1`.
+pub const D65_WHITE: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// sRGB transfer function: encoded channel to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ComplexField::powf((c + 0.055) / 1.055, 2.4)
+    }
+}
+
+/// Inverse of [`srgb_to_linear`]. Negative input maps through the linear
+/// segment, so out-of-gamut values survive a round trip.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * ComplexField::powf(c, 1.0 / 2.4) - 0.055
+    }
+}
+
+/// CIE `f` from XYZ ratios to Lab, with its linear toe.
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+        ComplexField::cbrt(t)
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+/// Inverse of [`lab_f`].
+fn lab_f_inv(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA {
+        t * t * t
+    } else {
+        3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+/// sRGB (D65) to CIELAB `[L*, a*, b*]` relative to [`D65_WHITE`]; white
+/// is `[100, 0, 0]`.
+pub fn srgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let xyz = [
+        0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+        0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b,
+        0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b,
+    ];
+    let [fx, fy, fz] = core::array::from_fn(|i| lab_f(xyz[i] / D65_WHITE[i]));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Inverse of [`srgb_to_lab`]. Colours outside the sRGB gamut come back
+/// with channels outside `[0, 1]`; nothing is clamped.
+pub fn lab_to_srgb([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let f = [fy + a / 500.0, fy, fy - b / 200.0];
+    let [x, y, z] = core::array::from_fn(|i| lab_f_inv(f[i]) * D65_WHITE[i]);
+    [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ]
+    .map(linear_to_srgb)
+}
+
+/// RGB to `[hue, saturation, value]`, each in `[0, 1]` (hue as a fraction
+/// of a full turn, 0 for grays).
+pub fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+    let hue = if chroma <= 0.0 {
+        0.0
+    } else if max == r {
+        Euclid::rem_euclid(&((g - b) / chroma), &6.0) / 6.0
+    } else if max == g {
+        ((b - r) / chroma + 2.0) / 6.0
+    } else {
+        ((r - g) / chroma + 4.0) / 6.0
+    };
+    let saturation = if max <= 0.0 { 0.0 } else { chroma / max };
+    [hue, saturation, max]
+}
+
+/// Inverse of [`rgb_to_hsv`]; hue wraps, so any real value is accepted.
+pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let sector = (Euclid::rem_euclid(&h, &1.0) * 6.0).min(6.0 - f32::EPSILON);
+    let frac = sector - (sector as u32) as f32;
+    let (p, q, t) = (
+        v * (1.0 - s),
+        v * (1.0 - s * frac),
+        v * (1.0 - s * (1.0 - frac)),
+    );
+    match sector as u32 {
+        0 => [v, t, p],
+        1 => [q, v, p],
+        2 => [p, v, t],
+        3 => [p, q, v],
+        4 => [t, p, v],
+        _ => [v, p, q],
+    }
+}
+
+/// Rotate `pixel`'s hue by `hue_deg` degrees and scale its saturation and
+/// value by `sat_mul` / `val_mul`, clamping both back into `[0, 1]`.
+pub fn adjust_hsv(pixel: [f32; 3], hue_deg: f32, sat_mul: f32, val_mul: f32) -> [f32; 3] {
+    let [h, s, v] = rgb_to_hsv(pixel);
+    hsv_to_rgb([
+        h + hue_deg / 360.0,
+        (s * sat_mul).clamp(0.0, 1.0),
+        (v * val_mul).clamp(0.0, 1.0),
+    ])
+}
+
+/// Black-point compensation: scale `pixel` so input black lands on
+/// `panel_black`, the panel's darkest ink, and white stays white, instead
+/// of everything darker than that ink clipping to it. Each channel maps
+/// linearly from `[0, 1]` onto `[panel_black, 1]`. That is in the same
+/// sRGB-encoded space the RGB decomposers mix in, so a dark ramp becomes
+/// a ramp from the ink towards white the palette can reproduce, at the
+/// cost of lifting the shadows and tinting them like the ink.
+pub fn black_point_compensate(pixel: [f32; 3], panel_black: [f32; 3]) -> [f32; 3] {
+    core::array::from_fn(|c| panel_black[c] + (1.0 - panel_black[c]) * pixel[c])
+}
+
+/// Parsed form of the binary's `--hsv h,s,v` argument: the parameters of
+/// [`adjust_hsv`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HsvAdjustment {
+    pub hue_deg: f32,
+    pub sat_mul: f32,
+    pub val_mul: f32,
+}
+
+impl HsvAdjustment {
+    pub fn apply(&self, pixel: [f32; 3]) -> [f32; 3] {
+        adjust_hsv(pixel, self.hue_deg, self.sat_mul, self.val_mul)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidHsvAdjustment;
+
+impl core::fmt::Display for InvalidHsvAdjustment {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(
+            "invalid HSV adjustment (expected <hue degrees>,<saturation factor>,<value factor>)",
+        )
+    }
+}
+
+impl core::error::Error for InvalidHsvAdjustment {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for HsvAdjustment {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{},{},{}", self.hue_deg, self.sat_mul, self.val_mul)
+    }
+}
+
+impl core::str::FromStr for HsvAdjustment {
+    type Err = InvalidHsvAdjustment;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(|part| part.trim().parse::<f32>());
+        let mut next = || match parts.next() {
+            Some(Ok(value)) if value.is_finite() => Ok(value),
+            _ => Err(InvalidHsvAdjustment),
+        };
+        let (hue_deg, sat_mul, val_mul) = (next()?, next()?, next()?);
+        if parts.next().is_some() || sat_mul < 0.0 || val_mul < 0.0 {
+            return Err(InvalidHsvAdjustment);
+        }
+        Ok(Self {
+            hue_deg,
+            sat_mul,
+            val_mul,
+        })
+    }
+}
+
+/// How the colour channels of an RGBA pixel relate to its alpha.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum AlphaMode {
+    /// Colour independent of alpha, as PNG stores it.
+    #[default]
+    Straight,
+    /// Encoded sRGB colour already multiplied by alpha, as some
+    /// compositors and GPU readbacks produce.
+    Premultiplied,
+}
+
+impl AlphaMode {
+    pub const LONG_HELP: &'static str = concat!(
+        "How the input's colour channels relate to its alpha.\n\n",
+        "Accepted values:\n",
+        " straight       Colour independent of alpha, as in PNG (default)\n",
+        " premultiplied  Encoded sRGB colour already multiplied by alpha\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidAlphaMode;
+
+impl core::fmt::Display for InvalidAlphaMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid alpha-mode name")
+    }
+}
+
+impl core::error::Error for InvalidAlphaMode {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for AlphaMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Straight => "straight",
+            Self::Premultiplied => "premultiplied",
+        })
+    }
+}
+
+impl core::str::FromStr for AlphaMode {
+    type Err = InvalidAlphaMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "straight" => Ok(Self::Straight),
+            "premultiplied" => Ok(Self::Premultiplied),
+            _ => Err(InvalidAlphaMode),
+        }
+    }
+}
+
+/// What an input's alpha channel does, the binary's `--alpha-mode`
+/// argument.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum AlphaHandling {
+    /// Composite over a background colour; see [`composite_over`].
+    #[default]
+    Composite,
+    /// Leave pixels less than half opaque out of the dither as a reserved
+    /// palette index, and keep error diffusion from crossing them; the
+    /// rest are composited as with [`Composite`](Self::Composite).
+    Mask,
+    /// Drop alpha and dither the colour channels as they are.
+    Ignore,
+}
+
+impl AlphaHandling {
+    pub const LONG_HELP: &'static str = concat!(
+        "What the input's alpha channel does; ignored for inputs without alpha.\n\n",
+        "Accepted values:\n",
+        " composite  Composite over --background (default)\n",
+        " mask       Pixels less than half opaque become --transparent-index and\n",
+        "            error doesn't diffuse through them; the rest are composited\n",
+        " ignore     Drop alpha, dithering the colour channels as they are\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidAlphaHandling;
+
+impl core::fmt::Display for InvalidAlphaHandling {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid alpha handling name")
+    }
+}
+
+impl core::error::Error for InvalidAlphaHandling {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for AlphaHandling {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Composite => "composite",
+            Self::Mask => "mask",
+            Self::Ignore => "ignore",
+        })
+    }
+}
+
+impl core::str::FromStr for AlphaHandling {
+    type Err = InvalidAlphaHandling;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "composite" => Ok(Self::Composite),
+            "mask" => Ok(Self::Mask),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(InvalidAlphaHandling),
+        }
+    }
+}
+
+/// Tone-mapping curve for [`tonemap`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ToneMap {
+    /// Leave values alone, so anything above 1 clips.
+    #[default]
+    None,
+    /// Reinhard's `x / (1 + x)` per channel: never reaches 1, and
+    /// halves a value of 1.
+    Reinhard,
+    /// Narkowicz's rational fit of the ACES filmic curve per channel,
+    /// clamped to `[0, 1]`: darker than the input below about 0.6 linear,
+    /// then a soft shoulder that saturates around 10.
+    Aces,
+}
+
+impl ToneMap {
+    pub const LONG_HELP: &'static str = concat!(
+        "Tone-mapping curve bringing high-dynamic-range (e.g. EXR) input into\n",
+        "range before dithering, instead of clipping everything above 1. Float\n",
+        "formats are taken as linear light, as they store it; 8- and 16-bit\n",
+        "input is sRGB-decoded to linear light first. Either way the result\n",
+        "is sRGB-encoded after mapping.\n\n",
+        "Accepted values:\n",
+        " none      Leave the input alone (default)\n",
+        " reinhard  x / (1 + x) per channel\n",
+        " aces      ACES filmic fit per channel\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidToneMap;
+
+impl core::fmt::Display for InvalidToneMap {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid tone-map name")
+    }
+}
+
+impl core::error::Error for InvalidToneMap {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for ToneMap {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Reinhard => "reinhard",
+            Self::Aces => "aces",
+        })
+    }
+}
+
+impl core::str::FromStr for ToneMap {
+    type Err = InvalidToneMap;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "reinhard" => Ok(Self::Reinhard),
+            "aces" => Ok(Self::Aces),
+            _ => Err(InvalidToneMap),
+        }
+    }
+}
+
+/// Map the linear-light `pixel`, with channels possibly far above 1, into
+/// `[0, 1]` by `operator`, keeping it linear; [`linear_to_srgb`] then
+/// gives the encoded colour the decomposers take. sRGB-encoded input
+/// must go through [`srgb_to_linear`] first. Negative channels map
+/// to 0 for the curves and are kept by [`ToneMap::None`].
+pub fn tonemap(pixel: [f32; 3], operator: ToneMap) -> [f32; 3] {
+    pixel.map(|c| match operator {
+        ToneMap::None => c,
+        ToneMap::Reinhard => {
+            let c = c.max(0.0);
+            c / (1.0 + c)
+        }
+        ToneMap::Aces => {
+            let c = c.max(0.0);
+            (c * (2.51 * c + 0.03) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+        }
+    })
+}
+
+/// Composite the sRGB-encoded pixel `[r, g, b, alpha]` over the opaque
+/// sRGB `background`, returning the encoded result.
+///
+/// Blending happens in linear light: averaging encoded values instead
+/// darkens every partly transparent pixel (50% red over white would give
+/// `[1, 0.5, 0.5]` rather than `[1, 0.735, 0.735]`), which shows up as
+/// dark fringes around antialiased edges. [`AlphaMode::Premultiplied`]
+/// colour is divided by alpha before linearising, since the transfer
+/// function doesn't commute with the multiplication. Alpha is clamped to
+/// `[0, 1]`; fully transparent pixels are the background.
+pub fn composite_over(rgba: [f32; 4], background: [f32; 3], mode: AlphaMode) -> [f32; 3] {
+    let [r, g, b, alpha] = rgba;
+    let alpha = alpha.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return background;
+    }
+    let color = match mode {
+        AlphaMode::Straight => [r, g, b],
+        AlphaMode::Premultiplied => [r, g, b].map(|c| c / alpha),
+    };
+    core::array::from_fn(|i| {
+        let (fg, bg) = (srgb_to_linear(color[i]), srgb_to_linear(background[i]));
+        linear_to_srgb(fg * alpha + bg * (1.0 - alpha))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn half_turn_maps_red_to_cyan() {
+        assert_close(
+            adjust_hsv([1.0, 0.0, 0.0], 180.0, 1.0, 1.0),
+            [0.0, 1.0, 1.0],
+        );
+        assert_close(
+            adjust_hsv([1.0, 0.0, 0.0], -180.0, 1.0, 1.0),
+            [0.0, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn identity_adjustment_round_trips() {
+        for pixel in [
+            [0.2, 0.5, 0.9],
+            [0.7, 0.7, 0.7],
+            [0.0, 0.0, 0.0],
+            [0.9, 0.1, 0.4],
+        ] {
+            assert_close(adjust_hsv(pixel, 0.0, 1.0, 1.0), pixel);
+        }
+    }
+
+    #[test]
+    fn zero_saturation_is_gray() {
+        assert_close(adjust_hsv([0.8, 0.2, 0.4], 30.0, 0.0, 1.0), [0.8, 0.8, 0.8]);
+    }
+
+    #[test]
+    fn black_point_compensation_keeps_white() {
+        let panel_black = [0.1, 0.0, 0.2];
+        assert_close(black_point_compensate([0.0; 3], panel_black), panel_black);
+        assert_close(black_point_compensate([1.0; 3], panel_black), [1.0; 3]);
+        assert_close(
+            black_point_compensate([0.5; 3], panel_black),
+            [0.55, 0.5, 0.6],
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn black_point_compensation_keeps_shadow_detail() {
+        use crate::decompose::DecomposerInputColor;
+        use crate::decompose::naive::NaiveDecomposer;
+        use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+        use crate::dither::{SliceImage, dither_indices};
+        use crate::palette::SPECTRA6;
+        use alloc::vec::Vec;
+
+        let points = SPECTRA6.map(|c| c.to_point());
+        let decomposer = NaiveDecomposer::new(&points).unwrap();
+        // A gray ramp from black to 0.08, below every channel of the
+        // panel's black ink.
+        let (width, height) = (64, 16);
+        let darks = |compensate: bool| {
+            let pixels: Vec<[f32; 3]> = (0..width * height)
+                .map(|i| {
+                    let gray = [0.08 * (i % width) as f32 / (width - 1) as f32; 3];
+                    match compensate {
+                        true => black_point_compensate(gray, points[0].into()),
+                        false => gray,
+                    }
+                })
+                .collect();
+            let image = SliceImage::new(width, height, &pixels).unwrap();
+            let mut out = alloc::vec![0; width * height];
+            dither_indices(
+                &decomposer,
+                &FLOYD_STEINBERG,
+                None,
+                &image,
+                &image,
+                &mut out,
+                true,
+            )
+            .unwrap();
+            // Pixels other than black in each quarter of the ramp.
+            let lit = |columns: core::ops::Range<usize>| {
+                (0..width * height)
+                    .filter(|i| columns.contains(&(i % width)) && out[*i] != 0)
+                    .count()
+            };
+            [lit(0..16), lit(16..32), lit(32..48), lit(48..64)]
+        };
+        let (plain, compensated) = (darks(false), darks(true));
+        // Uncompensated, the ramp sits below the ink and mostly clips to
+        // it; compensated, it brightens from the ink up, quarter by
+        // quarter, over a much wider range of ink mixes.
+        assert!(
+            compensated.windows(2).all(|pair| pair[0] < pair[1]),
+            "{compensated:?}"
+        );
+        assert!(
+            compensated[3] - compensated[0] > 4 * (plain[3] - plain[0]),
+            "{plain:?} vs {compensated:?}"
+        );
+    }
+
+    #[test]
+    fn lab_reference_values_and_round_trip() {
+        let close = |actual: [f32; 3], expected: [f32; 3], tolerance: f32| {
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < tolerance, "{actual:?} != {expected:?}");
+            }
+        };
+        close(srgb_to_lab([1.0; 3]), [100.0, 0.0, 0.0], 1e-3);
+        close(srgb_to_lab([0.0; 3]), [0.0; 3], 1e-4);
+        close(srgb_to_lab([1.0, 0.0, 0.0]), [53.24, 80.09, 67.20], 0.02);
+        close(srgb_to_lab([0.0, 0.0, 1.0]), [32.30, 79.19, -107.86], 0.02);
+        for rgb in [
+            [0.2, 0.5, 0.9],
+            [0.01, 0.02, 0.0],
+            [0.7, 0.7, 0.7],
+            [1.0, 1.0, 0.0],
+        ] {
+            close(lab_to_srgb(srgb_to_lab(rgb)), rgb, 1e-4);
+        }
+    }
+
+    #[test]
+    fn parses_triplet() {
+        assert_eq!(
+            "180, 1.5,0.9".parse::<HsvAdjustment>(),
+            Ok(HsvAdjustment {
+                hue_deg: 180.0,
+                sat_mul: 1.5,
+                val_mul: 0.9
+            })
+        );
+        assert!("180,1".parse::<HsvAdjustment>().is_err());
+        assert!("180,1,1,1".parse::<HsvAdjustment>().is_err());
+        assert!("0,-1,1".parse::<HsvAdjustment>().is_err());
+    }
+
+    #[test]
+    fn half_transparent_red_over_white_blends_in_linear_light() {
+        let white = [1.0; 3];
+        // Linear (1, 0, 0) and (1, 1, 1) average to (1, 0.5, 0.5).
+        let expected = [1.0, linear_to_srgb(0.5), linear_to_srgb(0.5)];
+        assert!((expected[1] - 0.735_4).abs() < 1e-4);
+        let straight = composite_over([1.0, 0.0, 0.0, 0.5], white, AlphaMode::Straight);
+        assert_close(straight, expected);
+        let premultiplied = composite_over([0.5, 0.0, 0.0, 0.5], white, AlphaMode::Premultiplied);
+        assert_close(premultiplied, expected);
+        // Opaque and fully transparent pixels pass through unblended.
+        let red = [0.8, 0.2, 0.1];
+        assert_close(
+            composite_over([0.8, 0.2, 0.1, 1.0], white, AlphaMode::Straight),
+            red,
+        );
+        for mode in [AlphaMode::Straight, AlphaMode::Premultiplied] {
+            assert_close(composite_over([0.0, 0.0, 0.0, 0.0], white, mode), white);
+        }
+    }
+
+    #[test]
+    fn tonemapping_keeps_highlights_apart() {
+        for operator in [ToneMap::Reinhard, ToneMap::Aces] {
+            let mapped: [[f32; 3]; 3] =
+                [1.0, 2.0, 4.0].map(|v| tonemap([v, v * 0.5, 0.0], operator));
+            for pixel in mapped {
+                assert!(
+                    pixel.iter().all(|c| (0.0..1.0).contains(c)),
+                    "{operator}: {pixel:?}"
+                );
+            }
+            // Brighter input stays brighter: nothing clips to one value.
+            assert!(
+                mapped[0][0] < mapped[1][0] && mapped[1][0] < mapped[2][0],
+                "{operator}"
+            );
+            assert!(mapped[2][1] < mapped[2][0], "{operator}");
+            let encoded = mapped[2].map(linear_to_srgb);
+            assert!(encoded.iter().all(|c| (0.0..1.0).contains(c)), "{operator}");
+        }
+        assert_close(tonemap([4.0, 0.5, -0.1], ToneMap::None), [4.0, 0.5, -0.1]);
+        assert_close(tonemap([1.0; 3], ToneMap::Reinhard), [0.5; 3]);
+        assert_eq!("aces".parse(), Ok(ToneMap::Aces));
+        assert_eq!("filmic".parse::<ToneMap>(), Err(InvalidToneMap));
+    }
+}
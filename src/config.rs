@@ -0,0 +1,766 @@
+//! Reproducible text record of a dither run's configuration.
+//!
+//! [`DitherConfig`] gathers what determines the output for a given input:
+//! the palettes, decomposition strategy, diffusion, noise and every other
+//! setting of the `dither` binary that changes the result. Its `Display`
+//! form is one `key=value` line per field in a fixed order, and `FromStr`
+//! parses it back to an equal value, so the string can go into a log or a
+//! PNG text chunk (see [`CONFIG_PNG_KEYWORD`]) and later rebuild the same
+//! run. With the `serde` feature it also (de)serializes, e.g. into a
+//! [`Sidecar`](crate::image::sidecar::Sidecar).
+//!
+//! Keys are the binary's option names and each value uses the same
+//! spelling as the corresponding `FromStr` type (and CLI argument), except
+//! diffusion, which records the coefficients themselves rather than a
+//! method name, or the tile size and threshold for adaptive diffusion;
+//! see [`DiffusionSetting`].
+
+use crate::colorspace::{AlphaHandling, AlphaMode, HsvAdjustment, ToneMap};
+use crate::decompose::bias::InkBias;
+use crate::decompose::subtractive::MixingModel;
+use crate::dither::DecomposeStrategy;
+use crate::dither::density::{DEFAULT_DENSITY_RADIUS, DensityLimit};
+use crate::dither::diffuse::EdgeMode;
+use crate::dither::diffusion_matrix::{DiffusionMatrix, ScanOrder};
+use crate::dither::previous::DEFAULT_PREVIOUS_TOLERANCE;
+use crate::dither::rgb::ErrorSpace;
+use crate::dither::subpixel::SubpixelLayout;
+use crate::dither::tiles::Region;
+use crate::noise::NoiseSource;
+use crate::palette::parse_hex_color;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// PNG `tEXt` keyword under which the binaries embed a [`DitherConfig`].
+pub const CONFIG_PNG_KEYWORD: &str = "epd-dither config";
+
+/// A diffusion matrix's divisor and `(dx, dy, weight)` targets, copied out
+/// so they can be printed, parsed and used as a matrix again.
+///
+/// Matrices that vary their weights per pixel (overriding
+/// [`weights_at`](DiffusionMatrix::weights_at)) are recorded by their
+/// static targets only.
+///
+/// Text form: `divisor:dx,dy,w;dx,dy,w;…`, e.g. `16:1,0,7;-1,1,3;0,1,5;1,1,1`
+/// for Floyd-Steinberg and `1:` for no diffusion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffusionCoefficients {
+    pub divisor: usize,
+    pub targets: Vec<(isize, usize, usize)>,
+}
+
+impl DiffusionCoefficients {
+    pub fn of<M: DiffusionMatrix + ?Sized>(matrix: &M) -> Self {
+        Self {
+            divisor: matrix.divisor(),
+            targets: matrix.targets().to_vec(),
+        }
+    }
+}
+
+impl DiffusionMatrix for DiffusionCoefficients {
+    fn divisor(&self) -> usize {
+        self.divisor
+    }
+    fn targets(&self) -> &[(isize, usize, usize)] {
+        &self.targets
+    }
+}
+
+impl core::fmt::Display for DiffusionCoefficients {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:", self.divisor)?;
+        for (i, (dx, dy, weight)) in self.targets.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{dx},{dy},{weight}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How a run diffused its error.
+///
+/// Text form: [`DiffusionCoefficients`]'s for a fixed kernel, and
+/// `adaptive:TILE,THRESHOLD` for adaptive diffusion, e.g.
+/// `adaptive:16,0.001`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum DiffusionSetting {
+    /// One kernel for every pixel.
+    Fixed(DiffusionCoefficients),
+    /// [`AdaptiveDiffusion`](crate::dither::diffusion_matrix::AdaptiveDiffusion)
+    /// classified from the input with these parameters; see
+    /// [`from_luma`](crate::dither::diffusion_matrix::AdaptiveDiffusion::from_luma).
+    Adaptive { tile_size: usize, threshold: f32 },
+}
+
+impl core::fmt::Display for DiffusionSetting {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Fixed(coefficients) => write!(f, "{coefficients}"),
+            Self::Adaptive {
+                tile_size,
+                threshold,
+            } => write!(f, "adaptive:{tile_size},{threshold}"),
+        }
+    }
+}
+
+impl core::str::FromStr for DiffusionSetting {
+    type Err = InvalidDitherConfig;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(params) = s.strip_prefix("adaptive:") else {
+            return s.parse().map(Self::Fixed);
+        };
+        let (tile_size, threshold) = params.split_once(',').ok_or(InvalidDitherConfig)?;
+        let tile_size = tile_size
+            .parse::<usize>()
+            .map_err(|_| InvalidDitherConfig)?;
+        let threshold = threshold.parse::<f32>().map_err(|_| InvalidDitherConfig)?;
+        if tile_size == 0 || !threshold.is_finite() {
+            return Err(InvalidDitherConfig);
+        }
+        Ok(Self::Adaptive {
+            tile_size,
+            threshold,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidDitherConfig;
+
+impl core::fmt::Display for InvalidDitherConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid dither configuration")
+    }
+}
+
+impl core::error::Error for InvalidDitherConfig {}
+
+impl core::str::FromStr for DiffusionCoefficients {
+    type Err = InvalidDitherConfig;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (divisor, targets) = s.split_once(':').ok_or(InvalidDitherConfig)?;
+        let divisor = divisor.parse::<usize>().map_err(|_| InvalidDitherConfig)?;
+        if divisor == 0 {
+            return Err(InvalidDitherConfig);
+        }
+        let targets = if targets.is_empty() {
+            Vec::new()
+        } else {
+            targets
+                .split(';')
+                .map(|target| {
+                    let mut parts = target.split(',');
+                    let mut next = || parts.next().ok_or(InvalidDitherConfig);
+                    let dx = next()?.parse::<isize>().map_err(|_| InvalidDitherConfig)?;
+                    let dy = next()?.parse::<usize>().map_err(|_| InvalidDitherConfig)?;
+                    let weight = next()?.parse::<usize>().map_err(|_| InvalidDitherConfig)?;
+                    if parts.next().is_some() {
+                        return Err(InvalidDitherConfig);
+                    }
+                    Ok((dx, dy, weight))
+                })
+                .collect::<Result<_, _>>()?
+        };
+        Ok(Self { divisor, targets })
+    }
+}
+
+/// Everything besides the input image that decides a dither's output:
+/// one field per output-affecting option of the `dither` binary, holding
+/// the value the run actually used (the calibrated palette, the loaded
+/// tiles, the seeded noise).
+///
+/// Input files the run reads besides the image (previous frame, masks)
+/// are recorded by path.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct DitherConfig {
+    /// Colours the decomposition works with (the dither palette, not the
+    /// output palette the PNG is labelled with).
+    pub palette: Vec<[u8; 3]>,
+    pub output_palette: Vec<[u8; 3]>,
+    /// Response exponent per ink, empty for linear inks.
+    pub ink_gamma: Vec<f32>,
+    #[cfg_attr(feature = "serde", serde(with = "text"))]
+    pub strategy: DecomposeStrategy,
+    pub mixing: MixingModel,
+    pub axes: Option<[(usize, usize); 3]>,
+    pub compactness: f32,
+    pub max_inks: Option<usize>,
+    pub neutral_lock: Option<f32>,
+    pub ink_bias: Vec<InkBias>,
+    pub tiles: Vec<(Region, Vec<[u8; 3]>)>,
+    pub diffusion: DiffusionSetting,
+    pub edges: EdgeMode,
+    pub scan: ScanOrder,
+    pub error_space: ErrorSpace,
+    pub lab_diffusion: bool,
+    pub confidence_weighting: bool,
+    pub lookahead: bool,
+    /// Use [`NoiseSource::WhiteSeeded`] rather than
+    /// [`NoiseSource::White`] for a config that reproduces the run.
+    #[cfg_attr(feature = "serde", serde(with = "text"))]
+    pub noise: NoiseSource,
+    pub noise_amplitude: f32,
+    pub tie_margin: f32,
+    pub noise_offset: [usize; 2],
+    pub noise_row_phase: bool,
+    pub index_order_seed: Option<u64>,
+    pub non_finite_fallback: Option<usize>,
+    pub alpha_mode: AlphaHandling,
+    pub alpha: AlphaMode,
+    pub background: [u8; 3],
+    pub transparent_index: Option<usize>,
+    pub tonemap: ToneMap,
+    pub hsv: Option<HsvAdjustment>,
+    pub bpc: bool,
+    pub prev: Option<String>,
+    pub prev_tolerance: f32,
+    pub ink_mask: Option<String>,
+    pub diffuse_mask: Option<String>,
+    pub passes: u16,
+    pub max_error: Option<f32>,
+    pub match_brightness: bool,
+    pub subpixel: Option<SubpixelLayout>,
+    pub max_density: Vec<DensityLimit>,
+    pub density_radius: usize,
+}
+
+impl DitherConfig {
+    /// `palette`, `strategy`, `diffusion` and `noise`, with every other
+    /// setting at the binary's default.
+    pub fn new(
+        palette: Vec<[u8; 3]>,
+        strategy: DecomposeStrategy,
+        diffusion: DiffusionSetting,
+        noise: NoiseSource,
+    ) -> Self {
+        Self {
+            output_palette: palette.clone(),
+            palette,
+            ink_gamma: Vec::new(),
+            strategy,
+            mixing: MixingModel::default(),
+            axes: None,
+            compactness: 0.0,
+            max_inks: None,
+            neutral_lock: None,
+            ink_bias: Vec::new(),
+            tiles: Vec::new(),
+            diffusion,
+            edges: EdgeMode::default(),
+            scan: ScanOrder::default(),
+            error_space: ErrorSpace::default(),
+            lab_diffusion: false,
+            confidence_weighting: false,
+            lookahead: false,
+            noise,
+            noise_amplitude: 1.0,
+            tie_margin: 0.0,
+            noise_offset: [0, 0],
+            noise_row_phase: false,
+            index_order_seed: None,
+            non_finite_fallback: None,
+            alpha_mode: AlphaHandling::default(),
+            alpha: AlphaMode::default(),
+            background: [255; 3],
+            transparent_index: None,
+            tonemap: ToneMap::default(),
+            hsv: None,
+            bpc: false,
+            prev: None,
+            prev_tolerance: DEFAULT_PREVIOUS_TOLERANCE,
+            ink_mask: None,
+            diffuse_mask: None,
+            passes: 1,
+            max_error: None,
+            match_brightness: false,
+            subpixel: None,
+            max_density: Vec::new(),
+            density_radius: DEFAULT_DENSITY_RADIUS,
+        }
+    }
+}
+
+fn write_palette(f: &mut core::fmt::Formatter<'_>, palette: &[[u8; 3]]) -> core::fmt::Result {
+    for (i, [r, g, b]) in palette.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "#{r:02X}{g:02X}{b:02X}")?;
+    }
+    Ok(())
+}
+
+fn write_list<T: core::fmt::Display>(
+    f: &mut core::fmt::Formatter<'_>,
+    items: impl IntoIterator<Item = T>,
+    separator: &str,
+) -> core::fmt::Result {
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+/// Nothing for `None`.
+fn write_option<T: core::fmt::Display>(
+    f: &mut core::fmt::Formatter<'_>,
+    value: &Option<T>,
+) -> core::fmt::Result {
+    match value {
+        Some(value) => write!(f, "{value}"),
+        None => Ok(()),
+    }
+}
+
+/// Text form: one `key=value` line per field, keyed by the binary's
+/// option name; lists are comma-separated (`;` between tiles and axis
+/// pairs) and an absent optional value is left empty.
+impl core::fmt::Display for DitherConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("palette=")?;
+        write_palette(f, &self.palette)?;
+        f.write_str("\noutput-palette=")?;
+        write_palette(f, &self.output_palette)?;
+        writeln!(f)?;
+        f.write_str("ink-gamma=")?;
+        write_list(f, &self.ink_gamma, ",")?;
+        writeln!(f)?;
+        writeln!(f, "strategy={}", self.strategy)?;
+        writeln!(f, "mixing={}", self.mixing)?;
+        f.write_str("axes=")?;
+        if let Some(axes) = &self.axes {
+            write_list(f, axes.iter().map(|(a, b)| alloc::format!("{a},{b}")), ";")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "compactness={}", self.compactness)?;
+        f.write_str("max-inks=")?;
+        write_option(f, &self.max_inks)?;
+        writeln!(f)?;
+        f.write_str("neutral-lock=")?;
+        write_option(f, &self.neutral_lock)?;
+        writeln!(f)?;
+        f.write_str("ink-bias=")?;
+        write_list(f, &self.ink_bias, ",")?;
+        writeln!(f)?;
+        f.write_str("tiles=")?;
+        for (i, (region, palette)) in self.tiles.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{region} ")?;
+            write_palette(f, palette)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "diffusion={}", self.diffusion)?;
+        writeln!(f, "edges={}", self.edges)?;
+        writeln!(f, "scan={}", self.scan)?;
+        writeln!(f, "error-space={}", self.error_space)?;
+        writeln!(f, "lab-diffusion={}", self.lab_diffusion)?;
+        writeln!(f, "confidence-weighting={}", self.confidence_weighting)?;
+        writeln!(f, "lookahead={}", self.lookahead)?;
+        writeln!(f, "noise={}", self.noise)?;
+        writeln!(f, "noise-amplitude={}", self.noise_amplitude)?;
+        writeln!(f, "tie-margin={}", self.tie_margin)?;
+        let [x, y] = self.noise_offset;
+        writeln!(f, "noise-offset={x},{y}")?;
+        writeln!(f, "noise-row-phase={}", self.noise_row_phase)?;
+        f.write_str("index-order-seed=")?;
+        write_option(f, &self.index_order_seed)?;
+        writeln!(f)?;
+        f.write_str("non-finite-fallback=")?;
+        write_option(f, &self.non_finite_fallback)?;
+        writeln!(f)?;
+        writeln!(f, "alpha-mode={}", self.alpha_mode)?;
+        writeln!(f, "alpha={}", self.alpha)?;
+        f.write_str("background=")?;
+        write_palette(f, &[self.background])?;
+        writeln!(f)?;
+        f.write_str("transparent-index=")?;
+        write_option(f, &self.transparent_index)?;
+        writeln!(f)?;
+        writeln!(f, "tonemap={}", self.tonemap)?;
+        f.write_str("hsv=")?;
+        write_option(f, &self.hsv)?;
+        writeln!(f)?;
+        writeln!(f, "bpc={}", self.bpc)?;
+        f.write_str("prev=")?;
+        write_option(f, &self.prev)?;
+        writeln!(f)?;
+        writeln!(f, "prev-tolerance={}", self.prev_tolerance)?;
+        f.write_str("ink-mask=")?;
+        write_option(f, &self.ink_mask)?;
+        writeln!(f)?;
+        f.write_str("diffuse-mask=")?;
+        write_option(f, &self.diffuse_mask)?;
+        writeln!(f)?;
+        writeln!(f, "passes={}", self.passes)?;
+        f.write_str("max-error=")?;
+        write_option(f, &self.max_error)?;
+        writeln!(f)?;
+        writeln!(f, "match-brightness={}", self.match_brightness)?;
+        f.write_str("subpixel=")?;
+        write_option(f, &self.subpixel)?;
+        writeln!(f)?;
+        f.write_str("max-density=")?;
+        write_list(f, &self.max_density, ",")?;
+        writeln!(f)?;
+        writeln!(f, "density-radius={}", self.density_radius)
+    }
+}
+
+fn parse<T: core::str::FromStr>(value: &str) -> Result<T, InvalidDitherConfig> {
+    value.parse().map_err(|_| InvalidDitherConfig)
+}
+
+/// `None` for an empty value.
+fn parse_option<T: core::str::FromStr>(value: &str) -> Result<Option<T>, InvalidDitherConfig> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    parse(value).map(Some)
+}
+
+/// Empty for an empty value.
+fn parse_list<T>(
+    value: &str,
+    separator: char,
+    item: impl Fn(&str) -> Result<T, InvalidDitherConfig>,
+) -> Result<Vec<T>, InvalidDitherConfig> {
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+    value.split(separator).map(item).collect()
+}
+
+fn parse_palette(value: &str) -> Result<Vec<[u8; 3]>, InvalidDitherConfig> {
+    value
+        .split(',')
+        .map(|color| parse_hex_color(color).ok_or(InvalidDitherConfig))
+        .collect()
+}
+
+fn parse_pair(value: &str) -> Result<(usize, usize), InvalidDitherConfig> {
+    let (a, b) = value.split_once(',').ok_or(InvalidDitherConfig)?;
+    Ok((parse(a)?, parse(b)?))
+}
+
+impl core::str::FromStr for DitherConfig {
+    type Err = InvalidDitherConfig;
+
+    /// Expects each key at most once, in any order, and at least
+    /// `palette`, `strategy`, `diffusion` and `noise`; the others default
+    /// as in [`DitherConfig::new`]. Blank lines are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line.split_once('=').ok_or(InvalidDitherConfig)?;
+            if fields.iter().any(|&(seen, _)| seen == key) {
+                return Err(InvalidDitherConfig);
+            }
+            fields.push((key, value));
+        }
+        let required = |key: &str| {
+            fields
+                .iter()
+                .find(|&&(seen, _)| seen == key)
+                .map(|&(_, value)| value)
+                .ok_or(InvalidDitherConfig)
+        };
+        let mut config = Self::new(
+            parse_palette(required("palette")?)?,
+            parse(required("strategy")?)?,
+            parse(required("diffusion")?)?,
+            parse(required("noise")?)?,
+        );
+        for &(key, value) in &fields {
+            match key {
+                "palette" | "strategy" | "diffusion" | "noise" => {}
+                "output-palette" => config.output_palette = parse_palette(value)?,
+                "ink-gamma" => config.ink_gamma = parse_list(value, ',', parse)?,
+                "mixing" => config.mixing = parse(value)?,
+                "axes" => {
+                    config.axes = match parse_list(value, ';', parse_pair)?.as_slice() {
+                        [] => None,
+                        &[a, b, c] => Some([a, b, c]),
+                        _ => return Err(InvalidDitherConfig),
+                    }
+                }
+                "compactness" => config.compactness = parse(value)?,
+                "max-inks" => config.max_inks = parse_option(value)?,
+                "neutral-lock" => config.neutral_lock = parse_option(value)?,
+                "ink-bias" => config.ink_bias = parse_list(value, ',', parse)?,
+                "tiles" => {
+                    config.tiles = parse_list(value, ';', |tile| {
+                        let (region, palette) = tile.split_once(' ').ok_or(InvalidDitherConfig)?;
+                        Ok((parse(region)?, parse_palette(palette)?))
+                    })?
+                }
+                "edges" => config.edges = parse(value)?,
+                "scan" => config.scan = parse(value)?,
+                "error-space" => config.error_space = parse(value)?,
+                "lab-diffusion" => config.lab_diffusion = parse(value)?,
+                "confidence-weighting" => config.confidence_weighting = parse(value)?,
+                "lookahead" => config.lookahead = parse(value)?,
+                "noise-amplitude" => config.noise_amplitude = parse(value)?,
+                "tie-margin" => config.tie_margin = parse(value)?,
+                "noise-offset" => {
+                    let (x, y) = parse_pair(value)?;
+                    config.noise_offset = [x, y];
+                }
+                "noise-row-phase" => config.noise_row_phase = parse(value)?,
+                "index-order-seed" => config.index_order_seed = parse_option(value)?,
+                "non-finite-fallback" => config.non_finite_fallback = parse_option(value)?,
+                "alpha-mode" => config.alpha_mode = parse(value)?,
+                "alpha" => config.alpha = parse(value)?,
+                "background" => {
+                    config.background = parse_hex_color(value).ok_or(InvalidDitherConfig)?
+                }
+                "transparent-index" => config.transparent_index = parse_option(value)?,
+                "tonemap" => config.tonemap = parse(value)?,
+                "hsv" => config.hsv = parse_option(value)?,
+                "bpc" => config.bpc = parse(value)?,
+                "prev" => config.prev = parse_option(value)?,
+                "prev-tolerance" => config.prev_tolerance = parse(value)?,
+                "ink-mask" => config.ink_mask = parse_option(value)?,
+                "diffuse-mask" => config.diffuse_mask = parse_option(value)?,
+                "passes" => config.passes = parse(value)?,
+                "max-error" => config.max_error = parse_option(value)?,
+                "match-brightness" => config.match_brightness = parse(value)?,
+                "subpixel" => config.subpixel = parse_option(value)?,
+                "max-density" => config.max_density = parse_list(value, ',', parse)?,
+                "density-radius" => config.density_radius = parse(value)?,
+                _ => return Err(InvalidDitherConfig),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Serde through the `Display` and `FromStr` text forms, for fields whose
+/// types have no serde derive of their own.
+#[cfg(feature = "serde")]
+mod text {
+    use alloc::string::String;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: core::fmt::Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: core::str::FromStr,
+        T::Err: core::fmt::Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::naive::NaiveDecomposerStrategy;
+    use crate::dither::diffusion_matrix::{FLOYD_STEINBERG, NO_DIFFUSE};
+    use crate::palette::SPECTRA6;
+    use alloc::string::ToString;
+
+    /// Every setting away from its default.
+    fn everything() -> DitherConfig {
+        DitherConfig {
+            output_palette: alloc::vec![[1, 2, 3]; 6],
+            ink_gamma: alloc::vec![1.0, 1.5, 0.8, 1.0, 1.0, 2.0],
+            mixing: MixingModel::Subtractive,
+            axes: Some([(0, 1), (2, 3), (4, 5)]),
+            compactness: 0.25,
+            max_inks: Some(3),
+            neutral_lock: Some(0.05),
+            ink_bias: alloc::vec![
+                InkBias {
+                    index: 2,
+                    factor: 0.8
+                },
+                InkBias {
+                    index: 4,
+                    factor: 1.5
+                }
+            ],
+            tiles: alloc::vec![
+                (
+                    Region {
+                        x: 0,
+                        y: 0,
+                        width: 10,
+                        height: 20
+                    },
+                    SPECTRA6.to_vec()
+                ),
+                (
+                    Region {
+                        x: 10,
+                        y: 0,
+                        width: 10,
+                        height: 20
+                    },
+                    alloc::vec![[9, 9, 9]; 6]
+                ),
+            ],
+            edges: EdgeMode::Redistribute,
+            scan: ScanOrder::Serpentine,
+            error_space: ErrorSpace::Rgb,
+            lab_diffusion: true,
+            confidence_weighting: true,
+            lookahead: true,
+            noise_amplitude: 0.5,
+            tie_margin: 0.01,
+            noise_offset: [3, 7],
+            noise_row_phase: true,
+            index_order_seed: Some(42),
+            non_finite_fallback: Some(1),
+            alpha_mode: AlphaHandling::Mask,
+            alpha: AlphaMode::Premultiplied,
+            background: [0, 128, 255],
+            transparent_index: Some(0),
+            tonemap: ToneMap::Reinhard,
+            hsv: Some(HsvAdjustment {
+                hue_deg: -20.0,
+                sat_mul: 1.2,
+                val_mul: 1.0,
+            }),
+            bpc: true,
+            prev: Some("previous.png".to_string()),
+            prev_tolerance: 0.2,
+            ink_mask: Some("0=mask.png".to_string()),
+            diffuse_mask: Some("barrier.png".to_string()),
+            passes: 3,
+            max_error: Some(0.02),
+            match_brightness: true,
+            subpixel: Some(SubpixelLayout {
+                columns: 3,
+                rows: 1,
+            }),
+            max_density: alloc::vec![DensityLimit {
+                index: 3,
+                max: 0.25
+            }],
+            density_radius: 4,
+            ..DitherConfig::new(
+                SPECTRA6.to_vec(),
+                "octahedron-closest".parse().unwrap(),
+                DiffusionSetting::Adaptive {
+                    tile_size: 16,
+                    threshold: 0.001,
+                },
+                NoiseSource::WhiteSeeded(1234),
+            )
+        }
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let configs = [
+            DitherConfig::new(
+                SPECTRA6.to_vec(),
+                "octahedron-closest".parse().unwrap(),
+                DiffusionSetting::Fixed(DiffusionCoefficients::of(&FLOYD_STEINBERG)),
+                NoiseSource::InterleavedGradient,
+            ),
+            DitherConfig::new(
+                alloc::vec![[0, 0, 0], [255, 255, 255]],
+                DecomposeStrategy::Naive(NaiveDecomposerStrategy::TetraBlend(3)),
+                DiffusionSetting::Fixed(DiffusionCoefficients::of(&NO_DIFFUSE)),
+                NoiseSource::BayerRect(3, 1),
+            ),
+            everything(),
+        ];
+        for config in configs {
+            let text = config.to_string();
+            assert_eq!(text.parse::<DitherConfig>(), Ok(config), "{text}");
+        }
+    }
+
+    #[test]
+    fn diffusion_text_form() {
+        let fs = DiffusionCoefficients::of(&FLOYD_STEINBERG);
+        assert_eq!(fs.to_string(), "16:1,0,7;-1,1,3;0,1,5;1,1,1");
+        assert_eq!(DiffusionCoefficients::of(&NO_DIFFUSE).to_string(), "1:");
+        assert_eq!(
+            "0:".parse::<DiffusionCoefficients>(),
+            Err(InvalidDitherConfig)
+        );
+        assert_eq!(
+            "16:1,0".parse::<DiffusionCoefficients>(),
+            Err(InvalidDitherConfig)
+        );
+    }
+
+    #[test]
+    fn rejects_missing_or_repeated_keys() {
+        let text = DitherConfig::new(
+            SPECTRA6.to_vec(),
+            DecomposeStrategy::DominantTexture,
+            DiffusionSetting::Fixed(DiffusionCoefficients::of(&FLOYD_STEINBERG)),
+            NoiseSource::None,
+        )
+        .to_string();
+        let missing: String = text.lines().skip(1).flat_map(|l| [l, "\n"]).collect();
+        assert_eq!(missing.parse::<DitherConfig>(), Err(InvalidDitherConfig));
+        let repeated = alloc::format!("{text}mixing=additive\n");
+        assert_eq!(repeated.parse::<DitherConfig>(), Err(InvalidDitherConfig));
+    }
+
+    #[test]
+    fn optional_keys_default() {
+        let config = "palette=#000000,#FFFFFF\nstrategy=naive-mix\ndiffusion=1:\nnoise=none\n"
+            .parse::<DitherConfig>()
+            .unwrap();
+        assert_eq!(
+            config,
+            DitherConfig::new(
+                alloc::vec![[0, 0, 0], [255, 255, 255]],
+                "naive-mix".parse().unwrap(),
+                DiffusionSetting::Fixed(DiffusionCoefficients::of(&NO_DIFFUSE)),
+                NoiseSource::None,
+            )
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_round_trips_through_serde() {
+        let config = everything();
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DitherConfig>(&json).unwrap(),
+            config,
+            "{json}"
+        );
+    }
+}
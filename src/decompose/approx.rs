@@ -0,0 +1,397 @@
+//! Approximate decomposition for large palettes.
+//!
+//! [`NaiveDecomposer`](crate::decompose::naive::NaiveDecomposer) considers
+//! every tetrahedron of palette colours, `C(n, 4)` of them: 4845 for 20
+//! colours, 230300 for 50. [`ApproxDecomposer`] keeps the palette in a 3-d
+//! tree and, per query, only decomposes against the `k` palette colours
+//! nearest to the input ([`DEFAULT_APPROX_NEIGHBOURS`] by default): the
+//! `C(k, 4)` tetrahedra they span. If none contains the input, it retries
+//! once with the [`MAX_APPROX_NEIGHBOURS`] nearest, then falls back to
+//! their faces and edges like the naive decomposer does outside the gamut.
+//! Nothing is precomputed beyond the tree, so memory stays linear in the
+//! palette.
+//!
+//! Accuracy: when some tetrahedron of the neighbours contains the input,
+//! the weights reconstruct it exactly, the same as the naive decomposer's,
+//! though usually from a different tetrahedron (the naive `FavorMix`
+//! choice among all containing ones may involve a farther ink). The input
+//! can lie inside the full palette's gamut but outside even the wider
+//! neighbours' hull, typically between a cluster of similar inks and a far
+//! one; it is then clipped onto the closest neighbour face or edge and the
+//! reconstruction is off by that distance. Out-of-gamut inputs also clip
+//! to the neighbours' hull, which may be farther than the palette's true
+//! closest gamut point. Per pixel, cost is `C(k, 4)` tetrahedra when the
+//! first try succeeds and `C(12, 4) = 495` plus faces and edges otherwise,
+//! against `C(n, 4)` for the naive decomposer.
+
+use crate::barycentric::line::LineProjector;
+use crate::barycentric::triangle::TriangleProjector;
+use crate::barycentric::{clamp_normalize, default_epsilon, find_duplicate, is_inside};
+use crate::decompose::Decomposer;
+use crate::decompose::naive::FLAT_TETRA_RATIO;
+use alloc::vec::Vec;
+use nalgebra::base::Vector4;
+use nalgebra::geometry::Point3;
+use tinyvec::ArrayVec;
+
+/// Palette colours each query is decomposed against when callers have no
+/// better choice.
+pub const DEFAULT_APPROX_NEIGHBOURS: usize = 6;
+
+/// Upper bound on [`ApproxDecomposer::with_neighbours`], and the number
+/// of neighbours retried with when the first `k` don't contain the input.
+pub const MAX_APPROX_NEIGHBOURS: usize = 12;
+
+/// Nearest palette entries to a query, closest first, as
+/// `(squared distance, palette index)`.
+type Neighbours = ArrayVec<[(f32, usize); MAX_APPROX_NEIGHBOURS]>;
+
+/// Static 3-d tree stored as a balanced implicit layout: each subrange's
+/// median is its node, split on axis `depth % 3`.
+struct KdTree {
+    nodes: Vec<(Point3<f32>, usize)>,
+}
+
+impl KdTree {
+    fn new(points: &[Point3<f32>]) -> Self {
+        let mut nodes: Vec<_> = points.iter().copied().zip(0..).collect();
+        Self::build(&mut nodes, 0);
+        Self { nodes }
+    }
+
+    fn build(nodes: &mut [(Point3<f32>, usize)], depth: usize) {
+        if nodes.len() <= 1 {
+            return;
+        }
+        let axis = depth % 3;
+        let mid = nodes.len() / 2;
+        nodes.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+        let (left, right) = nodes.split_at_mut(mid);
+        Self::build(left, depth + 1);
+        Self::build(&mut right[1..], depth + 1);
+    }
+
+    /// The `k` (at most [`MAX_APPROX_NEIGHBOURS`]) entries nearest to
+    /// `query`.
+    fn nearest(&self, query: &Point3<f32>, k: usize) -> Neighbours {
+        let mut found = Neighbours::new();
+        self.search(
+            &self.nodes,
+            0,
+            query,
+            k.min(MAX_APPROX_NEIGHBOURS),
+            &mut found,
+        );
+        found
+    }
+
+    fn search(
+        &self,
+        nodes: &[(Point3<f32>, usize)],
+        depth: usize,
+        query: &Point3<f32>,
+        k: usize,
+        found: &mut Neighbours,
+    ) {
+        if nodes.is_empty() {
+            return;
+        }
+        let mid = nodes.len() / 2;
+        let (point, index) = nodes[mid];
+        let distance = (point - query).norm_squared();
+        if found.len() < k || found.last().is_some_and(|&(worst, _)| distance < worst) {
+            if found.len() == k {
+                found.pop();
+            }
+            let at = found.partition_point(|&(d, _)| d <= distance);
+            found.insert(at, (distance, index));
+        }
+        let axis = depth % 3;
+        let offset = query[axis] - point[axis];
+        let (near, far) = if offset < 0.0 {
+            (&nodes[..mid], &nodes[mid + 1..])
+        } else {
+            (&nodes[mid + 1..], &nodes[..mid])
+        };
+        self.search(near, depth + 1, query, k, found);
+        if found.len() < k
+            || found
+                .last()
+                .is_some_and(|&(worst, _)| offset * offset < worst)
+        {
+            self.search(far, depth + 1, query, k, found);
+        }
+    }
+}
+
+/// Barycentric coordinates of `input` in the tetrahedron `[a, b, c, d]`
+/// from signed sub-volumes, or `None` if it is flat by the naive
+/// decomposer's [`FLAT_TETRA_RATIO`]. Solving directly is cheaper than
+/// building a [`TetrahedronProjector`](crate::barycentric::tetrahedron::TetrahedronProjector)
+/// for a tetrahedron used once.
+fn tetra_barycentric([a, b, c, d]: [Point3<f32>; 4], input: &Point3<f32>) -> Option<Vector4<f32>> {
+    let (e1, e2, e3, q) = (b - a, c - a, d - a, input - a);
+    let volume = e1.dot(&e2.cross(&e3));
+    let diameter_sq = [e1, e2, e3, c - b, d - b, d - c]
+        .iter()
+        .map(|e| e.norm_squared())
+        .fold(0.0, f32::max);
+    let flat = FLAT_TETRA_RATIO as f32;
+    if volume * volume <= flat * flat * diameter_sq * diameter_sq * diameter_sq {
+        return None;
+    }
+    let wb = q.dot(&e2.cross(&e3)) / volume;
+    let wc = e1.dot(&q.cross(&e3)) / volume;
+    let wd = e1.dot(&e2.cross(&q)) / volume;
+    Some(Vector4::new(1.0 - wb - wc - wd, wb, wc, wd))
+}
+
+pub struct ApproxDecomposer {
+    points: Vec<Point3<f32>>,
+    tree: KdTree,
+    neighbours: usize,
+    // Containment tolerance, see [`crate::barycentric`].
+    epsilon: f32,
+}
+
+impl ApproxDecomposer {
+    /// Returns `None` for an empty palette or one with duplicate colours
+    /// (see [`find_duplicate`]).
+    pub fn new(colors: &[Point3<f32>]) -> Option<Self> {
+        if colors.is_empty() || find_duplicate(colors).is_some() {
+            return None;
+        }
+        Some(Self {
+            points: colors.to_vec(),
+            tree: KdTree::new(colors),
+            neighbours: DEFAULT_APPROX_NEIGHBOURS,
+            epsilon: default_epsilon(),
+        })
+    }
+
+    /// Decompose against this many nearest colours, clamped to
+    /// `4..=`[`MAX_APPROX_NEIGHBOURS`].
+    pub fn with_neighbours(mut self, neighbours: usize) -> Self {
+        self.neighbours = neighbours.clamp(4, MAX_APPROX_NEIGHBOURS);
+        self
+    }
+
+    /// Set the containment tolerance (default
+    /// [`DEFAULT_EPSILON`](crate::barycentric::DEFAULT_EPSILON)).
+    pub fn with_epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn neighbours(&self) -> usize {
+        self.neighbours
+    }
+
+    /// Containing tetrahedron of `near` with the smallest largest weight
+    /// (the naive decomposer's `FavorMix` choice).
+    fn best_tetra(&self, input: &Point3<f32>, near: &[usize], out: &mut [f32]) -> bool {
+        let mut best: Option<(f32, [usize; 4], Vector4<f32>)> = None;
+        let m = near.len();
+        for a in 0..m {
+            for b in a + 1..m {
+                for c in b + 1..m {
+                    for d in c + 1..m {
+                        let indices = [near[a], near[b], near[c], near[d]];
+                        let Some(mut projected) =
+                            tetra_barycentric(indices.map(|i| self.points[i]), input)
+                        else {
+                            continue;
+                        };
+                        if !is_inside(&projected, &self.epsilon) {
+                            continue;
+                        }
+                        clamp_normalize(&mut projected);
+                        let score = projected.max();
+                        if best.is_none_or(|(s, _, _)| score < s) {
+                            best = Some((score, indices, projected));
+                        }
+                    }
+                }
+            }
+        }
+        let Some((_, indices, weights)) = best else {
+            return false;
+        };
+        for (index, weight) in indices.into_iter().zip(weights.iter()) {
+            out[index] = *weight;
+        }
+        true
+    }
+
+    /// Closest point on a face or edge of `near`.
+    fn clip_to_hull(&self, input: &Point3<f32>, near: &[usize], out: &mut [f32]) {
+        let m = near.len();
+        let mut best_distance = f32::INFINITY;
+        let mut best: ArrayVec<[(usize, f32); 3]> = ArrayVec::new();
+        for a in 0..m {
+            for b in a + 1..m {
+                for c in b + 1..m {
+                    let indices = [near[a], near[b], near[c]];
+                    let Some(triangle) = TriangleProjector::new(indices.map(|i| self.points[i]))
+                    else {
+                        continue;
+                    };
+                    let (mut projected, distance) = triangle.project(input);
+                    if !is_inside(&projected, &self.epsilon) || distance * distance >= best_distance
+                    {
+                        continue;
+                    }
+                    clamp_normalize(&mut projected);
+                    best_distance = distance * distance;
+                    best = indices.into_iter().zip(projected.iter().copied()).collect();
+                }
+                let indices = [near[a], near[b]];
+                let Some(line) = LineProjector::new(indices.map(|i| self.points[i])) else {
+                    continue;
+                };
+                let (projected, clipped) = line.clipping_project(input);
+                let distance = clipped
+                    .unwrap_or_else(|| (line.bary_to_point(&projected) - input).norm_squared());
+                if distance < best_distance {
+                    best_distance = distance;
+                    best = indices.into_iter().zip(projected.iter().copied()).collect();
+                }
+            }
+        }
+        if best.is_empty() {
+            // Fewer than two distinct neighbours: all weight on the nearest.
+            out[near[0]] = 1.0;
+        }
+        for (index, weight) in best {
+            out[index] = weight;
+        }
+    }
+}
+
+impl Decomposer<f32> for ApproxDecomposer {
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.points.len()
+    }
+
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        out.fill(0.0);
+        let nearest = |k: usize| -> ArrayVec<[usize; MAX_APPROX_NEIGHBOURS]> {
+            let found = self.tree.nearest(input, k);
+            found.iter().map(|&(_, index)| index).collect()
+        };
+        let near = nearest(self.neighbours);
+        if self.best_tetra(input, &near, out) {
+            return;
+        }
+        let near = if near.len() < MAX_APPROX_NEIGHBOURS.min(self.points.len()) {
+            let wider = nearest(MAX_APPROX_NEIGHBOURS);
+            if self.best_tetra(input, &wider, out) {
+                return;
+            }
+            wider
+        } else {
+            near
+        };
+        self.clip_to_hull(input, &near, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::noise::Pcg32;
+
+    fn palette(count: usize) -> Vec<Point3<f32>> {
+        let mut rng = Pcg32::new(20, 0);
+        (0..count)
+            .map(|_| Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()))
+            .collect()
+    }
+
+    fn reconstruct(points: &[Point3<f32>], weights: &[f32]) -> Point3<f32> {
+        points
+            .iter()
+            .zip(weights)
+            .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * *w)
+    }
+
+    #[test]
+    fn tree_finds_true_nearest() {
+        let points = palette(20);
+        let tree = KdTree::new(&points);
+        let mut rng = Pcg32::new(1, 0);
+        for _ in 0..200 {
+            let query = Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+            let mut expected: Vec<(f32, usize)> = points
+                .iter()
+                .enumerate()
+                .map(|(i, p)| ((p - query).norm_squared(), i))
+                .collect();
+            expected.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let found = tree.nearest(&query, 5);
+            assert_eq!(
+                found.iter().map(|n| n.1).collect::<Vec<_>>(),
+                expected[..5].iter().map(|n| n.1).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn close_to_exact_on_twenty_colours() {
+        let points = palette(20);
+        let exact = NaiveDecomposer::new(&points).unwrap();
+        let approx = ApproxDecomposer::new(&points).unwrap();
+        // Queries inside the palette's gamut, as random convex mixes.
+        let mut rng = Pcg32::new(2, 0);
+        let queries: Vec<Point3<f32>> = (0..200)
+            .map(|_| {
+                let mix: Vec<f32> = (0..points.len()).map(|_| rng.next_f32().powi(4)).collect();
+                let sum: f32 = mix.iter().sum();
+                let weights: Vec<f32> = mix.iter().map(|w| w / sum).collect();
+                reconstruct(&points, &weights)
+            })
+            .collect();
+
+        // The naive FavorMix pick among all 4845 tetrahedra usually mixes
+        // in a farther ink than any neighbour tetrahedron, so individual
+        // weights differ from the full decomposer's; the colours they
+        // reproduce must not. Weight by weight they match the exact
+        // decomposition over the neighbours alone.
+        let (mut e, mut a) = ([0.0; 20], [0.0; 20]);
+        for query in &queries {
+            exact.decompose_into(query, &mut e);
+            approx.decompose_into(query, &mut a);
+            assert!(a.iter().all(|&w| w >= 0.0));
+            assert!((a.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+            let error = (reconstruct(&points, &a) - reconstruct(&points, &e)).norm();
+            assert!(error < 1e-3, "{error}");
+
+            // The nearest neighbours, or the wider retry, whichever first
+            // contains the query; for these queries one always does.
+            let (near, local) = [DEFAULT_APPROX_NEIGHBOURS, MAX_APPROX_NEIGHBOURS]
+                .into_iter()
+                .find_map(|k| {
+                    let near: Vec<usize> =
+                        approx.tree.nearest(query, k).iter().map(|n| n.1).collect();
+                    let near_points: Vec<_> = near.iter().map(|&i| points[i]).collect();
+                    let mut local = alloc::vec![0.0; k];
+                    NaiveDecomposer::new(&near_points)
+                        .unwrap()
+                        .decompose_into(query, &mut local);
+                    ((reconstruct(&near_points, &local) - query).norm() < 1e-4)
+                        .then_some((near, local))
+                })
+                .unwrap();
+            let mut expected = [0.0; 20];
+            for (&index, &weight) in near.iter().zip(&local) {
+                expected[index] = weight;
+            }
+            for (index, (a, expected)) in a.iter().zip(expected).enumerate() {
+                assert!((a - expected).abs() < 1e-4, "{index}: {a} vs {expected}");
+            }
+        }
+    }
+}
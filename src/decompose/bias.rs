@@ -0,0 +1,177 @@
+//! Per-ink bias on decomposition weights.
+//!
+//! Some panels have inks that read weaker or stronger than their measured
+//! colour suggests, or inks one would rather use sparingly. [`apply_ink_bias`]
+//! scales selected barycentric weights and renormalises the rest, so the
+//! index picker sees fewer (or more) of those inks. The weights then no
+//! longer reconstruct the input exactly: biasing trades reconstruction
+//! accuracy for control over ink usage, and error diffusion will not undo
+//! it, since the bias is applied before the quantization error is formed.
+//!
+//! The helper is slice-based so it works on a `DVector` (`as_mut_slice`),
+//! a `Vector6` or a plain array alike; [`BiasedDecomposer`] applies it to
+//! any [`Decomposer`].
+
+use crate::decompose::Decomposer;
+
+/// Multiply the weight of palette entry `index` by `factor` (`< 1` uses
+/// the ink less, `> 1` more).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InkBias {
+    pub index: usize,
+    pub factor: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidInkBias;
+
+impl core::fmt::Display for InvalidInkBias {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid ink bias (expected <index>:<non-negative factor>)")
+    }
+}
+
+impl core::error::Error for InvalidInkBias {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for InkBias {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.index, self.factor)
+    }
+}
+
+impl core::str::FromStr for InkBias {
+    type Err = InvalidInkBias;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, factor) = s.split_once(':').ok_or(InvalidInkBias)?;
+        let index = index.parse::<usize>().map_err(|_| InvalidInkBias)?;
+        let factor = factor.parse::<f32>().map_err(|_| InvalidInkBias)?;
+        if !factor.is_finite() || factor < 0.0 {
+            return Err(InvalidInkBias);
+        }
+        Ok(Self { index, factor })
+    }
+}
+
+/// Scale `weights` by `biases` in place, then rescale so the total is
+/// unchanged. Biases naming an index outside `weights` are ignored, as is
+/// the renormalisation if every remaining weight ends up zero.
+pub fn apply_ink_bias(weights: &mut [f32], biases: &[InkBias]) {
+    if biases.is_empty() {
+        return;
+    }
+    let original_sum: f32 = weights.iter().sum();
+    for bias in biases {
+        if let Some(weight) = weights.get_mut(bias.index) {
+            *weight *= bias.factor;
+        }
+    }
+    let biased_sum: f32 = weights.iter().sum();
+    if biased_sum > 0.0 {
+        let scale = original_sum / biased_sum;
+        for weight in weights.iter_mut() {
+            *weight *= scale;
+        }
+    }
+}
+
+/// Wraps a decomposer, running [`apply_ink_bias`] on every decomposition.
+pub struct BiasedDecomposer<D, B> {
+    pub inner: D,
+    pub biases: B,
+}
+
+impl<D, B> BiasedDecomposer<D, B> {
+    pub fn new(inner: D, biases: B) -> Self {
+        Self { inner, biases }
+    }
+}
+
+impl<D, B> Decomposer<f32> for BiasedDecomposer<D, B>
+where
+    D: Decomposer<f32>,
+    B: AsRef<[InkBias]>,
+{
+    type Input = D::Input;
+
+    fn palette_size(&self) -> usize {
+        self.inner.palette_size()
+    }
+
+    fn decompose_into(&self, input: &D::Input, out: &mut [f32]) {
+        self.inner.decompose_into(input, out);
+        apply_ink_bias(out, self.biases.as_ref());
+    }
+
+    fn confidence(&self, input: &D::Input) -> f32 {
+        self.inner.confidence(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_index_and_factor() {
+        assert_eq!(
+            "2:0.5".parse::<InkBias>(),
+            Ok(InkBias {
+                index: 2,
+                factor: 0.5
+            })
+        );
+        assert!("2".parse::<InkBias>().is_err());
+        assert!("2:-1".parse::<InkBias>().is_err());
+    }
+
+    #[test]
+    fn bias_keeps_total() {
+        let mut weights = [0.25, 0.25, 0.5];
+        apply_ink_bias(
+            &mut weights,
+            &[InkBias {
+                index: 2,
+                factor: 0.0,
+            }],
+        );
+        assert_eq!(weights, [0.5, 0.5, 0.0]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn down_biased_colour_is_picked_less() {
+        use crate::decompose::DecomposerInputColor;
+        use crate::decompose::naive::NaiveDecomposer;
+        use crate::dither::DecomposingDitherStrategy;
+        use crate::dither::diffuse::PixelStrategy;
+        use crate::palette::SPECTRA6;
+        use nalgebra::geometry::Point3;
+
+        let points = SPECTRA6.map(|c| c.to_point());
+        // Palette order is K, W, Y, R, B, G; orange mixes yellow and red.
+        let orange = points[2] + (points[3] - points[2]) * 0.5;
+        let count_yellow = |biases: &[InkBias]| {
+            let decomposer = BiasedDecomposer::new(NaiveDecomposer::new(&points).unwrap(), biases);
+            let strategy = DecomposingDitherStrategy::new(decomposer, |p: Point3<f32>| p)
+                .with_noise(|x, y| crate::noise::bayer(x, y, 3));
+            let mut count = 0;
+            for y in 0..8 {
+                for x in 0..8 {
+                    if strategy.quantize(orange, x, y, Default::default()).0 == 2 {
+                        count += 1;
+                    }
+                }
+            }
+            count
+        };
+        let plain = count_yellow(&[]);
+        let biased = count_yellow(&[InkBias {
+            index: 2,
+            factor: 0.5,
+        }]);
+        assert!(biased < plain, "{biased} >= {plain}");
+    }
+}
@@ -0,0 +1,129 @@
+//! Memoizing decomposer for 8-bit RGB inputs.
+//!
+//! [`CachedDecomposer`] quantizes each input to 8 bits per channel and
+//! remembers the inner decomposer's weights per quantized colour. Pixels
+//! decoded from an 8-bit image land exactly on those colours, and
+//! photographs reuse a colour many times, so most lookups skip the inner
+//! projection.
+//!
+//! Use it on the ordered (noise-only, non-diffused) path. Error diffusion
+//! adds a continuously varying error to every input before decomposing,
+//! which scatters inputs across quantization buckets (low hit rate) and
+//! snaps the perturbed input to its bucket's first-seen weights, throwing
+//! away up to half an 8-bit step of the diffused error.
+//!
+//! The cache grows with the number of distinct colours seen (at most
+//! 2²⁴, one `palette_size` vector each) and is never evicted; call
+//! [`clear`](CachedDecomposer::clear) between unrelated images if that
+//! matters. It sits behind a `Mutex` so the wrapper stays `Sync`.
+//!
+//! Available behind the `std` Cargo feature.
+
+use crate::decompose::Decomposer;
+use nalgebra::DVector;
+use nalgebra::geometry::Point3;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub struct CachedDecomposer<D> {
+    pub inner: D,
+    cache: Mutex<HashMap<[u8; 3], DVector<f32>>>,
+}
+
+impl<D> CachedDecomposer<D> {
+    pub fn new(inner: D) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of distinct quantized colours cached so far.
+    pub fn len(&self) -> usize {
+        self.cache.lock().map_or(0, |cache| cache.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear();
+        }
+    }
+}
+
+/// Round each channel (clamped to `[0, 1]`) to the nearest 8-bit level.
+fn quantize(input: &Point3<f32>) -> [u8; 3] {
+    [input.x, input.y, input.z].map(|c| (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8)
+}
+
+impl<D> Decomposer<f32> for CachedDecomposer<D>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.inner.palette_size()
+    }
+
+    /// Weights for `input`'s 8-bit bucket, computed from the first input
+    /// that hit it. A poisoned lock falls back to decomposing uncached.
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        let key = quantize(input);
+        let Ok(mut cache) = self.cache.lock() else {
+            self.inner.decompose_into(input, out);
+            return;
+        };
+        let weights = cache.entry(key).or_insert_with(|| {
+            let mut weights = DVector::zeros(self.inner.palette_size());
+            self.inner.decompose_into(input, weights.as_mut_slice());
+            weights
+        });
+        out.copy_from_slice(weights.as_slice());
+    }
+
+    /// Not cached.
+    fn confidence(&self, input: &Point3<f32>) -> f32 {
+        self.inner.confidence(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use crate::decompose::octahedron::OctahedronDecomposer;
+    use crate::palette::SPECTRA6;
+
+    #[test]
+    fn cached_results_equal_uncached() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let cached = CachedDecomposer::new(OctahedronDecomposer::new(&points).unwrap());
+        let uncached = OctahedronDecomposer::new(&points).unwrap();
+        let colors: alloc::vec::Vec<[u8; 3]> = (0..512u32)
+            .map(|i| {
+                let i = (i * 37) % 200;
+                [
+                    (i * 5 % 256) as u8,
+                    (i * 11 % 256) as u8,
+                    (i * 3 % 256) as u8,
+                ]
+            })
+            .collect();
+        for _pass in 0..2 {
+            for color in &colors {
+                let input = color.to_point();
+                let (mut expected, mut actual) = ([0.0; 6], [0.0; 6]);
+                uncached.decompose_into(&input, &mut expected);
+                cached.decompose_into(&input, &mut actual);
+                assert_eq!(actual, expected, "{color:?}");
+            }
+        }
+        assert_eq!(cached.len(), 200);
+        cached.clear();
+        assert!(cached.is_empty());
+    }
+}
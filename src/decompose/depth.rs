@@ -0,0 +1,138 @@
+//! How deep inside a palette's gamut a colour lies.
+//!
+//! Inside the gamut a decomposer reproduces its input exactly; outside it
+//! can only project onto the boundary, and the error that error diffusion
+//! then carries to the neighbours can't be worked off by them either.
+//! [`HullDepth`] measures the distance from a point to the nearest face of
+//! the gamut's (convex) hull, scaled so the palette's centroid is at depth
+//! 1, and [`Decomposer::confidence`](super::Decomposer::confidence) reports
+//! it clamped to `[0, 1]`. For a regular tetrahedron that is four times
+//! the smallest barycentric weight; unlike the barycentric weights of
+//! whichever cell the decomposer picks, it doesn't drop to zero along the
+//! faces shared by two cells inside the hull.
+
+use nalgebra::base::{Scalar, Vector3};
+use nalgebra::geometry::Point3;
+use nalgebra::{ClosedAddAssign, ClosedDivAssign, ClosedMulAssign, ClosedSubAssign, ComplexField};
+use num_traits::identities::{One, Zero};
+use num_traits::{one, zero};
+
+/// Plane through a hull face, oriented so points inside the hull are at
+/// positive distance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FacePlane<T: Scalar> {
+    // Unit normal pointing into the hull.
+    pub(crate) normal: Vector3<T>,
+    // `normal · p` for points `p` on the plane.
+    pub(crate) offset: T,
+}
+
+impl<T: Scalar> FacePlane<T>
+where
+    T: ComplexField + ClosedSubAssign + ClosedMulAssign + ClosedAddAssign + ClosedDivAssign,
+{
+    /// Plane through `vertices`, facing `inside`. `None` if the vertices
+    /// are collinear or `inside` lies on the plane.
+    pub fn new(vertices: [&Point3<T>; 3], inside: &Point3<T>) -> Option<Self> {
+        let [a, b, c] = vertices;
+        let normal = (b - a).cross(&(c - a));
+        let length = normal.norm();
+        if length.is_zero() {
+            return None;
+        }
+        let mut normal = normal / T::from_real(length);
+        let mut offset = normal.dot(&a.coords);
+        let side = normal.dot(&inside.coords) - offset.clone();
+        if side.is_zero() {
+            return None;
+        }
+        if side.real() < zero() {
+            normal = -normal;
+            offset = -offset;
+        }
+        Some(Self { normal, offset })
+    }
+
+    /// Signed distance from `point` to the plane, positive on the inside.
+    pub fn distance(&self, point: &Point3<T>) -> T {
+        self.normal.dot(&point.coords) - self.offset.clone()
+    }
+}
+
+/// Depth below a set of [`FacePlane`]s: the distance to the nearest,
+/// relative to that of a reference point deep inside.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HullDepth<T: Scalar, F> {
+    pub(crate) faces: F,
+    // Depth of the reference point, positive.
+    pub(crate) reference: T,
+}
+
+impl<T: Scalar, F> HullDepth<T, F>
+where
+    T: ComplexField + ClosedSubAssign + ClosedMulAssign + ClosedAddAssign + ClosedDivAssign,
+    T: Zero + One + PartialOrd,
+    F: AsRef<[FacePlane<T>]>,
+{
+    /// Depth below `faces`, 1 at `reference`. `None` if there are no faces
+    /// or `reference` isn't strictly inside all of them.
+    pub fn new(faces: F, reference: &Point3<T>) -> Option<Self> {
+        let reference = Self::distance(faces.as_ref(), reference)?;
+        (reference > zero()).then_some(Self { faces, reference })
+    }
+
+    fn distance(faces: &[FacePlane<T>], point: &Point3<T>) -> Option<T> {
+        faces
+            .iter()
+            .map(|face| face.distance(point))
+            .reduce(|a, b| if b < a { b } else { a })
+    }
+
+    /// Depth of `point`: 1 at the reference point, 0 on the nearest face,
+    /// negative outside.
+    pub fn depth(&self, point: &Point3<T>) -> T {
+        Self::distance(self.faces.as_ref(), point).unwrap_or_else(zero) / self.reference.clone()
+    }
+
+    /// [`depth`](Self::depth) clamped to `[0, 1]`, as an `f32`.
+    pub fn confidence(&self, point: &Point3<T>) -> f32 {
+        let depth = self.depth(point);
+        // Negated so a NaN depth also counts as outside.
+        #[allow(clippy::neg_cmp_op_on_partial_ord)]
+        if !(depth > zero()) {
+            return 0.0;
+        }
+        if depth > one() {
+            return 1.0;
+        }
+        nalgebra::try_convert::<T, f64>(depth).map_or(0.0, |depth| depth as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_cube_corner_tetrahedron() {
+        let vertices = [
+            Point3::new(0.0f32, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+        ];
+        let centroid = Point3::new(0.25, 0.25, 0.25);
+        let faces = [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]].map(|[a, b, c]| {
+            FacePlane::new([&vertices[a], &vertices[b], &vertices[c]], &centroid).unwrap()
+        });
+        let depth = HullDepth::new(faces.clone(), &centroid).unwrap();
+        assert!((depth.depth(&centroid) - 1.0).abs() < 1e-6);
+        assert!((depth.confidence(&Point3::new(0.3, 0.3, 0.3)) - 0.4).abs() < 1e-6);
+        assert_eq!(depth.confidence(&vertices[1]), 0.0);
+        assert_eq!(depth.confidence(&Point3::new(-0.1, 0.2, 0.2)), 0.0);
+        assert!(depth.depth(&Point3::new(-0.1, 0.2, 0.2)) < 0.0);
+        // Not inside: no depth to measure.
+        assert!(HullDepth::new(faces, &Point3::new(1.0, 1.0, 1.0)).is_none());
+        assert!(FacePlane::new([&vertices[0], &vertices[1], &vertices[1]], &centroid).is_none());
+    }
+}
@@ -0,0 +1,625 @@
+//! Integer-only octahedron decomposition, for microcontrollers without an
+//! FPU.
+//!
+//! [`FixedOctahedronDecomposer`] is a port of
+//! [`OctahedronDecomposer`]'s `decompose_into` to Q16.16 fixed point:
+//! every value is an `i32` holding `value * 2^16`, products are taken in
+//! `i64` and rounded back. It is built from a prepared `f32` decomposer
+//! (on the host, or once at start-up on the target), converting its
+//! wedge, face and edge projectors, axis lines, face planes and strategy;
+//! after that no float operation runs. Inputs are RGB in `[0, ONE]`, e.g. from
+//! [`point_from_rgb8`]; the weights come out in the same scale.
+//!
+//! Precision: Q16.16 steps are about `1.5e-5`, and each weight is a dot
+//! product of up to four inverse-matrix coefficients with the input, so
+//! rounding grows with the coefficients. For the built-in Spectra 6
+//! palette the weights stay within `5e-4` of the `f32` decomposer's and
+//! sum to `ONE` within a few steps. Decisions that compare two values,
+//! which wedge an input falls in, the closest axis, or the closest edge
+//! for an input outside the gamut, can go the other way for inputs
+//! within that rounding of a tie; the weights then differ by more, but
+//! only where the float decomposer itself would switch for a slightly
+//! different input. Construction fails for palettes so
+//! nearly degenerate that their projectors have coefficients of
+//! [`COEFFICIENT_LIMIT`] or more, which 16 fractional bits would not
+//! resolve anyway.
+
+use crate::barycentric::line::LineProjector;
+use crate::barycentric::octahedron::OctahedronProjector;
+use crate::barycentric::tetrahedron::TetrahedronProjector;
+use crate::barycentric::triangle::TriangleProjector;
+use crate::decompose::Decomposer;
+use crate::decompose::depth::FacePlane;
+use crate::decompose::octahedron::{
+    AVERAGE_FACE_DEPTH, LineDistanceCalculator, OctahedronDecomposer, OctahedronDecomposerAxis,
+    OctahedronDecomposerAxisStrategy,
+};
+use nalgebra::geometry::Point3;
+
+/// Fractional bits of a Q16.16 value.
+pub const FRAC_BITS: u32 = 16;
+/// 1.0 in Q16.16.
+pub const ONE: i32 = 1 << FRAC_BITS;
+/// Largest magnitude (exclusive) of a projector coefficient
+/// [`FixedOctahedronDecomposer::new`] accepts. Palette colours, distance
+/// weights and the other position-like values must stay below 4.
+pub const COEFFICIENT_LIMIT: f32 = 128.0;
+const POSITION_LIMIT: f32 = 4.0;
+/// [`AVERAGE_FACE_DEPTH`] in Q16.16, rounded down.
+const AVERAGE_FACE_LIMIT: i32 = (AVERAGE_FACE_DEPTH * ONE as f64) as i32;
+
+/// `value` in Q16.16, rounded to nearest. `None` if it isn't finite or
+/// its magnitude is `limit` or more.
+fn to_fixed(value: f32, limit: f32) -> Option<i32> {
+    if !(-limit..limit).contains(&value) {
+        return None;
+    }
+    let scaled = value * ONE as f32;
+    Some((scaled + if scaled < 0.0 { -0.5 } else { 0.5 }) as i32)
+}
+
+fn to_fixed_3(values: [f32; 3], limit: f32) -> Option<[i32; 3]> {
+    Some([
+        to_fixed(values[0], limit)?,
+        to_fixed(values[1], limit)?,
+        to_fixed(values[2], limit)?,
+    ])
+}
+
+/// Q32.32 product sum back to Q16.16, rounded to nearest.
+fn narrow(value: i64) -> i32 {
+    ((value + (1 << (FRAC_BITS - 1))) >> FRAC_BITS) as i32
+}
+
+/// Q32.32 dot product.
+fn dot<const N: usize>(a: &[i32; N], b: &[i32; N]) -> i64 {
+    a.iter().zip(b).map(|(&a, &b)| a as i64 * b as i64).sum()
+}
+
+fn sub(a: &[i32; 3], b: &[i32; 3]) -> [i32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// 8-bit RGB as a Q16.16 input in `[0, ONE]`, the fixed-point
+/// counterpart of [`DecomposerInputColor::to_point`](crate::decompose::DecomposerInputColor::to_point).
+pub fn point_from_rgb8(rgb: [u8; 3]) -> Point3<i32> {
+    let channel = |c: u8| (c as i32 * ONE + 127) / 255;
+    Point3::new(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]))
+}
+
+/// Q16.16 weight as `f32`, e.g. to compare against the float decomposer.
+pub fn to_f32(value: i32) -> f32 {
+    value as f32 / ONE as f32
+}
+
+/// Clamp negative coordinates to zero and rescale to sum to [`ONE`] (left
+/// as is if nothing positive remains); see
+/// [`clamp_normalize`](crate::barycentric::clamp_normalize).
+fn clamp_normalize<const N: usize>(barycentric: &mut [i32; N]) {
+    for coord in barycentric.iter_mut() {
+        *coord = (*coord).max(0);
+    }
+    let sum: i64 = barycentric.iter().map(|&c| c as i64).sum();
+    if sum > 0 {
+        for coord in barycentric.iter_mut() {
+            *coord = ((*coord as i64 * ONE as i64 + sum / 2) / sum) as i32;
+        }
+    }
+}
+
+fn is_inside<const N: usize>(barycentric: &[i32; N], epsilon: i32) -> bool {
+    barycentric.iter().all(|&c| c >= -epsilon)
+}
+
+/// [`TetrahedronProjector`]: the rows of its `to_barycentric` matrix.
+struct FixedWedge([[i32; 4]; 4]);
+
+impl FixedWedge {
+    fn new(wedge: &TetrahedronProjector<f32>) -> Option<Self> {
+        let m = &wedge.to_barycentric;
+        let row = |r: usize| -> Option<[i32; 4]> {
+            Some([
+                to_fixed(m[(r, 0)], COEFFICIENT_LIMIT)?,
+                to_fixed(m[(r, 1)], COEFFICIENT_LIMIT)?,
+                to_fixed(m[(r, 2)], COEFFICIENT_LIMIT)?,
+                to_fixed(m[(r, 3)], COEFFICIENT_LIMIT)?,
+            ])
+        };
+        Some(Self([row(0)?, row(1)?, row(2)?, row(3)?]))
+    }
+
+    fn project(&self, pt: &[i32; 3]) -> [i32; 4] {
+        let homogeneous = [pt[0], pt[1], pt[2], ONE];
+        self.0.map(|row| narrow(dot(&row, &homogeneous)))
+    }
+}
+
+/// [`TriangleProjector`]: `v1` and the rows of its projection matrix.
+struct FixedFace {
+    v1: [i32; 3],
+    project_matrix: [[i32; 3]; 3],
+}
+
+impl FixedFace {
+    fn new(face: &TriangleProjector<f32>) -> Option<Self> {
+        let m = &face.project_matrix;
+        let row = |r: usize| to_fixed_3([m[(r, 0)], m[(r, 1)], m[(r, 2)]], COEFFICIENT_LIMIT);
+        Some(Self {
+            v1: to_fixed_3(face.v1.coords.into(), POSITION_LIMIT)?,
+            project_matrix: [row(0)?, row(1)?, row(2)?],
+        })
+    }
+
+    /// Barycentric `(w, u, v)` of `pt` projected onto the face's plane.
+    fn project(&self, pt: &[i32; 3]) -> [i32; 3] {
+        let offset = sub(pt, &self.v1);
+        let [_, u, v] = self.project_matrix.map(|row| narrow(dot(&row, &offset)));
+        [ONE - u - v, u, v]
+    }
+}
+
+/// [`LineProjector`].
+struct FixedLine {
+    origin: [i32; 3],
+    direction: [i32; 3],
+    direction_div_length_squared: [i32; 3],
+}
+
+impl FixedLine {
+    fn new(line: &LineProjector<f32>) -> Option<Self> {
+        Some(Self {
+            origin: to_fixed_3(line.origin.coords.into(), POSITION_LIMIT)?,
+            direction: to_fixed_3(line.direction.into(), POSITION_LIMIT)?,
+            direction_div_length_squared: to_fixed_3(
+                line.direction_div_length_squared.into(),
+                COEFFICIENT_LIMIT,
+            )?,
+        })
+    }
+
+    /// [`LineProjector::clipping_project`], with the squared distance to
+    /// the resulting point (Q32.32) whether clipped or not.
+    fn clipping_project(&self, pt: &[i32; 3]) -> ([i32; 2], i64) {
+        let offset = sub(pt, &self.origin);
+        let t = narrow(dot(&offset, &self.direction_div_length_squared));
+        let (barycentric, along) = if ONE - t < 0 {
+            ([0, ONE], ONE)
+        } else if t < 0 {
+            ([ONE, 0], 0)
+        } else {
+            ([ONE - t, t], t)
+        };
+        let on_line = self.direction.map(|d| narrow(d as i64 * along as i64));
+        let distance = sub(&offset, &on_line);
+        (barycentric, dot(&distance, &distance))
+    }
+}
+
+/// [`OctahedronProjector`].
+struct FixedProjector {
+    wedges: [FixedWedge; 4],
+    faces: [FixedFace; 8],
+    edges: [FixedLine; 12],
+    epsilon: i32,
+}
+
+impl FixedProjector {
+    fn new(projector: &OctahedronProjector<f32>) -> Option<Self> {
+        Some(Self {
+            wedges: crate::array_util::opt_array_transpose(
+                projector.wedges.each_ref().map(FixedWedge::new),
+            )?,
+            faces: crate::array_util::opt_array_transpose(
+                projector.faces.each_ref().map(FixedFace::new),
+            )?,
+            edges: crate::array_util::opt_array_transpose(
+                projector.edges.each_ref().map(FixedLine::new),
+            )?,
+            epsilon: to_fixed(projector.epsilon, COEFFICIENT_LIMIT)?.max(0),
+        })
+    }
+
+    fn wedge_to_global(index: usize, [north, south, a, b]: [i32; 4]) -> [i32; 6] {
+        let mut ret = [north, south, 0, 0, 0, 0];
+        ret[2 + (index % 4)] = a;
+        ret[2 + ((index + 1) % 4)] = b;
+        ret
+    }
+
+    fn face_to_global(index: usize, [pole, a, b]: [i32; 3]) -> [i32; 6] {
+        let mut ret = [0; 6];
+        ret[index / 4] = pole;
+        ret[2 + (index % 4)] = a;
+        ret[2 + ((index + 1) % 4)] = b;
+        ret
+    }
+
+    fn edge_to_global(index: usize, [a, b]: [i32; 2]) -> [i32; 6] {
+        let mut ret = [0; 6];
+        let (pole_index, equator_index) = (index / 4, index % 4);
+        if pole_index < 2 {
+            ret[pole_index] = a;
+            ret[2 + equator_index] = b;
+        } else {
+            ret[2 + equator_index] = a;
+            ret[2 + ((equator_index + 1) % 4)] = b;
+        }
+        ret
+    }
+
+    /// [`OctahedronProjector::project`], step for step.
+    fn project(&self, pt: &[i32; 3]) -> ([i32; 6], bool) {
+        let mut edges_to_check = [false; 12];
+        let mut best: Option<([i32; 6], i32)> = None;
+        for (wedge_index, wedge) in self.wedges.iter().enumerate() {
+            let mut local = wedge.project(pt);
+            let local_min = local.iter().copied().min().unwrap_or(0);
+            if is_inside(&local, self.epsilon) {
+                clamp_normalize(&mut local);
+                return (Self::wedge_to_global(wedge_index, local), true);
+            }
+            if best.is_none_or(|best| best.1 < local_min) {
+                best = Some((Self::wedge_to_global(wedge_index, local), local_min));
+            }
+            for (pole, &coord) in local.iter().take(2).enumerate() {
+                if coord <= 0 {
+                    let face_index = (1 - pole) * 4 + wedge_index;
+                    let mut face = self.faces[face_index].project(pt);
+                    if is_inside(&face, self.epsilon) {
+                        clamp_normalize(&mut face);
+                        return (Self::face_to_global(face_index, face), false);
+                    }
+                    if face[0] <= 0 {
+                        edges_to_check[8 + (face_index % 4)] = true;
+                    }
+                    for equator_vertex_index in 0..2 {
+                        if face[1 + equator_vertex_index] <= 0 {
+                            let other = ((face_index % 4) + (1 - equator_vertex_index)) % 4;
+                            edges_to_check[(face_index / 4) * 4 + other] = true;
+                        }
+                    }
+                }
+            }
+        }
+        let closest_edge = (0..12)
+            .filter(|&edge_index| edges_to_check[edge_index])
+            .map(|edge_index| {
+                let (local, distance) = self.edges[edge_index].clipping_project(pt);
+                (edge_index, local, distance)
+            })
+            .reduce(|a, b| if b.2 < a.2 { b } else { a });
+        match closest_edge {
+            Some((edge_index, local, _)) => (Self::edge_to_global(edge_index, local), false),
+            None => {
+                let mut best = best.map_or([0; 6], |(best, _)| best);
+                clamp_normalize(&mut best);
+                (best, true)
+            }
+        }
+    }
+}
+
+/// [`LineDistanceCalculator`]; squared distances come out in Q16.16, as
+/// `i64` since they can exceed its range for far-away inputs.
+struct FixedAxisLine {
+    origin: [i32; 3],
+    direction: [i32; 3],
+    direction_len_sq: i64,
+}
+
+impl FixedAxisLine {
+    fn new(line: &LineDistanceCalculator<f32>) -> Option<Self> {
+        let direction = to_fixed_3(line.direction.into(), POSITION_LIMIT)?;
+        let direction_len_sq = dot(&direction, &direction) >> FRAC_BITS;
+        (direction_len_sq > 0).then_some(Self {
+            origin: to_fixed_3(line.origin.coords.into(), POSITION_LIMIT)?,
+            direction,
+            direction_len_sq,
+        })
+    }
+
+    /// Q32.32 squared norm of `direction × offset`.
+    fn cross_norm_squared(direction: &[i32; 3], offset: &[i32; 3]) -> i64 {
+        let component = |a: usize, b: usize| {
+            narrow(direction[a] as i64 * offset[b] as i64 - direction[b] as i64 * offset[a] as i64)
+        };
+        let cross = [component(1, 2), component(2, 0), component(0, 1)];
+        dot(&cross, &cross)
+    }
+
+    fn distance_squared(&self, pt: &[i32; 3]) -> i64 {
+        let offset = sub(&self.origin, pt);
+        Self::cross_norm_squared(&self.direction, &offset) / self.direction_len_sq
+    }
+
+    fn weighted_distance_squared(&self, pt: &[i32; 3], weights: &[i32; 3]) -> i64 {
+        let scale = |v: [i32; 3]| core::array::from_fn(|i| narrow(v[i] as i64 * weights[i] as i64));
+        let direction = scale(self.direction);
+        let offset = scale(sub(&self.origin, pt));
+        let direction_len_sq = dot(&direction, &direction) >> FRAC_BITS;
+        if direction_len_sq <= 0 {
+            return i64::MAX;
+        }
+        Self::cross_norm_squared(&direction, &offset) / direction_len_sq
+    }
+}
+
+/// [`OctahedronDecomposerAxis`].
+struct FixedAxis {
+    poles: [usize; 2],
+    line: FixedAxisLine,
+    projector: FixedProjector,
+    color_to_vertex_index: [usize; 6],
+}
+
+impl FixedAxis {
+    fn new(axis: &OctahedronDecomposerAxis<f32>) -> Option<Self> {
+        Some(Self {
+            poles: axis.poles,
+            line: FixedAxisLine::new(&axis.distance_calc)?,
+            projector: FixedProjector::new(&axis.projector)?,
+            color_to_vertex_index: axis.color_to_vertex_index,
+        })
+    }
+
+    fn project(&self, pt: &[i32; 3]) -> ([i32; 6], bool) {
+        let (local, is_inside) = self.projector.project(pt);
+        (self.color_to_vertex_index.map(|i| local[i]), is_inside)
+    }
+}
+
+/// [`FacePlane`] divided by its [`HullDepth`](crate::decompose::depth::HullDepth)'s
+/// reference distance, so distances come out as depths.
+struct FixedFacePlane {
+    normal: [i32; 3],
+    offset: i32,
+}
+
+impl FixedFacePlane {
+    fn new(face: &FacePlane<f32>, reference: f32) -> Option<Self> {
+        Some(Self {
+            normal: to_fixed_3((face.normal / reference).into(), COEFFICIENT_LIMIT)?,
+            offset: to_fixed(face.offset / reference, COEFFICIENT_LIMIT)?,
+        })
+    }
+
+    fn depth(&self, pt: &[i32; 3]) -> i32 {
+        narrow(dot(&self.normal, pt)) - self.offset
+    }
+}
+
+/// [`OctahedronDecomposer`] in Q16.16 fixed point; see the module docs.
+pub struct FixedOctahedronDecomposer {
+    axis: [FixedAxis; 3],
+    strategy: OctahedronDecomposerAxisStrategy,
+    distance_weights: Option<[i32; 3]>,
+    faces: Option<[FixedFacePlane; 8]>,
+    // `OctahedronDecomposer::pole_depth`, 0 without face planes.
+    pole_depth: i32,
+}
+
+impl FixedOctahedronDecomposer {
+    /// Convert a prepared decomposer, keeping its axes, strategy, distance
+    /// weights and face planes. `None` if a coefficient is out of range;
+    /// see [`COEFFICIENT_LIMIT`].
+    pub fn new(decomposer: &OctahedronDecomposer<f32>) -> Option<Self> {
+        let distance_weights = match decomposer.distance_weights {
+            Some(weights) => Some(to_fixed_3(weights.into(), POSITION_LIMIT)?),
+            None => None,
+        };
+        let faces = match &decomposer.depth {
+            Some(depth) => Some(crate::array_util::opt_array_transpose(
+                depth
+                    .faces
+                    .each_ref()
+                    .map(|face| FixedFacePlane::new(face, depth.reference)),
+            )?),
+            None => None,
+        };
+        let pole_depth = match decomposer.pole_depth {
+            Some(depth) => to_fixed(depth, COEFFICIENT_LIMIT)?,
+            None => 0,
+        };
+        Some(Self {
+            axis: crate::array_util::opt_array_transpose(
+                decomposer.axis.each_ref().map(FixedAxis::new),
+            )?,
+            strategy: decomposer.strategy,
+            distance_weights,
+            faces,
+            pole_depth,
+        })
+    }
+
+    /// Whether `pt`, with the first axis's projection `weights`, lies
+    /// within [`AVERAGE_FACE_DEPTH`] of a face, checking the pole weights
+    /// before the planes as the float decomposer does.
+    fn near_face(&self, pt: &[i32; 3], weights: &[i32; 6]) -> bool {
+        let Some(faces) = &self.faces else {
+            return false;
+        };
+        let [a, b] = self.axis[0].poles.map(|pole| weights[pole]);
+        narrow(a.min(b) as i64 * self.pole_depth as i64) <= AVERAGE_FACE_LIMIT
+            && faces
+                .iter()
+                .any(|face| face.depth(pt) <= AVERAGE_FACE_LIMIT)
+    }
+
+    fn axis_distance_squared(&self, axis: &FixedAxis, pt: &[i32; 3]) -> i64 {
+        match &self.distance_weights {
+            Some(weights) => axis.line.weighted_distance_squared(pt, weights),
+            None => axis.line.distance_squared(pt),
+        }
+    }
+
+    /// [`Blended`](OctahedronDecomposerAxisStrategy::Blended) axis shares,
+    /// summing to [`ONE`] (within rounding).
+    fn blend_shares(&self, pt: &[i32; 3]) -> [i32; 3] {
+        let [a, b, c] = self
+            .axis
+            .each_ref()
+            .map(|axis| self.axis_distance_squared(axis, pt).min(1 << 23));
+        let shares = [b * c, a * c, a * b];
+        let total: i64 = shares.iter().sum();
+        if total == 0 {
+            return [ONE / 3; 3];
+        }
+        shares.map(|share| ((share << FRAC_BITS) / total) as i32)
+    }
+}
+
+impl Decomposer<i32> for FixedOctahedronDecomposer {
+    /// Q16.16 RGB, nominally in `[0, ONE]`. Channels are clamped to
+    /// `[-ONE, 2 * ONE]` to keep the arithmetic in range.
+    type Input = Point3<i32>;
+
+    fn palette_size(&self) -> usize {
+        6
+    }
+
+    fn decompose_into(&self, input: &Point3<i32>, out: &mut [i32]) {
+        let pt = [input.x, input.y, input.z].map(|c| c.clamp(-ONE, 2 * ONE));
+        let weights = match self.strategy {
+            OctahedronDecomposerAxisStrategy::Axis(axis) => {
+                self.axis[axis % self.axis.len()].project(&pt).0
+            }
+            OctahedronDecomposerAxisStrategy::Average => {
+                let (mut weights, is_inside) = self.axis[0].project(&pt);
+                if is_inside && !self.near_face(&pt, &weights) {
+                    for axis in &self.axis[1..] {
+                        let (other, _) = axis.project(&pt);
+                        for (w, o) in weights.iter_mut().zip(other) {
+                            *w += o;
+                        }
+                    }
+                    weights = weights.map(|w| (w + 1) / 3);
+                }
+                weights
+            }
+            OctahedronDecomposerAxisStrategy::Closest => {
+                let (axis, _) = self
+                    .axis
+                    .iter()
+                    .map(|axis| (axis, self.axis_distance_squared(axis, &pt)))
+                    .reduce(|a, b| if b.1 < a.1 { b } else { a })
+                    .unwrap_or((&self.axis[0], 0));
+                axis.project(&pt).0
+            }
+            OctahedronDecomposerAxisStrategy::Furthest => {
+                let (axis, _) = self
+                    .axis
+                    .iter()
+                    .map(|axis| (axis, self.axis_distance_squared(axis, &pt)))
+                    .reduce(|a, b| if b.1 > a.1 { b } else { a })
+                    .unwrap_or((&self.axis[0], 0));
+                axis.project(&pt).0
+            }
+            OctahedronDecomposerAxisStrategy::Blended => {
+                let shares = self.blend_shares(&pt);
+                let mut blended = [0i64; 6];
+                for (axis, share) in self.axis.iter().zip(shares) {
+                    for (b, w) in blended.iter_mut().zip(axis.project(&pt).0) {
+                        *b += w as i64 * share as i64;
+                    }
+                }
+                blended.map(narrow)
+            }
+        };
+        for (slot, weight) in out.iter_mut().zip(weights) {
+            *slot = weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use nalgebra::Vector3;
+
+    /// Largest weight difference and largest distance between the mixed
+    /// colours of the float decomposer and its fixed-point port, over an
+    /// 11³ grid of 8-bit colours in and out of gamut.
+    fn largest_difference(float: &OctahedronDecomposer<f32>, points: &[Point3<f32>]) -> [f32; 2] {
+        let fixed = FixedOctahedronDecomposer::new(float).unwrap();
+        let mix = |weights: [f32; 6]| {
+            points
+                .iter()
+                .zip(weights)
+                .fold(Vector3::zeros(), |acc, (p, w)| acc + p.coords * w)
+        };
+        let mut largest = [0.0f32; 2];
+        for i in 0..11 * 11 * 11 {
+            let step = |n: usize| ((n % 11) * 255 / 10) as u8;
+            let rgb = [step(i), step(i / 11), step(i / 121)];
+            let mut expected = [0.0; 6];
+            float.decompose_into(&rgb.to_point(), &mut expected);
+            let mut actual = [0; 6];
+            fixed.decompose_into(&point_from_rgb8(rgb), &mut actual);
+            let sum: i32 = actual.iter().sum();
+            assert!((sum - ONE).abs() <= 8, "{rgb:?}: {actual:?}");
+            let actual = actual.map(to_f32);
+            for (e, a) in expected.iter().zip(actual) {
+                largest[0] = largest[0].max((e - a).abs());
+            }
+            largest[1] = largest[1].max((mix(expected) - mix(actual)).norm());
+        }
+        largest
+    }
+
+    #[test]
+    fn fixed_point_matches_float() {
+        let points = crate::palette::SPECTRA6.map(|c| c.to_point());
+        let check = |float: OctahedronDecomposer<f32>| {
+            // Out of gamut, an input about equally close to two edges
+            // near a vertex may snap to the vertex in one and not the
+            // other: a small weight moves, the mix barely does.
+            let [weight, mix] = largest_difference(&float, &points);
+            assert!(weight < 2e-3 && mix < 1e-3, "{weight} {mix}");
+        };
+        for strategy in [
+            OctahedronDecomposerAxisStrategy::Axis(2),
+            OctahedronDecomposerAxisStrategy::Closest,
+            OctahedronDecomposerAxisStrategy::Furthest,
+            OctahedronDecomposerAxisStrategy::Average,
+            OctahedronDecomposerAxisStrategy::Blended,
+        ] {
+            check(
+                OctahedronDecomposer::new(&points)
+                    .unwrap()
+                    .with_strategy(strategy),
+            );
+        }
+        check(
+            OctahedronDecomposer::new(&points)
+                .unwrap()
+                .with_distance_weights(Some(Vector3::new(0.2126, 0.7152, 0.0722))),
+        );
+    }
+
+    #[test]
+    fn degenerate_coefficients_are_rejected() {
+        // An octahedron squashed along z: the wedge inverses grow as it
+        // flattens.
+        let squashed = |factor: f32| {
+            [
+                (0.9, 0.65, 0.6),
+                (0.1, 0.5, 0.5),
+                (0.5, 0.9, 0.5),
+                (0.5, 0.1, 0.5),
+                (0.5, 0.5, 0.9),
+                (0.45, 0.45, 0.0),
+            ]
+            .map(|(x, y, z)| Point3::new(x, y, 0.5 + (z - 0.5) * factor))
+        };
+        let fixed = |factor| {
+            let float = OctahedronDecomposer::new(&squashed(factor)).unwrap();
+            FixedOctahedronDecomposer::new(&float)
+        };
+        assert!(fixed(0.1).is_some());
+        assert!(fixed(0.003).is_none());
+        assert_eq!(to_fixed(f32::NAN, COEFFICIENT_LIMIT), None);
+        assert_eq!(to_fixed(-0.5, COEFFICIENT_LIMIT), Some(-ONE / 2));
+        assert_eq!(point_from_rgb8([0, 255, 128]), Point3::new(0, ONE, 32897));
+    }
+}
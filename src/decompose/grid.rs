@@ -0,0 +1,175 @@
+//! Fast path for palettes laid out on an axis-aligned grid.
+//!
+//! When every palette colour sits on the lattice spanned by a few levels
+//! per channel (the 8 corners of a colour cube, a 3×3×3 grid of synthetic
+//! inks, …) the palette's cells are boxes and decomposition separates per
+//! channel: find the pair of levels bracketing the input's value, and the
+//! weights are the trilinear interpolation weights of the enclosing cell's
+//! corners. No projection or containment search is needed, so a query
+//! costs three bracket lookups and eight corner weights, independent of
+//! palette size.
+//!
+//! Inputs outside the grid are clamped per channel, which for a box is the
+//! closest in-gamut colour and what the naive decomposer's clipping picks
+//! too. The weights themselves differ from the naive decomposer's (up to
+//! eight inks rather than one tetrahedron's four), but both rebuild the
+//! same colour.
+
+use crate::decompose::Decomposer;
+use alloc::vec::Vec;
+use nalgebra::geometry::Point3;
+
+/// Channel values closer than this are taken to be the same grid level.
+pub const GRID_LEVEL_TOLERANCE: f32 = 1e-6;
+
+pub struct GridDecomposer {
+    // Sorted distinct levels per channel.
+    levels: [Vec<f32>; 3],
+    // Palette index of grid point `(i, j, k)` at `i + n0 * (j + n1 * k)`.
+    indices: Vec<usize>,
+}
+
+impl GridDecomposer {
+    /// Returns `None` unless `points` are exactly the grid points of their
+    /// per-channel levels, each once (within [`GRID_LEVEL_TOLERANCE`]).
+    pub fn new(points: &[Point3<f32>]) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+        let levels: [Vec<f32>; 3] = core::array::from_fn(|axis| {
+            let mut values: Vec<f32> = points.iter().map(|p| p[axis]).collect();
+            values.sort_by(f32::total_cmp);
+            values.dedup_by(|b, a| *b - *a <= GRID_LEVEL_TOLERANCE);
+            values
+        });
+        if levels.iter().flatten().any(|v| !v.is_finite())
+            || levels.iter().map(Vec::len).product::<usize>() != points.len()
+        {
+            return None;
+        }
+        let mut indices = alloc::vec![usize::MAX; points.len()];
+        for (index, point) in points.iter().enumerate() {
+            let mut cell = 0;
+            for axis in (0..3).rev() {
+                let level = levels[axis]
+                    .iter()
+                    .position(|&l| (point[axis] - l).abs() <= GRID_LEVEL_TOLERANCE)?;
+                cell = cell * levels[axis].len() + level;
+            }
+            if indices[cell] != usize::MAX {
+                return None;
+            }
+            indices[cell] = index;
+        }
+        Some(Self { levels, indices })
+    }
+
+    /// Number of levels along each channel.
+    pub fn shape(&self) -> [usize; 3] {
+        self.levels.each_ref().map(Vec::len)
+    }
+
+    /// Lower level index and interpolation fraction towards the next level
+    /// for `value` on `axis`, clamped to the grid.
+    fn bracket(&self, axis: usize, value: f32) -> (usize, f32) {
+        let levels = &self.levels[axis];
+        if levels.len() < 2 {
+            return (0, 0.0);
+        }
+        let upper = levels
+            .partition_point(|&l| l <= value)
+            .clamp(1, levels.len() - 1);
+        let (low, high) = (levels[upper - 1], levels[upper]);
+        (upper - 1, ((value - low) / (high - low)).clamp(0.0, 1.0))
+    }
+}
+
+impl Decomposer<f32> for GridDecomposer {
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        out.fill(0.0);
+        let brackets: [(usize, f32); 3] =
+            core::array::from_fn(|axis| self.bracket(axis, input[axis]));
+        let [n0, n1, _] = self.shape();
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut cell = [0; 3];
+            for (axis, &(low, t)) in brackets.iter().enumerate() {
+                let up = corner >> axis & 1 == 1;
+                weight *= if up { t } else { 1.0 - t };
+                cell[axis] = low + usize::from(up);
+            }
+            if weight > 0.0 {
+                out[self.indices[cell[0] + n0 * (cell[1] + n1 * cell[2])]] += weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::noise::Pcg32;
+
+    fn rebuild(points: &[Point3<f32>], weights: &[f32]) -> Point3<f32> {
+        points
+            .iter()
+            .zip(weights)
+            .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * *w)
+    }
+
+    #[test]
+    fn cube_matches_bruteforce_reconstruction() {
+        // The 8 corners of the RGB cube, not in grid order.
+        let points = [
+            [1.0, 1.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 1.0, 0.0],
+        ]
+        .map(Point3::from);
+        let grid = GridDecomposer::new(&points).unwrap();
+        assert_eq!(grid.shape(), [2, 2, 2]);
+        let naive = NaiveDecomposer::new(&points).unwrap();
+        let mut rng = Pcg32::new(21, 0);
+        for _ in 0..500 {
+            // Some queries fall outside the cube and get clipped.
+            let input = Point3::from([0; 3].map(|_| rng.next_f32() * 1.4 - 0.2));
+            let (mut fast, mut slow) = ([0.0; 8], [0.0; 8]);
+            grid.decompose_into(&input, &mut fast);
+            naive.decompose_into(&input, &mut slow);
+            crate::decompose::assert_valid(&fast, &points, None, 1e-5).unwrap();
+            let distance = (rebuild(&points, &fast) - rebuild(&points, &slow)).norm();
+            assert!(distance < 1e-4, "{input}: {fast:?} vs {slow:?}");
+        }
+        // A corner gets all the weight.
+        let mut weights = [0.0; 8];
+        grid.decompose_into(&points[5], &mut weights);
+        assert_eq!(weights[5], 1.0);
+    }
+
+    #[test]
+    fn rejects_palettes_off_the_grid() {
+        let corners: Vec<Point3<f32>> = (0..8)
+            .map(|i| Point3::new((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2) as f32))
+            .collect();
+        assert!(GridDecomposer::new(&corners).is_some());
+        assert!(GridDecomposer::new(&corners[..7]).is_none());
+        let mut duplicated = corners.clone();
+        duplicated[7] = duplicated[0];
+        assert!(GridDecomposer::new(&duplicated).is_none());
+        let spectra =
+            crate::palette::SPECTRA6.map(|c| crate::decompose::DecomposerInputColor::to_point(&c));
+        assert!(GridDecomposer::new(&spectra).is_none());
+    }
+}
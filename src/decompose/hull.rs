@@ -0,0 +1,486 @@
+//! Delaunay tetrahedralization of a palette's convex hull.
+//!
+//! [`tetrahedralize`] splits the hull of a set of points into tetrahedra
+//! with the points as vertices, meeting face to face and covering the
+//! hull exactly once. Decomposing against it only needs the tetrahedron
+//! containing the input rather than every one of the `C(n, 4)` the naive
+//! decomposer tries, and the Delaunay choice (no point inside any
+//! tetrahedron's circumsphere) keeps the tetrahedra as round as the
+//! points allow, so each input mixes nearby inks.
+//!
+//! The construction is Bowyer-Watson, inserting points one at a time:
+//! the tetrahedra whose circumsphere contains the new point are removed
+//! and the hole is filled by joining the point to its boundary. The hull
+//! is closed off by "ghost" tetrahedra joining each hull face to a
+//! vertex at infinity, whose circumsphere is the half-space outside the
+//! face, so points outside the current hull go through the same steps
+//! and no enclosing super-tetrahedron has to be trimmed off afterwards.
+//!
+//! Predicates are evaluated in `f64` on coordinates scaled to the unit
+//! box, against small tolerances rather than exactly. Ties, as for the
+//! eight corners of a cube (all on one sphere) or points on a hull face,
+//! count as outside the circumsphere; when that leaves a hole the new
+//! point doesn't see every face of, the hole is grown across those faces
+//! until it does, so the result stays a valid tetrahedralization, if not
+//! a unique one. Each insertion scans every tetrahedron, so building is
+//! quadratic in the number of points: meant for palettes, not clouds.
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+use nalgebra::geometry::Point3;
+use nalgebra::{Vector3, convert};
+
+/// Vertex at infinity of a ghost tetrahedron, always in its last slot.
+const GHOST: usize = usize::MAX;
+
+/// Orientations and circumsphere tests within this of zero are ties, in
+/// unit-box coordinates.
+const TOLERANCE: f64 = 1e-12;
+
+/// Tetrahedra flatter than this, in unit-box coordinates (six times the
+/// volume), don't count as an initial tetrahedron: the points are taken
+/// to be coplanar.
+const FLAT_VOLUME: f64 = 1e-9;
+
+/// Delaunay tetrahedralization of the convex hull of `points`, as indices
+/// into `points`. Every tetrahedron `[a, b, c, d]` is positively oriented,
+/// `(b - a) × (c - a) · (d - a) > 0`.
+///
+/// Empty if there are fewer than four points, any is not finite, or they
+/// are all (nearly) coplanar. Duplicate points, and points within
+/// rounding of an earlier point, appear once.
+pub fn tetrahedralize(points: &[Point3<f32>]) -> Vec<[usize; 4]> {
+    let Some(mut mesh) = Mesh::new(points) else {
+        return Vec::new();
+    };
+    let Some(first) = mesh.initial_tetrahedron() else {
+        return Vec::new();
+    };
+    mesh.start(first);
+    for point in 0..points.len() {
+        if !first.contains(&point) {
+            mesh.insert(point);
+        }
+    }
+    mesh.tets
+        .iter()
+        .zip(&mesh.alive)
+        .filter(|&(tet, &alive)| alive && tet[3] != GHOST)
+        .map(|(tet, _)| *tet)
+        .collect()
+}
+
+fn orient(a: &Point3<f64>, b: &Point3<f64>, c: &Point3<f64>, d: &Point3<f64>) -> f64 {
+    (b - a).cross(&(c - a)).dot(&(d - a))
+}
+
+/// Positive if `e` is inside the circumsphere of the positively oriented
+/// `[a, b, c, d]`.
+fn insphere(
+    a: &Point3<f64>,
+    b: &Point3<f64>,
+    c: &Point3<f64>,
+    d: &Point3<f64>,
+    e: &Point3<f64>,
+) -> f64 {
+    let [a, b, c, d]: [Vector3<f64>; 4] = [a - e, b - e, c - e, d - e];
+    let det3 = |x: &Vector3<f64>, y: &Vector3<f64>, z: &Vector3<f64>| x.dot(&y.cross(z));
+    // Expansion of the lifted 4×4 determinant along its last column, sign
+    // flipped for this orientation convention.
+    a.norm_squared() * det3(&b, &c, &d) - b.norm_squared() * det3(&a, &c, &d)
+        + c.norm_squared() * det3(&a, &b, &d)
+        - d.norm_squared() * det3(&a, &b, &c)
+}
+
+/// Vertices of the face of `tet` opposite slot `slot`, sorted, as a key
+/// shared by both tetrahedra on that face.
+fn face_key(tet: &[usize; 4], slot: usize) -> [usize; 3] {
+    let mut key = [0; 3];
+    for (k, &v) in tet
+        .iter()
+        .enumerate()
+        .filter(|&(k, _)| k != slot)
+        .map(|(_, v)| v)
+        .enumerate()
+    {
+        key[k] = v;
+    }
+    key.sort_unstable();
+    key
+}
+
+struct Mesh {
+    points: Vec<Point3<f64>>,
+    tets: Vec<[usize; 4]>,
+    alive: Vec<bool>,
+    // Living tetrahedra on each face, two once the mesh is closed.
+    faces: BTreeMap<[usize; 3], Vec<usize>>,
+}
+
+impl Mesh {
+    /// `points` scaled into the unit box, or `None` if they're too few,
+    /// not finite, or all the same.
+    fn new(points: &[Point3<f32>]) -> Option<Self> {
+        if points.len() < 4 || points.iter().any(|p| !p.iter().all(|c| c.is_finite())) {
+            return None;
+        }
+        let points: Vec<Point3<f64>> = points.iter().map(|p| convert(*p)).collect();
+        let mut min = points[0];
+        let mut max = points[0];
+        for p in &points {
+            min = min.inf(p);
+            max = max.sup(p);
+        }
+        let extent = (max - min).max();
+        if extent <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            points: points
+                .iter()
+                .map(|p| Point3::from((p - min) / extent))
+                .collect(),
+            tets: Vec::new(),
+            alive: Vec::new(),
+            faces: BTreeMap::new(),
+        })
+    }
+
+    /// Four points spanning as much volume as a greedy pick finds,
+    /// positively oriented, or `None` if they're flat.
+    fn initial_tetrahedron(&self) -> Option<[usize; 4]> {
+        let p = &self.points;
+        let farthest = |score: &dyn Fn(&Point3<f64>) -> f64| {
+            (0..p.len())
+                .map(|i| (score(&p[i]), i))
+                .fold((f64::NEG_INFINITY, 0), |best, next| {
+                    if next.0 > best.0 { next } else { best }
+                })
+        };
+        let a = 0;
+        let (_, b) = farthest(&|x| (x - p[a]).norm_squared());
+        let (_, c) = farthest(&|x| (p[b] - p[a]).cross(&(x - p[a])).norm_squared());
+        let (volume, d) = farthest(&|x| orient(&p[a], &p[b], &p[c], x).abs());
+        if volume <= FLAT_VOLUME {
+            return None;
+        }
+        Some(if orient(&p[a], &p[b], &p[c], &p[d]) > 0.0 {
+            [a, b, c, d]
+        } else {
+            [b, a, c, d]
+        })
+    }
+
+    /// Mesh of `first` and the ghosts on its four faces.
+    fn start(&mut self, first: [usize; 4]) {
+        self.add(first);
+        for slot in 0..4 {
+            // Ghosts are oriented like real tetrahedra with the vertex at
+            // infinity outside: the opposite vertex is on the negative
+            // side of their face.
+            let [a, b, c] = face_key(&first, slot);
+            let [pa, pb, pc] = [a, b, c].map(|v| &self.points[v]);
+            if orient(pa, pb, pc, &self.points[first[slot]]) < 0.0 {
+                self.add([a, b, c, GHOST]);
+            } else {
+                self.add([b, a, c, GHOST]);
+            }
+        }
+    }
+
+    fn add(&mut self, tet: [usize; 4]) {
+        let id = self.tets.len();
+        self.tets.push(tet);
+        self.alive.push(true);
+        for slot in 0..4 {
+            self.faces.entry(face_key(&tet, slot)).or_default().push(id);
+        }
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.alive[id] = false;
+        for slot in 0..4 {
+            let key = face_key(&self.tets[id], slot);
+            if let Some(ids) = self.faces.get_mut(&key) {
+                ids.retain(|&other| other != id);
+                if ids.is_empty() {
+                    self.faces.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn neighbour(&self, id: usize, slot: usize) -> Option<usize> {
+        self.faces
+            .get(&face_key(&self.tets[id], slot))?
+            .iter()
+            .copied()
+            .find(|&other| other != id)
+    }
+
+    /// Six times the signed volume of `tet`; `None` for a ghost.
+    fn volume(&self, tet: &[usize; 4]) -> Option<f64> {
+        if tet[3] == GHOST {
+            return None;
+        }
+        let [a, b, c, d] = tet.map(|v| &self.points[v]);
+        Some(orient(a, b, c, d))
+    }
+
+    /// Whether `point` is strictly inside the circumsphere of `tet`, or
+    /// for a ghost strictly outside its face.
+    fn conflicts(&self, tet: &[usize; 4], point: usize) -> bool {
+        let p = &self.points[point];
+        let [a, b, c] = [0, 1, 2].map(|k| &self.points[tet[k]]);
+        if tet[3] == GHOST {
+            orient(a, b, c, p) > TOLERANCE
+        } else {
+            insphere(a, b, c, &self.points[tet[3]], p) > TOLERANCE
+        }
+    }
+
+    /// Whether `point` is inside or on the real tetrahedron `tet`.
+    fn contains(&self, tet: &[usize; 4], point: usize) -> bool {
+        tet[3] != GHOST
+            && (0..4).all(|slot| {
+                let mut sub = *tet;
+                sub[slot] = point;
+                self.volume(&sub).is_some_and(|v| v >= -TOLERANCE)
+            })
+    }
+
+    /// Bowyer-Watson step for `point`; see the module docs. Leaves the
+    /// mesh alone and returns `false` if no tetrahedron conflicts with it,
+    /// as for a duplicate.
+    fn insert(&mut self, point: usize) -> bool {
+        let mut cavity = vec![false; self.tets.len()];
+        let mut stack: Vec<usize> = (0..self.tets.len())
+            .filter(|&id| {
+                let tet = &self.tets[id];
+                self.alive[id]
+                    && (tet[3] == GHOST || self.contains(tet, point))
+                    && self.conflicts(tet, point)
+            })
+            .collect();
+        if stack.is_empty() {
+            return false;
+        }
+        for &id in &stack {
+            cavity[id] = true;
+        }
+        while let Some(id) = stack.pop() {
+            for slot in 0..4 {
+                if let Some(next) = self.neighbour(id, slot)
+                    && !cavity[next]
+                    && self.conflicts(&self.tets[next], point)
+                {
+                    cavity[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        // Grow the cavity until the point sees each of its faces, so the
+        // new tetrahedra all have positive volume.
+        let boundary = loop {
+            let mut boundary = Vec::new();
+            let mut grow = None;
+            for id in (0..self.tets.len()).filter(|&id| cavity[id]) {
+                for slot in 0..4 {
+                    let Some(next) = self.neighbour(id, slot) else {
+                        continue;
+                    };
+                    if cavity[next] {
+                        continue;
+                    }
+                    let mut tet = self.tets[id];
+                    tet[slot] = point;
+                    if self.volume(&tet).is_some_and(|v| v <= TOLERANCE) {
+                        grow = Some(next);
+                    }
+                    boundary.push(tet);
+                }
+            }
+            match grow {
+                Some(next) => cavity[next] = true,
+                None => break boundary,
+            }
+        };
+        if boundary.is_empty() {
+            return false;
+        }
+        for id in (0..cavity.len()).filter(|&id| cavity[id]) {
+            self.remove(id);
+        }
+        for tet in boundary {
+            self.add(tet);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(p: &Point3<f32>) -> Point3<f64> {
+        convert(*p)
+    }
+
+    /// Checks `tets` is a tetrahedralization of the hull of `points`:
+    /// positively oriented, every face shared by two tetrahedra on either
+    /// side of it or on the hull with all points behind it, and summing to
+    /// `hull_volume`.
+    fn assert_tetrahedralization(points: &[Point3<f32>], tets: &[[usize; 4]], hull_volume: f64) {
+        let mut faces: BTreeMap<[usize; 3], Vec<usize>> = BTreeMap::new();
+        let mut total = 0.0;
+        for (id, tet) in tets.iter().enumerate() {
+            let [a, b, c, d] = tet.map(|v| point(&points[v]));
+            let volume = orient(&a, &b, &c, &d);
+            assert!(volume > 0.0, "{tet:?} has volume {volume}");
+            total += volume / 6.0;
+            for slot in 0..4 {
+                faces.entry(face_key(tet, slot)).or_default().push(id);
+            }
+        }
+        // Side of `face` the rest of tetrahedron `id` is on.
+        let side = |face: &[usize; 3], id: usize| {
+            let tet: [usize; 4] = tets[id];
+            let fourth = tet.iter().find(|v| !face.contains(v)).unwrap();
+            let [a, b, c] = face.map(|v| point(&points[v]));
+            (orient(&a, &b, &c, &point(&points[*fourth])), [a, b, c])
+        };
+        for (face, ids) in &faces {
+            match ids[..] {
+                [first, second] => {
+                    assert!(
+                        side(face, first).0 * side(face, second).0 < 0.0,
+                        "{face:?} folds"
+                    );
+                }
+                [id] => {
+                    // A hull face: every point on the tetrahedron's side.
+                    let (inside, [a, b, c]) = side(face, id);
+                    for p in points {
+                        assert!(orient(&a, &b, &c, &point(p)) * inside.signum() > -1e-9);
+                    }
+                }
+                _ => panic!("face {face:?} shared by {ids:?}"),
+            }
+        }
+        assert!(
+            (total - hull_volume).abs() < 1e-9,
+            "{total} vs {hull_volume}"
+        );
+    }
+
+    /// Volume of the convex hull of `points` in general position, by
+    /// brute force: the facets are the triangles with every point on one
+    /// side.
+    fn hull_volume(points: &[Point3<f32>]) -> f64 {
+        let points: Vec<Point3<f64>> = points.iter().map(point).collect();
+        let centroid = Point3::from(
+            points.iter().map(|p| p.coords).sum::<Vector3<f64>>() / points.len() as f64,
+        );
+        let mut volume = 0.0;
+        for i in 0..points.len() {
+            for j in i + 1..points.len() {
+                for k in j + 1..points.len() {
+                    let sides = points
+                        .iter()
+                        .enumerate()
+                        .filter(|&(l, _)| l != i && l != j && l != k)
+                        .map(|(_, p)| orient(&points[i], &points[j], &points[k], p));
+                    if sides.clone().all(|s| s >= 0.0) || sides.clone().all(|s| s <= 0.0) {
+                        volume += orient(&points[i], &points[j], &points[k], &centroid).abs() / 6.0;
+                    }
+                }
+            }
+        }
+        volume
+    }
+
+    fn used(tets: &[[usize; 4]], count: usize) -> bool {
+        (0..count).all(|v| tets.iter().any(|tet| tet.contains(&v)))
+    }
+
+    #[test]
+    fn cube_splits_into_five_or_six() {
+        let cube: Vec<Point3<f32>> = (0..8)
+            .map(|i| Point3::new((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2) as f32))
+            .collect();
+        let tets = tetrahedralize(&cube);
+        assert!(tets.len() == 5 || tets.len() == 6, "{tets:?}");
+        assert_tetrahedralization(&cube, &tets, 1.0);
+        assert!(used(&tets, 8));
+    }
+
+    #[test]
+    fn random_points_are_covered_and_delaunay() {
+        let mut state = 0x2545_f491_u32;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+        let points: Vec<Point3<f32>> = (0..40)
+            .map(|_| Point3::new(next(), next(), next()))
+            .collect();
+        let tets = tetrahedralize(&points);
+        assert_tetrahedralization(&points, &tets, hull_volume(&points));
+        for tet in &tets {
+            let [a, b, c, d] = tet.map(|v| point(&points[v]));
+            for (i, p) in points.iter().enumerate() {
+                if !tet.contains(&i) {
+                    assert!(
+                        insphere(&a, &b, &c, &d, &point(p)) <= 1e-9,
+                        "{i} inside {tet:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_keeps_every_point() {
+        // Every point coplanar with others and many cospherical: all ties.
+        let grid: Vec<Point3<f32>> = (0..27)
+            .map(|i| {
+                Point3::new(
+                    (i % 3) as f32 / 2.0,
+                    (i / 3 % 3) as f32 / 2.0,
+                    (i / 9) as f32 / 2.0,
+                )
+            })
+            .collect();
+        let tets = tetrahedralize(&grid);
+        assert_tetrahedralization(&grid, &tets, 1.0);
+        assert!(used(&tets, 27));
+    }
+
+    #[test]
+    fn degenerate_inputs_give_nothing() {
+        let square = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+        ];
+        assert!(tetrahedralize(&square).is_empty());
+        assert!(tetrahedralize(&square[..3]).is_empty());
+        let mut spiky = square;
+        spiky[3] = Point3::new(0.0, 0.0, f32::NAN);
+        assert!(tetrahedralize(&spiky).is_empty());
+        // A duplicate is used once.
+        let solid = [
+            square[0],
+            square[1],
+            square[2],
+            Point3::new(0.0, 0.0, 1.0),
+            square[1],
+        ];
+        let tets = tetrahedralize(&solid);
+        assert_eq!(tets.len(), 1);
+    }
+}
@@ -29,6 +29,14 @@ pub trait DecomposerInputColor {
     /// projects unambiguously to one brightness). Used to validate that
     /// a palette is suitable for a grayscale strategy.
     fn is_grayscale(&self) -> bool;
+
+    /// True iff every channel is finite. Pixels failing this (NaN or
+    /// infinite samples from a corrupt source) get the ditherer's fallback
+    /// index instead of a decomposition; see
+    /// [`with_fallback`](crate::dither::DecomposingDitherStrategy::with_fallback).
+    fn is_finite(&self) -> bool {
+        self.to_point().iter().all(|c| c.is_finite())
+    }
 }
 
 impl DecomposerInputColor for [u8; 3] {
@@ -48,3 +56,17 @@ impl DecomposerInputColor for [u8; 3] {
         r == g && g == b
     }
 }
+
+/// A point already in the decomposer's input space, e.g. the sRGB targets
+/// of [`LabDiffusionStrategy`](crate::dither::lab::LabDiffusionStrategy).
+impl DecomposerInputColor for Point3<f32> {
+    fn to_point(&self) -> Point3<f32> {
+        *self
+    }
+    fn brightness(&self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
+    fn is_grayscale(&self) -> bool {
+        self.x == self.y && self.y == self.z
+    }
+}
@@ -0,0 +1,352 @@
+//! Precomputed lookup-table decomposer for RGB inputs.
+//!
+//! [`LutDecomposer`] samples another decomposer on a regular
+//! `resolution³` grid over the unit RGB cube once, then answers every
+//! query by trilinear interpolation between the eight surrounding grid
+//! points. Lookup cost no longer depends on the inner decomposer, which
+//! makes it the cheap per-pixel step for throughput-focused (and
+//! parallel, see `crate::dither::parallel`) ordered dithering.
+//!
+//! Accuracy: interpolated weights are still non-negative and sum to one.
+//! Reconstruction is linear in the weights, so inside a cell whose eight
+//! grid points all lie in the palette's gamut the weights reconstruct the
+//! input exactly. In cells touching the gamut boundary, where the inner
+//! decomposer clips to the nearest gamut point, the reconstruction stays
+//! within [`reconstruction_tolerance`](LutDecomposer::reconstruction_tolerance)
+//! (`√3 / (resolution - 1)`, one cell diagonal) of the inner decomposer's.
+//! Individual weights can differ by more wherever the inner decomposer
+//! switches between equally valid decompositions inside a cell (e.g. the
+//! octahedron's closest-axis choice); the dithered texture then differs,
+//! not the colour it averages to.
+//!
+//! Memory is `resolution³ · palette_size` weights, 4 bytes each: about
+//! 0.9 MB for a 6-colour palette at [`DEFAULT_LUT_RESOLUTION`]. For
+//! memory-constrained targets, a lower resolution shrinks that with the
+//! cube of the grid size (29 K weights at 17³), at the cost of a looser
+//! [`reconstruction_tolerance`](LutDecomposer::reconstruction_tolerance),
+//! and [`LutPrecision`] stores each weight in 2 or 1 bytes instead, decoded
+//! on lookup: 0.43 or 0.22 MB at 33³. The table is filled one grid point
+//! at a time, so building it takes no more memory than the result.
+
+use crate::decompose::Decomposer;
+use alloc::vec;
+use alloc::vec::Vec;
+use nalgebra::ComplexField;
+use nalgebra::geometry::Point3;
+
+/// Grid points per axis used when callers have no better choice.
+pub const DEFAULT_LUT_RESOLUTION: usize = 33;
+
+/// How a [`LutDecomposer`] stores its grid weights.
+///
+/// The fixed-point options round each grid point's weights to multiples
+/// of `1 / MAX` (`MAX` being 65535 or 255) that keep their sum, so
+/// looked-up weights still sum to one, and each stays within one step of
+/// the `f32` table's; see
+/// [`weight_tolerance`](LutDecomposer::weight_tolerance). The rounding
+/// errors sum to zero, so a reconstructed colour moves by at most the
+/// step times the sum of the palette colours' distances from their mean,
+/// usually far less as the errors partly cancel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LutPrecision {
+    /// 4 bytes per weight, exact.
+    #[default]
+    F32,
+    /// 2 bytes per weight, within `1 / 65535` (about `1.5e-5`): below
+    /// anything a dither can show.
+    U16,
+    /// 1 byte per weight, within `1 / 255` (about `0.004`): an ink share
+    /// that small is 1 pixel in 255, so the texture barely changes.
+    U8,
+}
+
+enum LutWeights {
+    F32(Vec<f32>),
+    U16(Vec<u16>),
+    U8(Vec<u8>),
+}
+
+pub struct LutDecomposer {
+    resolution: usize,
+    palette_size: usize,
+    /// `palette_size` weights per grid point, red varying fastest.
+    weights: LutWeights,
+}
+
+impl LutDecomposer {
+    /// Sample `decomposer` on a `resolution³` grid. Returns `None` if
+    /// `resolution < 2` or the palette is empty.
+    pub fn new<D>(decomposer: &D, resolution: usize) -> Option<Self>
+    where
+        D: Decomposer<f32, Input = Point3<f32>> + ?Sized,
+    {
+        Self::new_with_precision(decomposer, resolution, LutPrecision::F32)
+    }
+
+    /// [`new`](Self::new), storing the weights at `precision`.
+    pub fn new_with_precision<D>(
+        decomposer: &D,
+        resolution: usize,
+        precision: LutPrecision,
+    ) -> Option<Self>
+    where
+        D: Decomposer<f32, Input = Point3<f32>> + ?Sized,
+    {
+        let palette_size = decomposer.palette_size();
+        if resolution < 2 || palette_size == 0 {
+            return None;
+        }
+        let len = resolution * resolution * resolution * palette_size;
+        let step = 1.0 / (resolution - 1) as f32;
+        let sample = |point_index: usize, out: &mut [f32]| {
+            let coordinate = |axis: usize| {
+                let stride = resolution.pow(axis as u32);
+                ((point_index / stride) % resolution) as f32 * step
+            };
+            let input = Point3::new(coordinate(0), coordinate(1), coordinate(2));
+            decomposer.decompose_into(&input, out);
+        };
+        let mut scratch = vec![0.0; palette_size];
+        let weights = match precision {
+            LutPrecision::F32 => {
+                let mut weights = vec![0.0; len];
+                for (point_index, out) in weights.chunks_exact_mut(palette_size).enumerate() {
+                    sample(point_index, out);
+                }
+                LutWeights::F32(weights)
+            }
+            LutPrecision::U16 => {
+                let mut weights = vec![0; len];
+                for (point_index, out) in weights.chunks_exact_mut(palette_size).enumerate() {
+                    sample(point_index, &mut scratch);
+                    quantize(&mut scratch, out, u16::MAX);
+                }
+                LutWeights::U16(weights)
+            }
+            LutPrecision::U8 => {
+                let mut weights = vec![0; len];
+                for (point_index, out) in weights.chunks_exact_mut(palette_size).enumerate() {
+                    sample(point_index, &mut scratch);
+                    quantize(&mut scratch, out, u8::MAX);
+                }
+                LutWeights::U8(weights)
+            }
+        };
+        Some(Self {
+            resolution,
+            palette_size,
+            weights,
+        })
+    }
+
+    pub fn resolution(&self) -> usize {
+        self.resolution
+    }
+
+    pub fn precision(&self) -> LutPrecision {
+        match self.weights {
+            LutWeights::F32(_) => LutPrecision::F32,
+            LutWeights::U16(_) => LutPrecision::U16,
+            LutWeights::U8(_) => LutPrecision::U8,
+        }
+    }
+
+    /// Bound on how far each looked-up weight is from that of an `f32`
+    /// table of the same resolution: one fixed-point step, or 0.
+    pub fn weight_tolerance(&self) -> f32 {
+        match self.weights {
+            LutWeights::F32(_) => 0.0,
+            LutWeights::U16(_) => 1.0 / u16::MAX as f32,
+            LutWeights::U8(_) => 1.0 / u8::MAX as f32,
+        }
+    }
+
+    /// Bound on the distance between the colour reconstructed from this
+    /// table's weights and from the inner decomposer's; see the module
+    /// docs.
+    pub fn reconstruction_tolerance(&self) -> f32 {
+        ComplexField::sqrt(3.0f32) / (self.resolution - 1) as f32
+    }
+
+    /// Add `factor` times the weights of grid point `(r, g, b)` to `out`.
+    fn add_grid_weights(&self, r: usize, g: usize, b: usize, factor: f32, out: &mut [f32]) {
+        let point_index = r + self.resolution * (g + self.resolution * b);
+        let range = point_index * self.palette_size..(point_index + 1) * self.palette_size;
+        match &self.weights {
+            LutWeights::F32(weights) => {
+                for (slot, weight) in out.iter_mut().zip(&weights[range]) {
+                    *slot += factor * weight;
+                }
+            }
+            LutWeights::U16(weights) => {
+                let factor = factor / u16::MAX as f32;
+                for (slot, &weight) in out.iter_mut().zip(&weights[range]) {
+                    *slot += factor * weight as f32;
+                }
+            }
+            LutWeights::U8(weights) => {
+                let factor = factor / u8::MAX as f32;
+                for (slot, &weight) in out.iter_mut().zip(&weights[range]) {
+                    *slot += factor * weight as f32;
+                }
+            }
+        }
+    }
+}
+
+/// Round `weights` (clobbered) to multiples of `1 / max` in `out`,
+/// keeping their rounded sum: every weight is rounded down, then the
+/// steps still missing go to those with the largest remainders. Negative
+/// weights count as zero.
+fn quantize<Q: Copy + Into<f32> + TryFrom<u32>>(weights: &mut [f32], out: &mut [Q], max: Q) {
+    let max: f32 = max.into();
+    let to_q = |steps: f32| Q::try_from(steps as u32).ok();
+    let total = weights.iter().map(|w| w.max(0.0)).sum::<f32>().min(1.0) * max;
+    let mut missing = ComplexField::round(total) as i64;
+    for (weight, slot) in weights.iter_mut().zip(out.iter_mut()) {
+        let scaled = weight.clamp(0.0, 1.0) * max;
+        let steps = ComplexField::floor(scaled);
+        missing -= steps as i64;
+        if let Some(steps) = to_q(steps) {
+            *slot = steps;
+        }
+        // The remainder, in [0, 1).
+        *weight = scaled - steps;
+    }
+    for _ in 0..missing.max(0) {
+        let Some(largest) =
+            (0..weights.len()).reduce(|a, b| if weights[b] > weights[a] { b } else { a })
+        else {
+            break;
+        };
+        if let Some(steps) = to_q(out[largest].into() + 1.0) {
+            out[largest] = steps;
+        }
+        // Taken; below every untaken remainder.
+        weights[largest] = -1.0;
+    }
+}
+
+impl Decomposer<f32> for LutDecomposer {
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.palette_size
+    }
+
+    /// Trilinear interpolation; inputs outside the unit cube are clamped
+    /// onto it first.
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        let last = self.resolution - 1;
+        let cell = input.coords.map(|c| {
+            let scaled = c.clamp(0.0, 1.0) * last as f32;
+            let lower = (scaled as usize).min(last - 1);
+            (lower, scaled - lower as f32)
+        });
+        let [(r, fr), (g, fg), (b, fb)] = [cell.x, cell.y, cell.z];
+        out.fill(0.0);
+        for corner in 0..8 {
+            let pick = |bit: usize, base: usize, frac: f32| {
+                if corner & bit == 0 {
+                    (base, 1.0 - frac)
+                } else {
+                    (base + 1, frac)
+                }
+            };
+            let (cr, wr) = pick(1, r, fr);
+            let (cg, wg) = pick(2, g, fg);
+            let (cb, wb) = pick(4, b, fb);
+            let factor = wr * wg * wb;
+            if factor == 0.0 {
+                continue;
+            }
+            self.add_grid_weights(cr, cg, cb, factor, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use crate::decompose::octahedron::OctahedronDecomposer;
+    use crate::noise::Pcg32;
+    use crate::palette::SPECTRA6;
+
+    fn reconstruct(points: &[Point3<f32>], weights: &[f32]) -> Point3<f32> {
+        Point3::from(
+            points
+                .iter()
+                .zip(weights)
+                .fold(nalgebra::Vector3::zeros(), |acc, (p, w)| {
+                    acc + p.coords * *w
+                }),
+        )
+    }
+
+    #[test]
+    fn grid_points_match_inner_decomposer() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = OctahedronDecomposer::new(&points).unwrap();
+        let lut = LutDecomposer::new(&inner, 5).unwrap();
+        let input = Point3::new(0.25, 0.75, 0.5);
+        let (mut expected, mut actual) = ([0.0; 6], [0.0; 6]);
+        inner.decompose_into(&input, &mut expected);
+        lut.decompose_into(&input, &mut actual);
+        for (e, a) in expected.iter().zip(actual) {
+            assert!((e - a).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn reconstruction_within_tolerance() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = OctahedronDecomposer::new(&points).unwrap();
+        let lut = LutDecomposer::new(&inner, 9).unwrap();
+        let mut rng = Pcg32::new(7, 0);
+        for _ in 0..2000 {
+            let input = Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+            let (mut expected, mut actual) = ([0.0; 6], [0.0; 6]);
+            inner.decompose_into(&input, &mut expected);
+            lut.decompose_into(&input, &mut actual);
+            crate::decompose::assert_valid(&actual, &points, None, 1e-5).unwrap();
+            let distance = (reconstruct(&points, &actual) - reconstruct(&points, &expected)).norm();
+            assert!(
+                distance <= lut.reconstruction_tolerance(),
+                "{distance} at {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn quantized_weights_stay_within_tolerance() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = OctahedronDecomposer::new(&points).unwrap();
+        let exact = LutDecomposer::new(&inner, 9).unwrap();
+        for precision in [LutPrecision::U16, LutPrecision::U8] {
+            let lut = LutDecomposer::new_with_precision(&inner, 9, precision).unwrap();
+            assert_eq!(lut.precision(), precision);
+            let tolerance = lut.weight_tolerance() + 1e-6;
+            let mut rng = Pcg32::new(7, 0);
+            let mut largest = 0.0f32;
+            for _ in 0..2000 {
+                let input = Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+                let (mut expected, mut actual) = ([0.0; 6], [0.0; 6]);
+                exact.decompose_into(&input, &mut expected);
+                lut.decompose_into(&input, &mut actual);
+                crate::decompose::assert_valid(&actual, &points, None, 1e-5).unwrap();
+                for (e, a) in expected.iter().zip(actual) {
+                    largest = largest.max((e - a).abs());
+                }
+            }
+            assert!(largest <= tolerance, "{precision:?}: {largest}");
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_resolution() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = OctahedronDecomposer::new(&points).unwrap();
+        assert!(LutDecomposer::new(&inner, 1).is_none());
+    }
+}
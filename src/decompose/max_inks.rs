@@ -0,0 +1,204 @@
+//! Cap on the number of inks mixed at a single pixel.
+//!
+//! Some panels can only physically render two or three inks blended at
+//! one location, while the RGB decomposers happily spread weight over a
+//! full tetrahedron (four inks). [`MaxInksDecomposer`] keeps the inner
+//! decomposition when it already uses few enough inks; otherwise it takes
+//! the `max_inks` highest-weight inks and re-projects the input onto the
+//! sub-simplex they span (a triangle for 3, an edge for 2, a vertex for
+//! 1), clipping to its boundary. The result reconstructs the closest
+//! colour that mix can reach, not the input itself.
+//!
+//! For `max_inks >= 4` there is no re-projection: the top weights are
+//! kept and renormalised. Every built-in RGB decomposer already stays
+//! within four inks, so this only matters for custom ones.
+
+use crate::barycentric::line::LineProjector;
+use crate::barycentric::triangle::ClippingTriangleProjector;
+use crate::decompose::Decomposer;
+use alloc::vec::Vec;
+use nalgebra::geometry::Point3;
+
+pub struct MaxInksDecomposer<D> {
+    pub inner: D,
+    points: Vec<Point3<f32>>,
+    max_inks: usize,
+}
+
+impl<D> MaxInksDecomposer<D>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    /// `points` are the palette entries in the inner decomposer's input
+    /// space. Returns `None` if `max_inks` is 0 or `points` doesn't match
+    /// the inner palette size.
+    pub fn new(inner: D, points: &[Point3<f32>], max_inks: usize) -> Option<Self> {
+        if max_inks == 0 || points.len() != inner.palette_size() {
+            return None;
+        }
+        Some(Self {
+            inner,
+            points: points.to_vec(),
+            max_inks,
+        })
+    }
+
+    pub fn max_inks(&self) -> usize {
+        self.max_inks
+    }
+
+    /// Put all weight on the closest point of the simplex spanned by
+    /// `inks` (highest weight first), falling back to fewer inks if they
+    /// are degenerate (collinear or coincident).
+    fn reproject(&self, input: &Point3<f32>, inks: &[usize], out: &mut [f32]) {
+        out.fill(0.0);
+        if let [a, b, c, ..] = *inks
+            && let Some(triangle) =
+                ClippingTriangleProjector::new([a, b, c].map(|i| self.points[i]))
+        {
+            let (weights, _, _) = triangle.clipping_project(input);
+            for (index, weight) in [a, b, c].into_iter().zip(weights.iter()) {
+                out[index] = *weight;
+            }
+            return;
+        }
+        if let [a, b, ..] = *inks
+            && let Some(line) = LineProjector::new([self.points[a], self.points[b]])
+        {
+            let (weights, _) = line.clipping_project(input);
+            out[a] = weights[0];
+            out[b] = weights[1];
+            return;
+        }
+        if let Some(&a) = inks.first() {
+            out[a] = 1.0;
+        }
+    }
+}
+
+impl<D> Decomposer<f32> for MaxInksDecomposer<D>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.inner.palette_size()
+    }
+
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        self.inner.decompose_into(input, out);
+        let used = out.iter().filter(|&&w| w > 0.0).count();
+        if used <= self.max_inks {
+            return;
+        }
+        if self.max_inks <= 3 {
+            // Highest weights first; ties keep palette order.
+            let mut top = [usize::MAX; 3];
+            for index in 0..out.len() {
+                let mut candidate = index;
+                for slot in top.iter_mut().take(self.max_inks) {
+                    if *slot == usize::MAX || out[candidate] > out[*slot] {
+                        core::mem::swap(slot, &mut candidate);
+                        if candidate == usize::MAX {
+                            break;
+                        }
+                    }
+                }
+            }
+            self.reproject(input, &top[..self.max_inks], out);
+            return;
+        }
+        let mut inks: Vec<usize> = (0..out.len()).collect();
+        // Stable, so equal weights keep palette order.
+        inks.sort_by(|&a, &b| out[b].total_cmp(&out[a]));
+        inks.truncate(self.max_inks);
+        let kept: f32 = inks.iter().map(|&i| out[i]).sum();
+        for (index, weight) in out.iter_mut().enumerate() {
+            *weight = if inks.contains(&index) {
+                *weight / kept
+            } else {
+                0.0
+            };
+        }
+    }
+
+    fn confidence(&self, input: &Point3<f32>) -> f32 {
+        self.inner.confidence(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::noise::Pcg32;
+    use crate::palette::SPECTRA6;
+
+    fn random_inputs() -> impl Iterator<Item = Point3<f32>> {
+        let mut rng = Pcg32::new(11, 0);
+        (0..2000).map(move |_| Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()))
+    }
+
+    #[test]
+    fn two_inks_per_pixel_at_most() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = NaiveDecomposer::new(&points).unwrap();
+        let limited = MaxInksDecomposer::new(&inner, &points, 2).unwrap();
+        let mut reprojected = 0;
+        for input in random_inputs() {
+            let (mut full, mut weights) = ([0.0; 6], [0.0; 6]);
+            inner.decompose_into(&input, &mut full);
+            limited.decompose_into(&input, &mut weights);
+            assert!(
+                weights.iter().filter(|&&w| w > 0.0).count() <= 2,
+                "{weights:?}"
+            );
+            assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+            if full.iter().filter(|&&w| w > 0.0).count() > 2 {
+                reprojected += 1;
+            } else {
+                assert_eq!(weights, full);
+            }
+        }
+        assert!(reprojected > 0);
+    }
+
+    #[test]
+    fn three_inks_reproject_onto_closest_triangle_point() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = NaiveDecomposer::new(&points).unwrap();
+        let limited = MaxInksDecomposer::new(&inner, &points, 3).unwrap();
+        for input in random_inputs() {
+            let mut weights = [0.0; 6];
+            limited.decompose_into(&input, &mut weights);
+            let inks: Vec<usize> = (0..6).filter(|&i| weights[i] > 0.0).collect();
+            assert!(inks.len() <= 3, "{weights:?}");
+            if let [a, b, c] = inks[..] {
+                // On the triangle, the reconstruction is the point closest
+                // to the input, so no edge of it does better.
+                let reconstructed = points
+                    .iter()
+                    .zip(&weights)
+                    .fold(nalgebra::Vector3::zeros(), |acc, (p, w)| {
+                        acc + p.coords * *w
+                    });
+                let distance = (reconstructed - input.coords).norm();
+                for [i, j] in [[a, b], [b, c], [a, c]] {
+                    let line = LineProjector::new([points[i], points[j]]).unwrap();
+                    let edge = line.bary_to_point(&line.clipping_project(&input).0);
+                    assert!(distance <= (edge - input).norm() + 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_zero_inks() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let inner = NaiveDecomposer::new(&points).unwrap();
+        assert!(MaxInksDecomposer::new(&inner, &points, 0).is_none());
+        assert!(MaxInksDecomposer::new(&inner, &points[..5], 2).is_none());
+    }
+}
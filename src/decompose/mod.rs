@@ -1,9 +1,31 @@
+#[cfg(feature = "alloc")]
+pub mod approx;
+pub mod bias;
+#[cfg(feature = "std")]
+pub mod cached;
+pub mod depth;
+pub mod fixed;
 pub mod gray;
+#[cfg(feature = "alloc")]
+pub mod grid;
+#[cfg(feature = "alloc")]
+pub mod hull;
 pub mod input;
+#[cfg(feature = "alloc")]
+pub mod lut;
+#[cfg(feature = "alloc")]
+pub mod max_inks;
 pub mod naive;
+pub mod neutral;
 pub mod octahedron;
+pub mod response;
+pub mod subtractive;
+pub mod validate;
+#[cfg(feature = "alloc")]
+pub mod vector;
 
 pub use input::DecomposerInputColor;
+pub use validate::{InvalidDecomposition, assert_valid};
 
 /// Decomposes a colour-space point into per-palette weights.
 ///
@@ -25,4 +47,53 @@ pub trait Decomposer<T> {
     /// Decompose `input` into `out`. `out.len()` must equal
     /// [`palette_size`](Self::palette_size).
     fn decompose_into(&self, input: &Self::Input, out: &mut [T]);
+
+    /// How far inside the palette's gamut `input` lies, in `[0, 1]`: 1
+    /// well inside, falling to 0 on the boundary and outside, where the
+    /// decomposition is a projection rather than an exact mix. Used to
+    /// scale the diffused error (see
+    /// [`with_confidence_weighting`](crate::dither::DecomposingDitherStrategy::with_confidence_weighting)).
+    /// The default, for decomposers without a notion of depth, is 1.
+    fn confidence(&self, input: &Self::Input) -> f32 {
+        let _ = input;
+        1.0
+    }
+}
+
+/// Borrowing a decomposer is as good as owning it, e.g. to share one
+/// lookup table between strategies.
+impl<T, D: Decomposer<T> + ?Sized> Decomposer<T> for &D {
+    type Input = D::Input;
+
+    fn palette_size(&self) -> usize {
+        (**self).palette_size()
+    }
+
+    fn decompose_into(&self, input: &Self::Input, out: &mut [T]) {
+        (**self).decompose_into(input, out)
+    }
+
+    fn confidence(&self, input: &Self::Input) -> f32 {
+        (**self).confidence(input)
+    }
+}
+
+/// Boxed decomposers, e.g. from
+/// [`decomposer_for`](crate::registry::decomposer_for), drop into the
+/// strategies directly.
+#[cfg(feature = "alloc")]
+impl<T, D: Decomposer<T> + ?Sized> Decomposer<T> for alloc::boxed::Box<D> {
+    type Input = D::Input;
+
+    fn palette_size(&self) -> usize {
+        (**self).palette_size()
+    }
+
+    fn decompose_into(&self, input: &Self::Input, out: &mut [T]) {
+        (**self).decompose_into(input, out)
+    }
+
+    fn confidence(&self, input: &Self::Input) -> f32 {
+        (**self).confidence(input)
+    }
 }
@@ -8,6 +8,15 @@ pub enum NaiveDecomposerStrategy {
     /// concentrates on the most-interior tetrahedron. See
     /// `docs/tetra-blend-research.md`.
     TetraBlend(u32),
+    /// Among containing tetrahedra, and among faces and edges when clipping
+    /// out-of-gamut inputs, pick the one whose clamped weights rebuild the
+    /// colour closest to the input, rather than scoring by the largest
+    /// weight. Tetrahedra containing the input all rebuild it up to
+    /// rounding, so errors within the squared tolerance count as tied and
+    /// go to the `FavorMix` score: the choice doesn't hinge on rounding
+    /// noise, and only differs from `FavorMix` where clamping within the
+    /// tolerance moves the reconstruction.
+    FavorClosestReconstruction,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -21,6 +30,18 @@ impl core::fmt::Display for InvalidNaiveDecomposerStrategy {
 
 impl core::error::Error for InvalidNaiveDecomposerStrategy {}
 
+/// Inverse of `FromStr`.
+impl core::fmt::Display for NaiveDecomposerStrategy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::FavorMix => f.write_str("mix"),
+            Self::FavorDominant => f.write_str("dominant"),
+            Self::TetraBlend(p) => write!(f, "blend:{p}"),
+            Self::FavorClosestReconstruction => f.write_str("closest"),
+        }
+    }
+}
+
 impl core::str::FromStr for NaiveDecomposerStrategy {
     type Err = InvalidNaiveDecomposerStrategy;
 
@@ -29,6 +50,7 @@ impl core::str::FromStr for NaiveDecomposerStrategy {
             "mix" => Ok(Self::FavorMix),
             "dominant" => Ok(Self::FavorDominant),
             "blend" => Ok(Self::TetraBlend(1)),
+            "closest" => Ok(Self::FavorClosestReconstruction),
             _ if s.starts_with("blend:") => {
                 let p = s["blend:".len()..]
                     .parse::<u32>()
@@ -40,18 +62,30 @@ impl core::str::FromStr for NaiveDecomposerStrategy {
     }
 }
 
+/// Tetrahedra whose volume (as `6·V`) is below this fraction of their
+/// diameter cubed are treated as flat and skipped. Inverting a nearly
+/// singular vertex matrix amplifies rounding into wildly wrong barycentric
+/// coordinates, so inputs near a flat tetrahedron go to its faces instead,
+/// which span the same colours. A regular tetrahedron scores `√2/2 ≈ 0.71`.
+pub const FLAT_TETRA_RATIO: f64 = 1e-3;
+
 #[cfg(feature = "alloc")]
 pub use alloc_impl::NaiveDecomposer;
 
 #[cfg(feature = "alloc")]
 mod alloc_impl {
-    use super::NaiveDecomposerStrategy;
+    use super::{FLAT_TETRA_RATIO, NaiveDecomposerStrategy};
     use crate::barycentric::line::LineProjector;
     use crate::barycentric::tetrahedron::TetrahedronProjector;
     use crate::barycentric::triangle::TriangleProjector;
+    use crate::barycentric::{
+        DUPLICATE_DISTANCE, SimplexError, clamp_normalize, default_epsilon, find_duplicate,
+        is_inside,
+    };
+    use crate::decompose::depth::{FacePlane, HullDepth};
     use alloc::vec::Vec;
     use itertools::Itertools;
-    use nalgebra::base::{OVector, Scalar, Vector4};
+    use nalgebra::base::{Matrix3, OVector, Scalar, Vector4};
     use nalgebra::geometry::Point3;
     use nalgebra::{
         ClosedAddAssign, ClosedDivAssign, ClosedMulAssign, ClosedSubAssign, ComplexField, Const,
@@ -59,13 +93,36 @@ mod alloc_impl {
     use num_traits::identities::{One, Zero};
     use num_traits::zero;
 
+    /// Decomposes against every tetrahedron of palette colours, `C(n, 4)`
+    /// of them, clipping to faces and edges outside the gamut.
+    ///
+    /// With more than four colours the tetrahedra overlap: an input inside
+    /// the gamut is generally inside several, each with different weights
+    /// that rebuild it exactly, and the strategy picks between them.
+    /// `FavorMix` and `FavorDominant` score by the largest weight, so
+    /// where two tetrahedra's scores cross, pixels of nearly the same
+    /// colour can use different inks; `TetraBlend` averages over all of
+    /// them instead. Ties go to the first tetrahedron in enumeration
+    /// order, so every strategy is deterministic. A proper
+    /// tetrahedralization ([`crate::decompose::hull`]) has no overlap.
     pub struct NaiveDecomposer<T: Scalar + ComplexField> {
         num_colors: usize,
-        tetras: Vec<(TetrahedronProjector<T>, [usize; 4])>,
+        // Each tetrahedron with its palette indices and diameter (longest
+        // edge, in input-space units).
+        tetras: Vec<(TetrahedronProjector<T>, [usize; 4], T)>,
         faces: Vec<(TriangleProjector<T>, [usize; 3])>,
         edges: Vec<(LineProjector<T>, [usize; 2])>,
+        // Palette index quadruples and triples left out, and why.
+        skipped_tetras: Vec<([usize; 4], SimplexError)>,
+        skipped_faces: Vec<([usize; 3], SimplexError)>,
+        // Planes of the hull faces, `None` for a flat palette.
+        depth: Option<HullDepth<T, Vec<FacePlane<T>>>>,
         // Strategy used by the [`Decomposer`](super::Decomposer) trait impl.
         strategy: NaiveDecomposerStrategy,
+        // Containment tolerance, see [`crate::barycentric`].
+        epsilon: T,
+        // Penalty per unit of tetrahedron diameter, see `with_compactness`.
+        compactness: T,
     }
 
     impl<T: Scalar> NaiveDecomposer<T>
@@ -80,14 +137,44 @@ mod alloc_impl {
             + PartialOrd,
     {
         pub fn new(colors: &[Point3<T>]) -> Option<Self> {
+            if find_duplicate(colors).is_some() {
+                return None;
+            }
             let num_colors: usize = colors.len();
-            let tetras: Vec<(TetrahedronProjector<T>, [usize; 4])> = (0..num_colors)
+            let mut skipped_tetras = Vec::new();
+            let mut skipped_faces = Vec::new();
+            let tetras: Vec<(TetrahedronProjector<T>, [usize; 4], T)> = (0..num_colors)
                 .combinations(4)
                 .filter_map(|vertex_indices| {
                     let vertex_indices: [usize; 4] = vertex_indices.try_into().ok()?;
                     let vertex_points = vertex_indices.map(|i| colors[i].clone());
-                    let tetrahedron = TetrahedronProjector::new(vertex_points)?;
-                    Some((tetrahedron, vertex_indices))
+                    let diameter = vertex_points
+                        .iter()
+                        .tuple_combinations()
+                        .map(|(a, b)| T::from_real((b - a).norm()))
+                        .fold(
+                            zero(),
+                            |longest: T, length| {
+                                if length > longest { length } else { longest }
+                            },
+                        );
+                    let [a, b, c, d] = &vertex_points;
+                    let volume = Matrix3::from_columns(&[b - a, c - a, d - a]).determinant();
+                    let flat: T = nalgebra::convert::<f64, T>(FLAT_TETRA_RATIO)
+                        * diameter.clone()
+                        * diameter.clone()
+                        * diameter.clone();
+                    if volume.clone() * volume < flat.clone() * flat {
+                        skipped_tetras.push((vertex_indices, SimplexError::Degenerate));
+                        return None;
+                    }
+                    match TetrahedronProjector::new_checked(vertex_points) {
+                        Ok(tetrahedron) => Some((tetrahedron, vertex_indices, diameter)),
+                        Err(error) => {
+                            skipped_tetras.push((vertex_indices, error));
+                            None
+                        }
+                    }
                 })
                 .collect();
             let faces: Vec<(TriangleProjector<T>, [usize; 3])> = (0..num_colors)
@@ -95,8 +182,13 @@ mod alloc_impl {
                 .filter_map(|vertex_indices| {
                     let vertex_indices: [usize; 3] = vertex_indices.try_into().ok()?;
                     let vertex_points = vertex_indices.map(|i| colors[i].clone());
-                    let triangle = TriangleProjector::new(vertex_points)?;
-                    Some((triangle, vertex_indices))
+                    match TriangleProjector::new_checked(vertex_points) {
+                        Ok(triangle) => Some((triangle, vertex_indices)),
+                        Err(error) => {
+                            skipped_faces.push((vertex_indices, error));
+                            None
+                        }
+                    }
                 })
                 .collect();
             let edges: Vec<(LineProjector<T>, [usize; 2])> = (0..num_colors)
@@ -108,25 +200,166 @@ mod alloc_impl {
                     Some((line, vertex_indices))
                 })
                 .collect();
+            let depth = Self::hull_depth(colors, &faces);
             if !tetras.is_empty() || !faces.is_empty() || !edges.is_empty() {
                 Some(Self {
                     num_colors,
                     tetras,
                     faces,
                     edges,
+                    skipped_tetras,
+                    skipped_faces,
+                    depth,
                     strategy: Default::default(),
+                    epsilon: default_epsilon(),
+                    compactness: zero(),
                 })
             } else {
                 None
             }
         }
 
+        /// Palette index quadruples not used as tetrahedra, with the
+        /// reason: [`SimplexError::Degenerate`] also covers those flatter
+        /// than [`FLAT_TETRA_RATIO`](super::FLAT_TETRA_RATIO) allows. In
+        /// enumeration order.
+        pub fn skipped_tetrahedra(&self) -> &[([usize; 4], SimplexError)] {
+            &self.skipped_tetras
+        }
+
+        /// Palette index triples not used as faces, with the reason. In
+        /// enumeration order.
+        pub fn skipped_faces(&self) -> &[([usize; 3], SimplexError)] {
+            &self.skipped_faces
+        }
+
         /// Set the strategy used by [`Decomposer::decompose_into`](super::Decomposer::decompose_into).
         pub fn with_strategy(mut self, strategy: NaiveDecomposerStrategy) -> Self {
             self.strategy = strategy;
             self
         }
 
+        /// Set the containment tolerance (default
+        /// [`DEFAULT_EPSILON`](crate::barycentric::DEFAULT_EPSILON)).
+        /// Tetrahedra and faces are always tried in the same order, so with
+        /// a tolerance a point on a shared face resolves deterministically.
+        pub fn with_epsilon(mut self, epsilon: T) -> Self {
+            self.epsilon = epsilon;
+            self
+        }
+
+        /// Penalise tetrahedra by `compactness` per unit of diameter (their
+        /// longest edge, in input-space units) when choosing among those
+        /// containing the input, so mixes of far-apart inks (e.g. black and
+        /// yellow for a dark green) lose to tighter clusters that dither to
+        /// less texture. `FavorMix` minimises `max weight + k·diameter`,
+        /// `FavorDominant` maximises `max weight - k·diameter`, and
+        /// `TetraBlend` scales each tetrahedron's blend weight by
+        /// `exp(-k·diameter)`; `FavorClosestReconstruction` breaks ties like
+        /// `FavorMix`. Default 0 (no penalty); clipping to faces and
+        /// edges outside the gamut is unaffected.
+        pub fn with_compactness(mut self, compactness: T) -> Self {
+            self.compactness = compactness;
+            self
+        }
+
+        /// Depth below the faces with every colour on one side, measured
+        /// from the palette's centroid.
+        fn hull_depth(
+            colors: &[Point3<T>],
+            faces: &[(TriangleProjector<T>, [usize; 3])],
+        ) -> Option<HullDepth<T, Vec<FacePlane<T>>>> {
+            let mut centroid = Point3::origin();
+            for color in colors {
+                centroid.coords += &color.coords;
+            }
+            centroid.coords /= nalgebra::convert::<f64, T>(colors.len() as f64);
+            let tolerance: T = nalgebra::convert(-DUPLICATE_DISTANCE);
+            let planes = faces
+                .iter()
+                .filter_map(|(_, vertex_indices)| {
+                    FacePlane::new(vertex_indices.map(|i| &colors[i]), &centroid)
+                })
+                .filter(|plane| {
+                    colors
+                        .iter()
+                        .all(|color| plane.distance(color) >= tolerance)
+                })
+                .collect();
+            HullDepth::new(planes, &centroid)
+        }
+
+        /// Selection score of a containing tetrahedron (lower is better)
+        /// under the `FavorMix` / `FavorDominant` strategies.
+        fn tetra_score(&self, projected: &Vector4<T>, diameter: &T) -> T {
+            let penalty = self.compactness.clone() * diameter.clone();
+            match self.strategy {
+                NaiveDecomposerStrategy::FavorDominant => penalty - projected.max(),
+                _ => projected.max() + penalty,
+            }
+        }
+
+        /// Containing tetrahedron with the lowest `score`, the first in
+        /// enumeration order on ties, with its clamped weights. Tetrahedra
+        /// scored `None` are skipped.
+        fn best_tetra<F>(&self, input: &Point3<T>, score: F) -> Option<(Vector4<T>, &[usize; 4])>
+        where
+            F: Fn(&TetrahedronProjector<T>, &Vector4<T>, &T) -> Option<T>,
+        {
+            self.tetras
+                .iter()
+                .filter_map(|(tetra, vertex_indices, diameter)| {
+                    let projected = self.project_contained(tetra, input)?;
+                    let score = score(tetra, &projected, diameter)?;
+                    Some((projected, vertex_indices, score))
+                })
+                .reduce(|a, b| if b.2 < a.2 { b } else { a })
+                .map(|(projected, vertex_indices, _)| (projected, vertex_indices))
+        }
+
+        /// `FavorClosestReconstruction` among containing tetrahedra: those
+        /// whose squared reconstruction error is within `epsilon²` of the
+        /// lowest count as tied, and the tie goes to the `FavorMix` score.
+        /// Every tetrahedron containing the input rebuilds it up to
+        /// rounding, so comparing errors alone would pick by rounding
+        /// noise, and neighbouring pixels of one colour could land in
+        /// different tetrahedra. Scans the tetrahedra twice.
+        fn closest_tetra(&self, input: &Point3<T>) -> Option<(Vector4<T>, &[usize; 4])> {
+            let error = |tetra: &TetrahedronProjector<T>, projected: &Vector4<T>| {
+                T::from_real((tetra.bary_to_point(projected) - input).norm_squared())
+            };
+            let best = self
+                .tetras
+                .iter()
+                .filter_map(|(tetra, _, _)| {
+                    let projected = self.project_contained(tetra, input)?;
+                    Some(error(tetra, &projected))
+                })
+                .reduce(|a, b| if b < a { b } else { a })?;
+            let tied = best + self.epsilon.clone() * self.epsilon.clone();
+            self.best_tetra(input, |tetra, projected, diameter| {
+                (error(tetra, projected) <= tied).then(|| {
+                    // FavorMix's score.
+                    projected.max() + self.compactness.clone() * diameter.clone()
+                })
+            })
+        }
+
+        /// `tetra`'s barycentric coordinates for `input`, clamped and
+        /// renormalised, if it contains `input` within the tolerance.
+        fn project_contained(
+            &self,
+            tetra: &TetrahedronProjector<T>,
+            input: &Point3<T>,
+        ) -> Option<Vector4<T>> {
+            let mut projected = tetra.project(input);
+            if !is_inside(&projected, &self.epsilon) {
+                return None;
+            }
+            clamp_normalize(&mut projected);
+            Some(projected)
+        }
+
         /// Move local barycentric weights into the global-palette positions of
         /// `out`. `out` must already be zeroed. Local-vertex indices that exceed
         /// `num_colors` are silently dropped (their weight is discarded).
@@ -144,20 +377,6 @@ mod alloc_impl {
             }
         }
 
-        fn compare_tetra_projection_favor_mix<'t>(
-            a: (Vector4<T>, &'t [usize; 4]),
-            b: (Vector4<T>, &'t [usize; 4]),
-        ) -> (Vector4<T>, &'t [usize; 4]) {
-            if b.0.max() < a.0.max() { b } else { a }
-        }
-
-        fn compare_tetra_projection_favor_dominant<'t>(
-            a: (Vector4<T>, &'t [usize; 4]),
-            b: (Vector4<T>, &'t [usize; 4]),
-        ) -> (Vector4<T>, &'t [usize; 4]) {
-            if b.0.max() > a.0.max() { b } else { a }
-        }
-
         /// Blend all containing tetrahedra. Per tetrahedron the score is
         /// `α = (∏_j w_j)^power` (and `α = 1` if `power == 0`); the output is
         /// `Σ α·w / Σ α`. Returns `true` iff at least one tetrahedron
@@ -167,11 +386,10 @@ mod alloc_impl {
         fn blend_tetras_into(&self, input: &Point3<T>, out: &mut [T], power: u32) -> bool {
             let mut total: T = zero();
             let mut found_any = false;
-            for (tetra, vertex_indices) in self.tetras.iter() {
-                let projected = tetra.project(input);
-                if projected.min() < zero() {
+            for (tetra, vertex_indices, diameter) in self.tetras.iter() {
+                let Some(projected) = self.project_contained(tetra, input) else {
                     continue;
-                }
+                };
                 found_any = true;
                 // base = ∏_j w_j; alpha = base^power. Loop multiplications
                 // because T only requires ComplexField, not num_traits::Pow.
@@ -183,6 +401,7 @@ mod alloc_impl {
                 for _ in 0..power {
                     alpha *= base.clone();
                 }
+                alpha *= (-(self.compactness.clone() * diameter.clone())).exp();
                 for j in 0..4 {
                     let global = vertex_indices[j];
                     if global < self.num_colors {
@@ -228,6 +447,15 @@ mod alloc_impl {
             self.num_colors
         }
 
+        /// Depth below the nearest face of the palette's hull, 1 at the
+        /// centroid of the palette colours; see [`crate::decompose::depth`].
+        /// Always 1 for a flat palette.
+        fn confidence(&self, input: &Point3<T>) -> f32 {
+            self.depth
+                .as_ref()
+                .map_or(1.0, |depth| depth.confidence(input))
+        }
+
         fn decompose_into(&self, input: &Point3<T>, out: &mut [T]) {
             for slot in out.iter_mut() {
                 *slot = zero();
@@ -236,21 +464,14 @@ mod alloc_impl {
             let handled = if let NaiveDecomposerStrategy::TetraBlend(power) = self.strategy {
                 self.blend_tetras_into(input, out, power)
             } else {
-                let in_tetras = self.tetras.iter().filter_map(|(tetra, vertex_indices)| {
-                    let projected = tetra.project(input);
-                    if projected.min() < zero() {
-                        None
+                let in_tetras =
+                    if self.strategy == NaiveDecomposerStrategy::FavorClosestReconstruction {
+                        self.closest_tetra(input)
                     } else {
-                        Some((projected, vertex_indices))
-                    }
-                });
-                let in_tetras = in_tetras.reduce(match self.strategy {
-                    NaiveDecomposerStrategy::FavorMix => Self::compare_tetra_projection_favor_mix,
-                    NaiveDecomposerStrategy::FavorDominant => {
-                        Self::compare_tetra_projection_favor_dominant
-                    }
-                    NaiveDecomposerStrategy::TetraBlend(_) => unreachable!(),
-                });
+                        self.best_tetra(input, |_, projected, diameter| {
+                            Some(self.tetra_score(projected, diameter))
+                        })
+                    };
                 if let Some((local_barycentric, vertex_indices)) = in_tetras {
                     self.write_global_barycentric(local_barycentric, vertex_indices, out);
                     true
@@ -262,23 +483,27 @@ mod alloc_impl {
                 return;
             }
             let on_faces = self.faces.iter().filter_map(|(triangle, vertex_indices)| {
-                let (projected, distance) = triangle.project(input);
-                if projected.min() < zero() {
+                let (mut projected, distance) = triangle.project(input);
+                if !is_inside(&projected, &self.epsilon) {
                     return None;
                 }
+                clamp_normalize(&mut projected);
                 // Use distance^2, such that it is easier to compare (can ignore sign), and easier to
                 // compare against edge distances.
-                Some((
-                    distance.clone() * distance.clone(),
-                    projected,
-                    vertex_indices,
-                ))
+                let distance_sq =
+                    if self.strategy == NaiveDecomposerStrategy::FavorClosestReconstruction {
+                        T::from_real((triangle.bary_to_point(&projected) - input).norm_squared())
+                    } else {
+                        distance.clone() * distance.clone()
+                    };
+                Some((distance_sq, projected, vertex_indices))
             });
             let closest_face = on_faces.reduce(|a, b| if b.0 < a.0 { b } else { a });
             let on_edges = self.edges.iter().map(|(edge, vertex_indices)| {
-                let (projected, _) = edge.clipping_project(input);
-                let projected_pt = edge.bary_to_point(&projected);
-                let distance_sq: T = T::from_real((projected_pt - input).norm_squared());
+                let (projected, clipped_distance_sq) = edge.clipping_project(input);
+                let distance_sq = clipped_distance_sq
+                    .unwrap_or_else(|| (edge.bary_to_point(&projected) - input).norm_squared());
+                let distance_sq: T = T::from_real(distance_sq);
                 (distance_sq, projected, vertex_indices)
             });
             let closest_edge = on_edges.reduce(|a, b| if b.0 < a.0 { b } else { a });
@@ -304,3 +529,255 @@ mod alloc_impl {
         }
     }
 }
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::decompose::Decomposer;
+    use nalgebra::base::Vector3;
+    use nalgebra::geometry::Point3;
+
+    // Triangle in the z = 0 plane with one apex above and one below, so
+    // tetrahedra (0, 1, 2, 3) and (0, 1, 2, 4) share the face (0, 1, 2).
+    fn bipyramid() -> [Point3<f32>; 5] {
+        [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.2, 0.2, 1.0),
+            Point3::new(0.2, 0.2, -1.0),
+        ]
+    }
+
+    fn decompose(decomposer: &NaiveDecomposer<f32>, input: Point3<f32>) -> [f32; 5] {
+        let mut out = [0.0; 5];
+        decomposer.decompose_into(&input, &mut out);
+        out
+    }
+
+    #[test]
+    fn confidence_is_depth_below_the_hull() {
+        let decomposer = NaiveDecomposer::new(&bipyramid()).unwrap();
+        let centroid = Point3::new(0.28, 0.28, 0.0);
+        assert!((decomposer.confidence(&centroid) - 1.0).abs() < 1e-5);
+        // On the face tetrahedra (0, 1, 2, 3) and (0, 1, 2, 4) share, so
+        // without weight on the apex in either, but well inside the hull.
+        let shared = Point3::new(0.3, 0.3, 0.0);
+        assert!(decomposer.confidence(&shared) > 0.9);
+        // Near the outer face through inks 1, 2 and 3.
+        assert!(decomposer.confidence(&Point3::new(0.5, 0.45, 0.0)) < 0.2);
+        assert_eq!(decomposer.confidence(&Point3::new(1.0, 1.0, 0.0)), 0.0);
+        // No interior: no depth to measure, so no scaling either.
+        let flat = NaiveDecomposer::new(&bipyramid()[..3]).unwrap();
+        assert_eq!(flat.confidence(&Point3::new(0.2, 0.2, 0.5)), 1.0);
+    }
+
+    #[test]
+    fn skipped_simplices_are_reported_with_their_reason() {
+        use crate::barycentric::SimplexError;
+        // 1, 2 and 3 lie on one line, and 0 to 3 in the z = 0 plane.
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.5, 0.5, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+        ];
+        let decomposer = NaiveDecomposer::new(&points).unwrap();
+        assert_eq!(
+            decomposer.skipped_faces(),
+            [([1, 2, 3], SimplexError::Collinear)]
+        );
+        assert_eq!(
+            decomposer.skipped_tetrahedra(),
+            [
+                ([0, 1, 2, 3], SimplexError::Degenerate),
+                ([1, 2, 3, 4], SimplexError::Degenerate)
+            ]
+        );
+        assert!(
+            NaiveDecomposer::new(&bipyramid())
+                .unwrap()
+                .skipped_tetrahedra()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn compactness_reduces_black_yellow_mixes() {
+        use crate::decompose::DecomposerInputColor;
+        let points = crate::palette::SPECTRA6.map(|c| c.to_point());
+        // Palette order is K, W, Y, R, B, G. Inputs are random interior
+        // mixes dominated by green, so several tetrahedra contain each.
+        let black_yellow = |compactness: f32| {
+            let decomposer = NaiveDecomposer::new(&points)
+                .unwrap()
+                .with_compactness(compactness);
+            let mut rng = crate::noise::Pcg32::new(3, 0);
+            let mut total = 0.0;
+            for _ in 0..64 {
+                let mut mix: [f32; 6] = core::array::from_fn(|_| rng.next_f32());
+                mix[5] += 3.0;
+                let sum: f32 = mix.iter().sum();
+                let input = points
+                    .iter()
+                    .zip(mix)
+                    .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * (w / sum));
+                let mut out = [0.0; 6];
+                decomposer.decompose_into(&input, &mut out);
+                total += out[0].min(out[2]);
+            }
+            total
+        };
+        let loose = black_yellow(0.0);
+        let compact = black_yellow(4.0);
+        assert!(compact < loose * 0.8, "{compact} vs {loose}");
+    }
+
+    #[test]
+    fn nearly_coplanar_four_colours_decompose_cleanly() {
+        // Yellow 1e-6 off the plane through black, white and red.
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, 0.5, 0.500001),
+            Point3::new(1.0, 0.0, 0.0),
+        ];
+        let decomposer = NaiveDecomposer::new(&points).unwrap();
+        let mut rng = crate::noise::Pcg32::new(5, 0);
+        for _ in 0..500 {
+            let input = Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+            let mut out = [0.0; 4];
+            decomposer.decompose_into(&input, &mut out);
+            assert!(out.iter().all(|&w| w >= 0.0), "{input}: {out:?}");
+            assert!(
+                (out.iter().sum::<f32>() - 1.0).abs() < 1e-5,
+                "{input}: {out:?}"
+            );
+        }
+        // An input on the plane is reconstructed exactly.
+        let on_plane = Point3::new(0.8, 0.4, 0.4);
+        let mut out = [0.0; 4];
+        decomposer.decompose_into(&on_plane, &mut out);
+        let rebuilt = points
+            .iter()
+            .zip(out)
+            .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * w);
+        assert!((rebuilt - on_plane).norm() < 1e-3, "{out:?}");
+    }
+
+    #[test]
+    fn shared_face_is_assigned_stably() {
+        let points = bipyramid();
+        let on_face = Point3::new(0.3, 0.3, 0.0);
+        for strategy in [
+            NaiveDecomposerStrategy::FavorMix,
+            NaiveDecomposerStrategy::FavorDominant,
+            NaiveDecomposerStrategy::TetraBlend(1),
+        ] {
+            let decomposer = NaiveDecomposer::new(&points)
+                .unwrap()
+                .with_strategy(strategy);
+            let reference = decompose(&decomposer, on_face);
+            let rebuilt = points
+                .iter()
+                .zip(reference)
+                .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * w);
+            assert!(
+                (rebuilt - on_face).norm() < 1e-5,
+                "{strategy:?}: {reference:?}"
+            );
+            // A rounding error to either side of the face must not flip the
+            // assignment to a different tetrahedron.
+            for dz in [1e-7, -1e-7] {
+                let weights = decompose(&decomposer, on_face + Vector3::new(0.0, 0.0, dz));
+                assert!(weights.iter().all(|&w| w >= 0.0), "{weights:?}");
+                for (w, r) in weights.iter().zip(reference) {
+                    assert!((w - r).abs() < 1e-5, "{strategy:?} {dz}: {weights:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn closest_reconstruction_is_never_worse() {
+        use crate::decompose::DecomposerInputColor;
+        // SPECTRA6, and SPECTRA6 plus a mid gray inside its gamut so that
+        // tetrahedra overlap.
+        let spectra = crate::palette::SPECTRA6.map(|c| c.to_point());
+        let mut overlapping = spectra.to_vec();
+        overlapping.push(Point3::new(0.5, 0.5, 0.5));
+        for points in [&spectra[..], &overlapping[..]] {
+            let error = |strategy, input: &Point3<f32>| {
+                let decomposer = NaiveDecomposer::new(points)
+                    .unwrap()
+                    .with_strategy(strategy);
+                let mut out = [0.0; 7];
+                decomposer.decompose_into(input, &mut out[..points.len()]);
+                crate::decompose::assert_valid(&out[..points.len()], points, None, 1e-5).unwrap();
+                let rebuilt = points
+                    .iter()
+                    .zip(out)
+                    .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * w);
+                (rebuilt - input).norm()
+            };
+            let mut rng = crate::noise::Pcg32::new(9, 0);
+            for _ in 0..300 {
+                let input = Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32());
+                let closest = error(NaiveDecomposerStrategy::FavorClosestReconstruction, &input);
+                for strategy in [
+                    NaiveDecomposerStrategy::FavorMix,
+                    NaiveDecomposerStrategy::FavorDominant,
+                    NaiveDecomposerStrategy::TetraBlend(1),
+                ] {
+                    let other = error(strategy, &input);
+                    assert!(
+                        closest <= other + 1e-6,
+                        "{strategy:?} {input}: {closest} > {other}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn closest_reconstruction_breaks_ties_stably() {
+        // A fifth colour inside the tetrahedron of the other four: inputs
+        // near it are inside several tetrahedra, each rebuilding them.
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.2, 0.2, 0.2),
+        ];
+        let closest = NaiveDecomposer::new(&points)
+            .unwrap()
+            .with_strategy(NaiveDecomposerStrategy::FavorClosestReconstruction);
+        let mix = NaiveDecomposer::new(&points).unwrap();
+        let mut rng = crate::noise::Pcg32::new(11, 0);
+        for _ in 0..200 {
+            let input = Point3::new(rng.next_f32(), rng.next_f32(), rng.next_f32()) * 0.3;
+            if input.coords.sum() > 0.9 {
+                continue;
+            }
+            let reference = decompose(&closest, input);
+            let rebuilt = points
+                .iter()
+                .zip(reference)
+                .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * w);
+            assert!((rebuilt - input).norm() < 1e-5, "{input}: {reference:?}");
+            assert_eq!(reference, decompose(&mix, input), "{input}");
+            // Rounding-sized nudges keep the same tetrahedron.
+            for nudge in [Vector3::new(1e-7, 0.0, 0.0), Vector3::new(0.0, -1e-7, 1e-7)] {
+                let weights = decompose(&closest, input + nudge);
+                for (w, r) in weights.iter().zip(reference) {
+                    assert!(
+                        (w - r).abs() < 1e-4,
+                        "{input}: {weights:?} vs {reference:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Keep neutral inputs on the black–white axis.
+//!
+//! An RGB decomposer splits a gray into whatever mix reconstructs it,
+//! which on a colour panel often pulls in complementary inks (yellow and
+//! blue, red and green) next to or instead of black and white. Averaged,
+//! that's still gray; dithered, it shows as coloured speckle.
+//! [`NeutralLockDecomposer`] sends every input whose chroma (largest
+//! minus smallest channel) is at most a threshold straight to the line
+//! between two neutral poles, so such pixels only ever mix those two
+//! inks. Other inputs go to the inner decomposer unchanged. The switch is
+//! hard, so a smooth ramp out of gray changes ink mix abruptly where it
+//! crosses the threshold; keep the threshold small.
+//!
+//! Chroma is measured in the decomposer's input space: RGB in `[0, 1]`
+//! for additive mixing, density for
+//! [subtractive](crate::decompose::subtractive) mixing, where the same
+//! threshold admits more near-white and fewer near-black colours. A gray
+//! off the pole line (e.g. a palette white that is slightly tinted) is
+//! projected onto it, clipped to its ends.
+
+use crate::barycentric::line::LineProjector;
+use crate::decompose::Decomposer;
+use nalgebra::geometry::Point3;
+
+pub struct NeutralLockDecomposer<D> {
+    pub inner: D,
+    poles: [usize; 2],
+    line: LineProjector<f32>,
+    threshold: f32,
+}
+
+impl<D> NeutralLockDecomposer<D>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    /// `points` are the palette entries in the inner decomposer's input
+    /// space and `poles` the indices of its black and white. Returns
+    /// `None` if `points` doesn't match the inner palette size, a pole is
+    /// out of range, or the poles coincide.
+    pub fn new(
+        inner: D,
+        points: &[Point3<f32>],
+        poles: [usize; 2],
+        threshold: f32,
+    ) -> Option<Self> {
+        if points.len() != inner.palette_size() {
+            return None;
+        }
+        let line = LineProjector::new([*points.get(poles[0])?, *points.get(poles[1])?])?;
+        Some(Self {
+            inner,
+            poles,
+            line,
+            threshold,
+        })
+    }
+
+    /// Whether `input` is handled on the pole line.
+    pub fn is_neutral(&self, input: &Point3<f32>) -> bool {
+        let (min, max) = input
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &c| {
+                (min.min(c), max.max(c))
+            });
+        max - min <= self.threshold
+    }
+}
+
+impl<D> Decomposer<f32> for NeutralLockDecomposer<D>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.inner.palette_size()
+    }
+
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        if !self.is_neutral(input) {
+            return self.inner.decompose_into(input, out);
+        }
+        let (weights, _) = self.line.clipping_project(input);
+        out.fill(0.0);
+        out[self.poles[0]] = weights[0];
+        out[self.poles[1]] = weights[1];
+    }
+
+    fn confidence(&self, input: &Point3<f32>) -> f32 {
+        self.inner.confidence(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::palette::SPECTRA6;
+
+    #[test]
+    fn grays_mix_only_black_and_white() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let naive = NaiveDecomposer::new(&points).unwrap();
+        let locked = NeutralLockDecomposer::new(&naive, &points, [0, 1], 0.05).unwrap();
+        let (mut weights, mut inner) = ([0.0; 6], [0.0; 6]);
+        let mut colored = false;
+        let mut white = 0.0;
+        for level in [0.0, 0.2, 0.4, 0.5, 0.6, 0.8, 1.0] {
+            let gray = Point3::new(level, level + 0.02, level);
+            locked.decompose_into(&gray, &mut weights);
+            assert!(
+                weights[2..].iter().all(|&w| w == 0.0),
+                "{level}: {weights:?}"
+            );
+            assert!((weights[0] + weights[1] - 1.0).abs() < 1e-5);
+            assert!(weights[1] >= white, "{level}: {weights:?}");
+            white = weights[1];
+            naive.decompose_into(&gray, &mut inner);
+            colored |= inner[2..].iter().any(|&w| w > 0.01);
+        }
+        // Without the lock, some of these grays pick up coloured inks.
+        assert!(colored);
+        let orange = Point3::new(0.9, 0.5, 0.1);
+        locked.decompose_into(&orange, &mut weights);
+        naive.decompose_into(&orange, &mut inner);
+        assert_eq!(weights, inner);
+    }
+}
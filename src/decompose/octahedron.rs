@@ -1,15 +1,18 @@
+use crate::barycentric::find_duplicate;
 use crate::barycentric::octahedron::OctahedronProjector;
+use crate::bytes::{ByteReader, ByteWriter};
+use crate::decompose::depth::{FacePlane, HullDepth};
 use nalgebra::base::{Scalar, Vector3, Vector6};
 use nalgebra::geometry::Point3;
 use nalgebra::{ClosedAddAssign, ClosedDivAssign, ClosedMulAssign, ClosedSubAssign, ComplexField};
 use num_traits::identities::{One, Zero};
-use num_traits::one;
+use num_traits::{one, zero};
 
-struct LineDistanceCalculator<T: Scalar + ComplexField> {
+pub(crate) struct LineDistanceCalculator<T: Scalar + ComplexField> {
     // P = origin + t * direction
-    origin: Point3<T>,
-    direction: Vector3<T>,
-    direction_len_sq: T::RealField,
+    pub(crate) origin: Point3<T>,
+    pub(crate) direction: Vector3<T>,
+    pub(crate) direction_len_sq: T::RealField,
 }
 
 impl<T: Scalar> LineDistanceCalculator<T>
@@ -18,6 +21,9 @@ where
     T: ComplexField,
 {
     fn new(vertices: [Point3<T>; 2]) -> Option<Self> {
+        if find_duplicate(&vertices).is_some() {
+            return None;
+        }
         let [origin, target] = vertices;
         let direction = target - &origin;
         let direction_len_sq = direction.norm_squared();
@@ -36,27 +42,53 @@ where
         // See https://mathworld.wolfram.com/Point-LineDistance3-Dimensional.html
         self.direction.cross(&(&self.origin - pt)).norm_squared() / self.direction_len_sq.clone()
     }
+
+    /// [`distance_squared`](Self::distance_squared) in a space with each
+    /// channel scaled by `weights`.
+    fn weighted_distance_squared(&self, pt: &Point3<T>, weights: &Vector3<T>) -> T::RealField
+    where
+        T: ClosedMulAssign,
+    {
+        let direction = self.direction.component_mul(weights);
+        let offset = (&self.origin - pt).component_mul(weights);
+        direction.cross(&offset).norm_squared() / direction.norm_squared()
+    }
 }
 
-struct OctahedronDecomposerAxis<T: Scalar + ComplexField> {
+pub(crate) struct OctahedronDecomposerAxis<T: Scalar + ComplexField> {
     // Which two poles are used as central axis
-    poles: [usize; 2],
+    pub(crate) poles: [usize; 2],
     // Calculator for distance to axis central line
-    distance_calc: LineDistanceCalculator<T>,
+    pub(crate) distance_calc: LineDistanceCalculator<T>,
     // Projector to find barycentric coordinates
-    projector: OctahedronProjector<T>,
+    pub(crate) projector: OctahedronProjector<T>,
     // Ordering from projector local barycentric coordinates to global barycentric coordinates
-    color_to_vertex_index: [usize; 6],
+    pub(crate) color_to_vertex_index: [usize; 6],
 }
 
+/// Depth (see [`crate::decompose::depth`]) below which
+/// [`Average`](OctahedronDecomposerAxisStrategy::Average) takes the first
+/// axis's projection instead of averaging all three. A point on a face is
+/// a mix of that face's three colours only, the same for every axis, so
+/// near the faces the projections barely differ and the two extra ones can
+/// be skipped; deeper inside they do differ.
+pub(crate) const AVERAGE_FACE_DEPTH: f64 = 0.01;
+
 /// Decomposer for palettes whose points (colours) form a regular convex
 /// octahedron (the structurally-symmetric Spectra 6 case). On a single core
 /// of an ESP32-S3 it can decompose an 800×480 f32 image in under 5 seconds.
 pub struct OctahedronDecomposer<T: Scalar + ComplexField> {
     // Possible axis to use in decomposition
-    axis: [OctahedronDecomposerAxis<T>; 3],
+    pub(crate) axis: [OctahedronDecomposerAxis<T>; 3],
     // Strategy used by the [`Decomposer`](super::Decomposer) trait impl.
-    strategy: OctahedronDecomposerAxisStrategy,
+    pub(crate) strategy: OctahedronDecomposerAxisStrategy,
+    // Per-channel scale for the closest/furthest axis distance, if any.
+    pub(crate) distance_weights: Option<Vector3<T>>,
+    // Planes of the eight faces, one pole of each axis per face.
+    pub(crate) depth: Option<HullDepth<T, [FacePlane<T>; 8]>>,
+    // Least depth per unit of the smaller first-axis pole weight; see
+    // `pole_depth`.
+    pub(crate) pole_depth: Option<T>,
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
@@ -65,7 +97,14 @@ pub enum OctahedronDecomposerAxisStrategy {
     #[default]
     Closest,
     Furthest,
+    /// Mean of the three axes' projections. Outside the hull, and within
+    /// depth 0.01 of a face (see [`crate::decompose::depth`]), just the
+    /// first axis's.
     Average,
+    /// Mix every axis's projection, weighted by inverse squared distance
+    /// to its line, so the result moves smoothly where
+    /// [`Closest`](Self::Closest) would switch axes.
+    Blended,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -79,6 +118,19 @@ impl core::fmt::Display for InvalidOctahedronDecomposerAxisStrategy {
 
 impl core::error::Error for InvalidOctahedronDecomposerAxisStrategy {}
 
+/// Inverse of `FromStr`.
+impl core::fmt::Display for OctahedronDecomposerAxisStrategy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Axis(n) => write!(f, "axis:{n}"),
+            Self::Closest => f.write_str("closest"),
+            Self::Furthest => f.write_str("furthest"),
+            Self::Average => f.write_str("average"),
+            Self::Blended => f.write_str("blended"),
+        }
+    }
+}
+
 impl core::str::FromStr for OctahedronDecomposerAxisStrategy {
     type Err = InvalidOctahedronDecomposerAxisStrategy;
 
@@ -87,6 +139,7 @@ impl core::str::FromStr for OctahedronDecomposerAxisStrategy {
             "closest" => Ok(Self::Closest),
             "furthest" => Ok(Self::Furthest),
             "average" => Ok(Self::Average),
+            "blended" => Ok(Self::Blended),
             _ if s.starts_with("axis:") => {
                 let n = s["axis:".len()..]
                     .parse::<usize>()
@@ -149,7 +202,83 @@ where
 {
     pub fn new(colors: &[Point3<T>]) -> Option<Self> {
         let colors: &[Point3<T>; 6] = colors.try_into().ok()?;
+        if find_duplicate(colors).is_some() {
+            return None;
+        }
         let opposite_map = OctahedronProjector::find_opposites(colors)?;
+        Self::from_opposites(colors, opposite_map)
+    }
+
+    /// Like [`new`](Self::new), but with the three axes given as pairs of
+    /// colour indices instead of found by
+    /// [`find_opposites`](OctahedronProjector::find_opposites), for
+    /// measured palettes where that picks the wrong pairs or none. `None`
+    /// unless the pairs use each of the six indices once and every pair is
+    /// roughly opposite: seen from the centroid of the six colours, the
+    /// two must be more than 120° apart (180° in a regular octahedron,
+    /// 90° for neighbours).
+    pub fn new_with_axes(colors: &[Point3<T>], axes: [(usize, usize); 3]) -> Option<Self> {
+        let colors: &[Point3<T>; 6] = colors.try_into().ok()?;
+        if find_duplicate(colors).is_some() {
+            return None;
+        }
+        let mut seen = [false; 6];
+        for index in axes.iter().flat_map(|&(a, b)| [a, b]) {
+            if core::mem::replace(seen.get_mut(index)?, true) {
+                return None;
+            }
+        }
+        axes.iter()
+            .all(|&pair| Self::roughly_opposite(pair, colors))
+            .then(|| Self::from_opposites(colors, axes))?
+    }
+
+    /// Whether `pair` is more than 120° apart seen from the centroid of
+    /// `colors`, see [`new_with_axes`](Self::new_with_axes).
+    fn roughly_opposite((a, b): (usize, usize), colors: &[Point3<T>; 6]) -> bool {
+        let mut centroid = Vector3::zeros();
+        for color in colors {
+            centroid += &color.coords;
+        }
+        let centroid = Point3::from(centroid / nalgebra::convert::<f64, T>(6.0));
+        let (u, v) = (&colors[a] - &centroid, &colors[b] - &centroid);
+        let dot = u.dot(&v).real();
+        // cos < -1/2, without the square roots.
+        let four: T::RealField = nalgebra::convert(4.0);
+        dot < zero() && four * dot.clone() * dot > u.norm_squared() * v.norm_squared()
+    }
+
+    /// Like [`new`](Self::new), but instead of trusting
+    /// [`find_opposites`](OctahedronProjector::find_opposites), tries every
+    /// way of pairing the six colours into three axes and keeps the one
+    /// with the lowest mean squared reconstruction error over `samples`
+    /// (a representative set of input colours, e.g. a grid over the
+    /// gamut). Returns the decomposer together with the chosen pairing.
+    ///
+    /// Useful for measured palettes that are only roughly octahedral, where
+    /// the geometric pole test either rejects the palette or picks axes
+    /// that aren't the best fit in practice. Returns `None` if no pairing
+    /// yields a non-degenerate octahedron, or if `samples` is empty.
+    pub fn new_optimized(
+        colors: &[Point3<T>],
+        samples: &[Point3<T>],
+    ) -> Option<(Self, [(usize, usize); 3])> {
+        let colors: &[Point3<T>; 6] = colors.try_into().ok()?;
+        if samples.is_empty() {
+            return None;
+        }
+        PAIRINGS
+            .iter()
+            .filter_map(|pairing| {
+                let decomposer = Self::from_opposites(colors, *pairing)?;
+                let error = mean_squared_error(&decomposer, colors, samples);
+                Some((decomposer, *pairing, error))
+            })
+            .reduce(|a, b| if b.2 < a.2 { b } else { a })
+            .map(|(decomposer, pairing, _)| (decomposer, pairing))
+    }
+
+    fn from_opposites(colors: &[Point3<T>; 6], opposite_map: [(usize, usize); 3]) -> Option<Self> {
         let axis: [OctahedronDecomposerAxis<T>; 3] =
             crate::array_util::opt_array_transpose(core::array::from_fn(|axis_index| {
                 let vertex_index_to_color: [usize; 6] = [
@@ -162,18 +291,188 @@ where
                 ];
                 OctahedronDecomposerAxis::new(vertex_index_to_color, colors)
             }))?;
+        let depth = Self::hull_depth(&axis);
         Some(Self {
-            axis,
             strategy: Default::default(),
+            distance_weights: None,
+            pole_depth: Self::pole_depth(&axis, depth.as_ref()),
+            depth,
+            axis,
         })
     }
 
+    /// Depth below the eight faces, measured from the centroid of the six
+    /// colours. The colours are read back from the axis lines, so this
+    /// works for deserialized axes too.
+    fn hull_depth(
+        axis: &[OctahedronDecomposerAxis<T>; 3],
+    ) -> Option<HullDepth<T, [FacePlane<T>; 8]>> {
+        let poles: [[Point3<T>; 2]; 3] = core::array::from_fn(|index| {
+            let line = &axis[index].distance_calc;
+            [line.origin.clone(), &line.origin + &line.direction]
+        });
+        let mut centroid = Point3::origin();
+        for pole in poles.iter().flatten() {
+            centroid.coords += &pole.coords;
+        }
+        centroid.coords /= nalgebra::convert::<f64, T>(6.0);
+        let faces = crate::array_util::opt_array_transpose(core::array::from_fn(|face| {
+            let [a, b, c] = core::array::from_fn(|index| &poles[index][face >> index & 1]);
+            FacePlane::new([a, b, c], &centroid)
+        }))?;
+        HullDepth::new(faces, &centroid)
+    }
+
+    /// Depth per unit weight of the first axis's poles: a point the first
+    /// axis projects inside, with both pole weights at least `w`, lies at
+    /// depth `w` times this or more. Every face holds at most one of the
+    /// two poles and distance to a plane is affine in the weights, so the
+    /// bound is the least, over the faces, of the two poles' summed
+    /// distances.
+    fn pole_depth(
+        axis: &[OctahedronDecomposerAxis<T>; 3],
+        depth: Option<&HullDepth<T, [FacePlane<T>; 8]>>,
+    ) -> Option<T> {
+        let depth = depth?;
+        let line = &axis[0].distance_calc;
+        let poles = [line.origin.clone(), &line.origin + &line.direction];
+        depth
+            .faces
+            .iter()
+            .map(|face| face.distance(&poles[0]) + face.distance(&poles[1]))
+            .reduce(|a, b| if b < a { b } else { a })
+            .map(|least| least / depth.reference.clone())
+    }
+
     /// Set the axis-selection strategy used by [`Decomposer::decompose_into`](super::Decomposer::decompose_into).
     pub fn with_strategy(mut self, strategy: OctahedronDecomposerAxisStrategy) -> Self {
         self.strategy = strategy;
         self
     }
 
+    /// Scale each channel by `weights` when measuring the input's distance
+    /// to the axes for [`Closest`](OctahedronDecomposerAxisStrategy::Closest)
+    /// and [`Furthest`](OctahedronDecomposerAxisStrategy::Furthest), e.g.
+    /// luma coefficients so an error in green counts for more than one in
+    /// blue. The projection onto the chosen axis still works in raw
+    /// coordinates. Components should be positive; `None` (the default)
+    /// measures plain Euclidean distance.
+    pub fn with_distance_weights(mut self, weights: Option<Vector3<T>>) -> Self {
+        self.distance_weights = weights;
+        self
+    }
+
+    fn axis_distance_squared(
+        &self,
+        axis: &OctahedronDecomposerAxis<T>,
+        input: &Point3<T>,
+    ) -> T::RealField {
+        match &self.distance_weights {
+            Some(weights) => axis.distance_calc.weighted_distance_squared(input, weights),
+            None => axis.distance_calc.distance_squared(input),
+        }
+    }
+
+    /// Share of each axis in a [`Blended`](OctahedronDecomposerAxisStrategy::Blended)
+    /// decomposition: proportional to `1 / d²` for distance `d` to the axis
+    /// line, computed as the product of the other two distances so an
+    /// input on a line gets that axis alone. The centre, on all three
+    /// lines, gets an even split.
+    fn blend_shares(&self, input: &Point3<T>) -> [T; 3] {
+        let [a, b, c] = self
+            .axis
+            .each_ref()
+            .map(|axis| self.axis_distance_squared(axis, input));
+        let shares = [b.clone() * c.clone(), a.clone() * c, a * b];
+        let total = shares[0].clone() + shares[1].clone() + shares[2].clone();
+        if total.is_zero() {
+            let third: T = one::<T>() / (one::<T>() + one() + one());
+            return [third.clone(), third.clone(), third];
+        }
+        shares.map(|share| T::from_real(share / total.clone()))
+    }
+
+    /// [`decompose_into`](super::Decomposer::decompose_into) as a
+    /// distribution: negative weights (rounding from the projection, for
+    /// inputs on or just outside the surface) are clipped to zero and the
+    /// rest renormalised to sum to one. If nothing positive is left, the
+    /// largest raw weight gets everything.
+    pub fn decompose_clamped(&self, input: &Point3<T>) -> [T; 6] {
+        use super::Decomposer;
+        let mut weights: [T; 6] = core::array::from_fn(|_| zero());
+        self.decompose_into(input, &mut weights);
+        let largest = (1..6).fold(
+            0,
+            |best, i| {
+                if weights[i] > weights[best] { i } else { best }
+            },
+        );
+        // False for NaN too.
+        let positive = |w: &T| w.partial_cmp(&zero()) == Some(core::cmp::Ordering::Greater);
+        let mut total: T = zero();
+        for weight in weights.iter_mut() {
+            if !positive(weight) {
+                *weight = zero();
+            }
+            total += weight.clone();
+        }
+        if !positive(&total) {
+            return core::array::from_fn(|i| if i == largest { one() } else { zero() });
+        }
+        weights.map(|weight| weight / total.clone())
+    }
+
+    /// Whether `input`, with the first axis's projection `weights`, lies
+    /// within [`AVERAGE_FACE_DEPTH`] of a face, where the three axes
+    /// decompose it (nearly) alike. `false` without face planes. Most
+    /// points are ruled out by their pole weights (see
+    /// [`pole_depth`](Self::pole_depth)) before the eight planes are
+    /// evaluated.
+    fn near_face(&self, input: &Point3<T>, weights: &Vector6<T>) -> bool {
+        let (Some(depth), Some(pole_depth)) = (&self.depth, &self.pole_depth) else {
+            return false;
+        };
+        let limit: T = nalgebra::convert(AVERAGE_FACE_DEPTH);
+        let [a, b] = self.axis[0].poles.map(|pole| weights[pole].clone());
+        let least = if b < a { b } else { a };
+        least * pole_depth.clone() <= limit && depth.depth(input) <= limit
+    }
+
+    /// Index of the axis the strategy decomposes `input` on: the fixed
+    /// one for [`Axis`](OctahedronDecomposerAxisStrategy::Axis), the
+    /// closest or furthest line, and for
+    /// [`Blended`](OctahedronDecomposerAxisStrategy::Blended) the axis with
+    /// the largest share. `None` for
+    /// [`Average`](OctahedronDecomposerAxisStrategy::Average), which uses
+    /// all three alike. Meant for seeing where the choice switches, e.g.
+    /// as an image.
+    pub fn chosen_axis(&self, input: &Point3<T>) -> Option<usize> {
+        // Only the closest and furthest picks need the distances.
+        let pick = |better: fn(&T::RealField, &T::RealField) -> bool| {
+            let distances = self
+                .axis
+                .each_ref()
+                .map(|axis| self.axis_distance_squared(axis, input));
+            (1..3).fold(0, |best, i| {
+                if better(&distances[i], &distances[best]) {
+                    i
+                } else {
+                    best
+                }
+            })
+        };
+        match self.strategy {
+            OctahedronDecomposerAxisStrategy::Axis(axis) => Some(axis % self.axis.len()),
+            OctahedronDecomposerAxisStrategy::Closest => Some(pick(|a, b| a < b)),
+            OctahedronDecomposerAxisStrategy::Furthest => Some(pick(|a, b| a > b)),
+            OctahedronDecomposerAxisStrategy::Average => None,
+            OctahedronDecomposerAxisStrategy::Blended => {
+                let shares = self.blend_shares(input);
+                Some((1..3).fold(0, |best, i| if shares[i] > shares[best] { i } else { best }))
+            }
+        }
+    }
+
     pub fn get_axis_from_color(&self, color_index: usize) -> Option<usize> {
         self.axis.iter().enumerate().find_map(|(axis_index, axis)| {
             if axis.poles[0] == color_index || axis.poles[1] == color_index {
@@ -185,6 +484,198 @@ where
     }
 }
 
+impl LineDistanceCalculator<f32> {
+    const SERIALIZED_LEN: usize = (3 + 3 + 1) * 4;
+
+    fn write_bytes(&self, writer: &mut ByteWriter) {
+        writer.f32s(self.origin.coords.as_slice());
+        writer.f32s(self.direction.as_slice());
+        writer.f32s(&[self.direction_len_sq]);
+    }
+
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let origin = Point3::from(reader.f32s::<3>()?);
+        let direction = Vector3::from(reader.f32s::<3>()?);
+        let [direction_len_sq] = reader.f32s::<1>()?;
+        (direction_len_sq > 0.0).then_some(Self {
+            origin,
+            direction,
+            direction_len_sq,
+        })
+    }
+}
+
+impl OctahedronDecomposerAxis<f32> {
+    const SERIALIZED_LEN: usize = 2
+        + 6
+        + LineDistanceCalculator::<f32>::SERIALIZED_LEN
+        + OctahedronProjector::<f32>::SERIALIZED_LEN;
+
+    fn write_bytes(&self, writer: &mut ByteWriter) {
+        writer.bytes(&self.poles.map(|pole| pole as u8));
+        writer.bytes(&self.color_to_vertex_index.map(|index| index as u8));
+        self.distance_calc.write_bytes(writer);
+        self.projector.write_bytes(writer);
+    }
+
+    /// Rejects index tables that aren't a permutation of the six colours,
+    /// or whose poles aren't the projector's first two vertices.
+    fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+        let poles = reader.bytes::<2>()?.map(usize::from);
+        let color_to_vertex_index = reader.bytes::<6>()?.map(usize::from);
+        let mut seen = [false; 6];
+        for &vertex_index in &color_to_vertex_index {
+            *seen.get_mut(vertex_index)? = true;
+        }
+        if seen != [true; 6]
+            || color_to_vertex_index.get(poles[0]) != Some(&0)
+            || color_to_vertex_index.get(poles[1]) != Some(&1)
+        {
+            return None;
+        }
+        Some(Self {
+            poles,
+            distance_calc: LineDistanceCalculator::read_bytes(reader)?,
+            projector: OctahedronProjector::read_bytes(reader)?,
+            color_to_vertex_index,
+        })
+    }
+}
+
+/// Leading bytes of an [`OctahedronDecomposer::to_bytes`] blob.
+const SERIALIZED_MAGIC: [u8; 4] = *b"EPDO";
+/// Bumped whenever the blob layout changes.
+const SERIALIZED_VERSION: u8 = 2;
+
+impl OctahedronDecomposer<f32> {
+    /// Size of the [`to_bytes`](Self::to_bytes) blob.
+    pub const SERIALIZED_LEN: usize = SERIALIZED_MAGIC.len()
+        + 1
+        + 2
+        + 3 * OctahedronDecomposerAxis::<f32>::SERIALIZED_LEN
+        + 3 * 4;
+
+    /// Serialize the prepared axes, so a target can load them with
+    /// [`from_bytes`](Self::from_bytes) instead of running
+    /// [`new`](Self::new). The layout is fixed and little-endian:
+    ///
+    /// * `b"EPDO"`, then a version byte;
+    /// * the strategy as a tag byte (0 axis, 1 closest, 2 furthest,
+    ///   3 average, 4 blended) and an argument byte (the axis index for tag 0);
+    /// * per axis: the two pole colour indices, the colour-to-vertex
+    ///   table (6 bytes), then the axis line and every wedge, face and
+    ///   edge projector as `f32` matrices in column-major order;
+    /// * the three distance weights as `f32` (all 1 when unweighted).
+    ///
+    /// Only the `f32` decomposer serializes; that's what runs on-device.
+    pub fn to_bytes(&self) -> [u8; Self::SERIALIZED_LEN] {
+        let mut bytes = [0; Self::SERIALIZED_LEN];
+        let mut writer = ByteWriter::new(&mut bytes);
+        writer.bytes(&SERIALIZED_MAGIC);
+        writer.u8(SERIALIZED_VERSION);
+        let (tag, argument) = match self.strategy {
+            OctahedronDecomposerAxisStrategy::Axis(axis) => (0, (axis % self.axis.len()) as u8),
+            OctahedronDecomposerAxisStrategy::Closest => (1, 0),
+            OctahedronDecomposerAxisStrategy::Furthest => (2, 0),
+            OctahedronDecomposerAxisStrategy::Average => (3, 0),
+            OctahedronDecomposerAxisStrategy::Blended => (4, 0),
+        };
+        writer.bytes(&[tag, argument]);
+        for axis in &self.axis {
+            axis.write_bytes(&mut writer);
+        }
+        let weights = self.distance_weights.unwrap_or(Vector3::repeat(1.0));
+        writer.f32s(weights.as_slice());
+        bytes
+    }
+
+    /// Inverse of [`to_bytes`](Self::to_bytes). Returns `None` unless
+    /// `bytes` is exactly [`SERIALIZED_LEN`](Self::SERIALIZED_LEN) long
+    /// with the expected magic and version, every value is finite, the
+    /// index tables are consistent, the three axes use distinct poles and
+    /// the distance weights are positive.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.bytes::<4>()? != SERIALIZED_MAGIC || reader.u8()? != SERIALIZED_VERSION {
+            return None;
+        }
+        let strategy = match reader.bytes::<2>()? {
+            [0, axis @ 0..3] => OctahedronDecomposerAxisStrategy::Axis(usize::from(axis)),
+            [1, 0] => OctahedronDecomposerAxisStrategy::Closest,
+            [2, 0] => OctahedronDecomposerAxisStrategy::Furthest,
+            [3, 0] => OctahedronDecomposerAxisStrategy::Average,
+            [4, 0] => OctahedronDecomposerAxisStrategy::Blended,
+            _ => return None,
+        };
+        let axis: [OctahedronDecomposerAxis<f32>; 3] =
+            crate::array_util::opt_array_transpose(core::array::from_fn(|_| {
+                OctahedronDecomposerAxis::read_bytes(&mut reader)
+            }))?;
+        let weights = Vector3::from(reader.f32s::<3>()?);
+        if !reader.is_empty() || weights.iter().any(|&w| w <= 0.0) {
+            return None;
+        }
+        let distance_weights = (weights != Vector3::repeat(1.0)).then_some(weights);
+        let mut seen = [false; 6];
+        for pole in axis.iter().flat_map(|axis| axis.poles) {
+            *seen.get_mut(pole)? = true;
+        }
+        let depth = Self::hull_depth(&axis);
+        (seen == [true; 6]).then_some(Self {
+            pole_depth: Self::pole_depth(&axis, depth.as_ref()),
+            depth,
+            axis,
+            strategy,
+            distance_weights,
+        })
+    }
+}
+
+/// All 15 ways to split six palette indices into three unordered pairs.
+/// Orientation within a pair doesn't change the octahedron's faces.
+#[rustfmt::skip]
+const PAIRINGS: [[(usize, usize); 3]; 15] = [
+    [(0, 1), (2, 3), (4, 5)], [(0, 1), (2, 4), (3, 5)], [(0, 1), (2, 5), (3, 4)],
+    [(0, 2), (1, 3), (4, 5)], [(0, 2), (1, 4), (3, 5)], [(0, 2), (1, 5), (3, 4)],
+    [(0, 3), (1, 2), (4, 5)], [(0, 3), (1, 4), (2, 5)], [(0, 3), (1, 5), (2, 4)],
+    [(0, 4), (1, 2), (3, 5)], [(0, 4), (1, 3), (2, 5)], [(0, 4), (1, 5), (2, 3)],
+    [(0, 5), (1, 2), (3, 4)], [(0, 5), (1, 3), (2, 4)], [(0, 5), (1, 4), (2, 3)],
+];
+
+/// Mean squared distance between each sample and the colour rebuilt from
+/// its decomposition.
+fn mean_squared_error<T>(
+    decomposer: &OctahedronDecomposer<T>,
+    colors: &[Point3<T>; 6],
+    samples: &[Point3<T>],
+) -> T::RealField
+where
+    T: Scalar
+        + ComplexField
+        + ClosedSubAssign
+        + ClosedMulAssign
+        + ClosedAddAssign
+        + ClosedDivAssign
+        + Zero
+        + One
+        + PartialOrd,
+{
+    use super::Decomposer;
+    let mut total: T::RealField = num_traits::zero();
+    let mut count: T::RealField = num_traits::zero();
+    for sample in samples {
+        let mut weights: [T; 6] = core::array::from_fn(|_| num_traits::zero());
+        decomposer.decompose_into(sample, &mut weights);
+        let mut rebuilt: Vector3<T> = num_traits::zero();
+        for (color, weight) in colors.iter().zip(weights) {
+            rebuilt += &color.coords * weight;
+        }
+        total += (rebuilt - &sample.coords).norm_squared();
+        count += num_traits::one();
+    }
+    total / count
+}
+
 impl<T: Scalar> super::Decomposer<T> for OctahedronDecomposer<T>
 where
     T: ComplexField
@@ -202,16 +693,26 @@ where
         6
     }
 
+    /// Depth below the nearest face, 1 at the centroid of the six colours;
+    /// see [`crate::decompose::depth`].
+    fn confidence(&self, input: &Point3<T>) -> f32 {
+        self.depth
+            .as_ref()
+            .map_or(1.0, |depth| depth.confidence(input))
+    }
+
     fn decompose_into(&self, input: &Point3<T>, out: &mut [T]) {
         let weights: Vector6<T> = match self.strategy {
-            OctahedronDecomposerAxisStrategy::Axis(axis) => {
-                let axis = &self.axis[axis % self.axis.len()];
-                axis.project(input).0
+            OctahedronDecomposerAxisStrategy::Axis(_)
+            | OctahedronDecomposerAxisStrategy::Closest
+            | OctahedronDecomposerAxisStrategy::Furthest => {
+                let axis = self.chosen_axis(input).unwrap_or(0);
+                self.axis[axis].project(input).0
             }
             OctahedronDecomposerAxisStrategy::Average => {
                 let axis = &self.axis[0];
                 let (mut barycentric_global, is_inside) = axis.project(input);
-                if is_inside {
+                if is_inside && !self.near_face(input, &barycentric_global) {
                     let mut divisor: T = one();
                     for axis_index in 1..self.axis.len() {
                         let (current, _) = self.axis[axis_index].project(input);
@@ -222,23 +723,13 @@ where
                 }
                 barycentric_global
             }
-            OctahedronDecomposerAxisStrategy::Closest => {
-                let (axis, _) = self
-                    .axis
-                    .iter()
-                    .map(|axis| (axis, axis.distance_calc.distance_squared(input)))
-                    .reduce(|a, b| if b.1 < a.1 { b } else { a })
-                    .unwrap_or((&self.axis[0], num_traits::zero()));
-                axis.project(input).0
-            }
-            OctahedronDecomposerAxisStrategy::Furthest => {
-                let (axis, _) = self
-                    .axis
-                    .iter()
-                    .map(|axis| (axis, axis.distance_calc.distance_squared(input)))
-                    .reduce(|a, b| if b.1 > a.1 { b } else { a })
-                    .unwrap_or((&self.axis[0], num_traits::zero()));
-                axis.project(input).0
+            OctahedronDecomposerAxisStrategy::Blended => {
+                let shares = self.blend_shares(input);
+                let mut blended: Vector6<T> = Vector6::zeros();
+                for (axis, share) in self.axis.iter().zip(shares) {
+                    blended += axis.project(input).0 * share;
+                }
+                blended
             }
         };
         // Owned `Matrix` doesn't implement `IntoIterator`; destructure the
@@ -249,3 +740,408 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::Decomposer;
+
+    fn skewed_palette() -> [Point3<f32>; 6] {
+        // Regular octahedron around (0.5, 0.5, 0.5), with the +x vertex
+        // pulled towards +y/+z and the -z vertex pushed outwards.
+        [
+            Point3::new(0.9, 0.65, 0.6),
+            Point3::new(0.1, 0.5, 0.5),
+            Point3::new(0.5, 0.9, 0.5),
+            Point3::new(0.5, 0.1, 0.5),
+            Point3::new(0.5, 0.5, 0.9),
+            Point3::new(0.45, 0.45, 0.0),
+        ]
+    }
+
+    fn grid() -> [Point3<f32>; 125] {
+        core::array::from_fn(|i| {
+            let step = |n: usize| (n % 5) as f32 / 4.0;
+            Point3::new(step(i), step(i / 5), step(i / 25))
+        })
+    }
+
+    #[test]
+    fn optimized_is_no_worse_than_default() {
+        let colors = skewed_palette();
+        let samples = grid();
+        let default = OctahedronDecomposer::new(&colors).unwrap();
+        let (optimized, pairing) = OctahedronDecomposer::new_optimized(&colors, &samples).unwrap();
+        let default_error = mean_squared_error(&default, &colors, &samples);
+        let optimized_error = mean_squared_error(&optimized, &colors, &samples);
+        assert!(optimized_error <= default_error);
+        let mut seen = [false; 6];
+        for (a, b) in pairing {
+            seen[a] = true;
+            seen[b] = true;
+        }
+        assert_eq!(seen, [true; 6]);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        use crate::decompose::Decomposer;
+        let colors = skewed_palette();
+        for strategy in [
+            OctahedronDecomposerAxisStrategy::Axis(1),
+            OctahedronDecomposerAxisStrategy::Closest,
+            OctahedronDecomposerAxisStrategy::Furthest,
+            OctahedronDecomposerAxisStrategy::Average,
+            OctahedronDecomposerAxisStrategy::Blended,
+        ] {
+            let original = OctahedronDecomposer::new(&colors)
+                .unwrap()
+                .with_strategy(strategy);
+            let loaded = OctahedronDecomposer::from_bytes(&original.to_bytes()).unwrap();
+            assert_eq!(loaded.strategy, strategy);
+            for sample in grid() {
+                let mut expected = [0.0; 6];
+                let mut actual = [0.0; 6];
+                original.decompose_into(&sample, &mut expected);
+                loaded.decompose_into(&sample, &mut actual);
+                assert_eq!(expected, actual);
+                let confidence = original.confidence(&sample);
+                assert!((loaded.confidence(&sample) - confidence).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn confidence_falls_from_centre_to_faces() {
+        use crate::decompose::Decomposer;
+        let colors = skewed_palette();
+        let decomposer = OctahedronDecomposer::new(&colors).unwrap();
+        let mut centroid = Point3::origin();
+        for color in &colors {
+            centroid.coords += color.coords / 6.0;
+        }
+        assert!((decomposer.confidence(&centroid) - 1.0).abs() < 1e-5);
+        // Halfway from the centroid to a vertex, then just short of it.
+        let halfway = centroid + (colors[2] - centroid) * 0.5;
+        let confidence = decomposer.confidence(&halfway);
+        assert!(confidence > 0.4 && confidence < 0.6, "{confidence}");
+        let near = centroid + (colors[2] - centroid) * 0.95;
+        assert!(decomposer.confidence(&near) < 0.06);
+        for outside in [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)] {
+            assert_eq!(decomposer.confidence(&outside), 0.0);
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_corrupt_blobs() {
+        let bytes = OctahedronDecomposer::new(&skewed_palette())
+            .unwrap()
+            .to_bytes();
+        assert!(OctahedronDecomposer::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        let mut bad_magic = bytes;
+        bad_magic[0] = b'X';
+        assert!(OctahedronDecomposer::from_bytes(&bad_magic).is_none());
+        let mut bad_pole = bytes;
+        bad_pole[7] = 6;
+        assert!(OctahedronDecomposer::from_bytes(&bad_pole).is_none());
+        let mut nan = bytes;
+        nan[bytes.len() - 4..].copy_from_slice(&f32::NAN.to_le_bytes());
+        assert!(OctahedronDecomposer::from_bytes(&nan).is_none());
+    }
+
+    #[test]
+    fn luma_weights_change_closest_axis() {
+        use crate::decompose::{Decomposer, DecomposerInputColor};
+        let points = crate::palette::SPECTRA6.map(|c| c.to_point());
+        let decompose = |decomposer: &OctahedronDecomposer<f32>| {
+            let mut weights = [0.0; 6];
+            decomposer.decompose_into(&Point3::new(0.1, 0.5, 0.1), &mut weights);
+            weights
+        };
+        let with = |strategy| {
+            OctahedronDecomposer::new(&points)
+                .unwrap()
+                .with_strategy(strategy)
+        };
+        // Green-ish, and within 1e-4 of both the green-red and the
+        // black-white axis; with luma weights, blue-yellow is clearly
+        // closest.
+        let plain = with(OctahedronDecomposerAxisStrategy::Closest);
+        let weighted = with(OctahedronDecomposerAxisStrategy::Closest)
+            .with_distance_weights(Some(Vector3::new(0.2126, 0.7152, 0.0722)));
+        let [green_red, blue_yellow] =
+            [0, 1].map(|axis| decompose(&with(OctahedronDecomposerAxisStrategy::Axis(axis))));
+        assert_eq!(decompose(&plain), green_red);
+        assert_eq!(decompose(&weighted), blue_yellow);
+        assert_ne!(green_red, blue_yellow);
+        let loaded = OctahedronDecomposer::from_bytes(&weighted.to_bytes()).unwrap();
+        assert_eq!(decompose(&loaded), blue_yellow);
+    }
+
+    #[test]
+    fn blended_weights_are_continuous_across_axis_switch() {
+        use crate::decompose::Decomposer;
+        let colors = skewed_palette();
+        let with = |strategy| {
+            OctahedronDecomposer::new(&colors)
+                .unwrap()
+                .with_strategy(strategy)
+        };
+        let (closest, blended) = (
+            with(OctahedronDecomposerAxisStrategy::Closest),
+            with(OctahedronDecomposerAxisStrategy::Blended),
+        );
+        let decompose = |decomposer: &OctahedronDecomposer<f32>, input| {
+            let mut weights = [0.0; 6];
+            decomposer.decompose_into(&input, &mut weights);
+            weights
+        };
+        let largest_step = |decomposer: &OctahedronDecomposer<f32>| {
+            // Sweep from near one axis line to near another.
+            let steps = 1000;
+            let at = |i: usize| {
+                let t = i as f32 / steps as f32;
+                Point3::new(0.3 + 0.2 * t, 0.5 - 0.2 * t, 0.5)
+            };
+            (1..=steps)
+                .map(|i| {
+                    let [previous, current] = [i - 1, i].map(|i| decompose(decomposer, at(i)));
+                    let total = previous
+                        .iter()
+                        .zip(current)
+                        .map(|(a, b)| (a - b).abs())
+                        .sum();
+                    assert!((current.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+                    assert!(current.iter().all(|&w| w >= -1e-6), "{current:?}");
+                    total
+                })
+                .fold(0.0f32, f32::max)
+        };
+        // Closest jumps where the nearest axis changes; blending doesn't.
+        assert!(largest_step(&closest) > 0.1);
+        assert!(largest_step(&blended) < 0.01);
+    }
+
+    #[test]
+    fn clamped_weights_are_a_distribution() {
+        use crate::decompose::Decomposer;
+        let colors = skewed_palette();
+        let centre = Point3::new(0.5, 0.5, 0.5);
+        for strategy in [
+            OctahedronDecomposerAxisStrategy::Closest,
+            OctahedronDecomposerAxisStrategy::Average,
+            OctahedronDecomposerAxisStrategy::Blended,
+        ] {
+            let decomposer = OctahedronDecomposer::new(&colors)
+                .unwrap()
+                .with_strategy(strategy);
+            // Well inside: nothing to clip.
+            for t in [0.0, 0.3, 0.6] {
+                for color in &colors {
+                    let input = centre + (color - centre) * t;
+                    let mut raw = [0.0; 6];
+                    decomposer.decompose_into(&input, &mut raw);
+                    let clamped = decomposer.decompose_clamped(&input);
+                    for (r, c) in raw.iter().zip(clamped) {
+                        assert!((r - c).abs() < 1e-5, "{raw:?} vs {clamped:?}");
+                    }
+                    crate::decompose::assert_valid(&raw, &colors, Some(&input), 1e-5).unwrap();
+                }
+            }
+            // On and just past each vertex and the edge midpoints between
+            // neighbouring vertices.
+            for a in &colors {
+                for b in &colors {
+                    for t in [1.0, 1.0001, 1.01, 1.2] {
+                        let input = centre + ((a - centre) + (b - centre)) * (0.5 * t);
+                        let clamped = decomposer.decompose_clamped(&input);
+                        crate::decompose::assert_valid(clamped.as_slice(), &colors, None, 1e-5)
+                            .unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn manual_axes_must_pair_opposite_colours() {
+        let colors = skewed_palette();
+        let samples = grid();
+        let default = OctahedronDecomposer::new(&colors).unwrap();
+        let manual =
+            OctahedronDecomposer::new_with_axes(&colors, [(0, 1), (2, 3), (4, 5)]).unwrap();
+        for sample in &samples {
+            let (mut expected, mut weights) = ([0.0; 6], [0.0; 6]);
+            default.decompose_into(sample, &mut expected);
+            manual.decompose_into(sample, &mut weights);
+            for (w, e) in weights.iter().zip(expected) {
+                assert!(
+                    (w - e).abs() < 1e-5,
+                    "{sample:?}: {weights:?} vs {expected:?}"
+                );
+            }
+        }
+        // Neighbours, not opposites.
+        assert!(OctahedronDecomposer::new_with_axes(&colors, [(0, 2), (1, 4), (3, 5)]).is_none());
+        // Index reused or out of range.
+        assert!(OctahedronDecomposer::new_with_axes(&colors, [(0, 1), (2, 3), (4, 0)]).is_none());
+        assert!(OctahedronDecomposer::new_with_axes(&colors, [(0, 1), (2, 3), (4, 6)]).is_none());
+    }
+
+    /// Mean of all three axes' projections, what `Average` computed before
+    /// it skipped the near-face case.
+    fn full_average(decomposer: &OctahedronDecomposer<f32>, input: &Point3<f32>) -> [f32; 6] {
+        let (mut sum, is_inside) = decomposer.axis[0].project(input);
+        if is_inside {
+            sum += decomposer.axis[1].project(input).0;
+            sum += decomposer.axis[2].project(input).0;
+            sum /= 3.0;
+        }
+        sum.into()
+    }
+
+    #[test]
+    fn average_skips_only_near_faces() {
+        let colors = skewed_palette();
+        let decomposer = OctahedronDecomposer::new(&colors)
+            .unwrap()
+            .with_strategy(OctahedronDecomposerAxisStrategy::Average);
+        let depth = decomposer.depth.as_ref().unwrap();
+        let mut skipped = 0;
+        for sample in grid() {
+            let mut weights = [0.0; 6];
+            decomposer.decompose_into(&sample, &mut weights);
+            let expected = full_average(&decomposer, &sample);
+            let d = depth.depth(&sample);
+            // The pole-weight shortcut never rules out a point the planes
+            // would find near a face.
+            let (first, is_inside) = decomposer.axis[0].project(&sample);
+            if is_inside {
+                assert_eq!(
+                    decomposer.near_face(&sample, &first),
+                    d <= AVERAGE_FACE_DEPTH as f32,
+                    "{sample:?} at depth {d}"
+                );
+            }
+            // Deeper inside, and outside, nothing changes; near a face the
+            // axes differ by about the depth.
+            let tolerance = if d > AVERAGE_FACE_DEPTH as f32 || d < 0.0 {
+                1e-6
+            } else {
+                skipped += 1;
+                2.0 * AVERAGE_FACE_DEPTH as f32
+            };
+            for (w, e) in weights.iter().zip(expected) {
+                assert!(
+                    (w - e).abs() <= tolerance,
+                    "{sample:?} at depth {d}: {weights:?} vs {expected:?}"
+                );
+            }
+        }
+        assert!(skipped > 0);
+    }
+
+    #[test]
+    fn average_matches_full_average_near_faces() {
+        let colors =
+            crate::palette::SPECTRA6.map(|c| crate::decompose::DecomposerInputColor::to_point(&c));
+        let decomposer = OctahedronDecomposer::new(&colors)
+            .unwrap()
+            .with_strategy(OctahedronDecomposerAxisStrategy::Average);
+        let depth = decomposer.depth.as_ref().unwrap();
+        let centroid = Point3::from(colors.iter().map(|c| c.coords).sum::<Vector3<f32>>() / 6.0);
+        // Mixes of three mutually neighbouring colours, i.e. points on a
+        // face, nudged just inside: saturated colours a photo often holds.
+        let mut queries = alloc::vec::Vec::new();
+        for (a, b, c) in [(0, 2, 4), (1, 3, 5), (0, 3, 4), (1, 2, 5)] {
+            for i in 1..20 {
+                for j in 1..20 - i {
+                    let (u, v) = (i as f32 / 20.0, j as f32 / 20.0);
+                    let on_face = colors[a].coords * u
+                        + colors[b].coords * v
+                        + colors[c].coords * (1.0 - u - v);
+                    queries.push(Point3::from(on_face * 0.997 + centroid.coords * 0.003));
+                }
+            }
+        }
+        queries.retain(|q| (0.0..=AVERAGE_FACE_DEPTH as f32).contains(&depth.depth(q)));
+        assert!(queries.len() > 100, "{}", queries.len());
+
+        for query in &queries {
+            let mut weights = [0.0; 6];
+            decomposer.decompose_into(query, &mut weights);
+            let expected = full_average(&decomposer, query);
+            for (w, e) in weights.iter().zip(expected) {
+                assert!(
+                    (w - e).abs() <= 2.0 * AVERAGE_FACE_DEPTH as f32,
+                    "{query:?}: {weights:?} vs {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn optimized_requires_samples() {
+        assert!(OctahedronDecomposer::new_optimized(&skewed_palette(), &[]).is_none());
+    }
+
+    #[test]
+    fn spectra6_axes_agree_on_colour() {
+        let colors =
+            crate::palette::SPECTRA6.map(|c| crate::decompose::DecomposerInputColor::to_point(&c));
+        let decomposer = OctahedronDecomposer::new(&colors).unwrap();
+        let black_white = decomposer.get_axis_from_color(0).unwrap();
+        assert_eq!(decomposer.get_axis_from_color(1), Some(black_white));
+        let rebuild = |weights: &[f32; 6]| {
+            colors
+                .iter()
+                .zip(weights)
+                .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * *w)
+        };
+        let gray = Point3::from((colors[0].coords + colors[1].coords) / 2.0);
+        let mixed = rebuild(&[0.3, 0.1, 0.2, 0.15, 0.05, 0.2]);
+        for axis in 0..3 {
+            let decomposer = OctahedronDecomposer::new(&colors)
+                .unwrap()
+                .with_strategy(OctahedronDecomposerAxisStrategy::Axis(axis));
+            for (index, color) in colors.iter().enumerate() {
+                let mut weights = [0.0; 6];
+                decomposer.decompose_into(color, &mut weights);
+                assert!(
+                    (weights[index] - 1.0).abs() < 1e-5,
+                    "axis {axis}: {weights:?}"
+                );
+            }
+            // Every axis splits an in-gamut colour differently, but each
+            // rebuilds it exactly.
+            for input in [gray, mixed] {
+                let mut weights = [0.0; 6];
+                decomposer.decompose_into(&input, &mut weights);
+                assert!(
+                    (rebuild(&weights) - input).norm() < 1e-5,
+                    "axis {axis}: {weights:?}"
+                );
+            }
+        }
+        // Gray on the black/white axis is a pure black/white mix, for that
+        // axis and for the closest-axis default that picks it.
+        for strategy in [
+            OctahedronDecomposerAxisStrategy::Axis(black_white),
+            OctahedronDecomposerAxisStrategy::Closest,
+        ] {
+            let mut weights = [0.0; 6];
+            OctahedronDecomposer::new(&colors)
+                .unwrap()
+                .with_strategy(strategy)
+                .decompose_into(&gray, &mut weights);
+            let expected = [0.5, 0.5, 0.0, 0.0, 0.0, 0.0];
+            assert!(
+                weights
+                    .iter()
+                    .zip(expected)
+                    .all(|(w, e)| (w - e).abs() < 1e-5),
+                "{strategy}: {weights:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Per-ink response curves on decomposition weights.
+//!
+//! The decomposers assume inks mix linearly: a quarter of the pixels in
+//! red moves the average a quarter of the way to red. Measured panels
+//! often deviate, e.g. a sparse scattering of an ink reads weaker than
+//! its share. A response exponent `gamma` for an ink models a share `w`
+//! of it as looking like `w^gamma` of its full effect; [`apply_ink_gamma`]
+//! inverts that, raising the ink's weight to `w^(1/gamma)` so it shows up
+//! as the decomposer intended, and renormalises. `gamma > 1` thus uses
+//! more of a weak ink at partial coverage and `gamma < 1` less of a
+//! strong one; full and zero coverage are unchanged, as is every weight
+//! for `gamma = 1`.
+//!
+//! Like [`crate::decompose::bias`], this runs on the decomposer output, so
+//! the picked indices and the diffused error both see the corrected
+//! weights.
+
+use crate::decompose::Decomposer;
+use nalgebra::ComplexField;
+
+/// Raise `weights[i]` to `1 / gammas[i]` in place, then rescale so the
+/// total is unchanged. Non-positive weights and non-positive or
+/// non-finite exponents are left alone, as are weights without an
+/// exponent and exponents without a weight.
+pub fn apply_ink_gamma(weights: &mut [f32], gammas: &[f32]) {
+    if gammas.iter().all(|&gamma| gamma == 1.0) {
+        return;
+    }
+    let original_sum: f32 = weights.iter().sum();
+    for (weight, &gamma) in weights.iter_mut().zip(gammas) {
+        if *weight > 0.0 && gamma > 0.0 && gamma.is_finite() && gamma != 1.0 {
+            *weight = ComplexField::powf(*weight, 1.0 / gamma);
+        }
+    }
+    let corrected_sum: f32 = weights.iter().sum();
+    if corrected_sum > 0.0 {
+        let scale = original_sum / corrected_sum;
+        for weight in weights.iter_mut() {
+            *weight *= scale;
+        }
+    }
+}
+
+/// Wraps a decomposer, running [`apply_ink_gamma`] on every decomposition.
+pub struct GammaDecomposer<D, G> {
+    pub inner: D,
+    pub gammas: G,
+}
+
+impl<D, G> GammaDecomposer<D, G> {
+    pub fn new(inner: D, gammas: G) -> Self {
+        Self { inner, gammas }
+    }
+}
+
+impl<D, G> Decomposer<f32> for GammaDecomposer<D, G>
+where
+    D: Decomposer<f32>,
+    G: AsRef<[f32]>,
+{
+    type Input = D::Input;
+
+    fn palette_size(&self) -> usize {
+        self.inner.palette_size()
+    }
+
+    fn decompose_into(&self, input: &D::Input, out: &mut [f32]) {
+        self.inner.decompose_into(input, out);
+        apply_ink_gamma(out, self.gammas.as_ref());
+    }
+
+    fn confidence(&self, input: &D::Input) -> f32 {
+        self.inner.confidence(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_gamma_changes_nothing() {
+        let original = [0.1, 0.2, 0.3, 0.4];
+        let mut weights = original;
+        apply_ink_gamma(&mut weights, &[1.0; 4]);
+        assert_eq!(weights, original);
+        apply_ink_gamma(&mut weights, &[]);
+        assert_eq!(weights, original);
+    }
+
+    #[test]
+    fn weak_ink_gets_more_weight() {
+        // Half red that only reads as a quarter: use more red, keep the
+        // other inks' proportions and the total.
+        let mut weights = [0.5, 0.25, 0.25];
+        apply_ink_gamma(&mut weights, &[2.0, 1.0, 1.0]);
+        let red = 0.5f32.sqrt();
+        let scale = 1.0 / (red + 0.5);
+        let expected = [red * scale, 0.25 * scale, 0.25 * scale];
+        for (w, e) in weights.iter().zip(expected) {
+            assert!((w - e).abs() < 1e-6, "{weights:?}");
+        }
+        // Full coverage is unchanged.
+        let mut solid = [1.0, 0.0, 0.0];
+        apply_ink_gamma(&mut solid, &[2.0, 0.5, 3.0]);
+        assert_eq!(solid, [1.0, 0.0, 0.0]);
+    }
+}
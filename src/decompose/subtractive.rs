@@ -0,0 +1,169 @@
+//! Subtractive ("Kubelka-Munk-lite") mixing model.
+//!
+//! The RGB decomposers mix palette colours linearly in their input space,
+//! which models an additive display. E-paper inks sit side by side on a
+//! reflective substrate and are viewed as a spatial average, but many
+//! people find ink mixes better predicted by a multiplicative model:
+//! reflectances multiply, so optical *densities* `D = -ln(R)` add. This
+//! module maps colours into per-channel density space so the existing
+//! geometric decomposers can run there unchanged.
+//!
+//! Assumptions:
+//!
+//! * Each RGB channel is treated as an independent reflectance in
+//!   `[0, 1]`; no scattering term, no inter-channel crosstalk.
+//! * Reflectances are clamped to [`MIN_REFLECTANCE`] before the log so
+//!   pure black maps to a finite density.
+//! * The decomposition is still barycentric, just in density space; the
+//!   palette must keep its structure after the transform. The log warps
+//!   an RGB octahedron, so measured palettes such as Spectra 6 generally
+//!   need [`NaiveDecomposer`](crate::decompose::naive::NaiveDecomposer)
+//!   in this mode.
+
+use crate::decompose::Decomposer;
+use nalgebra::ComplexField;
+use nalgebra::geometry::Point3;
+
+/// Lowest reflectance used in the density transform (one 8-bit step), so
+/// black maps to a density of `ln(255) ≈ 5.54` rather than infinity.
+pub const MIN_REFLECTANCE: f32 = 1.0 / 255.0;
+
+/// Optical density of a single reflectance value.
+pub fn to_density_scalar(reflectance: f32) -> f32 {
+    -ComplexField::ln(reflectance.clamp(MIN_REFLECTANCE, 1.0))
+}
+
+/// Per-channel optical density of an RGB reflectance.
+pub fn to_density(color: &Point3<f32>) -> Point3<f32> {
+    color.map(to_density_scalar)
+}
+
+/// Inverse of [`to_density`] (up to the [`MIN_REFLECTANCE`] clamp).
+pub fn from_density(density: &Point3<f32>) -> Point3<f32> {
+    density.map(|d| ComplexField::exp(-d))
+}
+
+/// Wraps an RGB decomposer built on density-space palette points (see
+/// [`to_density`]) so it accepts ordinary reflectance inputs.
+pub struct SubtractiveDecomposer<D> {
+    pub inner: D,
+}
+
+impl<D> SubtractiveDecomposer<D> {
+    pub fn new(inner: D) -> Self {
+        Self { inner }
+    }
+}
+
+impl<D> Decomposer<f32> for SubtractiveDecomposer<D>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    type Input = Point3<f32>;
+
+    fn palette_size(&self) -> usize {
+        self.inner.palette_size()
+    }
+
+    fn decompose_into(&self, input: &Point3<f32>, out: &mut [f32]) {
+        self.inner.decompose_into(&to_density(input), out);
+    }
+
+    fn confidence(&self, input: &Point3<f32>) -> f32 {
+        self.inner.confidence(&to_density(input))
+    }
+}
+
+/// Library-grade enum equivalent of the binary's `--mixing` argument.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum MixingModel {
+    /// Linear mixing in the decomposer's input space.
+    #[default]
+    Additive,
+    /// Linear mixing in optical-density space; see the module docs.
+    Subtractive,
+}
+
+impl MixingModel {
+    pub const LONG_HELP: &'static str = concat!(
+        "Colour mixing model used by the decomposition.\n\n",
+        "Accepted values:\n",
+        " additive     Mix linearly in RGB (default)\n",
+        " subtractive  Mix linearly in optical density, -ln(reflectance)\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidMixingModel;
+
+impl core::fmt::Display for InvalidMixingModel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid mixing-model name")
+    }
+}
+
+impl core::error::Error for InvalidMixingModel {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for MixingModel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Additive => "additive",
+            Self::Subtractive => "subtractive",
+        })
+    }
+}
+
+impl core::str::FromStr for MixingModel {
+    type Err = InvalidMixingModel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "additive" => Ok(Self::Additive),
+            "subtractive" => Ok(Self::Subtractive),
+            _ => Err(InvalidMixingModel),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::palette::SPECTRA6;
+
+    fn weights<D: Decomposer<f32, Input = Point3<f32>>>(d: &D, input: Point3<f32>) -> [f32; 6] {
+        let mut out = [0.0; 6];
+        d.decompose_into(&input, &mut out);
+        out
+    }
+
+    #[test]
+    fn density_round_trips() {
+        let color = Point3::new(0.25, 0.5, 1.0);
+        let back = from_density(&to_density(&color));
+        assert!((back - color).norm() < 1e-6);
+    }
+
+    #[test]
+    fn secondary_colour_decomposes_differently() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let additive = NaiveDecomposer::new(&points).unwrap();
+        let subtractive = SubtractiveDecomposer::new(
+            NaiveDecomposer::new(&points.map(|p| to_density(&p))).unwrap(),
+        );
+        // Orange: halfway along the red-yellow edge in RGB. Palette order
+        // is K, W, Y, R, B, G.
+        let orange = points[2] + (points[3] - points[2]) * 0.5;
+        let a = weights(&additive, orange);
+        let s = weights(&subtractive, orange);
+        assert!((a[2] - 0.5).abs() < 1e-4 && (a[3] - 0.5).abs() < 1e-4);
+        assert!((s[2] - a[2]).abs() > 0.05);
+    }
+}
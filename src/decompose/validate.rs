@@ -0,0 +1,139 @@
+//! The contract every [`Decomposer`](crate::Decomposer) meets, as a check.
+//!
+//! Weights are non-negative and sum to one, and for an input inside the
+//! palette's gamut they mix back to that input. [`assert_valid`] tests one
+//! decomposition against that and says what's wrong, for decomposer tests
+//! and the binary's `--check-decompositions`.
+
+use nalgebra::geometry::Point3;
+
+/// Why [`assert_valid`] rejected a decomposition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidDecomposition {
+    /// There are `weights` weights for `palette` palette entries.
+    LengthMismatch { weights: usize, palette: usize },
+    /// The weight at `index` is negative or NaN.
+    NegativeWeight { index: usize, weight: f32 },
+    /// The weights sum to `sum` rather than one.
+    Sum { sum: f32 },
+    /// The weights mix to a point `distance` away from the input.
+    Reconstruction { distance: f32 },
+}
+
+impl core::fmt::Display for InvalidDecomposition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::LengthMismatch { weights, palette } => {
+                write!(f, "{weights} weights for {palette} palette entries")
+            }
+            Self::NegativeWeight { index, weight } => {
+                write!(f, "weight {index} is {weight}, expected at least 0")
+            }
+            Self::Sum { sum } => write!(f, "weights sum to {sum}, expected 1"),
+            Self::Reconstruction { distance } => {
+                write!(f, "weights mix to a colour {distance} from the input")
+            }
+        }
+    }
+}
+
+impl core::error::Error for InvalidDecomposition {}
+
+/// Check `weights` as a decomposition over `palette` (in the decomposer's
+/// input space). With `original`, they must also mix back to it: pass it
+/// only for inputs inside the gamut, since outside it decomposers pick a
+/// nearby reachable colour instead. The sum and the reconstruction
+/// distance may be off by `tolerance`.
+// Comparisons are negated so NaN weights, sums and distances fail them.
+#[allow(clippy::neg_cmp_op_on_partial_ord)]
+pub fn assert_valid(
+    weights: &[f32],
+    palette: &[Point3<f32>],
+    original: Option<&Point3<f32>>,
+    tolerance: f32,
+) -> Result<(), InvalidDecomposition> {
+    if weights.len() != palette.len() {
+        return Err(InvalidDecomposition::LengthMismatch {
+            weights: weights.len(),
+            palette: palette.len(),
+        });
+    }
+    if let Some((index, &weight)) = weights.iter().enumerate().find(|(_, w)| !(**w >= 0.0)) {
+        return Err(InvalidDecomposition::NegativeWeight { index, weight });
+    }
+    let sum: f32 = weights.iter().sum();
+    if !((sum - 1.0).abs() <= tolerance) {
+        return Err(InvalidDecomposition::Sum { sum });
+    }
+    if let Some(original) = original {
+        let mixed = palette
+            .iter()
+            .zip(weights)
+            .fold(Point3::origin(), |acc, (p, w)| acc + p.coords * *w);
+        let distance = (mixed - original).norm();
+        if !(distance <= tolerance) {
+            return Err(InvalidDecomposition::Reconstruction { distance });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corners() -> [Point3<f32>; 4] {
+        [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn accepts_a_mix_of_the_corners() {
+        let input = Point3::new(0.2, 0.3, 0.1);
+        let weights = [0.4, 0.2, 0.3, 0.1];
+        assert_eq!(
+            assert_valid(&weights, &corners(), Some(&input), 1e-6),
+            Ok(())
+        );
+        // Out of gamut: only the weights themselves are checked.
+        assert_eq!(assert_valid(&weights, &corners(), None, 1e-6), Ok(()));
+    }
+
+    #[test]
+    fn rejects_broken_decompositions() {
+        let palette = corners();
+        let input = Point3::new(0.2, 0.3, 0.1);
+        let check = |weights: &[f32]| assert_valid(weights, &palette, Some(&input), 1e-4);
+        assert_eq!(
+            check(&[0.5, 0.5]),
+            Err(InvalidDecomposition::LengthMismatch {
+                weights: 2,
+                palette: 4
+            })
+        );
+        assert_eq!(
+            check(&[0.6, 0.2, 0.3, -0.1]),
+            Err(InvalidDecomposition::NegativeWeight {
+                index: 3,
+                weight: -0.1
+            })
+        );
+        assert!(matches!(
+            check(&[0.4, f32::NAN, 0.3, 0.1]),
+            Err(InvalidDecomposition::NegativeWeight { index: 1, .. })
+        ));
+        assert!(matches!(
+            check(&[0.4, 0.2, 0.3, 0.2]),
+            Err(InvalidDecomposition::Sum { sum }) if (sum - 1.1).abs() < 1e-6
+        ));
+        // Sums to one, but mixes to (0.3, 0.2, 0.1).
+        assert!(matches!(
+            check(&[0.4, 0.3, 0.2, 0.1]),
+            Err(InvalidDecomposition::Reconstruction { distance }) if (distance - 0.1414).abs() < 1e-3
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+//! Moving weights between fixed- and dynamic-size vectors.
+//!
+//! Some weights have a natural fixed shape (the octahedron's `Vector6`,
+//! see [`OctahedronProjector::project`]), while
+//! [`DecomposingDitherStrategy`] and anything else sized by
+//! [`Decomposer::palette_size`](crate::Decomposer::palette_size) work on a
+//! `DVector`. These two helpers bridge them; the dynamic → fixed direction
+//! checks the length instead of panicking.
+//!
+//! [`OctahedronProjector::project`]: crate::barycentric::octahedron::OctahedronProjector::project
+//! [`DecomposingDitherStrategy`]: crate::dither::DecomposingDitherStrategy
+
+use nalgebra::{DVector, SVector, Scalar};
+
+/// `vector` as a `DVector` of the same length.
+pub fn owned_to_dynamic_vector<T: Scalar, const N: usize>(vector: SVector<T, N>) -> DVector<T> {
+    DVector::from_iterator(N, vector.into_iter().cloned())
+}
+
+/// `vector` as an `N`-element `SVector`, or `None` unless it has exactly
+/// `N` entries.
+pub fn dynamic_to_fixed<T: Scalar, const N: usize>(vector: &DVector<T>) -> Option<SVector<T, N>> {
+    (vector.len() == N).then(|| SVector::from_column_slice(vector.as_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector6;
+
+    #[test]
+    fn round_trips_through_dynamic() {
+        let fixed = Vector6::new(0.1f32, 0.0, 0.25, 0.4, 0.0, 0.25);
+        let dynamic = owned_to_dynamic_vector(fixed);
+        assert_eq!(dynamic.len(), 6);
+        assert_eq!(dynamic.as_slice(), fixed.as_slice());
+        assert_eq!(dynamic_to_fixed::<_, 6>(&dynamic), Some(fixed));
+    }
+
+    #[test]
+    fn rejects_mismatched_lengths() {
+        let dynamic = DVector::from_vec(alloc::vec![1.0f32, 2.0, 3.0, 4.0]);
+        assert_eq!(dynamic_to_fixed::<_, 6>(&dynamic), None);
+        assert_eq!(dynamic_to_fixed::<_, 3>(&dynamic), None);
+        assert_eq!(
+            dynamic_to_fixed::<_, 0>(&DVector::<f32>::zeros(0)),
+            Some(SVector::zeros())
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Error barriers: regions error diffusion doesn't cross.
+//!
+//! A [`DiffuseMask`] marks each pixel as diffusing or not. Plugged into a
+//! [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy)
+//! (see
+//! [`with_diffuse_mask`](crate::dither::DecomposingDitherStrategy::with_diffuse_mask))
+//! it keeps error out of and in masked-out pixels: they are quantized on
+//! their own, and their neighbours treat them as past the image edge, so
+//! [`EdgeMode`](crate::dither::diffuse::EdgeMode) decides what becomes of
+//! error aimed at them. E.g. a photo composited next to a solid UI panel,
+//! with the panel masked out, keeps the panel solid instead of speckled
+//! by the photo's error along the seam.
+//!
+//! With a [`fill`](DiffuseMask::with_fill) index, masked-out pixels aren't
+//! dithered at all but emit that index, e.g. to leave an image's
+//! transparent areas as a reserved palette entry.
+
+use alloc::vec::Vec;
+
+/// Per-pixel diffusion switch. Pixels outside the mask diffuse.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffuseMask {
+    width: usize,
+    height: usize,
+    diffuses: Vec<bool>,
+    fill: Option<usize>,
+}
+
+impl DiffuseMask {
+    /// Mask from row-major `diffuses`, `false` for masked-out pixels.
+    /// `None` unless there are exactly `width * height` entries.
+    pub fn new(width: usize, height: usize, diffuses: Vec<bool>) -> Option<Self> {
+        (diffuses.len() == width * height).then_some(Self {
+            width,
+            height,
+            diffuses,
+            fill: None,
+        })
+    }
+
+    /// Emit palette index `fill` for masked-out pixels instead of
+    /// quantizing them. `None` quantizes them on their own.
+    pub fn with_fill(mut self, fill: Option<usize>) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    pub fn fill(&self) -> Option<usize> {
+        self.fill
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether error diffuses into and out of `(x, y)`; `true` outside the
+    /// mask.
+    pub fn diffuses(&self, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return true;
+        }
+        self.diffuses
+            .get(y * self.width + x)
+            .copied()
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::{ImageReader, ImageSize, ImageWriter};
+    use crate::registry::{FactoryOptions, decompose_ditherer_with};
+
+    const WHITE: usize = 1;
+
+    /// 32×16: orange on the left, white on the right, recording the
+    /// indices written.
+    struct Composite(Vec<usize>);
+
+    impl ImageSize for Composite {
+        fn width(&self) -> usize {
+            32
+        }
+        fn height(&self) -> usize {
+            16
+        }
+    }
+
+    impl ImageReader<[u8; 3]> for Composite {
+        fn get_pixel(&self, x: usize, _: usize) -> [u8; 3] {
+            if x < 16 {
+                [200, 90, 30]
+            } else {
+                crate::palette::SPECTRA6[WHITE]
+            }
+        }
+    }
+
+    impl ImageWriter<usize> for Composite {
+        fn put_pixel(&mut self, x: usize, y: usize, index: usize) {
+            self.0[y * 32 + x] = index;
+        }
+    }
+
+    fn dither(diffuse_mask: Option<DiffuseMask>) -> Vec<usize> {
+        let ditherer = decompose_ditherer_with::<[u8; 3], _, Composite>(
+            "naive-mix".parse().unwrap(),
+            "ign".parse().unwrap(),
+            &crate::palette::SPECTRA6,
+            crate::dither::diffusion_matrix::FLOYD_STEINBERG,
+            &FactoryOptions {
+                diffuse_mask,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut image = Composite(alloc::vec![0; 32 * 16]);
+        ditherer.dyn_dither_into(&mut image);
+        image.0
+    }
+
+    #[test]
+    fn error_does_not_cross_the_mask_edge() {
+        let solid = |indices: &[usize]| (0..32 * 16).all(|i| i % 32 < 16 || indices[i] == WHITE);
+        // Without a mask the orange's error speckles the white panel.
+        assert!(!solid(&dither(None)));
+        let panel = (0..32 * 16).map(|i| i % 32 < 16).collect();
+        let masked = dither(Some(DiffuseMask::new(32, 16, panel).unwrap()));
+        assert!(solid(&masked));
+        // The photo side is still dithered.
+        let left: Vec<usize> = (0..32 * 16)
+            .filter(|i| i % 32 < 16)
+            .map(|i| masked[i])
+            .collect();
+        assert!(left.iter().any(|&i| i != left[0]));
+        assert!(DiffuseMask::new(2, 2, alloc::vec![true; 3]).is_none());
+    }
+
+    #[test]
+    fn filled_pixels_keep_the_fill_and_pass_no_error() {
+        const FILL: usize = 4;
+        let panel = || (0..32 * 16).map(|i| i % 32 < 16).collect();
+        let quantized = dither(Some(DiffuseMask::new(32, 16, panel()).unwrap()));
+        let filled = dither(Some(
+            DiffuseMask::new(32, 16, panel())
+                .unwrap()
+                .with_fill(Some(FILL)),
+        ));
+        for i in 0..32 * 16 {
+            if i % 32 < 16 {
+                // The photo side doesn't see what the panel became.
+                assert_eq!(filled[i], quantized[i], "pixel {i}");
+            } else {
+                assert_eq!(filled[i], FILL, "pixel {i}");
+            }
+        }
+    }
+}
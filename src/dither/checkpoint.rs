@@ -0,0 +1,244 @@
+//! Resumable dithering: save a dither's progress and carry on later.
+//!
+//! [`ResumableDitherer::dither_rows_into`](crate::dither::ResumableDitherer::dither_rows_into)
+//! dithers a range of rows and leaves the error still to be diffused in a
+//! byte buffer. A [`Checkpoint`] bundles that buffer with the rows done so
+//! far and a description of the settings; written to disk every so often,
+//! it lets a long run over a very large image pick up where it was
+//! interrupted: [`restore`](Checkpoint::restore) the finished rows into a
+//! fresh output, then dither the rest from
+//! [`next_row`](Checkpoint::next_row) on with the saved
+//! [`errors`](Checkpoint::errors). The result matches an uninterrupted
+//! run exactly, as long as the input and settings are the same; comparing
+//! [`config`](Checkpoint::config) is how callers check the latter.
+//!
+//! The saved rows are palette indices, one byte each, so palettes are
+//! limited to 256 entries. Strategies that read back their own output
+//! (see [`PixelStrategy::reads_output`](crate::dither::diffuse::PixelStrategy::reads_output))
+//! don't see the row above the first resumed one, so they may differ
+//! there; none of the registry's do.
+//!
+//! The byte layout is [`CHECKPOINT_MAGIC`], then as little-endian `u64`s
+//! the width, height and next row, then the config and the error buffer,
+//! each as a `u64` length and the bytes, then the saved indices.
+
+use crate::dither::error_buffer::read_u64;
+use crate::dither::image_traits::{ImageReader, ImageWriter};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// First bytes of a serialized [`Checkpoint`], including a format version.
+pub const CHECKPOINT_MAGIC: [u8; 8] = *b"EPDCKPT1";
+
+/// A checkpoint that doesn't parse, or error state a ditherer can't resume
+/// from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCheckpoint;
+
+impl core::fmt::Display for InvalidCheckpoint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid or incompatible dither checkpoint")
+    }
+}
+
+impl core::error::Error for InvalidCheckpoint {}
+
+/// Progress of a dither, see the module docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub width: usize,
+    pub height: usize,
+    /// Rows done; the first row still to dither.
+    pub next_row: usize,
+    /// Palette indices of rows `..next_row`, row-major.
+    pub indices: Vec<u8>,
+    /// Pending error, as
+    /// [`dither_rows_into`](crate::dither::ResumableDitherer::dither_rows_into)
+    /// left it.
+    pub errors: Vec<u8>,
+    /// Caller-defined description of the settings, e.g. a
+    /// [`DitherConfig`](crate::config::DitherConfig) text, plus whatever
+    /// else the output depends on, such as a hash of the input.
+    pub config: String,
+}
+
+impl Checkpoint {
+    /// Checkpoint of `output`, a `width`×`height` image dithered up to
+    /// `next_row`. `None` if an index there doesn't fit a byte.
+    pub fn capture<O: ImageReader<usize> + ?Sized>(
+        output: &O,
+        width: usize,
+        height: usize,
+        next_row: usize,
+        errors: Vec<u8>,
+        config: String,
+    ) -> Option<Self> {
+        let next_row = next_row.min(height);
+        let indices = (0..width * next_row)
+            .map(|i| u8::try_from(output.get_pixel(i % width, i / width)).ok())
+            .collect::<Option<_>>()?;
+        Some(Self {
+            width,
+            height,
+            next_row,
+            indices,
+            errors,
+            config,
+        })
+    }
+
+    /// Write the saved rows into `output`.
+    pub fn restore<O: ImageWriter<usize> + ?Sized>(&self, output: &mut O) {
+        for (i, &index) in self.indices.iter().enumerate() {
+            output.put_pixel(i % self.width, i / self.width, usize::from(index));
+        }
+    }
+
+    /// Serialize as laid out in the module docs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::from(CHECKPOINT_MAGIC);
+        for value in [self.width, self.height, self.next_row] {
+            out.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+        for bytes in [self.config.as_bytes(), &self.errors] {
+            out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            out.extend_from_slice(bytes);
+        }
+        out.extend_from_slice(&self.indices);
+        out
+    }
+
+    /// Inverse of [`to_bytes`](Self::to_bytes). Fails on another magic,
+    /// truncation, a config that isn't UTF-8, or saved indices not
+    /// covering exactly the rows done.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidCheckpoint> {
+        let mut bytes = bytes
+            .strip_prefix(&CHECKPOINT_MAGIC)
+            .ok_or(InvalidCheckpoint)?;
+        fn size(bytes: &mut &[u8]) -> Result<usize, InvalidCheckpoint> {
+            read_u64(bytes)
+                .and_then(|value| usize::try_from(value).ok())
+                .ok_or(InvalidCheckpoint)
+        }
+        fn block(bytes: &mut &[u8]) -> Result<Vec<u8>, InvalidCheckpoint> {
+            let len = size(bytes)?;
+            if len > bytes.len() {
+                return Err(InvalidCheckpoint);
+            }
+            let (block, rest) = bytes.split_at(len);
+            *bytes = rest;
+            Ok(block.to_vec())
+        }
+        let width = size(&mut bytes)?;
+        let height = size(&mut bytes)?;
+        let next_row = size(&mut bytes)?;
+        let config = String::from_utf8(block(&mut bytes)?).map_err(|_| InvalidCheckpoint)?;
+        let errors = block(&mut bytes)?;
+        if next_row > height || width.checked_mul(next_row) != Some(bytes.len()) {
+            return Err(InvalidCheckpoint);
+        }
+        Ok(Self {
+            width,
+            height,
+            next_row,
+            indices: bytes.to_vec(),
+            errors,
+            config,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::{ImageSize, ImageWriter};
+    use crate::registry::{FactoryOptions, decompose_ditherer_with};
+
+    const WIDTH: usize = 24;
+    const HEIGHT: usize = 20;
+
+    /// A colourful gradient, recording the indices written.
+    struct Gradient(Vec<usize>);
+
+    impl ImageSize for Gradient {
+        fn width(&self) -> usize {
+            WIDTH
+        }
+        fn height(&self) -> usize {
+            HEIGHT
+        }
+    }
+
+    impl ImageReader<[u8; 3]> for Gradient {
+        fn get_pixel(&self, x: usize, y: usize) -> [u8; 3] {
+            [(x * 10) as u8, (y * 12) as u8, 128]
+        }
+    }
+
+    impl ImageReader<usize> for Gradient {
+        fn get_pixel(&self, x: usize, y: usize) -> usize {
+            self.0[y * WIDTH + x]
+        }
+    }
+
+    impl ImageWriter<usize> for Gradient {
+        fn put_pixel(&mut self, x: usize, y: usize, index: usize) {
+            self.0[y * WIDTH + x] = index;
+        }
+    }
+
+    #[test]
+    fn resuming_matches_an_uninterrupted_run() {
+        let ditherer = decompose_ditherer_with::<[u8; 3], _, Gradient>(
+            "octahedron-closest".parse().unwrap(),
+            "bayer:2".parse().unwrap(),
+            &crate::palette::SPECTRA6,
+            crate::dither::diffusion_matrix::FLOYD_STEINBERG,
+            &FactoryOptions::default(),
+        )
+        .unwrap();
+        let blank = || Gradient(alloc::vec![usize::MAX; WIDTH * HEIGHT]);
+        let mut whole = blank();
+        ditherer.dyn_dither_into(&mut whole);
+
+        // Interrupted after row K: only the checkpoint bytes survive.
+        const K: usize = 7;
+        let mut first = blank();
+        let mut errors = Vec::new();
+        ditherer
+            .dyn_dither_rows_into(&mut first, 0..K, &mut errors)
+            .unwrap();
+        let saved = Checkpoint::capture(&first, WIDTH, HEIGHT, K, errors, "config".into())
+            .unwrap()
+            .to_bytes();
+        drop(first);
+
+        let checkpoint = Checkpoint::from_bytes(&saved).unwrap();
+        assert_eq!(
+            (checkpoint.next_row, checkpoint.config.as_str()),
+            (K, "config")
+        );
+        let mut resumed = blank();
+        checkpoint.restore(&mut resumed);
+        let mut errors = checkpoint.errors;
+        ditherer
+            .dyn_dither_rows_into(&mut resumed, K..HEIGHT, &mut errors)
+            .unwrap();
+        assert_eq!(resumed.0, whole.0);
+
+        // Damaged error state and checkpoints are refused.
+        let mut damaged = Vec::new();
+        ditherer
+            .dyn_dither_rows_into(&mut blank(), 0..1, &mut damaged)
+            .unwrap();
+        damaged[0] ^= 1;
+        assert_eq!(
+            ditherer.dyn_dither_rows_into(&mut blank(), 1..2, &mut damaged),
+            Err(InvalidCheckpoint)
+        );
+        assert_eq!(
+            Checkpoint::from_bytes(&saved[..saved.len() - 1]),
+            Err(InvalidCheckpoint)
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Post-selection ink-density constraint for panels with manufacturing
+//! limits (e.g. a maximum "on" density for one ink over a small area).
+//!
+//! [`limit_density`] runs over an already-dithered palette-index image in
+//! raster order. For each pixel whose colour has a [`DensityLimit`], it
+//! checks every `(2·radius+1)²` window containing the pixel and, if
+//! keeping the colour would push any of them over the limit, swaps the
+//! pixel to the nearest palette colour (by Euclidean distance in
+//! decomposer input space) that still fits its own limit.
+//!
+//! This is a heuristic: the replacement only considers colour distance
+//! rather than re-running the decomposition, and pixels are never
+//! revisited. Expect tone shifts in regions where a limit actually binds.
+
+use crate::dither::image_traits::{ImageReader, ImageSize, ImageWriter};
+use crate::palette::InkKey;
+use nalgebra::geometry::Point3;
+
+/// Window radius the binary's `--max-density` uses unless told otherwise.
+pub const DEFAULT_DENSITY_RADIUS: usize = 2;
+
+/// Maximum fraction of pixels (in `[0, 1]`) that palette entry `index`
+/// may occupy within the [`limit_density`] window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DensityLimit {
+    pub index: usize,
+    pub max: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidDensityLimit;
+
+impl core::fmt::Display for InvalidDensityLimit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid density limit (expected <ink>:<fraction in [0, 1]>)")
+    }
+}
+
+impl core::error::Error for InvalidDensityLimit {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for DensityLimit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.index, self.max)
+    }
+}
+
+impl core::str::FromStr for DensityLimit {
+    type Err = InvalidDensityLimit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, max) = s.split_once(':').ok_or(InvalidDensityLimit)?;
+        let index = index.parse::<usize>().map_err(|_| InvalidDensityLimit)?;
+        Ok(Self {
+            index,
+            max: parse_fraction(max)?,
+        })
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f32, InvalidDensityLimit> {
+    let max = s.parse::<f32>().map_err(|_| InvalidDensityLimit)?;
+    if !(0.0..=1.0).contains(&max) {
+        return Err(InvalidDensityLimit);
+    }
+    Ok(max)
+}
+
+/// A [`DensityLimit`] as the binary's `--max-density` takes it, with
+/// the entry named by an [`InkKey`] (`red:0.25`, `#9F0000:0.25`,
+/// `3:0.25`) that [`DensityLimitSpec::resolve`] looks up in a palette.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DensityLimitSpec {
+    pub ink: InkKey,
+    pub max: f32,
+}
+
+impl DensityLimitSpec {
+    /// The limit for the `palette` entry [`Self::ink`] names, or `None`
+    /// if it names none.
+    pub fn resolve(&self, palette: &[[u8; 3]]) -> Option<DensityLimit> {
+        let index = self.ink.index_in(palette)?;
+        Some(DensityLimit {
+            index,
+            max: self.max,
+        })
+    }
+}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for DensityLimitSpec {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.ink, self.max)
+    }
+}
+
+impl core::str::FromStr for DensityLimitSpec {
+    type Err = InvalidDensityLimit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ink, max) = s.rsplit_once(':').ok_or(InvalidDensityLimit)?;
+        let ink = ink.parse().map_err(|_| InvalidDensityLimit)?;
+        Ok(Self {
+            ink,
+            max: parse_fraction(max)?,
+        })
+    }
+}
+
+fn limit_for(limits: &[DensityLimit], index: usize) -> Option<f32> {
+    limits.iter().find(|l| l.index == index).map(|l| l.max)
+}
+
+/// True iff placing `index` at `(x, y)` keeps every `(2·radius+1)²`
+/// window containing `(x, y)` (clipped to the image) at or below `max`.
+/// Only pixels before `(x, y)` in raster order are counted; later ones
+/// are checked again when their turn comes.
+fn fits_windows<I>(image: &I, x: usize, y: usize, radius: usize, index: usize, max: f32) -> bool
+where
+    I: ImageSize + ImageReader<usize> + ?Sized,
+{
+    let width = image.width();
+    let height = image.height();
+    let clip = |c: usize, limit: usize| (c.saturating_sub(radius), (c + radius).min(limit - 1));
+    let (cx_min, cx_max) = clip(x, width);
+    let (cy_min, cy_max) = clip(y, height);
+    for cy in cy_min..=cy_max {
+        for cx in cx_min..=cx_max {
+            let (wx_min, wx_max) = clip(cx, width);
+            let (wy_min, wy_max) = clip(cy, height);
+            let area = (wx_max - wx_min + 1) * (wy_max - wy_min + 1);
+            let mut count = 1;
+            for wy in wy_min..=wy_max.min(y) {
+                for wx in wx_min..=wx_max {
+                    if wy == y && wx >= x {
+                        break;
+                    }
+                    if image.get_pixel(wx, wy) == index {
+                        count += 1;
+                    }
+                }
+            }
+            if count as f32 > max * area as f32 {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Enforce `limits` on a dithered index image in place. `palette` holds
+/// the colours the indices refer to (in decomposer input space) and is
+/// only used to pick replacements. Indices outside `palette` and colours
+/// without a limit are left untouched, as is a pixel for which no
+/// replacement fits.
+pub fn limit_density<I>(
+    image: &mut I,
+    palette: &[Point3<f32>],
+    limits: &[DensityLimit],
+    radius: usize,
+) where
+    I: ImageSize + ImageReader<usize> + ImageWriter<usize> + ?Sized,
+{
+    if limits.is_empty() {
+        return;
+    }
+    let within = |image: &I, x: usize, y: usize, index: usize| match limit_for(limits, index) {
+        None => true,
+        Some(max) => fits_windows(image, x, y, radius, index, max),
+    };
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let current = image.get_pixel(x, y);
+            if current >= palette.len() || within(image, x, y, current) {
+                continue;
+            }
+            let replacement = (0..palette.len())
+                .filter(|&candidate| candidate != current && within(image, x, y, candidate))
+                .map(|candidate| {
+                    let distance_sq = (palette[candidate] - palette[current]).norm_squared();
+                    (candidate, distance_sq)
+                })
+                .reduce(|a, b| if b.1 < a.1 { b } else { a });
+            if let Some((replacement, _)) = replacement {
+                image.put_pixel(x, y, replacement);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct IndexImage {
+        width: usize,
+        data: [usize; 32 * 32],
+    }
+
+    impl ImageSize for IndexImage {
+        fn width(&self) -> usize {
+            self.width
+        }
+        fn height(&self) -> usize {
+            self.data.len() / self.width
+        }
+    }
+
+    impl ImageReader<usize> for IndexImage {
+        fn get_pixel(&self, x: usize, y: usize) -> usize {
+            self.data[y * self.width + x]
+        }
+    }
+
+    impl ImageWriter<usize> for IndexImage {
+        fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+            self.data[y * self.width + x] = pixel;
+        }
+    }
+
+    #[test]
+    fn parses_index_and_fraction() {
+        assert_eq!(
+            "3:0.25".parse::<DensityLimit>(),
+            Ok(DensityLimit {
+                index: 3,
+                max: 0.25
+            })
+        );
+        assert!("3".parse::<DensityLimit>().is_err());
+        assert!("3:1.5".parse::<DensityLimit>().is_err());
+    }
+
+    #[test]
+    fn resolves_ink_names_and_colours() {
+        let palette = crate::palette::BWRY;
+        let resolve = |s: &str| s.parse::<DensityLimitSpec>().unwrap().resolve(&palette);
+        let red = Some(DensityLimit { index: 3, max: 0.2 });
+        assert_eq!(resolve("red:0.2"), red);
+        assert_eq!(resolve("#FF0000:0.2"), red);
+        assert_eq!(resolve("3:0.2"), red);
+        assert_eq!(resolve("#123456:0.2"), None);
+        assert!("red:2".parse::<DensityLimitSpec>().is_err());
+    }
+
+    #[test]
+    fn flat_region_respects_limit() {
+        // Black, dark gray, white: the capped black gives way to the
+        // nearest entry, dark gray, and never to white.
+        let palette = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.2, 0.2, 0.2),
+            Point3::new(1.0, 1.0, 1.0),
+        ];
+        let mut image = IndexImage {
+            width: 32,
+            data: [0; 32 * 32],
+        };
+        let limits = [DensityLimit { index: 0, max: 0.3 }];
+        limit_density(&mut image, &palette, &limits, 2);
+        let count = |index| image.data.iter().filter(|&&i| i == index).count();
+        let total = image.data.len();
+        assert!(count(0) as f32 <= 0.3 * total as f32, "{}", count(0));
+        // Not over-enforced: black keeps most of its allowance.
+        assert!(count(0) as f32 >= 0.2 * total as f32, "{}", count(0));
+        assert_eq!(count(1), total - count(0));
+        for y in 0..28 {
+            for x in 0..28 {
+                let window = (y..y + 5)
+                    .flat_map(|wy| (x..x + 5).map(move |wx| (wx, wy)))
+                    .filter(|&(wx, wy)| image.get_pixel(wx, wy) == 0)
+                    .count();
+                assert!(window as f32 <= 0.3 * 25.0, "({x}, {y}): {window}");
+            }
+        }
+    }
+}
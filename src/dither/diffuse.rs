@@ -4,7 +4,7 @@ use core::ops::{AddAssign, Div, Mul};
 
 pub trait PixelStrategy {
     type Source;
-    type Target;
+    type Target: Clone;
     type QuantizationError: Default
         + Clone
         + Mul<usize, Output = Self::QuantizationError>
@@ -21,6 +21,181 @@ pub trait PixelStrategy {
         y: usize,
         error: Self::QuantizationError,
     ) -> (Self::Target, Self::QuantizationError);
+
+    /// Whether error diffuses into and out of `(x, y)`. Where it doesn't,
+    /// the pixel is quantized without error, its own error is dropped, and
+    /// neighbours treat it as past the image edge (see [`EdgeMode`]), so
+    /// error doesn't cross it either. The default diffuses everywhere; see
+    /// [`crate::dither::barrier`].
+    fn diffuses(&self, x: usize, y: usize) -> bool {
+        let _ = (x, y);
+        true
+    }
+
+    /// Whether [`quantize_with_output`](Self::quantize_with_output) looks
+    /// at its [`OutputWindow`]. With the default `false`, the diffusion
+    /// loop keeps no copy of the output and calls [`quantize`](Self::quantize).
+    fn reads_output(&self) -> bool {
+        false
+    }
+
+    /// Quantize one pixel, seeing what has already been written around it,
+    /// for neighbour-aware choices (model-based dithering, avoiding runs of
+    /// one ink). Called instead of [`quantize`](Self::quantize) when
+    /// [`reads_output`](Self::reads_output) is true; the default ignores
+    /// `output`.
+    fn quantize_with_output(
+        &self,
+        source: Self::Source,
+        x: usize,
+        y: usize,
+        error: Self::QuantizationError,
+        output: &OutputWindow<'_, Self::Target>,
+    ) -> (Self::Target, Self::QuantizationError) {
+        let _ = output;
+        self.quantize(source, x, y, error)
+    }
+
+    /// Whether [`quantize_with_lookahead`](Self::quantize_with_lookahead)
+    /// looks at the next pixel. With the default `false`, the diffusion
+    /// loop doesn't read ahead.
+    fn looks_ahead(&self) -> bool {
+        false
+    }
+
+    /// Quantize one pixel, knowing the next one in scan order, to choose a
+    /// target that also suits where its error lands. Called instead of
+    /// [`quantize`](Self::quantize) when [`looks_ahead`](Self::looks_ahead)
+    /// is true and [`reads_output`](Self::reads_output) isn't; `next` is
+    /// `None` at the end of a row. The default ignores `next`.
+    fn quantize_with_lookahead(
+        &self,
+        source: Self::Source,
+        x: usize,
+        y: usize,
+        error: Self::QuantizationError,
+        next: Option<Lookahead<Self::Source, Self::QuantizationError>>,
+    ) -> (Self::Target, Self::QuantizationError) {
+        let _ = next;
+        self.quantize(source, x, y, error)
+    }
+}
+
+/// The pixel after the one being quantized, for
+/// [`PixelStrategy::quantize_with_lookahead`].
+#[derive(Clone, Debug)]
+pub struct Lookahead<S, E> {
+    pub source: S,
+    /// Column of the next pixel; it is on the same row.
+    pub x: usize,
+    /// Error diffused into the next pixel so far, already divided by the
+    /// matrix divisor as [`quantize`](PixelStrategy::quantize) will see it.
+    pub error: E,
+    /// Share of the current pixel's error the matrix sends to the next
+    /// one (e.g. 7/16 for Floyd-Steinberg), before any edge
+    /// redistribution.
+    pub share: f32,
+}
+
+/// Read-only view of the output [`diffuse_dither`] has written so far near
+/// the pixel being quantized, for
+/// [`PixelStrategy::quantize_with_output`].
+///
+/// The window is the whole previous row plus the pixels of the current row
+/// already visited: those to the left, or to the right on reversed
+/// serpentine rows. Anything else reads as `None`, as does the previous
+/// row at the first row of a [`diffuse_dither_rows`] call, since earlier
+/// strips aren't kept.
+#[derive(Clone, Copy, Debug)]
+pub struct OutputWindow<'a, T> {
+    y: usize,
+    previous: &'a [Option<T>],
+    current: &'a [Option<T>],
+}
+
+impl<'a, T> OutputWindow<'a, T> {
+    /// Window on row `y`, with `previous` and `current` indexed by `x` and
+    /// `None` where nothing is visible.
+    pub fn new(y: usize, previous: &'a [Option<T>], current: &'a [Option<T>]) -> Self {
+        Self {
+            y,
+            previous,
+            current,
+        }
+    }
+
+    /// Output at `(x, y)`, in full-image coordinates, if visible.
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a T> {
+        let row = if y == self.y {
+            self.current
+        } else if y.checked_add(1) == Some(self.y) {
+            self.previous
+        } else {
+            return None;
+        };
+        row.get(x)?.as_ref()
+    }
+}
+
+/// What [`diffuse_dither`] does with error aimed past the left or right
+/// edge of the image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum EdgeMode {
+    /// Drop it, so edge columns receive less error than the interior and
+    /// drift in tone.
+    #[default]
+    Drop,
+    /// Scale up the weights of the targets that stay inside the image so
+    /// they carry the whole error. Error aimed below the last row is still
+    /// dropped.
+    Redistribute,
+}
+
+impl EdgeMode {
+    pub const LONG_HELP: &'static str = concat!(
+        "Handling of diffusion error aimed past the left/right edge.\n\n",
+        "Accepted values:\n",
+        " drop          Discard it (default)\n",
+        " redistribute  Spread it over the in-image targets\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidEdgeMode;
+
+impl core::fmt::Display for InvalidEdgeMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid edge-mode name")
+    }
+}
+
+impl core::error::Error for InvalidEdgeMode {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for EdgeMode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Drop => "drop",
+            Self::Redistribute => "redistribute",
+        })
+    }
+}
+
+impl core::str::FromStr for EdgeMode {
+    type Err = InvalidEdgeMode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "redistribute" => Ok(Self::Redistribute),
+            _ => Err(InvalidEdgeMode),
+        }
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -89,6 +264,50 @@ impl Iterator for RangeWithDir {
     }
 }
 
+/// Error buffer sized for `matrix` over rows `width` pixels wide, for
+/// carrying diffusion across [`diffuse_dither_rows`] calls.
+#[cfg(feature = "alloc")]
+pub fn error_buffer_for<E, M>(
+    matrix: &M,
+    width: usize,
+) -> crate::dither::error_buffer::ErrorRingBuffer<E>
+where
+    E: Default,
+    M: crate::dither::diffusion_matrix::DiffusionMatrix + ?Sized,
+{
+    crate::dither::error_buffer::ErrorRingBuffer::new(width, error_rows(matrix))
+}
+
+/// Rows of pending error `matrix` needs: one past its deepest target.
+#[cfg(feature = "alloc")]
+fn error_rows<M>(matrix: &M) -> usize
+where
+    M: crate::dither::diffusion_matrix::DiffusionMatrix + ?Sized,
+{
+    // Find maximum y diffuse and height of error matrix (We're only ever working with a couple of
+    // rows at a time, no need to allocate a full extra image)
+    let max_y_diffuse = matrix
+        .targets()
+        .iter()
+        .map(|(_, dy, _)| *dy)
+        .max()
+        .unwrap_or(0);
+    max_y_diffuse + 1
+}
+
+/// Error buffer passed to [`diffuse_dither_rows`] is narrower than the
+/// image or holds fewer rows than the matrix reaches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorBufferTooSmall;
+
+impl core::fmt::Display for ErrorBufferTooSmall {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("error buffer is too small for the image or diffusion matrix")
+    }
+}
+
+impl core::error::Error for ErrorBufferTooSmall {}
+
 #[cfg(feature = "alloc")]
 pub fn diffuse_dither<
     S: PixelStrategy + ?Sized,
@@ -100,7 +319,83 @@ pub fn diffuse_dither<
     inout: &mut I,
     serpentine: bool,
 ) {
-    use alloc::vec::Vec;
+    diffuse_dither_with_edges(strategy, matrix, inout, serpentine, EdgeMode::Drop)
+}
+
+/// [`diffuse_dither`] with a choice of what happens to error aimed past
+/// the side edges; see [`EdgeMode`].
+#[cfg(feature = "alloc")]
+pub fn diffuse_dither_with_edges<
+    S: PixelStrategy + ?Sized,
+    M: crate::dither::diffusion_matrix::DiffusionMatrix + ?Sized,
+    I: ImageSize + ImageReader<S::Source> + ImageWriter<S::Target> + ?Sized,
+>(
+    strategy: &S,
+    matrix: &M,
+    inout: &mut I,
+    serpentine: bool,
+    edges: EdgeMode,
+) {
+    let mut errors = error_buffer_for(matrix, inout.width());
+    let height = inout.height();
+    dither_rows(
+        strategy,
+        matrix,
+        inout,
+        serpentine,
+        edges,
+        0..height,
+        &mut errors,
+    );
+}
+
+/// [`diffuse_dither`] over `rows` only, taking pending error from and
+/// leaving it in `errors` (see [`error_buffer_for`]). Dithering an image
+/// strip by strip, top to bottom, with one buffer gives the same output
+/// as dithering it whole: error that would cross into the next strip
+/// waits in the buffer instead of being dropped at the strip edge.
+///
+/// Coordinates stay those of the full image, so noise and serpentine
+/// direction line up across strips; `inout` only has to serve the pixels
+/// in `rows`. Rows past the end of `inout` are skipped. Fails, dithering
+/// nothing, unless `errors` is at least as wide as `inout` and as deep as
+/// [`error_buffer_for`] would make it.
+#[cfg(feature = "alloc")]
+pub fn diffuse_dither_rows<
+    S: PixelStrategy + ?Sized,
+    M: crate::dither::diffusion_matrix::DiffusionMatrix + ?Sized,
+    I: ImageSize + ImageReader<S::Source> + ImageWriter<S::Target> + ?Sized,
+>(
+    strategy: &S,
+    matrix: &M,
+    inout: &mut I,
+    serpentine: bool,
+    edges: EdgeMode,
+    rows: core::ops::Range<usize>,
+    errors: &mut crate::dither::error_buffer::ErrorRingBuffer<S::QuantizationError>,
+) -> Result<(), ErrorBufferTooSmall> {
+    if errors.width() < inout.width() || errors.rows() < error_rows(matrix) {
+        return Err(ErrorBufferTooSmall);
+    }
+    dither_rows(strategy, matrix, inout, serpentine, edges, rows, errors);
+    Ok(())
+}
+
+/// [`diffuse_dither_rows`] with `errors` already checked.
+#[cfg(feature = "alloc")]
+fn dither_rows<
+    S: PixelStrategy + ?Sized,
+    M: crate::dither::diffusion_matrix::DiffusionMatrix + ?Sized,
+    I: ImageSize + ImageReader<S::Source> + ImageWriter<S::Target> + ?Sized,
+>(
+    strategy: &S,
+    matrix: &M,
+    inout: &mut I,
+    serpentine: bool,
+    edges: EdgeMode,
+    rows: core::ops::Range<usize>,
+    errors: &mut crate::dither::error_buffer::ErrorRingBuffer<S::QuantizationError>,
+) {
     // Store width and height once for easy access and to make sure it doesn't change out from under
     // us ;)
     let width = inout.width();
@@ -108,35 +403,379 @@ pub fn diffuse_dither<
     // Get divisor & diffusion targets
     let error_divisor = matrix.divisor();
     let diffuse_targets = matrix.targets();
-    // Find maximum y diffuse and height of error matrix (We're only ever working with a couple of
-    // rows at a time, no need to allocate a full extra image)
-    let max_y_diffuse = diffuse_targets
+    let mut weights: alloc::vec::Vec<usize> = alloc::vec![0; diffuse_targets.len()];
+    // Previous and current output rows, for strategies that read them.
+    let reads_output = strategy.reads_output();
+    let row_len = if reads_output { width } else { 0 };
+    let mut written: [alloc::vec::Vec<Option<S::Target>>; 2] =
+        [alloc::vec![None; row_len], alloc::vec![None; row_len]];
+    let looks_ahead = !reads_output && strategy.looks_ahead();
+    // Where the matrix's weight for the next pixel sits, if it has one.
+    let ahead = diffuse_targets
         .iter()
-        .map(|(_, dy, _)| *dy)
-        .min()
-        .unwrap_or(0);
-    let errors_height = max_y_diffuse + 1;
-    let mut errors: Vec<S::QuantizationError> = Vec::new();
-    errors.resize_with(width * errors_height, Default::default);
-    for y in 0..height {
+        .position(|(dx, dy, _)| (*dx, *dy) == (1, 0));
+    for y in rows.start..rows.end.min(height) {
         let dir: isize = if serpentine && (y % 2) == 1 { -1 } else { 1 };
+        written.swap(0, 1);
+        written[1].fill(None);
         for x in RangeWithDir::new(0, width, dir) {
             let source: S::Source = inout.get_pixel(x, y);
-            // Use core::mem::take such that the error will be reset, as it will be re-used for the
-            // next row.
-            let error: S::QuantizationError =
-                core::mem::take(&mut errors[x + ((y % errors_height) * width)]);
-            let (target, error) = strategy.quantize(source, x, y, error / error_divisor);
+            // Taking resets the slot, as it will be re-used for a later row.
+            let error: S::QuantizationError = errors.take(x, y) / error_divisor;
+            let diffuses = strategy.diffuses(x, y);
+            let error = if diffuses { error } else { Default::default() };
+            matrix.weights_at(x, y, &mut weights);
+            let (target, error) = if reads_output {
+                let window = OutputWindow::new(y, &written[0], &written[1]);
+                let quantized = strategy.quantize_with_output(source, x, y, error, &window);
+                written[1][x] = Some(quantized.0.clone());
+                quantized
+            } else if looks_ahead {
+                // A masked-out neighbour is as good as the row's end.
+                let next = add_usize_isize_clamped(x, dir, width)
+                    .filter(|&nx| diffuses && strategy.diffuses(nx, y))
+                    .map(|nx| Lookahead {
+                        source: inout.get_pixel(nx, y),
+                        x: nx,
+                        error: errors.get(nx, y).clone() / error_divisor,
+                        share: ahead.map_or(0.0, |k| weights[k] as f32 / error_divisor as f32),
+                    });
+                strategy.quantize_with_lookahead(source, x, y, error, next)
+            } else {
+                strategy.quantize(source, x, y, error)
+            };
             inout.put_pixel(x, y, target);
+            if !diffuses {
+                continue;
+            }
             // Diffuse the error
-            for (dx, dy, mul) in diffuse_targets {
-                if let (Some(tx), Some(ty)) = (
-                    add_usize_isize_clamped(x, dx * dir, width),
-                    add_usize_usize_clamped(y, *dy, height),
-                ) {
-                    errors[tx + ((ty % errors_height) * width)] += error.clone() * *mul;
+            // Target pixel, if inside the image and diffusing.
+            let target_at = |dx: isize, dy: usize| {
+                let tx = add_usize_isize_clamped(x, dx * dir, width)?;
+                let ty = add_usize_usize_clamped(y, dy, height)?;
+                strategy.diffuses(tx, ty).then_some((tx, ty))
+            };
+            // Weight on existing rows, and the part of it inside the image.
+            let (mut on_rows, mut inside) = (0, 0);
+            if edges == EdgeMode::Redistribute {
+                for ((dx, dy, _), mul) in diffuse_targets.iter().zip(&weights) {
+                    if add_usize_usize_clamped(y, *dy, height).is_some() {
+                        on_rows += *mul;
+                        if target_at(*dx, *dy).is_some() {
+                            inside += *mul;
+                        }
+                    }
+                }
+            }
+            for ((dx, dy, _), mul) in diffuse_targets.iter().zip(&weights) {
+                if let Some((tx, ty)) = target_at(*dx, *dy) {
+                    if inside > 0 && inside < on_rows {
+                        errors.add(tx, ty, error.clone() * (*mul * on_rows) / inside);
+                    } else {
+                        errors.add(tx, ty, error.clone() * *mul);
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::decompose::Decomposer;
+    use crate::dither::DecomposingDitherStrategy;
+    use crate::dither::diffusion_matrix::{DiffusionMatrix, FLOYD_STEINBERG};
+
+    /// Two inks, mixed in proportion to the input level.
+    struct Level;
+
+    impl Decomposer<f32> for Level {
+        type Input = f32;
+        fn palette_size(&self) -> usize {
+            2
+        }
+        fn decompose_into(&self, input: &f32, out: &mut [f32]) {
+            out.copy_from_slice(&[1.0 - input, *input]);
+        }
+    }
+
+    struct Ramp([usize; 256]);
+
+    impl ImageSize for Ramp {
+        fn width(&self) -> usize {
+            16
+        }
+        fn height(&self) -> usize {
+            16
+        }
+    }
+
+    impl ImageReader<f32> for Ramp {
+        fn get_pixel(&self, x: usize, y: usize) -> f32 {
+            (x + y) as f32 / 31.0
+        }
+    }
+
+    impl ImageWriter<usize> for Ramp {
+        fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+            self.0[y * 16 + x] = pixel;
+        }
+    }
+
+    /// `width`×`height` image of one level, collecting the output.
+    struct Flat {
+        width: usize,
+        level: f32,
+        out: alloc::vec::Vec<usize>,
+    }
+
+    impl ImageSize for Flat {
+        fn width(&self) -> usize {
+            self.width
+        }
+        fn height(&self) -> usize {
+            self.out.len() / self.width
+        }
+    }
+
+    impl ImageReader<f32> for Flat {
+        fn get_pixel(&self, _: usize, _: usize) -> f32 {
+            self.level
+        }
+    }
+
+    impl ImageWriter<usize> for Flat {
+        fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+            self.out[y * self.width + x] = pixel;
+        }
+    }
+
+    #[test]
+    fn redistributed_edges_keep_flat_tone() {
+        let strategy = DecomposingDitherStrategy::new(Level, |v: f32| v);
+        let (width, height) = (48, 512);
+        // Largest gap between the mean of the two outermost columns on
+        // either side and the mean of the interior.
+        let edge_drift = |edges, level| {
+            let mut image = Flat {
+                width,
+                level,
+                out: alloc::vec![0; width * height],
+            };
+            diffuse_dither_with_edges(&strategy, &FLOYD_STEINBERG, &mut image, true, edges);
+            let mean = |columns: core::ops::Range<usize>| {
+                let count = columns.len() * height;
+                let total: usize = columns
+                    .flat_map(|x| (0..height).map(move |y| y * width + x))
+                    .map(|i| image.out[i])
+                    .sum();
+                total as f32 / count as f32
+            };
+            let interior = mean(8..width - 8);
+            (mean(0..2) - interior)
+                .abs()
+                .max((mean(width - 2..width) - interior).abs())
+        };
+        let mut dropped = 0.0f32;
+        for level in [0.1, 0.3, 0.7] {
+            let drift = edge_drift(EdgeMode::Redistribute, level);
+            assert!(drift < 0.03, "level {level}: {drift}");
+            dropped = dropped.max(edge_drift(EdgeMode::Drop, level));
+        }
+        assert!(dropped > 0.05, "{dropped}");
+        assert_eq!("redistribute".parse(), Ok(EdgeMode::Redistribute));
+    }
+
+    /// Wraps a strategy and checks, at every pixel, that the output window
+    /// shows exactly what was written before it.
+    struct Watcher<S> {
+        inner: S,
+        /// First row of the [`diffuse_dither_rows`] call.
+        first_row: usize,
+        seen: core::cell::RefCell<[Option<usize>; 256]>,
+    }
+
+    impl<S: PixelStrategy<Source = f32, Target = usize>> PixelStrategy for Watcher<S> {
+        type Source = f32;
+        type Target = usize;
+        type QuantizationError = S::QuantizationError;
+
+        fn quantize(
+            &self,
+            source: f32,
+            x: usize,
+            y: usize,
+            error: S::QuantizationError,
+        ) -> (usize, S::QuantizationError) {
+            self.inner.quantize(source, x, y, error)
+        }
+
+        fn reads_output(&self) -> bool {
+            true
+        }
+
+        fn quantize_with_output(
+            &self,
+            source: f32,
+            x: usize,
+            y: usize,
+            error: S::QuantizationError,
+            output: &OutputWindow<'_, usize>,
+        ) -> (usize, S::QuantizationError) {
+            let mut seen = self.seen.borrow_mut();
+            for ny in y.saturating_sub(2)..(y + 2).min(16) {
+                for nx in 0..16 {
+                    let visible = ny + 1 == y && y > self.first_row || ny == y;
+                    let expected = if visible { seen[ny * 16 + nx] } else { None };
+                    assert_eq!(
+                        output.get(nx, ny).copied(),
+                        expected,
+                        "({nx}, {ny}) at ({x}, {y})"
+                    );
+                }
+            }
+            assert_eq!(output.get(x, y), None);
+            let quantized = self.quantize(source, x, y, error);
+            seen[y * 16 + x] = Some(quantized.0);
+            quantized
+        }
+    }
+
+    #[test]
+    fn output_window_shows_written_neighbours() {
+        let inner = DecomposingDitherStrategy::new(Level, |v: f32| v);
+        let mut plain = Ramp([0; 256]);
+        diffuse_dither(&inner, &FLOYD_STEINBERG, &mut plain, true);
+
+        let mut watched = Ramp([0; 256]);
+        let mut errors = error_buffer_for(&FLOYD_STEINBERG, 16);
+        let mut watcher = Watcher {
+            inner,
+            first_row: 0,
+            seen: core::cell::RefCell::new([None; 256]),
+        };
+        for rows in [0..5, 5..16] {
+            watcher.first_row = rows.start;
+            diffuse_dither_rows(
+                &watcher,
+                &FLOYD_STEINBERG,
+                &mut watched,
+                true,
+                EdgeMode::Drop,
+                rows,
+                &mut errors,
+            )
+            .unwrap();
+        }
+        // Reading the output doesn't change the pick.
+        assert_eq!(watched.0, plain.0);
+        let seen = watcher.seen.into_inner();
+        assert!(seen.iter().zip(plain.0).all(|(s, p)| *s == Some(p)));
+    }
+
+    #[test]
+    fn strips_with_carried_error_match_whole_image() {
+        let strategy = DecomposingDitherStrategy::new(Level, |v: f32| v)
+            .with_noise(|x, y| crate::noise::interleaved_gradient_noise(x as f32, y as f32));
+        let mut whole = Ramp([0; 256]);
+        diffuse_dither(&strategy, &FLOYD_STEINBERG, &mut whole, true);
+
+        let mut strips = Ramp([0; 256]);
+        let mut errors = error_buffer_for(&FLOYD_STEINBERG, 16);
+        for rows in [0..8, 8..16] {
+            diffuse_dither_rows(
+                &strategy,
+                &FLOYD_STEINBERG,
+                &mut strips,
+                true,
+                EdgeMode::Drop,
+                rows,
+                &mut errors,
+            )
+            .unwrap();
+        }
+        assert_eq!(strips.0, whole.0);
+
+        // Restarting the error per strip leaves a seam.
+        let mut restarted = Ramp([0; 256]);
+        for rows in [0..8, 8..16] {
+            let mut errors = error_buffer_for(&FLOYD_STEINBERG, 16);
+            diffuse_dither_rows(
+                &strategy,
+                &FLOYD_STEINBERG,
+                &mut restarted,
+                true,
+                EdgeMode::Drop,
+                rows,
+                &mut errors,
+            )
+            .unwrap();
+        }
+        assert_ne!(restarted.0, whole.0);
+    }
+
+    #[test]
+    fn error_buffer_covers_deepest_target() {
+        use crate::dither::diffusion_matrix::{
+            DynamicDiffusionMatrix, JARVIS_JUDICE_AND_NINKE, NO_DIFFUSE,
+        };
+        let rows = |matrix: &dyn DiffusionMatrix| error_buffer_for::<f32, _>(matrix, 4).rows();
+        assert_eq!(rows(&NO_DIFFUSE), 1);
+        assert_eq!(rows(&FLOYD_STEINBERG), 2);
+        assert_eq!(rows(&JARVIS_JUDICE_AND_NINKE), 3);
+        assert_eq!(
+            rows(&DynamicDiffusionMatrix::scaled(&FLOYD_STEINBERG, 2)),
+            3
+        );
+    }
+
+    #[test]
+    fn rejects_error_buffer_too_small() {
+        use crate::dither::error_buffer::ErrorRingBuffer;
+        let strategy = DecomposingDitherStrategy::new(Level, |v: f32| v);
+        let dither = |image: &mut Ramp, width, rows| {
+            diffuse_dither_rows(
+                &strategy,
+                &FLOYD_STEINBERG,
+                image,
+                false,
+                EdgeMode::Drop,
+                0..16,
+                &mut ErrorRingBuffer::new(width, rows),
+            )
+        };
+        let mut image = Ramp([7; 256]);
+        assert_eq!(dither(&mut image, 15, 2), Err(ErrorBufferTooSmall));
+        // Floyd-Steinberg reaches one row down, so needs two.
+        assert_eq!(dither(&mut image, 16, 1), Err(ErrorBufferTooSmall));
+        assert_eq!(image.0, [7; 256]);
+        assert_eq!(dither(&mut image, 16, 2), Ok(()));
+    }
+
+    #[test]
+    fn deep_kernel_matches_whole_buffer() {
+        // A full-height buffer can't alias rows; the ring buffer must not
+        // either.
+        use crate::dither::diffusion_matrix::DynamicDiffusionMatrix;
+        let strategy = DecomposingDitherStrategy::new(Level, |v: f32| v);
+        for factor in [1, 2, 3] {
+            let matrix = DynamicDiffusionMatrix::scaled(&FLOYD_STEINBERG, factor);
+            let mut ring = Ramp([0; 256]);
+            diffuse_dither(&strategy, &matrix, &mut ring, false);
+            let mut full = Ramp([0; 256]);
+            let mut errors = crate::dither::error_buffer::ErrorRingBuffer::new(16, 16);
+            diffuse_dither_rows(
+                &strategy,
+                &matrix,
+                &mut full,
+                false,
+                EdgeMode::Drop,
+                0..16,
+                &mut errors,
+            )
+            .unwrap();
+            assert_eq!(ring.0, full.0, "factor {factor}");
+        }
+    }
+}
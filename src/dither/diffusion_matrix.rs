@@ -1,6 +1,36 @@
 pub trait DiffusionMatrix {
     fn divisor(&self) -> usize;
     fn targets(&self) -> &[(isize, usize, usize)];
+
+    /// Per-pixel weights for the error diffused from `(x, y)`, written into
+    /// `out` in the same order as [`targets`](Self::targets) (`out` has the
+    /// same length). The default copies the static weights; matrices that
+    /// vary their coefficients across the image override this and should
+    /// keep the weights summing to the same total.
+    fn weights_at(&self, x: usize, y: usize, out: &mut [usize]) {
+        let _ = (x, y);
+        for (weight, (_, _, target)) in out.iter_mut().zip(self.targets()) {
+            *weight = *target;
+        }
+    }
+
+    /// Whether this kernel is best run in serpentine order (alternate rows
+    /// right to left) rather than raster order; [`ScanOrder::Auto`]
+    /// follows it. Defaults to serpentine, which breaks up the diagonal
+    /// worms most kernels leave in raster order.
+    fn prefers_serpentine(&self) -> bool {
+        true
+    }
+
+    /// Whether every target lies ahead of the current pixel in scan order:
+    /// on a later row, or further along the same one (`dx > 0`). The
+    /// diffusion loop relies on this; error aimed at a pixel already done
+    /// would instead surface rows later, where the buffer reuses its slot.
+    /// Serpentine rows mirror `dx` along with the scan, so the condition
+    /// is the same for either order.
+    fn is_causal(&self) -> bool {
+        self.targets().iter().all(|&(dx, dy, _)| dy > 0 || dx > 0)
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -11,13 +41,24 @@ impl DiffusionMatrix for alloc::boxed::Box<dyn DiffusionMatrix> {
     fn targets(&self) -> &[(isize, usize, usize)] {
         self.as_ref().targets()
     }
+    fn weights_at(&self, x: usize, y: usize, out: &mut [usize]) {
+        self.as_ref().weights_at(x, y, out)
+    }
+    fn prefers_serpentine(&self) -> bool {
+        self.as_ref().prefers_serpentine()
+    }
+    fn is_causal(&self) -> bool {
+        self.as_ref().is_causal()
+    }
 }
 
 /// Borrowed-data diffusion matrix: pairs a divisor with a `'static` slice
-/// of `(dx, dy, weight)` targets. Lets us hand a built-in matrix back as
-/// a concrete value without allocating or committing to dyn dispatch.
+/// of `(dx, dy, weight)` targets and a
+/// [`prefers_serpentine`](DiffusionMatrix::prefers_serpentine) flag. Lets
+/// us hand a built-in matrix back as a concrete value without allocating
+/// or committing to dyn dispatch.
 #[derive(Clone, Copy, Debug)]
-pub struct RefDiffusionMatrix(pub usize, pub &'static [(isize, usize, usize)]);
+pub struct RefDiffusionMatrix(pub usize, pub &'static [(isize, usize, usize)], pub bool);
 
 impl DiffusionMatrix for RefDiffusionMatrix {
     fn divisor(&self) -> usize {
@@ -26,16 +67,119 @@ impl DiffusionMatrix for RefDiffusionMatrix {
     fn targets(&self) -> &[(isize, usize, usize)] {
         self.1
     }
+    fn prefers_serpentine(&self) -> bool {
+        self.2
+    }
+}
+
+/// Owned diffusion matrix, for kernels built at runtime rather than
+/// picked from the built-ins.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DynamicDiffusionMatrix {
+    pub divisor: usize,
+    pub targets: alloc::vec::Vec<(isize, usize, usize)>,
+    pub serpentine: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl DynamicDiffusionMatrix {
+    /// `base` spread over a neighbourhood `factor` times as wide: every
+    /// target's `dx` and `dy` are multiplied by `factor`, weights and
+    /// divisor are kept, as is the scan-order preference. Like
+    /// [`DiffusionCoefficients`](crate::config::DiffusionCoefficients),
+    /// only the static targets are copied. A `factor` of zero is treated
+    /// as one.
+    pub fn scaled(base: &dyn DiffusionMatrix, factor: usize) -> Self {
+        let factor = factor.max(1);
+        Self {
+            divisor: base.divisor(),
+            targets: base
+                .targets()
+                .iter()
+                .map(|&(dx, dy, weight)| (dx * factor as isize, dy * factor, weight))
+                .collect(),
+            serpentine: base.prefers_serpentine(),
+        }
+    }
+
+    /// `a` and `b` mixed, with `weight` (clamped to `[0, 1]`) the share
+    /// of `b`: each target's error share is `(1 - weight)` times its share
+    /// in `a` plus `weight` times its share in `b`, counting a target
+    /// missing from one kernel as zero there. Targets keep `a`'s order,
+    /// followed by those only in `b`.
+    ///
+    /// `weight` is rounded to a multiple of 1/[`BLEND_STEPS`] so the
+    /// result stays integer: the divisor is `BLEND_STEPS` times the least
+    /// common multiple of both divisors, reduced by the common factor of
+    /// all weights. Kernels that diffuse all their error give one that
+    /// does too; Atkinson's undiffused quarter carries over by its share.
+    /// Serpentine is preferred unless both kernels prefer raster order.
+    /// Like [`scaled`](Self::scaled), only the static targets are used.
+    pub fn blend(a: &dyn DiffusionMatrix, b: &dyn DiffusionMatrix, weight: f32) -> Self {
+        let share_b = (weight.clamp(0.0, 1.0) * BLEND_STEPS as f32 + 0.5) as usize;
+        let share_a = BLEND_STEPS - share_b;
+        let common = lcm(a.divisor().max(1), b.divisor().max(1));
+        let mut targets: alloc::vec::Vec<(isize, usize, usize)> = alloc::vec::Vec::new();
+        for (matrix, share) in [(a, share_a), (b, share_b)] {
+            let scale = share * (common / matrix.divisor().max(1));
+            for &(dx, dy, w) in matrix.targets() {
+                match targets.iter_mut().find(|t| (t.0, t.1) == (dx, dy)) {
+                    Some(target) => target.2 += w * scale,
+                    None => targets.push((dx, dy, w * scale)),
+                }
+            }
+        }
+        targets.retain(|t| t.2 > 0);
+        let divisor = common * BLEND_STEPS;
+        let factor = targets.iter().fold(divisor, |g, t| gcd(g, t.2));
+        Self {
+            divisor: divisor / factor,
+            targets: targets
+                .into_iter()
+                .map(|(dx, dy, w)| (dx, dy, w / factor))
+                .collect(),
+            serpentine: a.prefers_serpentine() || b.prefers_serpentine(),
+        }
+    }
+}
+
+/// Resolution of the `weight` in [`DynamicDiffusionMatrix::blend`].
+#[cfg(feature = "alloc")]
+pub const BLEND_STEPS: usize = 256;
+
+#[cfg(feature = "alloc")]
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(feature = "alloc")]
+fn lcm(a: usize, b: usize) -> usize {
+    a / gcd(a, b) * b
+}
+
+#[cfg(feature = "alloc")]
+impl DiffusionMatrix for DynamicDiffusionMatrix {
+    fn divisor(&self) -> usize {
+        self.divisor
+    }
+    fn targets(&self) -> &[(isize, usize, usize)] {
+        &self.targets
+    }
+    fn prefers_serpentine(&self) -> bool {
+        self.serpentine
+    }
 }
 
 // Built-in diffusion matrices. Each kernel is shown in its conventional
 // raster-scan layout: `*` is the current pixel, weights to the right and
 // below are diffused; the divisor below normalises them. The
 // `(dx, dy, w)` triples are the same data, laid out so the column of
-// each `dx` lines up across rows.
+// each `dx` lines up across rows. All but Atkinson prefer serpentine
+// order.
 
 /// No diffusion at all.
-pub const NO_DIFFUSE: RefDiffusionMatrix = RefDiffusionMatrix(1, &[]);
+pub const NO_DIFFUSE: RefDiffusionMatrix = RefDiffusionMatrix(1, &[], true);
 
 #[rustfmt::skip]
 /// Floyd-Steinberg, divisor 16:
@@ -46,7 +190,7 @@ pub const NO_DIFFUSE: RefDiffusionMatrix = RefDiffusionMatrix(1, &[]);
 pub const FLOYD_STEINBERG: RefDiffusionMatrix = RefDiffusionMatrix(16, &[
                             ( 1, 0, 7),
     (-1, 1, 3), ( 0, 1, 5), ( 1, 1, 1),
-]);
+], true);
 
 #[rustfmt::skip]
 /// Jarvis, Judice and Ninke, divisor 48:
@@ -59,7 +203,7 @@ pub const JARVIS_JUDICE_AND_NINKE: RefDiffusionMatrix = RefDiffusionMatrix(48, &
                                         ( 1, 0, 7), ( 2, 0, 5),
     (-2, 1, 3), (-1, 1, 5), ( 0, 1, 7), ( 1, 1, 5), ( 2, 1, 3),
     (-2, 2, 1), (-1, 2, 3), ( 0, 2, 5), ( 1, 2, 3), ( 2, 2, 1),
-]);
+], true);
 
 #[rustfmt::skip]
 /// Atkinson, divisor 8:
@@ -68,11 +212,14 @@ pub const JARVIS_JUDICE_AND_NINKE: RefDiffusionMatrix = RefDiffusionMatrix(48, &
 ///    1  1  1
 ///       1
 /// ```
+/// Prefers raster order, the order it was designed for on the original
+/// Macintosh; it diffuses only three quarters of the error, so it worms
+/// less than the others to begin with.
 pub const ATKINSON: RefDiffusionMatrix = RefDiffusionMatrix(8, &[
                             ( 1, 0, 1), ( 2, 0, 1),
     (-1, 1, 1), ( 0, 1, 1), ( 1, 1, 1),
                 ( 0, 2, 1),
-]);
+], false);
 
 #[rustfmt::skip]
 /// Sierra, divisor 32:
@@ -85,7 +232,199 @@ pub const SIERRA: RefDiffusionMatrix = RefDiffusionMatrix(32, &[
                                         ( 1, 0, 5), ( 2, 0, 3),
     (-2, 1, 2), (-1, 1, 4), ( 0, 1, 5), ( 1, 1, 4), ( 2, 1, 2),
                 (-1, 2, 2), ( 0, 2, 3), ( 1, 2, 2),
-]);
+], true);
+
+#[rustfmt::skip]
+/// Sierra Lite, divisor 4:
+/// ```text
+///       *  2
+///    1  1
+/// ```
+pub const SIERRA_LITE: RefDiffusionMatrix = RefDiffusionMatrix(4, &[
+                            ( 1, 0, 2),
+    (-1, 1, 1), ( 0, 1, 1),
+], true);
+
+/// Kernel picked by [`AdaptiveDiffusion`] for one tile.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdaptiveKernel {
+    /// [`SIERRA_LITE`], for flat tiles.
+    Light,
+    /// [`JARVIS_JUDICE_AND_NINKE`], for detailed tiles.
+    Heavy,
+}
+
+/// Per-tile adaptive error diffusion: tiles whose luma variance is at most
+/// a threshold use the light [`SIERRA_LITE`] kernel (less smearing of flat
+/// areas), the rest use the heavy [`JARVIS_JUDICE_AND_NINKE`] kernel (better
+/// detail). Both kernels are expressed over JJN's offsets with a shared
+/// divisor of 48, so switching happens through
+/// [`weights_at`](DiffusionMatrix::weights_at) without changing targets.
+///
+/// The classification is computed once from the input up front (see
+/// [`from_luma`](Self::from_luma)), which is why this isn't a constant
+/// like the other built-ins.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdaptiveDiffusion {
+    tile_size: usize,
+    tiles_x: usize,
+    kernels: alloc::vec::Vec<AdaptiveKernel>,
+}
+
+/// Default [`AdaptiveDiffusion`] tile edge, in pixels.
+#[cfg(feature = "alloc")]
+pub const DEFAULT_ADAPTIVE_TILE: usize = 16;
+
+/// Default [`AdaptiveDiffusion`] variance threshold, for luma in `[0, 1]`
+/// (a standard deviation of about 0.03).
+#[cfg(feature = "alloc")]
+pub const DEFAULT_ADAPTIVE_THRESHOLD: f32 = 0.001;
+
+#[cfg(feature = "alloc")]
+impl AdaptiveDiffusion {
+    /// Classify each `tile_size`² tile of a `width`×`height` image by the
+    /// variance of `luma(x, y)` over it. A `tile_size` of zero is treated
+    /// as one.
+    pub fn from_luma(
+        width: usize,
+        height: usize,
+        tile_size: usize,
+        threshold: f32,
+        luma: impl Fn(usize, usize) -> f32,
+    ) -> Self {
+        let tile_size = tile_size.max(1);
+        let tiles_x = width.div_ceil(tile_size);
+        let tiles_y = height.div_ceil(tile_size);
+        let mut kernels = alloc::vec::Vec::with_capacity(tiles_x * tiles_y);
+        for tile_y in 0..tiles_y {
+            for tile_x in 0..tiles_x {
+                let (mut sum, mut sum_sq, mut count) = (0.0f32, 0.0f32, 0.0f32);
+                for y in tile_y * tile_size..((tile_y + 1) * tile_size).min(height) {
+                    for x in tile_x * tile_size..((tile_x + 1) * tile_size).min(width) {
+                        let v = luma(x, y);
+                        sum += v;
+                        sum_sq += v * v;
+                        count += 1.0;
+                    }
+                }
+                let mean = sum / count;
+                let variance = sum_sq / count - mean * mean;
+                kernels.push(if variance <= threshold {
+                    AdaptiveKernel::Light
+                } else {
+                    AdaptiveKernel::Heavy
+                });
+            }
+        }
+        Self {
+            tile_size,
+            tiles_x,
+            kernels,
+        }
+    }
+
+    /// Kernel used for the pixel at `(x, y)`; pixels outside the classified
+    /// area get [`AdaptiveKernel::Heavy`].
+    pub fn kernel_at(&self, x: usize, y: usize) -> AdaptiveKernel {
+        let tile_x = x / self.tile_size;
+        let tile_y = y / self.tile_size;
+        if tile_x >= self.tiles_x {
+            return AdaptiveKernel::Heavy;
+        }
+        self.kernels
+            .get(tile_y * self.tiles_x + tile_x)
+            .copied()
+            .unwrap_or(AdaptiveKernel::Heavy)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DiffusionMatrix for AdaptiveDiffusion {
+    fn divisor(&self) -> usize {
+        JARVIS_JUDICE_AND_NINKE.divisor()
+    }
+    fn targets(&self) -> &[(isize, usize, usize)] {
+        JARVIS_JUDICE_AND_NINKE.targets()
+    }
+    fn weights_at(&self, x: usize, y: usize, out: &mut [usize]) {
+        match self.kernel_at(x, y) {
+            AdaptiveKernel::Heavy => JARVIS_JUDICE_AND_NINKE.weights_at(x, y, out),
+            AdaptiveKernel::Light => {
+                let scale = self.divisor() / SIERRA_LITE.divisor();
+                for (weight, (dx, dy, _)) in out.iter_mut().zip(self.targets()) {
+                    *weight = SIERRA_LITE
+                        .targets()
+                        .iter()
+                        .find(|(lx, ly, _)| lx == dx && ly == dy)
+                        .map_or(0, |(_, _, w)| w * scale);
+                }
+            }
+        }
+    }
+}
+
+/// Fixed-point scale applied to the Floyd-Steinberg weights by
+/// [`PerturbedFloydSteinberg`], so jitter can be finer than one sixteenth.
+const PERTURB_SCALE: usize = 256;
+
+#[rustfmt::skip]
+const PERTURBED_FLOYD_STEINBERG_TARGETS: [(isize, usize, usize); 4] = [
+                                                   ( 1, 0, 7 * PERTURB_SCALE),
+    (-1, 1, 3 * PERTURB_SCALE), ( 0, 1, 5 * PERTURB_SCALE), ( 1, 1, PERTURB_SCALE),
+];
+
+/// Floyd-Steinberg with per-pixel coefficient perturbation (Ulichney).
+/// At every pixel, weight is moved between the `7`/`1` pair and between
+/// the `3`/`5` pair by a pseudo-random amount derived from a hash of
+/// `(x, y, seed)`, which breaks up the regular worm patterns of plain
+/// error diffusion while keeping the total diffused error constant.
+///
+/// `amount` in `[0, 1]` scales the jitter relative to the smaller weight
+/// of each pair (so `1.0` can zero it out); `0.0` is exactly
+/// [`FLOYD_STEINBERG`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerturbedFloydSteinberg {
+    pub amount: f32,
+    pub seed: u64,
+}
+
+impl PerturbedFloydSteinberg {
+    /// Two independent jitters in `[-1, 1]` for pixel `(x, y)`.
+    fn jitter(&self, x: usize, y: usize) -> (f32, f32) {
+        let z = crate::noise::hash_coordinates(x, y, self.seed);
+        let unit = |bits: u64| (bits & 0xFFFF_FFFF) as f32 / u32::MAX as f32 * 2.0 - 1.0;
+        (unit(z), unit(z >> 32))
+    }
+}
+
+impl DiffusionMatrix for PerturbedFloydSteinberg {
+    fn divisor(&self) -> usize {
+        16 * PERTURB_SCALE
+    }
+    fn targets(&self) -> &[(isize, usize, usize)] {
+        &PERTURBED_FLOYD_STEINBERG_TARGETS
+    }
+    fn weights_at(&self, x: usize, y: usize, out: &mut [usize]) {
+        let amount = self.amount.clamp(0.0, 1.0);
+        let (a, b) = self.jitter(x, y);
+        // Both pairs are shifted by at most their smaller weight, so every
+        // weight stays non-negative and each pair keeps its sum.
+        let shift = |jitter: f32, limit: usize| (jitter * amount * limit as f32) as isize;
+        let d71 = shift(a, PERTURB_SCALE);
+        let d35 = shift(b, 3 * PERTURB_SCALE);
+        let weights = [
+            (7 * PERTURB_SCALE).saturating_add_signed(d71),
+            (3 * PERTURB_SCALE).saturating_add_signed(d35),
+            (5 * PERTURB_SCALE).saturating_add_signed(-d35),
+            PERTURB_SCALE.saturating_add_signed(-d71),
+        ];
+        for (weight, value) in out.iter_mut().zip(weights) {
+            *weight = value;
+        }
+    }
+}
 
 /// Library-grade enum equivalent of the binary's `--diffuse` argument:
 /// names a built-in diffusion matrix. Use [`to_matrix`](Self::to_matrix)
@@ -99,6 +438,83 @@ pub enum DiffuseMethod {
     JarvisJudiceAndNinke,
     Atkinson,
     Sierra,
+    SierraLite,
+    /// Per-tile choice between Sierra Lite and JJN; see
+    /// [`AdaptiveDiffusion`]. Needs the input image, so
+    /// [`try_to_matrix`](Self::try_to_matrix) can't build it.
+    Adaptive,
+}
+
+/// Row order for error diffusion, the binary's `--scan` argument.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ScanOrder {
+    /// Whatever the matrix prefers; see
+    /// [`DiffusionMatrix::prefers_serpentine`].
+    #[default]
+    Auto,
+    /// Every row left to right.
+    Raster,
+    /// Odd rows right to left.
+    Serpentine,
+}
+
+impl ScanOrder {
+    pub const LONG_HELP: &'static str = concat!(
+        "Row order for error diffusion.\n\n",
+        "Accepted values:\n",
+        " auto        The kernel's preference: raster for Atkinson, serpentine otherwise (default)\n",
+        " raster      Every row left to right\n",
+        " serpentine  Alternate rows right to left\n",
+    );
+
+    /// Whether to diffuse `matrix` in serpentine order.
+    pub fn is_serpentine<M: DiffusionMatrix + ?Sized>(self, matrix: &M) -> bool {
+        match self {
+            Self::Auto => matrix.prefers_serpentine(),
+            Self::Raster => false,
+            Self::Serpentine => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidScanOrder;
+
+impl core::fmt::Display for InvalidScanOrder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid scan-order name")
+    }
+}
+
+impl core::error::Error for InvalidScanOrder {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for ScanOrder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Raster => "raster",
+            Self::Serpentine => "serpentine",
+        })
+    }
+}
+
+impl core::str::FromStr for ScanOrder {
+    type Err = InvalidScanOrder;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "raster" => Ok(Self::Raster),
+            "serpentine" => Ok(Self::Serpentine),
+            _ => Err(InvalidScanOrder),
+        }
+    }
 }
 
 impl DiffuseMethod {
@@ -110,15 +526,33 @@ impl DiffuseMethod {
         " jarvis-judice-and-ninke Jarvis, Judice, and Ninke\n",
         " atkinson                Atkinson\n",
         " sierra                  Sierra\n",
+        " sierra-lite             Sierra Lite\n",
+        " adaptive                Sierra Lite on flat tiles, JJN on detailed ones\n",
     );
 
+    /// The built-in matrix. [`Adaptive`](Self::Adaptive) depends on the
+    /// image, so it falls back to [`FLOYD_STEINBERG`] here; use
+    /// [`try_to_matrix`](Self::try_to_matrix) to tell it apart.
     pub fn to_matrix(&self) -> impl DiffusionMatrix + use<> {
+        self.built_in().unwrap_or(FLOYD_STEINBERG)
+    }
+
+    /// [`to_matrix`](Self::to_matrix), but `None` for
+    /// [`Adaptive`](Self::Adaptive); build an `AdaptiveDiffusion` from the
+    /// input instead.
+    pub fn try_to_matrix(&self) -> Option<impl DiffusionMatrix + use<>> {
+        self.built_in()
+    }
+
+    fn built_in(&self) -> Option<RefDiffusionMatrix> {
         match self {
-            Self::None => NO_DIFFUSE,
-            Self::FloydSteinberg => FLOYD_STEINBERG,
-            Self::JarvisJudiceAndNinke => JARVIS_JUDICE_AND_NINKE,
-            Self::Atkinson => ATKINSON,
-            Self::Sierra => SIERRA,
+            Self::None => Some(NO_DIFFUSE),
+            Self::FloydSteinberg => Some(FLOYD_STEINBERG),
+            Self::JarvisJudiceAndNinke => Some(JARVIS_JUDICE_AND_NINKE),
+            Self::Atkinson => Some(ATKINSON),
+            Self::Sierra => Some(SIERRA),
+            Self::SierraLite => Some(SIERRA_LITE),
+            Self::Adaptive => None,
         }
     }
 }
@@ -134,6 +568,21 @@ impl core::fmt::Display for InvalidDiffuseMethod {
 
 impl core::error::Error for InvalidDiffuseMethod {}
 
+/// Inverse of `FromStr`.
+impl core::fmt::Display for DiffuseMethod {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::FloydSteinberg => "floyd-steinberg",
+            Self::JarvisJudiceAndNinke => "jarvis-judice-and-ninke",
+            Self::Atkinson => "atkinson",
+            Self::Sierra => "sierra",
+            Self::SierraLite => "sierra-lite",
+            Self::Adaptive => "adaptive",
+        })
+    }
+}
+
 impl core::str::FromStr for DiffuseMethod {
     type Err = InvalidDiffuseMethod;
 
@@ -144,7 +593,194 @@ impl core::str::FromStr for DiffuseMethod {
             "jarvis-judice-and-ninke" => Ok(Self::JarvisJudiceAndNinke),
             "atkinson" => Ok(Self::Atkinson),
             "sierra" => Ok(Self::Sierra),
+            "sierra-lite" => Ok(Self::SierraLite),
+            "adaptive" => Ok(Self::Adaptive),
             _ => Err(InvalidDiffuseMethod),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights(matrix: &impl DiffusionMatrix, x: usize, y: usize) -> [usize; 4] {
+        let mut out = [0; 4];
+        matrix.weights_at(x, y, &mut out);
+        out
+    }
+
+    #[test]
+    fn unperturbed_matches_floyd_steinberg() {
+        let matrix = PerturbedFloydSteinberg {
+            amount: 0.0,
+            seed: 7,
+        };
+        let plain = FLOYD_STEINBERG;
+        for (x, y) in [(0, 0), (3, 9), (1000, 17)] {
+            let scaled = weights(&plain, x, y).map(|w| w * matrix.divisor() / plain.divisor());
+            assert_eq!(weights(&matrix, x, y), scaled);
+        }
+        let offsets = |m: &dyn DiffusionMatrix| {
+            m.targets()
+                .iter()
+                .map(|t| (t.0, t.1))
+                .eq(plain.targets().iter().map(|t| (t.0, t.1)))
+        };
+        assert!(offsets(&matrix));
+    }
+
+    #[test]
+    fn perturbation_keeps_sum() {
+        let matrix = PerturbedFloydSteinberg {
+            amount: 1.0,
+            seed: 42,
+        };
+        let mut varied = false;
+        for y in 0..8 {
+            for x in 0..8 {
+                let w = weights(&matrix, x, y);
+                assert_eq!(w.iter().sum::<usize>(), matrix.divisor());
+                varied |= w != weights(&matrix, 0, 0);
+            }
+        }
+        assert!(varied);
+    }
+
+    #[test]
+    fn perturbation_pins_weights() {
+        let matrix = PerturbedFloydSteinberg {
+            amount: 0.5,
+            seed: 42,
+        };
+        // Half-strength jitter moves the 7/1 pair by at most 128 and the
+        // 3/5 pair by at most 384 of the 256-scaled weights.
+        assert_eq!(weights(&matrix, 0, 0), [1712, 953, 1095, 336]);
+        assert_eq!(weights(&matrix, 1, 0), [1840, 619, 1429, 208]);
+        assert_eq!(weights(&matrix, 5, 3), [1787, 675, 1373, 261]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn scaled_matrix_reaches_further() {
+        let same = DynamicDiffusionMatrix::scaled(&FLOYD_STEINBERG, 1);
+        assert_eq!(same.divisor(), FLOYD_STEINBERG.divisor());
+        assert_eq!(same.targets(), FLOYD_STEINBERG.targets());
+        let wide = DynamicDiffusionMatrix::scaled(&FLOYD_STEINBERG, 2);
+        assert_eq!(wide.divisor(), FLOYD_STEINBERG.divisor());
+        assert_eq!(
+            wide.targets(),
+            &[(2, 0, 7), (-2, 2, 3), (0, 2, 5), (2, 2, 1)]
+        );
+        assert_eq!(DynamicDiffusionMatrix::scaled(&FLOYD_STEINBERG, 0), same);
+        assert!(!DynamicDiffusionMatrix::scaled(&ATKINSON, 2).prefers_serpentine());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn detects_error_aimed_backwards() {
+        for kernel in [
+            NO_DIFFUSE,
+            FLOYD_STEINBERG,
+            JARVIS_JUDICE_AND_NINKE,
+            ATKINSON,
+            SIERRA,
+        ] {
+            assert!(kernel.is_causal());
+            assert!(DynamicDiffusionMatrix::scaled(&kernel, 3).is_causal());
+        }
+        let with = |targets: &[(isize, usize, usize)]| DynamicDiffusionMatrix {
+            divisor: 16,
+            targets: targets.to_vec(),
+            serpentine: true,
+        };
+        // Left on the same row, and the pixel itself.
+        assert!(!with(&[(1, 0, 7), (-1, 0, 3), (0, 1, 6)]).is_causal());
+        assert!(!with(&[(0, 0, 8), (0, 1, 8)]).is_causal());
+        assert!(with(&[(-2, 1, 8), (0, 2, 8)]).is_causal());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn auto_scan_follows_kernel_preference() {
+        for method in [
+            DiffuseMethod::None,
+            DiffuseMethod::FloydSteinberg,
+            DiffuseMethod::JarvisJudiceAndNinke,
+            DiffuseMethod::Atkinson,
+            DiffuseMethod::Sierra,
+            DiffuseMethod::SierraLite,
+        ] {
+            let matrix = method.try_to_matrix().unwrap();
+            let raster = method == DiffuseMethod::Atkinson;
+            assert_eq!(ScanOrder::Auto.is_serpentine(&matrix), !raster, "{method}");
+            // An explicit order wins either way.
+            assert!(ScanOrder::Serpentine.is_serpentine(&matrix), "{method}");
+            assert!(!ScanOrder::Raster.is_serpentine(&matrix), "{method}");
+        }
+        assert!(DiffuseMethod::Adaptive.try_to_matrix().is_none());
+        let fallback = DiffuseMethod::Adaptive.to_matrix();
+        assert_eq!(fallback.divisor(), FLOYD_STEINBERG.divisor());
+        assert_eq!(fallback.targets(), FLOYD_STEINBERG.targets());
+        assert_eq!("auto".parse(), Ok(ScanOrder::default()));
+        for order in [ScanOrder::Auto, ScanOrder::Raster, ScanOrder::Serpentine] {
+            assert_eq!(alloc::format!("{order}").parse(), Ok(order));
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn blended_kernels_combine_shares() {
+        for kernel in [FLOYD_STEINBERG, JARVIS_JUDICE_AND_NINKE, ATKINSON] {
+            for weight in [0.0, 0.3, 1.0] {
+                let same = DynamicDiffusionMatrix::blend(&kernel, &kernel, weight);
+                assert_eq!(same.divisor(), kernel.divisor());
+                assert_eq!(same.targets(), kernel.targets());
+                assert_eq!(same.prefers_serpentine(), kernel.prefers_serpentine());
+            }
+        }
+        let half = DynamicDiffusionMatrix::blend(&FLOYD_STEINBERG, &ATKINSON, 0.5);
+        // Floyd-Steinberg's 7/16 plus Atkinson's 1/8, halved: 9/32.
+        assert_eq!(half.divisor(), 32);
+        assert_eq!(
+            half.targets(),
+            &[
+                (1, 0, 9),
+                (-1, 1, 5),
+                (0, 1, 7),
+                (1, 1, 3),
+                (2, 0, 2),
+                (0, 2, 2)
+            ]
+        );
+        assert!(half.prefers_serpentine());
+        // Full kernels blend into a full kernel.
+        for weight in [0.1, 0.5, 0.77] {
+            let mixed = DynamicDiffusionMatrix::blend(&SIERRA, &JARVIS_JUDICE_AND_NINKE, weight);
+            let sum: usize = mixed.targets().iter().map(|t| t.2).sum();
+            assert_eq!(sum, mixed.divisor(), "{weight}");
+        }
+        let only_a = DynamicDiffusionMatrix::blend(&FLOYD_STEINBERG, &SIERRA, 0.0);
+        assert_eq!(only_a.targets(), FLOYD_STEINBERG.targets());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn adaptive_picks_kernel_by_tile_variance() {
+        // Left half flat gray, right half a checkerboard.
+        let luma = |x: usize, y: usize| {
+            if x < 16 { 0.5 } else { ((x + y) % 2) as f32 }
+        };
+        let matrix = AdaptiveDiffusion::from_luma(32, 16, 8, DEFAULT_ADAPTIVE_THRESHOLD, luma);
+        assert_eq!(matrix.kernel_at(3, 3), AdaptiveKernel::Light);
+        assert_eq!(matrix.kernel_at(20, 3), AdaptiveKernel::Heavy);
+
+        let mut light = [0; 12];
+        matrix.weights_at(3, 3, &mut light);
+        assert_eq!(light, [24, 0, 0, 12, 12, 0, 0, 0, 0, 0, 0, 0]);
+        let mut heavy = [0; 12];
+        matrix.weights_at(20, 3, &mut heavy);
+        assert_eq!(heavy, [7, 5, 3, 5, 7, 5, 3, 1, 3, 5, 3, 1]);
+        assert_eq!(light.iter().sum::<usize>(), matrix.divisor());
+    }
+}
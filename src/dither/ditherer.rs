@@ -1,9 +1,14 @@
 #[cfg(feature = "alloc")]
+use crate::dither::checkpoint::InvalidCheckpoint;
+use crate::dither::diffuse::EdgeMode;
+#[cfg(feature = "alloc")]
 use crate::dither::diffuse::PixelStrategy;
 #[cfg(feature = "alloc")]
-use crate::dither::diffuse::diffuse_dither;
+use crate::dither::diffuse::{diffuse_dither_rows, diffuse_dither_with_edges, error_buffer_for};
 #[cfg(feature = "alloc")]
 use crate::dither::diffusion_matrix::DiffusionMatrix;
+#[cfg(feature = "alloc")]
+use crate::dither::error_buffer::{ErrorBytes, ErrorRingBuffer};
 use crate::dither::image_traits::{ImageReader, ImageSize, ImageWriter};
 
 /// A dither pipeline with strategy, diffusion matrix, and (optional) input
@@ -22,6 +27,27 @@ pub trait Ditherer {
         I: ImageSize + ImageReader<Self::Input> + ImageWriter<Self::Output> + ?Sized;
 }
 
+/// A [`Ditherer`] that can stop between rows and carry on later; see
+/// [`crate::dither::checkpoint`].
+#[cfg(feature = "alloc")]
+pub trait ResumableDitherer: Ditherer {
+    /// Dither only `rows` of `inout`, continuing from the pending error
+    /// saved in `errors` and saving it there again for the rows after; an
+    /// empty `errors` starts afresh. Running an image's rows in order this
+    /// way gives the same output as [`dither_into`](Ditherer::dither_into),
+    /// even with `errors` stored away and read back between calls. Fails,
+    /// leaving `inout` alone, if `errors` isn't what this ditherer saved
+    /// for an image as wide.
+    fn dither_rows_into<I>(
+        &self,
+        inout: &mut I,
+        rows: core::ops::Range<usize>,
+        errors: &mut alloc::vec::Vec<u8>,
+    ) -> Result<(), InvalidCheckpoint>
+    where
+        I: ImageSize + ImageReader<Self::Input> + ImageWriter<Self::Output> + ?Sized;
+}
+
 /// Dyn-safe view of [`Ditherer`] with the reader/writer locked to a single
 /// concrete `InOut`. Implementations of `Ditherer` get this for free via
 /// the blanket impl below; bespoke implementors only have to write
@@ -49,6 +75,35 @@ where
     }
 }
 
+/// Dyn-safe view of [`ResumableDitherer`], as [`DynDitherer`] is of
+/// [`Ditherer`].
+#[cfg(feature = "alloc")]
+pub trait DynResumableDitherer<InOut: ?Sized>: DynDitherer<InOut> {
+    /// [`ResumableDitherer::dither_rows_into`].
+    fn dyn_dither_rows_into(
+        &self,
+        inout: &mut InOut,
+        rows: core::ops::Range<usize>,
+        errors: &mut alloc::vec::Vec<u8>,
+    ) -> Result<(), InvalidCheckpoint>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T, InOut> DynResumableDitherer<InOut> for T
+where
+    T: ResumableDitherer,
+    InOut: ImageSize + ImageReader<T::Input> + ImageWriter<T::Output> + ?Sized,
+{
+    fn dyn_dither_rows_into(
+        &self,
+        inout: &mut InOut,
+        rows: core::ops::Range<usize>,
+        errors: &mut alloc::vec::Vec<u8>,
+    ) -> Result<(), InvalidCheckpoint> {
+        <T as ResumableDitherer>::dither_rows_into(self, inout, rows, errors)
+    }
+}
+
 /// Default [`Ditherer`] implementation: a [`PixelStrategy`] paired with a
 /// [`DiffusionMatrix`] and a `serpentine` flag. The strategy already owns
 /// its own input-conversion function (see
@@ -67,6 +122,7 @@ pub struct BundledDitherer<S, M> {
     pub strategy: S,
     pub matrix: M,
     pub serpentine: bool,
+    pub edges: EdgeMode,
 }
 
 impl<S, M> BundledDitherer<S, M> {
@@ -77,6 +133,7 @@ impl<S, M> BundledDitherer<S, M> {
             strategy,
             matrix,
             serpentine: true,
+            edges: EdgeMode::Drop,
         }
     }
 
@@ -84,6 +141,12 @@ impl<S, M> BundledDitherer<S, M> {
         self.serpentine = serpentine;
         self
     }
+
+    /// What happens to error aimed past the side edges; see [`EdgeMode`].
+    pub fn with_edges(mut self, edges: EdgeMode) -> Self {
+        self.edges = edges;
+        self
+    }
 }
 
 #[cfg(feature = "alloc")]
@@ -99,6 +162,51 @@ where
     where
         I: ImageSize + ImageReader<S::Source> + ImageWriter<S::Target> + ?Sized,
     {
-        diffuse_dither(&self.strategy, &self.matrix, inout, self.serpentine);
+        diffuse_dither_with_edges(
+            &self.strategy,
+            &self.matrix,
+            inout,
+            self.serpentine,
+            self.edges,
+        );
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<S, M> ResumableDitherer for BundledDitherer<S, M>
+where
+    S: PixelStrategy,
+    S::QuantizationError: ErrorBytes,
+    M: DiffusionMatrix,
+{
+    fn dither_rows_into<I>(
+        &self,
+        inout: &mut I,
+        rows: core::ops::Range<usize>,
+        errors: &mut alloc::vec::Vec<u8>,
+    ) -> Result<(), InvalidCheckpoint>
+    where
+        I: ImageSize + ImageReader<S::Source> + ImageWriter<S::Target> + ?Sized,
+    {
+        let fresh = error_buffer_for(&self.matrix, inout.width());
+        let mut buffer = if errors.is_empty() {
+            fresh
+        } else {
+            ErrorRingBuffer::from_bytes(errors)
+                .filter(|b| (b.width(), b.rows()) == (fresh.width(), fresh.rows()))
+                .ok_or(InvalidCheckpoint)?
+        };
+        diffuse_dither_rows(
+            &self.strategy,
+            &self.matrix,
+            inout,
+            self.serpentine,
+            self.edges,
+            rows,
+            &mut buffer,
+        )
+        .map_err(|_| InvalidCheckpoint)?;
+        *errors = buffer.to_bytes();
+        Ok(())
     }
 }
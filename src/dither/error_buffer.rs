@@ -0,0 +1,178 @@
+use alloc::vec::Vec;
+use core::ops::AddAssign;
+
+/// Ring buffer of pending diffusion errors covering `rows` image rows of
+/// `width` pixels each. Row `y` lives in slot `y % rows`, so a buffer with
+/// `rows = max_dy + 1` holds every row a diffusion kernel can reach from
+/// the row currently being processed.
+///
+/// Rows are recycled implicitly: [`take`](Self::take) moves the pending
+/// error out and leaves `E::default()` behind, so once every pixel of row
+/// `y` has been taken, that slot is clean for row `y + rows`. The invariant
+/// callers must keep is that [`add`](Self::add) only targets rows in
+/// `current..current + rows`; anything further ahead aliases a row that
+/// hasn't been consumed yet. Serpentine traversal is safe because
+/// recycling is per pixel, not per row.
+pub struct ErrorRingBuffer<E> {
+    width: usize,
+    rows: usize,
+    data: Vec<E>,
+}
+
+impl<E: Default> ErrorRingBuffer<E> {
+    /// Zero-initialised (`E::default()`) buffer. `rows` is clamped to at
+    /// least one.
+    pub fn new(width: usize, rows: usize) -> Self {
+        let rows = rows.max(1);
+        let mut data = Vec::new();
+        data.resize_with(width * rows, Default::default);
+        Self { width, rows, data }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        x + ((y % self.rows) * self.width)
+    }
+
+    /// Move the pending error for `(x, y)` out, resetting the slot.
+    pub fn take(&mut self, x: usize, y: usize) -> E {
+        let index = self.index(x, y);
+        core::mem::take(&mut self.data[index])
+    }
+
+    /// The pending error for `(x, y)`, left in place.
+    pub fn get(&self, x: usize, y: usize) -> &E {
+        &self.data[self.index(x, y)]
+    }
+
+    /// Accumulate `error` onto the pending error for `(x, y)`.
+    pub fn add(&mut self, x: usize, y: usize, error: E)
+    where
+        E: AddAssign<E>,
+    {
+        let index = self.index(x, y);
+        self.data[index] += error;
+    }
+}
+
+/// Pending-error values an [`ErrorRingBuffer`] can be saved with, e.g. to
+/// resume a dither later (see [`crate::dither::checkpoint`]). Values must
+/// read back bit for bit, so a resumed dither matches an uninterrupted
+/// one.
+pub trait ErrorBytes: Sized {
+    /// Append `self` to `out`.
+    fn write_bytes(&self, out: &mut Vec<u8>);
+
+    /// Read what [`write_bytes`](Self::write_bytes) wrote off the front of
+    /// `bytes`, advancing it. `None` if `bytes` doesn't start with one.
+    fn read_bytes(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+/// Read a little-endian `u64` off the front of `bytes`.
+pub(crate) fn read_u64(bytes: &mut &[u8]) -> Option<u64> {
+    let (head, rest) = bytes.split_first_chunk::<8>()?;
+    *bytes = rest;
+    Some(u64::from_le_bytes(*head))
+}
+
+impl<E: Default + ErrorBytes> ErrorRingBuffer<E> {
+    /// Width and row count, each as a little-endian `u64`, then every
+    /// slot in storage order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.width as u64).to_le_bytes());
+        out.extend_from_slice(&(self.rows as u64).to_le_bytes());
+        for error in &self.data {
+            error.write_bytes(&mut out);
+        }
+        out
+    }
+
+    /// Inverse of [`to_bytes`](Self::to_bytes). `None` if `bytes` is
+    /// truncated, has bytes left over or holds no rows.
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
+        let width = usize::try_from(read_u64(&mut bytes)?).ok()?;
+        let rows = usize::try_from(read_u64(&mut bytes)?).ok()?;
+        if rows == 0 {
+            return None;
+        }
+        let mut data = Vec::new();
+        for _ in 0..width.checked_mul(rows)? {
+            data.push(E::read_bytes(&mut bytes)?);
+        }
+        bytes.is_empty().then_some(Self { width, rows, data })
+    }
+}
+
+impl ErrorBytes for f32 {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn read_bytes(bytes: &mut &[u8]) -> Option<Self> {
+        let (head, rest) = bytes.split_first_chunk::<4>()?;
+        *bytes = rest;
+        Some(f32::from_le_bytes(*head))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_resets_slot() {
+        let mut buffer: ErrorRingBuffer<i32> = ErrorRingBuffer::new(3, 2);
+        buffer.add(1, 0, 5);
+        buffer.add(1, 0, 2);
+        assert_eq!(buffer.take(1, 0), 7);
+        assert_eq!(buffer.take(1, 0), 0);
+    }
+
+    #[test]
+    fn rows_are_independent_within_window() {
+        let mut buffer: ErrorRingBuffer<i32> = ErrorRingBuffer::new(2, 2);
+        buffer.add(0, 4, 1);
+        buffer.add(0, 5, 10);
+        assert_eq!(buffer.take(0, 4), 1);
+        assert_eq!(buffer.take(0, 5), 10);
+    }
+
+    #[test]
+    fn rows_recycle_modulo_height() {
+        let mut buffer: ErrorRingBuffer<i32> = ErrorRingBuffer::new(2, 2);
+        buffer.add(1, 1, 3);
+        // Row 3 shares slot 1 with row 1.
+        assert_eq!(buffer.take(1, 3), 3);
+        buffer.add(1, 3, 4);
+        assert_eq!(buffer.take(1, 1), 4);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut buffer: ErrorRingBuffer<f32> = ErrorRingBuffer::new(3, 2);
+        buffer.add(2, 1, -0.5);
+        buffer.add(0, 0, f32::NAN);
+        let bytes = buffer.to_bytes();
+        let mut restored = ErrorRingBuffer::<f32>::from_bytes(&bytes).unwrap();
+        assert_eq!((restored.width(), restored.rows()), (3, 2));
+        assert_eq!(restored.take(2, 1), -0.5);
+        assert!(restored.take(0, 0).is_nan());
+        assert!(ErrorRingBuffer::<f32>::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(ErrorRingBuffer::<f32>::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_none());
+    }
+
+    #[test]
+    fn zero_rows_clamps_to_one() {
+        let buffer: ErrorRingBuffer<i32> = ErrorRingBuffer::new(4, 0);
+        assert_eq!(buffer.rows(), 1);
+        assert_eq!(buffer.width(), 4);
+    }
+}
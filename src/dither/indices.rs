@@ -0,0 +1,192 @@
+//! Floats in, palette indices out: the whole dither pipeline without the
+//! `image` crate.
+//!
+//! [`dither_indices`] wires a [`Decomposer`] over RGB points into a
+//! [`DecomposingDitherStrategy`] and [`diffuse_dither`], reading `[f32; 3]`
+//! pixels in the decomposer's input space and writing one `u8` palette
+//! index per pixel into a caller-provided buffer. It is what an embedded
+//! target with its own framebuffer needs, and doesn't allocate beyond the
+//! diffusion error rows. [`SliceImage`] serves a row-major pixel slice as
+//! the source.
+
+use crate::decompose::Decomposer;
+use crate::dither::DecomposingDitherStrategy;
+use crate::dither::diffuse::diffuse_dither;
+use crate::dither::diffusion_matrix::DiffusionMatrix;
+use crate::dither::image_traits::{ImageReader, ImageSize, ImageWriter};
+use nalgebra::geometry::Point3;
+
+/// `out` in [`dither_indices`] holds fewer than `width * height` entries,
+/// or the palette has more than 256 entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidIndexBuffer;
+
+impl core::fmt::Display for InvalidIndexBuffer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("index buffer is too small or palette exceeds 256 entries")
+    }
+}
+
+impl core::error::Error for InvalidIndexBuffer {}
+
+/// Row-major pixel slice viewed as an image.
+#[derive(Clone, Copy, Debug)]
+pub struct SliceImage<'a, T> {
+    width: usize,
+    height: usize,
+    pixels: &'a [T],
+}
+
+impl<'a, T> SliceImage<'a, T> {
+    /// `None` unless `pixels` holds exactly `width * height` entries.
+    pub fn new(width: usize, height: usize, pixels: &'a [T]) -> Option<Self> {
+        (pixels.len() == width.checked_mul(height)?).then_some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl<T> ImageSize for SliceImage<'_, T> {
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl<T: Copy> ImageReader<T> for SliceImage<'_, T> {
+    fn get_pixel(&self, x: usize, y: usize) -> T {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Source and size from the caller, indices into `out`.
+struct IndexTarget<'a> {
+    src: &'a dyn ImageReader<[f32; 3]>,
+    width: usize,
+    height: usize,
+    out: &'a mut [u8],
+}
+
+impl ImageSize for IndexTarget<'_> {
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl ImageReader<[f32; 3]> for IndexTarget<'_> {
+    fn get_pixel(&self, x: usize, y: usize) -> [f32; 3] {
+        self.src.get_pixel(x, y)
+    }
+}
+
+impl ImageWriter<usize> for IndexTarget<'_> {
+    fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+        // Indices stay below the palette size, checked to fit a `u8`.
+        self.out[y * self.width + x] = pixel as u8;
+    }
+}
+
+/// Dither the `size.width()`×`size.height()` image `src` into `out`, one
+/// row-major palette index per pixel. Pixels are points in `decomposer`'s
+/// input space (for the built-in palettes, sRGB-encoded values scaled to
+/// `[0, 1]`, not linearised, as
+/// [`to_point`](crate::decompose::DecomposerInputColor::to_point) returns
+/// them for `[u8; 3]`).
+///
+/// `noise` is the ordered-dither threshold per pixel in `[0, 1)` (see
+/// [`crate::noise`]); `None` picks each pixel's dominant colour and leaves
+/// the texture to `matrix`. Entries of `out` past `width * height` are
+/// left untouched.
+pub fn dither_indices(
+    decomposer: &dyn Decomposer<f32, Input = Point3<f32>>,
+    matrix: &dyn DiffusionMatrix,
+    noise: Option<&dyn Fn(usize, usize) -> f32>,
+    src: &dyn ImageReader<[f32; 3]>,
+    size: &dyn ImageSize,
+    out: &mut [u8],
+    serpentine: bool,
+) -> Result<(), InvalidIndexBuffer> {
+    let (width, height) = (size.width(), size.height());
+    if decomposer.palette_size() > 256
+        || width.checked_mul(height).is_none_or(|len| out.len() < len)
+    {
+        return Err(InvalidIndexBuffer);
+    }
+    let mut target = IndexTarget {
+        src,
+        width,
+        height,
+        out,
+    };
+    let strategy =
+        DecomposingDitherStrategy::new(decomposer, |pixel: [f32; 3]| Point3::from(pixel));
+    match noise {
+        Some(noise) => diffuse_dither(&strategy.with_noise(noise), matrix, &mut target, serpentine),
+        None => diffuse_dither(&strategy, matrix, &mut target, serpentine),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::DecomposerInputColor;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+    use crate::palette::BWRY;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn slice_image_dithers_into_index_buffer() {
+        let points = BWRY.map(|c| c.to_point());
+        let decomposer = NaiveDecomposer::new(&points).unwrap();
+        let (width, height) = (12, 5);
+        // Palette colours in the corners, a ramp from white to red between.
+        let pixels: Vec<[f32; 3]> = (0..width * height)
+            .map(|i| match (i % width, i / width) {
+                (0, 0) => points[0].into(),
+                (11, 4) => points[3].into(),
+                (x, _) => (points[1] + (points[3] - points[1]) * (x as f32 / 11.0)).into(),
+            })
+            .collect();
+        let image = SliceImage::new(width, height, &pixels).unwrap();
+        let noise = |x: usize, y: usize| crate::noise::bayer(x, y, 2);
+        let mut out = [0xFF; 61];
+        dither_indices(
+            &decomposer,
+            &FLOYD_STEINBERG,
+            Some(&noise),
+            &image,
+            &image,
+            &mut out,
+            true,
+        )
+        .unwrap();
+        assert_eq!(out[0], 0);
+        assert_eq!(out[59], 3);
+        assert!(out[..60].iter().all(|&i| i < 4), "{out:?}");
+        assert!(out[..60].contains(&1) && out[..60].contains(&3));
+        assert_eq!(out[60], 0xFF);
+
+        assert_eq!(
+            dither_indices(
+                &decomposer,
+                &FLOYD_STEINBERG,
+                None,
+                &image,
+                &image,
+                &mut out[..59],
+                true
+            ),
+            Err(InvalidIndexBuffer)
+        );
+        assert!(SliceImage::new(width, height, &pixels[1..]).is_none());
+    }
+}
@@ -0,0 +1,269 @@
+//! Error diffusion in CIELAB.
+//!
+//! [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy)
+//! diffuses error in barycentric weight space: each neighbour gets a share
+//! of "too much ink 3, too little ink 5". [`LabDiffusionStrategy`] instead
+//! diffuses the colour error itself, measured in CIELAB (D65, see
+//! [`crate::colorspace::srgb_to_lab`]), so the error a pixel passes on is
+//! the perceptual difference between the colour it should have shown and
+//! the ink it got.
+//!
+//! Only the error lives in Lab. Ink mixing is not linear in Lab (the
+//! average of two inks' Lab values is not the Lab value of their spatial
+//! mix), so choosing the ink still goes through the decomposer in its own
+//! RGB input space. Per pixel:
+//!
+//! 1. the source colour is converted to Lab and the pending error added;
+//! 2. that target goes back to sRGB (possibly out of gamut) and is
+//!    decomposed there, so weights reflect how the inks actually mix;
+//! 3. an index is picked from the weights, as
+//!    [`select_index`](crate::dither::select_index) does;
+//! 4. the error diffused onwards is the target's Lab value minus the
+//!    picked ink's.
+//!
+//! Targets pushed far out of gamut by accumulated error decompose to the
+//! decomposer's clipped result, so the error keeps pointing back towards
+//! the gamut.
+
+use crate::colorspace::{lab_to_srgb, srgb_to_lab};
+use crate::decompose::{Decomposer, DecomposerInputColor};
+use crate::dither::diffuse::PixelStrategy;
+use crate::dither::error_buffer::ErrorBytes;
+use crate::dither::select_index;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::{AddAssign, Div, Mul};
+use nalgebra::geometry::Point3;
+
+/// Pending error in CIELAB units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LabQuantizationError(pub [f32; 3]);
+
+impl Mul<usize> for LabQuantizationError {
+    type Output = Self;
+    fn mul(self, rhs: usize) -> Self {
+        Self(self.0.map(|c| c * rhs as f32))
+    }
+}
+
+impl Div<usize> for LabQuantizationError {
+    type Output = Self;
+    fn div(self, rhs: usize) -> Self {
+        Self(self.0.map(|c| c / rhs as f32))
+    }
+}
+
+impl AddAssign<LabQuantizationError> for LabQuantizationError {
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a += b;
+        }
+    }
+}
+
+/// The three components as little-endian `f32`s.
+impl ErrorBytes for LabQuantizationError {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        for c in self.0 {
+            c.write_bytes(out);
+        }
+    }
+
+    fn read_bytes(bytes: &mut &[u8]) -> Option<Self> {
+        Some(Self([
+            f32::read_bytes(bytes)?,
+            f32::read_bytes(bytes)?,
+            f32::read_bytes(bytes)?,
+        ]))
+    }
+}
+
+/// [`PixelStrategy`] diffusing colour error in Lab; see the module docs.
+///
+/// `decomposer` takes sRGB points in `[0, 1]` (the
+/// [`to_point`](DecomposerInputColor::to_point) space; wrap it, e.g. in a
+/// [`SubtractiveDecomposer`](crate::decompose::subtractive::SubtractiveDecomposer),
+/// to mix differently). Source pixels that aren't
+/// [finite](DecomposerInputColor::is_finite) get the
+/// [`with_fallback`](Self::with_fallback) index, or index 0 without one,
+/// and pass no error on.
+pub struct LabDiffusionStrategy<D, N, Src> {
+    pub decomposer: D,
+    /// Lab value of each palette entry.
+    palette: Vec<[f32; 3]>,
+    pub noise: Option<N>,
+    pub noise_amplitude: f32,
+    pub fallback: usize,
+    _phantom: PhantomData<fn(Src)>,
+}
+
+impl<D, Src> LabDiffusionStrategy<D, fn(usize, usize) -> f32, Src>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    /// `palette` holds the inks as sRGB points, in the decomposer's index
+    /// order. `None` unless it matches the decomposer's palette size.
+    pub fn new(decomposer: D, palette: &[Point3<f32>]) -> Option<Self> {
+        (palette.len() == decomposer.palette_size()).then(|| Self {
+            decomposer,
+            palette: palette
+                .iter()
+                .map(|p| srgb_to_lab(p.coords.into()))
+                .collect(),
+            noise: None,
+            noise_amplitude: 1.0,
+            fallback: 0,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<D, N, Src> LabDiffusionStrategy<D, N, Src> {
+    pub fn with_noise<N2>(self, noise: N2) -> LabDiffusionStrategy<D, N2, Src> {
+        LabDiffusionStrategy {
+            decomposer: self.decomposer,
+            palette: self.palette,
+            noise: Some(noise),
+            noise_amplitude: self.noise_amplitude,
+            fallback: self.fallback,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// See [`crate::noise::scale_amplitude`].
+    pub fn with_noise_amplitude(mut self, amplitude: f32) -> Self {
+        self.noise_amplitude = amplitude;
+        self
+    }
+
+    /// Index emitted for non-finite source pixels.
+    pub fn with_fallback(mut self, fallback: usize) -> Self {
+        self.fallback = fallback;
+        self
+    }
+}
+
+impl<D, N, Src> PixelStrategy for LabDiffusionStrategy<D, N, Src>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+    N: Fn(usize, usize) -> f32,
+    Src: DecomposerInputColor,
+{
+    type Source = Src;
+    type Target = usize;
+    type QuantizationError = LabQuantizationError;
+
+    fn quantize(
+        &self,
+        source: Src,
+        x: usize,
+        y: usize,
+        error: LabQuantizationError,
+    ) -> (usize, LabQuantizationError) {
+        if !source.is_finite() {
+            return (self.fallback, LabQuantizationError::default());
+        }
+        let mut target = srgb_to_lab(source.to_point().coords.into());
+        for (t, e) in target.iter_mut().zip(error.0) {
+            *t += e;
+        }
+        let mut weights = alloc::vec![0.0; self.decomposer.palette_size()];
+        self.decomposer
+            .decompose_into(&Point3::from(lab_to_srgb(target)), &mut weights);
+        let noise = self
+            .noise
+            .as_ref()
+            .map(|n| crate::noise::scale_amplitude(n(x, y), self.noise_amplitude));
+        let index = select_index(&weights, noise);
+        let ink = self.palette[index];
+        let error = core::array::from_fn(|i| target[i] - ink[i]);
+        (index, LabQuantizationError(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::palette::SPECTRA6;
+
+    type NaiveLab = LabDiffusionStrategy<NaiveDecomposer<f32>, fn(usize, usize) -> f32, [u8; 3]>;
+
+    fn strategy() -> NaiveLab {
+        let points = SPECTRA6.map(|c| c.to_point());
+        LabDiffusionStrategy::new(NaiveDecomposer::new(&points).unwrap(), &points).unwrap()
+    }
+
+    #[test]
+    fn palette_colours_pass_no_error() {
+        let strategy = strategy();
+        for (i, color) in SPECTRA6.iter().enumerate() {
+            let (index, error) = strategy.quantize(*color, 0, 0, Default::default());
+            assert_eq!(index, i);
+            assert!(error.0.iter().all(|e| e.abs() < 1e-2), "{error:?}");
+        }
+    }
+
+    #[test]
+    fn error_is_lab_difference_to_the_picked_ink() {
+        let strategy = strategy();
+        let source = [128, 128, 128];
+        let (index, error) = strategy.quantize(source, 0, 0, Default::default());
+        let expected = srgb_to_lab(source.to_point().coords.into());
+        let ink = srgb_to_lab(SPECTRA6[index].to_point().coords.into());
+        for i in 0..3 {
+            assert!((error.0[i] - (expected[i] - ink[i])).abs() < 1e-3);
+        }
+        // Pending error shifts the target before the pick.
+        let (dark, _) = strategy.quantize(source, 0, 0, LabQuantizationError([-60.0, 0.0, 0.0]));
+        assert_eq!(dark, 0);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn lab_diffusion_scores_like_weight_diffusion() {
+        use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+        use crate::dither::{ImageCombinedRW, ImageReader};
+        use crate::image::palette_image::{PaletteImage, VerifiedPalette};
+        use crate::metrics::psnr;
+        use crate::noise::NoiseSource;
+        use crate::registry::{FactoryOptions, decompose_ditherer_with};
+        use image::{Rgb, Rgb32FImage, RgbImage};
+        // Hue across, lightness down.
+        let input = Rgb32FImage::from_fn(64, 64, |x, y| {
+            let hsv = [x as f32 / 64.0, 0.6, 0.2 + 0.8 * y as f32 / 64.0];
+            Rgb(crate::colorspace::hsv_to_rgb(hsv))
+        });
+        let original = RgbImage::from_fn(64, 64, |x, y| {
+            Rgb(input.get_pixel(x, y).0.map(|c| (c * 255.0).round() as u8))
+        });
+        let palette: Vec<Rgb<u8>> = SPECTRA6.iter().map(|&c| Rgb(c)).collect();
+        let score = |lab_diffusion| {
+            let ditherer = decompose_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+                "naive-mix".parse().unwrap(),
+                NoiseSource::None,
+                &palette,
+                FLOYD_STEINBERG,
+                &FactoryOptions {
+                    lab_diffusion,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let writer = PaletteImage::new(64, 64, VerifiedPalette::new(palette.clone()).unwrap());
+            let mut inout = ImageCombinedRW::new(input.clone(), writer).unwrap();
+            ditherer.dyn_dither_into(&mut inout);
+            let output = RgbImage::from_fn(64, 64, |x, y| {
+                let index: usize = ImageReader::get_pixel(&inout.writer, x as usize, y as usize);
+                palette[index]
+            });
+            psnr(&original, &output).unwrap()
+        };
+        let (weights, lab) = (score(false), score(true));
+        assert!(lab.is_finite() && weights.is_finite());
+        assert!(
+            (lab - weights).abs() < 1.5,
+            "lab {lab} dB, weights {weights} dB"
+        );
+    }
+}
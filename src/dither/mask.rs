@@ -0,0 +1,190 @@
+//! Spatially varying ink weighting: a per-pixel
+//! [`ink_bias`](crate::decompose::bias).
+//!
+//! An [`InkMask`] holds, for every pixel, a factor for each of a set of
+//! palette entries. Plugged into a
+//! [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy)
+//! (see [`with_mask`](crate::dither::DecomposingDitherStrategy::with_mask))
+//! it scales those entries' decomposition weights at each pixel and
+//! renormalises the rest, as [`apply_ink_bias`] does for the whole image;
+//! e.g. a mask that is 2 for red over one region uses more red there.
+//!
+//! A factor of zero also keeps error diffusion from bringing the ink back:
+//! the entry is never picked at that pixel, and its share of the diffused
+//! error is dropped there rather than carried across the region.
+//!
+//! [`apply_ink_bias`]: crate::decompose::bias::apply_ink_bias
+
+use alloc::vec::Vec;
+
+/// Per-pixel factors for the palette entries in [`inks`](Self::inks).
+/// Pixels outside the mask are left alone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InkMask {
+    width: usize,
+    height: usize,
+    inks: Vec<usize>,
+    factors: Vec<f32>,
+}
+
+impl InkMask {
+    /// Mask from row-major `factors`, `inks.len()` per pixel, the `k`-th
+    /// scaling palette entry `inks[k]`. `None` unless there are exactly
+    /// `width * height * inks.len()` factors, all finite and non-negative.
+    pub fn new(width: usize, height: usize, inks: Vec<usize>, factors: Vec<f32>) -> Option<Self> {
+        if factors.len() != width * height * inks.len()
+            || !factors.iter().all(|f| f.is_finite() && *f >= 0.0)
+        {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            inks,
+            factors,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Palette entries the mask scales, in factor order.
+    pub fn inks(&self) -> &[usize] {
+        &self.inks
+    }
+
+    /// Factors at `(x, y)`, one per entry of [`inks`](Self::inks); `None`
+    /// outside the mask.
+    pub fn factors(&self, x: usize, y: usize) -> Option<&[f32]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let start = (y * self.width + x) * self.inks.len();
+        self.factors.get(start..start + self.inks.len())
+    }
+
+    /// Scale `weights` by the factors at `(x, y)` in place, then rescale
+    /// so the total is unchanged; see
+    /// [`apply_ink_bias`](crate::decompose::bias::apply_ink_bias).
+    pub fn apply(&self, x: usize, y: usize, weights: &mut [f32]) {
+        let Some(factors) = self.factors(x, y) else {
+            return;
+        };
+        let original_sum: f32 = weights.iter().sum();
+        for (&ink, &factor) in self.inks.iter().zip(factors) {
+            if let Some(weight) = weights.get_mut(ink) {
+                *weight *= factor;
+            }
+        }
+        let scaled_sum: f32 = weights.iter().sum();
+        if scaled_sum > 0.0 {
+            let scale = original_sum / scaled_sum;
+            for weight in weights.iter_mut() {
+                *weight *= scale;
+            }
+        }
+    }
+
+    /// Zero the entries of `weights` whose factor at `(x, y)` is zero.
+    pub(crate) fn exclude(&self, x: usize, y: usize, weights: &mut [f32]) {
+        let Some(factors) = self.factors(x, y) else {
+            return;
+        };
+        for (&ink, &factor) in self.inks.iter().zip(factors) {
+            if factor == 0.0
+                && let Some(weight) = weights.get_mut(ink)
+            {
+                *weight = 0.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::{ImageReader, ImageSize, ImageWriter};
+    use crate::registry::{FactoryOptions, decompose_ditherer_with};
+
+    const RED: usize = 3;
+
+    /// 32×16 of flat orange, recording the indices written.
+    struct Flat(Vec<usize>);
+
+    impl ImageSize for Flat {
+        fn width(&self) -> usize {
+            32
+        }
+        fn height(&self) -> usize {
+            16
+        }
+    }
+
+    impl ImageReader<[u8; 3]> for Flat {
+        fn get_pixel(&self, _: usize, _: usize) -> [u8; 3] {
+            [200, 90, 30]
+        }
+    }
+
+    impl ImageWriter<usize> for Flat {
+        fn put_pixel(&mut self, x: usize, y: usize, index: usize) {
+            self.0[y * 32 + x] = index;
+        }
+    }
+
+    fn dither(mask: Option<InkMask>) -> Vec<usize> {
+        let ditherer = decompose_ditherer_with::<[u8; 3], _, Flat>(
+            "naive-mix".parse().unwrap(),
+            "ign".parse().unwrap(),
+            &crate::palette::SPECTRA6,
+            crate::dither::diffusion_matrix::FLOYD_STEINBERG,
+            &FactoryOptions {
+                mask,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut image = Flat(alloc::vec![0; 32 * 16]);
+        ditherer.dyn_dither_into(&mut image);
+        image.0
+    }
+
+    #[test]
+    fn zero_factor_removes_the_ink_from_its_region() {
+        // Red zeroed in the left half, unchanged in the right.
+        let factors = (0..32 * 16)
+            .map(|i| if i % 32 < 16 { 0.0 } else { 1.0 })
+            .collect();
+        let mask = InkMask::new(32, 16, alloc::vec![RED], factors).unwrap();
+        let red = |indices: &[usize], left: bool| {
+            (0..32 * 16)
+                .filter(|i| (i % 32 < 16) == left && indices[*i] == RED)
+                .count()
+        };
+        let plain = dither(None);
+        assert!(red(&plain, true) > 0 && red(&plain, false) > 0);
+        let masked = dither(Some(mask));
+        assert_eq!(red(&masked, true), 0);
+        assert!(red(&masked, false) > 0);
+    }
+
+    #[test]
+    fn factors_scale_and_renormalise() {
+        let mask = InkMask::new(2, 1, alloc::vec![0, 2], alloc::vec![1.0, 1.0, 2.0, 0.0]).unwrap();
+        let mut weights = [0.25, 0.25, 0.5];
+        mask.apply(0, 0, &mut weights);
+        assert_eq!(weights, [0.25, 0.25, 0.5]);
+        mask.apply(1, 0, &mut weights);
+        assert_eq!(weights, [2.0 / 3.0, 1.0 / 3.0, 0.0]);
+        // Outside the mask: unchanged.
+        mask.apply(2, 0, &mut weights);
+        assert_eq!(weights, [2.0 / 3.0, 1.0 / 3.0, 0.0]);
+        assert!(InkMask::new(2, 1, alloc::vec![0], alloc::vec![1.0]).is_none());
+        assert!(InkMask::new(1, 1, alloc::vec![0], alloc::vec![-1.0]).is_none());
+    }
+}
@@ -1,14 +1,45 @@
+#[cfg(feature = "alloc")]
+pub mod barrier;
+#[cfg(feature = "alloc")]
+pub mod checkpoint;
+pub mod density;
 pub mod diffuse;
 pub mod diffusion_matrix;
 pub mod ditherer;
 #[cfg(feature = "alloc")]
+pub mod error_buffer;
+#[cfg(feature = "alloc")]
 pub mod with_decomposer;
 pub mod image_traits;
+#[cfg(feature = "alloc")]
+pub mod indices;
+#[cfg(feature = "alloc")]
+pub mod lab;
+#[cfg(feature = "alloc")]
+pub mod mask;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+#[cfg(feature = "alloc")]
+pub mod previous;
+#[cfg(feature = "alloc")]
+pub mod rgb;
+#[cfg(feature = "alloc")]
+pub mod subpixel;
+#[cfg(feature = "alloc")]
+pub mod tiles;
+#[cfg(feature = "alloc")]
+pub mod usage;
 
 #[cfg(feature = "alloc")]
 pub use with_decomposer::{
     DecomposeStrategy, DecomposedQuantizationError, DecomposingDitherStrategy,
-    InvalidDecomposeStrategy,
+    InvalidDecomposeStrategy, PickMode, select_index,
 };
 pub use ditherer::{BundledDitherer, Ditherer, DynDitherer};
+#[cfg(feature = "alloc")]
+pub use ditherer::{DynResumableDitherer, ResumableDitherer};
+#[cfg(feature = "alloc")]
+pub use error_buffer::ErrorRingBuffer;
 pub use image_traits::{ImageCombinedRW, ImageReader, ImageSize, ImageWriter};
+#[cfg(feature = "alloc")]
+pub use indices::{SliceImage, dither_indices};
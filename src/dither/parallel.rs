@@ -0,0 +1,170 @@
+//! Multithreaded ordered dithering (`rayon` feature).
+//!
+//! Error diffusion is inherently serial: every pixel depends on the error
+//! left by its predecessors. Noise-only (ordered) dithering has no such
+//! dependency, so once decomposition is a cheap table lookup via
+//! [`LutDecomposer`] the whole image parallelizes row by row.
+//! [`ParallelOptions`] caps the thread count and sets how many rows each
+//! task takes.
+
+use crate::decompose::lut::LutDecomposer;
+use crate::decompose::{Decomposer, DecomposerInputColor};
+use crate::dither::with_decomposer::select_index;
+use crate::dither::{ImageReader, ImageSize};
+use alloc::vec;
+use alloc::vec::Vec;
+use rayon::prelude::*;
+use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
+
+/// How [`dither_image_lut_parallel_with`] spreads its work.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParallelOptions {
+    /// Worker threads of a pool built for the call; `None` uses the rayon
+    /// global pool. On small boards this caps CPU use and per-thread
+    /// weight buffers.
+    pub threads: Option<usize>,
+    /// Rows per work item, `None` for one. Larger chunks mean fewer, longer
+    /// tasks: less scheduling overhead, coarser load balancing. A count
+    /// whose chunk length overflows `usize` is treated as one.
+    pub chunk_rows: Option<usize>,
+}
+
+/// Dither `image` with `lut` and positional `noise`, without error
+/// diffusion, spreading rows over the rayon global pool. Returns palette
+/// indices in row-major order.
+///
+/// Picks exactly what a [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy) over the same `lut`
+/// and `noise` would with [`NO_DIFFUSE`](crate::dither::diffusion_matrix::NO_DIFFUSE);
+/// how that compares to dithering with the decomposer the table was built
+/// from is covered in [`crate::decompose::lut`]. For a reduced noise
+/// amplitude, wrap `noise` in [`crate::noise::scale_amplitude`].
+pub fn dither_image_lut_parallel<P, N, I>(lut: &LutDecomposer, noise: N, image: &I) -> Vec<usize>
+where
+    P: DecomposerInputColor,
+    N: Fn(usize, usize) -> f32 + Sync,
+    I: ImageSize + ImageReader<P> + Sync + ?Sized,
+{
+    dither_rows(lut, &noise, image, 1)
+}
+
+/// [`dither_image_lut_parallel`] with a thread cap and chunk size. The
+/// output doesn't depend on either. Fails only if a pool was requested
+/// and couldn't be built.
+///
+/// Error diffusion has no equivalent: it stays serial whatever the
+/// options.
+pub fn dither_image_lut_parallel_with<P, N, I>(
+    lut: &LutDecomposer,
+    noise: N,
+    image: &I,
+    options: ParallelOptions,
+) -> Result<Vec<usize>, ThreadPoolBuildError>
+where
+    P: DecomposerInputColor,
+    N: Fn(usize, usize) -> f32 + Sync,
+    I: ImageSize + ImageReader<P> + Sync + ?Sized,
+{
+    let chunk_rows = options.chunk_rows.unwrap_or(1).max(1);
+    match options.threads {
+        Some(threads) => {
+            let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+            Ok(pool.install(|| dither_rows(lut, &noise, image, chunk_rows)))
+        }
+        None => Ok(dither_rows(lut, &noise, image, chunk_rows)),
+    }
+}
+
+fn dither_rows<P, N, I>(lut: &LutDecomposer, noise: &N, image: &I, chunk_rows: usize) -> Vec<usize>
+where
+    P: DecomposerInputColor,
+    N: Fn(usize, usize) -> f32 + Sync,
+    I: ImageSize + ImageReader<P> + Sync + ?Sized,
+{
+    let width = image.width();
+    let mut indices = vec![0; width * image.height()];
+    if width == 0 {
+        return indices;
+    }
+    // Chunks too long to address fall back to one row each.
+    let (chunk_rows, chunk_len) = width
+        .checked_mul(chunk_rows)
+        .map_or((1, width), |len| (chunk_rows, len));
+    indices
+        .par_chunks_mut(chunk_len)
+        .enumerate()
+        .for_each(|(chunk, rows)| {
+            let mut weights = vec![0.0; lut.palette_size()];
+            for (row, indices) in rows.chunks_mut(width).enumerate() {
+                let y = chunk * chunk_rows + row;
+                for (x, index) in indices.iter_mut().enumerate() {
+                    lut.decompose_into(&image.get_pixel(x, y).to_point(), &mut weights);
+                    *index = select_index(&weights, Some(noise(x, y)));
+                }
+            }
+        });
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::octahedron::OctahedronDecomposer;
+    use crate::dither::DecomposingDitherStrategy;
+    use crate::dither::diffuse::PixelStrategy;
+    use crate::palette::SPECTRA6;
+
+    struct Gradient;
+
+    impl ImageSize for Gradient {
+        fn width(&self) -> usize {
+            40
+        }
+        fn height(&self) -> usize {
+            24
+        }
+    }
+
+    impl ImageReader<[u8; 3]> for Gradient {
+        fn get_pixel(&self, x: usize, y: usize) -> [u8; 3] {
+            [(x * 6) as u8, (y * 10) as u8, ((x + y) * 4) as u8]
+        }
+    }
+
+    #[test]
+    fn matches_serial_strategy() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let lut = LutDecomposer::new(&OctahedronDecomposer::new(&points).unwrap(), 9).unwrap();
+        let noise = |x, y| crate::noise::bayer(x, y, 3);
+        let parallel = dither_image_lut_parallel(&lut, noise, &Gradient);
+        let serial =
+            DecomposingDitherStrategy::new(&lut, |p: [u8; 3]| p.to_point()).with_noise(noise);
+        for y in 0..Gradient.height() {
+            for x in 0..Gradient.width() {
+                let expected = serial
+                    .quantize(Gradient.get_pixel(x, y), x, y, Default::default())
+                    .0;
+                assert_eq!(parallel[y * Gradient.width() + x], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn independent_of_thread_count_and_chunking() {
+        let points = SPECTRA6.map(|c| c.to_point());
+        let lut = LutDecomposer::new(&OctahedronDecomposer::new(&points).unwrap(), 9).unwrap();
+        let noise = |x, y| crate::noise::bayer(x, y, 3);
+        let reference = dither_image_lut_parallel(&lut, noise, &Gradient);
+        for threads in [None, Some(1), Some(2), Some(5)] {
+            // 7 doesn't divide the 24 rows, so the last chunk is short.
+            for chunk_rows in [None, Some(1), Some(7), Some(100), Some(usize::MAX)] {
+                let options = ParallelOptions {
+                    threads,
+                    chunk_rows,
+                };
+                let indices =
+                    dither_image_lut_parallel_with(&lut, noise, &Gradient, options).unwrap();
+                assert_eq!(indices, reference, "{options:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Refresh-aware index selection: prefer keeping a pixel's previous
+//! palette index when that costs little accuracy.
+//!
+//! On some e-paper panels every pixel that changes between refreshes adds
+//! flicker and ghosting. [`PreviousFrame`] holds the indices currently on
+//! the panel; plugged into a
+//! [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy)
+//! (see [`with_previous`](crate::dither::DecomposingDitherStrategy::with_previous))
+//! it overrides the picked index with the previous one whenever the
+//! previous index's weight is within [`tolerance`](PreviousFrame::tolerance)
+//! of the picked one's. The quantization error is formed against the index
+//! actually emitted, so error diffusion still compensates for the swap.
+
+use crate::dither::DecomposingDitherStrategy;
+use crate::dither::diffuse::diffuse_dither;
+use crate::dither::diffusion_matrix::DiffusionMatrix;
+use crate::dither::image_traits::{ImageReader, ImageSize, ImageWriter};
+use alloc::vec::Vec;
+
+/// Default for [`PreviousFrame::tolerance`].
+pub const DEFAULT_PREVIOUS_TOLERANCE: f32 = 0.1;
+
+/// Snapshot of the palette indices of the previous frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviousFrame {
+    width: usize,
+    height: usize,
+    indices: Vec<usize>,
+    /// Largest weight deficit (in barycentric units) accepted to keep the
+    /// previous index. `0` keeps it only on ties; `1` always keeps it.
+    pub tolerance: f32,
+}
+
+impl PreviousFrame {
+    /// Frame from row-major `indices`; `None` unless there are exactly
+    /// `width * height` of them.
+    pub fn new(width: usize, height: usize, indices: Vec<usize>) -> Option<Self> {
+        if indices.len() != width * height {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            indices,
+            tolerance: DEFAULT_PREVIOUS_TOLERANCE,
+        })
+    }
+
+    /// Copy the indices out of `image`, with [`DEFAULT_PREVIOUS_TOLERANCE`].
+    pub fn from_image<I>(image: &I) -> Self
+    where
+        I: ImageSize + ImageReader<usize> + ?Sized,
+    {
+        let width = image.width();
+        let height = image.height();
+        let mut indices = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                indices.push(image.get_pixel(x, y));
+            }
+        }
+        Self {
+            width,
+            height,
+            indices,
+            tolerance: DEFAULT_PREVIOUS_TOLERANCE,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Previous index at `(x, y)`, or `None` outside the frame.
+    pub fn get(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(self.indices[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Index to emit at `(x, y)` given the strategy's pick `chosen` and the
+    /// clipped per-palette `weights`.
+    pub(crate) fn prefer(&self, x: usize, y: usize, weights: &[f32], chosen: usize) -> usize {
+        match self.get(x, y) {
+            Some(previous)
+                if previous < weights.len()
+                    && weights[previous] >= weights[chosen] - self.tolerance =>
+            {
+                previous
+            }
+            _ => chosen,
+        }
+    }
+}
+
+/// [`diffuse_dither`] with `strategy`, keeping the index from `previous`
+/// wherever that is within `tolerance` of the best pick (see the module
+/// docs). Any previous frame already attached to `strategy` is replaced.
+pub fn dither_with_previous<D, F, N, Src, M, I, P>(
+    strategy: DecomposingDitherStrategy<D, F, N, Src>,
+    matrix: &M,
+    inout: &mut I,
+    serpentine: bool,
+    previous: &P,
+    tolerance: f32,
+) where
+    DecomposingDitherStrategy<D, F, N, Src>:
+        crate::dither::diffuse::PixelStrategy<Source = Src, Target = usize>,
+    M: DiffusionMatrix + ?Sized,
+    I: ImageSize + ImageReader<Src> + ImageWriter<usize> + ?Sized,
+    P: ImageSize + ImageReader<usize> + ?Sized,
+{
+    let frame = PreviousFrame::from_image(previous).with_tolerance(tolerance);
+    let strategy = strategy.with_previous(Some(frame));
+    diffuse_dither(&strategy, matrix, inout, serpentine);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::Decomposer;
+    use crate::dither::diffusion_matrix::NO_DIFFUSE;
+
+    /// Ignores its input and always returns `[0.5, 0.3, 0.2]`.
+    struct Uneven;
+
+    impl Decomposer<f32> for Uneven {
+        type Input = ();
+        fn palette_size(&self) -> usize {
+            3
+        }
+        fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+            out.copy_from_slice(&[0.5, 0.3, 0.2]);
+        }
+    }
+
+    struct Indices([usize; 64]);
+
+    impl ImageSize for Indices {
+        fn width(&self) -> usize {
+            8
+        }
+        fn height(&self) -> usize {
+            8
+        }
+    }
+
+    impl ImageReader<()> for Indices {
+        fn get_pixel(&self, _x: usize, _y: usize) {}
+    }
+
+    impl ImageReader<usize> for Indices {
+        fn get_pixel(&self, x: usize, y: usize) -> usize {
+            self.0[y * 8 + x]
+        }
+    }
+
+    impl ImageWriter<usize> for Indices {
+        fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+            self.0[y * 8 + x] = pixel;
+        }
+    }
+
+    #[test]
+    fn keeps_previous_index_within_tolerance() {
+        let strategy = || DecomposingDitherStrategy::new(Uneven, |_: ()| ());
+        let mut fresh = Indices([usize::MAX; 64]);
+        diffuse_dither(&strategy(), &NO_DIFFUSE, &mut fresh, false);
+        assert_eq!(fresh.0, [0; 64]);
+
+        // Entry 1 is 0.2 below the pick and entry 2 is 0.3 below it, so a
+        // tolerance of 0.25 keeps previous 1s but not previous 2s.
+        let previous = Indices(core::array::from_fn(|i| [0, 1, 2][i % 3]));
+        let mut kept = Indices([usize::MAX; 64]);
+        dither_with_previous(strategy(), &NO_DIFFUSE, &mut kept, false, &previous, 0.25);
+        let expected: [usize; 64] = core::array::from_fn(|i| [0, 1, 0][i % 3]);
+        assert_eq!(kept.0, expected);
+
+        dither_with_previous(strategy(), &NO_DIFFUSE, &mut kept, false, &previous, 0.0);
+        assert_eq!(kept.0, [0; 64]);
+    }
+}
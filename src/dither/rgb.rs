@@ -0,0 +1,291 @@
+//! Classic per-channel error diffusion in RGB.
+//!
+//! [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy)
+//! diffuses error in barycentric weight space and
+//! [`LabDiffusionStrategy`](crate::dither::lab::LabDiffusionStrategy)
+//! diffuses colour error but still picks inks through a decomposer.
+//! [`RgbDiffusionStrategy`] is the textbook algorithm, for comparison and
+//! for its look: add the pending error to each of R, G and B on its own,
+//! emit the nearest palette colour, and pass on the difference. No
+//! decomposer is involved, so there is no noise or mixing model either.
+//!
+//! The palette's gamut is usually much smaller than the RGB cube, so a
+//! target is clamped to the cube before the nearest colour is looked up;
+//! otherwise error from a region the inks can't reach piles up without
+//! bound and smears far across the image.
+
+use crate::decompose::DecomposerInputColor;
+use crate::dither::diffuse::PixelStrategy;
+use crate::dither::error_buffer::ErrorBytes;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::{AddAssign, Div, Mul};
+use nalgebra::geometry::Point3;
+
+/// Space error diffusion carries error in, the binary's `--error-space`
+/// argument.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ErrorSpace {
+    /// Per-ink weight error; see
+    /// [`DecomposingDitherStrategy`](crate::dither::DecomposingDitherStrategy).
+    #[default]
+    Decomposed,
+    /// Per-channel RGB error with a nearest-colour pick; see
+    /// [`RgbDiffusionStrategy`].
+    Rgb,
+}
+
+impl ErrorSpace {
+    pub const LONG_HELP: &'static str = concat!(
+        "Space the diffused error is measured in.\n\n",
+        "Accepted values:\n",
+        " decomposed  Per-ink weight error from the decomposer (default)\n",
+        " rgb         Classic per-channel RGB error, then the nearest palette colour\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidErrorSpace;
+
+impl core::fmt::Display for InvalidErrorSpace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid error-space name")
+    }
+}
+
+impl core::error::Error for InvalidErrorSpace {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for ErrorSpace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Decomposed => "decomposed",
+            Self::Rgb => "rgb",
+        })
+    }
+}
+
+impl core::str::FromStr for ErrorSpace {
+    type Err = InvalidErrorSpace;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decomposed" => Ok(Self::Decomposed),
+            "rgb" => Ok(Self::Rgb),
+            _ => Err(InvalidErrorSpace),
+        }
+    }
+}
+
+/// Pending error per RGB channel, in the
+/// [`to_point`](DecomposerInputColor::to_point) space's `[0, 1]` units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RgbQuantizationError(pub [f32; 3]);
+
+impl Mul<usize> for RgbQuantizationError {
+    type Output = Self;
+    fn mul(self, rhs: usize) -> Self {
+        Self(self.0.map(|c| c * rhs as f32))
+    }
+}
+
+impl Div<usize> for RgbQuantizationError {
+    type Output = Self;
+    fn div(self, rhs: usize) -> Self {
+        Self(self.0.map(|c| c / rhs as f32))
+    }
+}
+
+impl AddAssign<RgbQuantizationError> for RgbQuantizationError {
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a += b;
+        }
+    }
+}
+
+/// The three channels as little-endian `f32`s.
+impl ErrorBytes for RgbQuantizationError {
+    fn write_bytes(&self, out: &mut Vec<u8>) {
+        for c in self.0 {
+            c.write_bytes(out);
+        }
+    }
+
+    fn read_bytes(bytes: &mut &[u8]) -> Option<Self> {
+        Some(Self([
+            f32::read_bytes(bytes)?,
+            f32::read_bytes(bytes)?,
+            f32::read_bytes(bytes)?,
+        ]))
+    }
+}
+
+/// [`PixelStrategy`] diffusing RGB error channel by channel; see the
+/// module docs.
+///
+/// Source pixels that aren't [finite](DecomposerInputColor::is_finite) get
+/// the [`with_fallback`](Self::with_fallback) index, or index 0 without
+/// one, and pass no error on.
+pub struct RgbDiffusionStrategy<Src> {
+    palette: Vec<Point3<f32>>,
+    pub fallback: usize,
+    _phantom: PhantomData<fn(Src)>,
+}
+
+impl<Src> RgbDiffusionStrategy<Src> {
+    /// `palette` holds the inks as RGB points in `[0, 1]`. `None` if it is
+    /// empty.
+    pub fn new(palette: &[Point3<f32>]) -> Option<Self> {
+        (!palette.is_empty()).then(|| Self {
+            palette: palette.to_vec(),
+            fallback: 0,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Index emitted for non-finite source pixels.
+    pub fn with_fallback(mut self, fallback: usize) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Index of the palette entry nearest `target`, the first on ties.
+    fn nearest(&self, target: &Point3<f32>) -> usize {
+        let distance = |index: usize| (self.palette[index] - target).norm_squared();
+        (0..self.palette.len())
+            .reduce(|best, index| {
+                if distance(index) < distance(best) {
+                    index
+                } else {
+                    best
+                }
+            })
+            .unwrap_or(0)
+    }
+}
+
+impl<Src: DecomposerInputColor> PixelStrategy for RgbDiffusionStrategy<Src> {
+    type Source = Src;
+    type Target = usize;
+    type QuantizationError = RgbQuantizationError;
+
+    fn quantize(
+        &self,
+        source: Src,
+        _x: usize,
+        _y: usize,
+        error: RgbQuantizationError,
+    ) -> (usize, RgbQuantizationError) {
+        if !source.is_finite() {
+            return (self.fallback, RgbQuantizationError::default());
+        }
+        let mut target = source.to_point();
+        for (t, e) in target.coords.iter_mut().zip(error.0) {
+            *t = (*t + e).clamp(0.0, 1.0);
+        }
+        let index = self.nearest(&target);
+        let ink = self.palette[index];
+        let error = core::array::from_fn(|i| target[i] - ink[i]);
+        (index, RgbQuantizationError(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::SPECTRA6;
+
+    fn strategy() -> RgbDiffusionStrategy<[u8; 3]> {
+        RgbDiffusionStrategy::new(&SPECTRA6.map(|c| c.to_point())).unwrap()
+    }
+
+    #[test]
+    fn error_is_rgb_difference_to_the_nearest_ink() {
+        let strategy = strategy();
+        for (i, color) in SPECTRA6.iter().enumerate() {
+            let (index, error) = strategy.quantize(*color, 0, 0, Default::default());
+            assert_eq!((index, error), (i, RgbQuantizationError::default()));
+        }
+        let source = [128, 128, 128];
+        let (index, error) = strategy.quantize(source, 0, 0, Default::default());
+        let ink = SPECTRA6[index].to_point();
+        for i in 0..3 {
+            assert!((error.0[i] - (source.to_point()[i] - ink[i])).abs() < 1e-6);
+        }
+        // Pending error shifts the target, clamped to the cube.
+        let (dark, error) = strategy.quantize(source, 0, 0, RgbQuantizationError([-2.0; 3]));
+        assert_eq!(dark, 0);
+        let black: [f32; 3] = SPECTRA6[0].to_point().coords.into();
+        assert_eq!(error.0, black.map(|c| -c));
+        assert!(RgbDiffusionStrategy::<[u8; 3]>::new(&[]).is_none());
+    }
+
+    #[test]
+    fn round_trips_through_strings() {
+        for space in [ErrorSpace::Decomposed, ErrorSpace::Rgb] {
+            assert_eq!(alloc::format!("{space}").parse(), Ok(space));
+        }
+        assert_eq!("lab".parse::<ErrorSpace>(), Err(InvalidErrorSpace));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn rgb_diffusion_tracks_a_gradient_like_decomposed() {
+        use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+        use crate::dither::{ImageCombinedRW, ImageReader};
+        use crate::image::palette_image::{PaletteImage, VerifiedPalette};
+        use crate::noise::NoiseSource;
+        use crate::registry::{FactoryOptions, decompose_ditherer_with};
+        use image::{Rgb, Rgb32FImage};
+        // Hue across, lightness down.
+        let input = Rgb32FImage::from_fn(64, 64, |x, y| {
+            let hsv = [x as f32 / 64.0, 0.6, 0.2 + 0.8 * y as f32 / 64.0];
+            Rgb(crate::colorspace::hsv_to_rgb(hsv))
+        });
+        let palette: Vec<Rgb<u8>> = SPECTRA6.iter().map(|&c| Rgb(c)).collect();
+        let dither = |error_space| {
+            let ditherer = decompose_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+                "naive-mix".parse().unwrap(),
+                NoiseSource::None,
+                &palette,
+                FLOYD_STEINBERG,
+                &FactoryOptions {
+                    error_space,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let writer = PaletteImage::new(64, 64, VerifiedPalette::new(palette.clone()).unwrap());
+            let mut inout = ImageCombinedRW::new(input.clone(), writer).unwrap();
+            ditherer.dyn_dither_into(&mut inout);
+            let indices: Vec<usize> = (0..64 * 64)
+                .map(|i| ImageReader::get_pixel(&inout.writer, i % 64, i / 64))
+                .collect();
+            indices
+        };
+        // Mean brightness per 8-row band, input and output.
+        let bands = |brightness: &dyn Fn(usize, usize) -> f32| -> Vec<f32> {
+            (0..8)
+                .map(|band| {
+                    let pixels = (0..64).flat_map(|x| (0..8).map(move |y| (x, band * 8 + y)));
+                    pixels.map(|(x, y)| brightness(x, y)).sum::<f32>() / 512.0
+                })
+                .collect()
+        };
+        let expected = bands(&|x, y| input.get_pixel(x as u32, y as u32).brightness());
+        let (decomposed, rgb) = (dither(ErrorSpace::Decomposed), dither(ErrorSpace::Rgb));
+        assert_ne!(decomposed, rgb);
+        for indices in [decomposed, rgb] {
+            let got = bands(&|x, y| palette[indices[y * 64 + x]].brightness());
+            for (band, (g, e)) in got.iter().zip(&expected).enumerate() {
+                assert!((g - e).abs() < 0.06, "band {band}: {got:?} vs {expected:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Dithering for panels whose pixels are split into separate subpixel
+//! cells, like an RGB-stripe LCD: each cell shows one ink, and the eye
+//! mixes the cells of a pixel.
+//!
+//! Whole-pixel dithering picks one ink per pixel. [`SubpixelStrategy`]
+//! instead gives every pixel a [`SubpixelLayout`] worth of cells and
+//! shares them out by the pixel's decomposition: an ink with weight `w`
+//! gets about `w` times the cell count, rounded by largest remainder so
+//! the counts add up. Inks then fill the cells in palette order, row by
+//! row, so the same mix always lands in the same cells. What rounding
+//! leaves over is diffused to the neighbouring pixels as per-ink weight
+//! error, as [`DecomposingDitherStrategy`] does for whole pixels; with
+//! `n` cells each pixel can show `n + 1` levels of each ink, so there is
+//! much less of it.
+//!
+//! The output is [`SubpixelWriter`]: an index image `columns` times wider
+//! and `rows` times taller than the input.
+//!
+//! [`DecomposingDitherStrategy`]: crate::dither::DecomposingDitherStrategy
+
+use crate::decompose::Decomposer;
+use crate::dither::diffuse::PixelStrategy;
+use crate::dither::{DecomposedQuantizationError, ImageSize, ImageWriter, select_index};
+use alloc::vec::Vec;
+use nalgebra::DVector;
+
+/// Cells per pixel, `columns` across and `rows` down. Text form:
+/// `COLUMNSxROWS`, e.g. `3x1` for a vertical-stripe panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubpixelLayout {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl SubpixelLayout {
+    /// Number of cells per pixel; `None` if that is zero or overflows.
+    pub fn cells(&self) -> Option<usize> {
+        self.columns.checked_mul(self.rows).filter(|&n| n > 0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSubpixelLayout;
+
+impl core::fmt::Display for InvalidSubpixelLayout {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid subpixel layout, expected `COLUMNSxROWS` with both at least 1")
+    }
+}
+
+impl core::error::Error for InvalidSubpixelLayout {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for SubpixelLayout {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}x{}", self.columns, self.rows)
+    }
+}
+
+impl core::str::FromStr for SubpixelLayout {
+    type Err = InvalidSubpixelLayout;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (columns, rows) = s.split_once('x').ok_or(InvalidSubpixelLayout)?;
+        let parse = |part: &str| match part.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(InvalidSubpixelLayout),
+        };
+        Ok(Self {
+            columns: parse(columns)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+/// Shares each pixel's cells out between inks; see the module docs. The
+/// target is the ink index of every cell, row-major within the pixel.
+/// Pixels whose weights (error included) aren't finite get the
+/// [`with_fallback`](Self::with_fallback) index, or index 0 without one,
+/// in every cell and pass no error on.
+pub struct SubpixelStrategy<D> {
+    pub decomposer: D,
+    layout: SubpixelLayout,
+    cells: usize,
+    pub fallback: usize,
+}
+
+impl<D: Decomposer<f32>> SubpixelStrategy<D> {
+    /// `None` unless the layout has [`cells`](SubpixelLayout::cells) and
+    /// the decomposer's palette has entries to fill them with.
+    pub fn new(decomposer: D, layout: SubpixelLayout) -> Option<Self> {
+        let cells = layout.cells()?;
+        (decomposer.palette_size() > 0).then_some(Self {
+            decomposer,
+            layout,
+            cells,
+            fallback: 0,
+        })
+    }
+
+    /// Index emitted for pixels with non-finite weights.
+    pub fn with_fallback(mut self, fallback: usize) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn layout(&self) -> SubpixelLayout {
+        self.layout
+    }
+}
+
+/// Split `cells` between entries in proportion to `weights` (negative ones
+/// count as zero), giving leftover cells to the largest remainders, lowest
+/// index first on ties. All cells go to [`select_index`]'s pick if no
+/// weight is positive.
+fn apportion(weights: &[f32], cells: usize) -> Vec<usize> {
+    let mut counts = alloc::vec![0; weights.len()];
+    let sum: f32 = weights.iter().filter(|&&w| w > 0.0).sum();
+    if sum <= 0.0 {
+        if let Some(count) = counts.get_mut(select_index(weights, None)) {
+            *count = cells;
+        }
+        return counts;
+    }
+    let mut remainders = Vec::with_capacity(weights.len());
+    for (index, &weight) in weights.iter().enumerate() {
+        let share = weight.max(0.0) / sum * cells as f32;
+        counts[index] = (share as usize).min(cells);
+        remainders.push((index, share - counts[index] as f32));
+    }
+    remainders.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    let placed: usize = counts.iter().sum();
+    for (index, _) in remainders.iter().cycle().take(cells.saturating_sub(placed)) {
+        counts[*index] += 1;
+    }
+    counts
+}
+
+impl<D: Decomposer<f32>> PixelStrategy for SubpixelStrategy<D> {
+    type Source = D::Input;
+    type Target = Vec<usize>;
+    type QuantizationError = DecomposedQuantizationError;
+
+    fn quantize(
+        &self,
+        source: D::Input,
+        _x: usize,
+        _y: usize,
+        error: DecomposedQuantizationError,
+    ) -> (Vec<usize>, DecomposedQuantizationError) {
+        let mut weights = DVector::zeros(self.decomposer.palette_size());
+        self.decomposer
+            .decompose_into(&source, weights.as_mut_slice());
+        if let Some(error) = error.0 {
+            weights += error;
+        }
+        let n = self.cells;
+        if !weights.iter().all(|w| w.is_finite()) {
+            let cells = alloc::vec![self.fallback; n];
+            return (cells, DecomposedQuantizationError::default());
+        }
+        let counts = apportion(weights.as_slice(), n);
+        let mut cells = Vec::with_capacity(n);
+        for (index, &count) in counts.iter().enumerate() {
+            cells.extend(core::iter::repeat_n(index, count));
+            weights[index] -= count as f32 / n as f32;
+        }
+        (cells, DecomposedQuantizationError(Some(weights)))
+    }
+}
+
+/// Writes [`SubpixelStrategy`] targets into an index image `inner` with
+/// one pixel per cell. Reports the size of the pixel grid, so it pairs
+/// with the unscaled input in an
+/// [`ImageCombinedRW`](crate::dither::ImageCombinedRW).
+pub struct SubpixelWriter<W> {
+    pub inner: W,
+    layout: SubpixelLayout,
+}
+
+impl<W: ImageSize> SubpixelWriter<W> {
+    /// Returns `None` unless the layout has
+    /// [`cells`](SubpixelLayout::cells) and `inner`'s size is a multiple
+    /// of it.
+    pub fn new(inner: W, layout: SubpixelLayout) -> Option<Self> {
+        layout.cells()?;
+        if !inner.width().is_multiple_of(layout.columns)
+            || !inner.height().is_multiple_of(layout.rows)
+        {
+            return None;
+        }
+        Some(Self { inner, layout })
+    }
+}
+
+impl<W: ImageSize> ImageSize for SubpixelWriter<W> {
+    fn width(&self) -> usize {
+        self.inner.width() / self.layout.columns
+    }
+    fn height(&self) -> usize {
+        self.inner.height() / self.layout.rows
+    }
+}
+
+impl<W: ImageWriter<usize>> ImageWriter<Vec<usize>> for SubpixelWriter<W> {
+    fn put_pixel(&mut self, x: usize, y: usize, pixel: Vec<usize>) {
+        let SubpixelLayout { columns, rows } = self.layout;
+        // `new` checked that the product doesn't overflow.
+        for (k, index) in pixel.into_iter().enumerate().take(columns * rows) {
+            self.inner
+                .put_pixel(x * columns + k % columns, y * rows + k / columns, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::naive::NaiveDecomposer;
+    use crate::dither::ImageCombinedRW;
+    use crate::dither::diffuse::diffuse_dither;
+    use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+    use crate::dither::{ImageReader, ImageSize, ImageWriter};
+    use nalgebra::geometry::Point3;
+
+    /// Black, white and red.
+    fn points() -> [Point3<f32>; 3] {
+        [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, 0.0, 0.0),
+        ]
+    }
+
+    /// `width` × `height` of flat `colour`.
+    struct Flat {
+        colour: Point3<f32>,
+        width: usize,
+        height: usize,
+    }
+
+    impl ImageSize for Flat {
+        fn width(&self) -> usize {
+            self.width
+        }
+        fn height(&self) -> usize {
+            self.height
+        }
+    }
+
+    impl ImageReader<Point3<f32>> for Flat {
+        fn get_pixel(&self, _: usize, _: usize) -> Point3<f32> {
+            self.colour
+        }
+    }
+
+    /// Row-major cell indices.
+    struct Cells {
+        width: usize,
+        height: usize,
+        indices: Vec<usize>,
+    }
+
+    impl ImageSize for Cells {
+        fn width(&self) -> usize {
+            self.width
+        }
+        fn height(&self) -> usize {
+            self.height
+        }
+    }
+
+    impl ImageWriter<usize> for Cells {
+        fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+            self.indices[y * self.width + x] = pixel;
+        }
+    }
+
+    fn dither(colour: Point3<f32>, layout: SubpixelLayout) -> Cells {
+        let (width, height) = (8, 8);
+        let decomposer = NaiveDecomposer::new(&points()).unwrap();
+        let strategy = SubpixelStrategy::new(&decomposer, layout).unwrap();
+        let cells = Cells {
+            width: width * layout.columns,
+            height: height * layout.rows,
+            indices: alloc::vec![usize::MAX; width * height * layout.cells().unwrap()],
+        };
+        let writer = SubpixelWriter::new(cells, layout).unwrap();
+        let reader = Flat {
+            colour,
+            width,
+            height,
+        };
+        let mut inout = ImageCombinedRW::new(reader, writer).unwrap();
+        diffuse_dither(&strategy, &FLOYD_STEINBERG, &mut inout, true);
+        inout.writer.inner
+    }
+
+    #[test]
+    fn three_stripes_share_cells_by_weight() {
+        let stripes: SubpixelLayout = "3x1".parse().unwrap();
+        assert_eq!(
+            stripes,
+            SubpixelLayout {
+                columns: 3,
+                rows: 1
+            }
+        );
+        // An even mix of the three inks: one cell each, in palette order.
+        let even = dither(Point3::new(2.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0), stripes);
+        assert_eq!(even.width, 24);
+        for row in even.indices.chunks(24) {
+            for pixel in row.chunks(3) {
+                assert_eq!(pixel, [0, 1, 2]);
+            }
+        }
+        // Two thirds white: the same two white cells in every pixel.
+        let light = dither(Point3::new(2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0), stripes);
+        assert!(light.indices.chunks(3).all(|pixel| pixel == [0, 1, 1]));
+        // Half white falls between levels: pixels alternate one and two
+        // white cells, and the white share still comes out at a half.
+        let half = dither(Point3::new(0.5, 0.5, 0.5), stripes);
+        assert!(half.indices.iter().all(|&i| i < 2));
+        let white = half.indices.iter().filter(|&&i| i == 1).count();
+        assert!(
+            (white as f32 / half.indices.len() as f32 - 0.5).abs() < 0.03,
+            "{white}"
+        );
+    }
+
+    #[test]
+    fn non_finite_weights_take_the_fallback() {
+        let decomposer = NaiveDecomposer::new(&points()).unwrap();
+        let layout = SubpixelLayout {
+            columns: 2,
+            rows: 1,
+        };
+        let strategy = SubpixelStrategy::new(&decomposer, layout)
+            .unwrap()
+            .with_fallback(2);
+        // Corrupt error carried in from a neighbour.
+        let nan = DecomposedQuantizationError(Some(DVector::from_element(3, f32::NAN)));
+        let (cells, error) = strategy.quantize(Point3::new(0.5, 0.5, 0.5), 0, 0, nan);
+        assert_eq!(cells, [2, 2]);
+        assert!(error.0.is_none());
+        let empty = SubpixelLayout {
+            columns: 0,
+            rows: 1,
+        };
+        assert!(SubpixelStrategy::new(&decomposer, empty).is_none());
+    }
+
+    #[test]
+    fn layout_text_round_trips() {
+        let layout: SubpixelLayout = "2x2".parse().unwrap();
+        assert_eq!(layout.cells(), Some(4));
+        let huge = SubpixelLayout {
+            columns: usize::MAX,
+            rows: 2,
+        };
+        assert_eq!(huge.cells(), None);
+        assert_eq!(alloc::format!("{layout}").parse(), Ok(layout));
+        for bad in ["3", "0x1", "3x", "ax1"] {
+            assert_eq!(bad.parse::<SubpixelLayout>(), Err(InvalidSubpixelLayout));
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! One image, different palettes per region: for panels built from
+//! several modules whose inks measure slightly differently.
+//!
+//! [`TiledStrategy`] hands each pixel to the strategy of the first
+//! [`Region`] containing it, falling back to a base strategy elsewhere.
+//! It is still a single [`PixelStrategy`] to [`diffuse_dither`], so error
+//! diffusion runs over the whole image in one pass and error crosses a
+//! seam like any other pixel boundary instead of restarting there.
+//!
+//! Error is diffused as per-ink weight error (see
+//! [`DecomposedQuantizationError`](crate::dither::DecomposedQuantizationError)),
+//! so every region's palette must list the same inks in the same order:
+//! "too little red" on one side of a seam is then made up with the red of
+//! the other side's calibration. The output indices are shared too.
+//!
+//! [`diffuse_dither`]: crate::dither::diffuse::diffuse_dither
+
+use crate::dither::diffuse::{Lookahead, OutputWindow, PixelStrategy};
+use alloc::vec::Vec;
+
+/// Axis-aligned pixel rectangle. Text form: `x,y,width,height`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x.checked_sub(self.x).is_some_and(|dx| dx < self.width)
+            && y.checked_sub(self.y).is_some_and(|dy| dy < self.height)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidRegion;
+
+impl core::fmt::Display for InvalidRegion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid region, expected `x,y,width,height`")
+    }
+}
+
+impl core::error::Error for InvalidRegion {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for Region {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl core::str::FromStr for Region {
+    type Err = InvalidRegion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(|part| part.trim().parse::<usize>());
+        let mut next = || {
+            parts
+                .next()
+                .ok_or(InvalidRegion)?
+                .map_err(|_| InvalidRegion)
+        };
+        let region = Self {
+            x: next()?,
+            y: next()?,
+            width: next()?,
+            height: next()?,
+        };
+        if parts.next().is_some() {
+            return Err(InvalidRegion);
+        }
+        Ok(region)
+    }
+}
+
+/// [`PixelStrategy`] using a different strategy per [`Region`]; see the
+/// module docs.
+pub struct TiledStrategy<S> {
+    base: S,
+    tiles: Vec<(Region, S)>,
+}
+
+impl<S> TiledStrategy<S> {
+    /// `base` covers pixels outside every region; where regions overlap,
+    /// the first listed wins.
+    pub fn new(base: S, tiles: Vec<(Region, S)>) -> Self {
+        Self { base, tiles }
+    }
+
+    /// The strategy responsible for `(x, y)`.
+    pub fn at(&self, x: usize, y: usize) -> &S {
+        self.tiles
+            .iter()
+            .find(|(region, _)| region.contains(x, y))
+            .map_or(&self.base, |(_, strategy)| strategy)
+    }
+}
+
+impl<S: PixelStrategy> PixelStrategy for TiledStrategy<S> {
+    type Source = S::Source;
+    type Target = S::Target;
+    type QuantizationError = S::QuantizationError;
+
+    fn quantize(
+        &self,
+        source: S::Source,
+        x: usize,
+        y: usize,
+        error: S::QuantizationError,
+    ) -> (S::Target, S::QuantizationError) {
+        self.at(x, y).quantize(source, x, y, error)
+    }
+
+    fn diffuses(&self, x: usize, y: usize) -> bool {
+        self.at(x, y).diffuses(x, y)
+    }
+
+    fn reads_output(&self) -> bool {
+        self.base.reads_output() || self.tiles.iter().any(|(_, s)| s.reads_output())
+    }
+
+    fn quantize_with_output(
+        &self,
+        source: S::Source,
+        x: usize,
+        y: usize,
+        error: S::QuantizationError,
+        output: &OutputWindow<'_, S::Target>,
+    ) -> (S::Target, S::QuantizationError) {
+        self.at(x, y)
+            .quantize_with_output(source, x, y, error, output)
+    }
+
+    fn looks_ahead(&self) -> bool {
+        self.base.looks_ahead() || self.tiles.iter().any(|(_, s)| s.looks_ahead())
+    }
+
+    fn quantize_with_lookahead(
+        &self,
+        source: S::Source,
+        x: usize,
+        y: usize,
+        error: S::QuantizationError,
+        next: Option<Lookahead<S::Source, S::QuantizationError>>,
+    ) -> (S::Target, S::QuantizationError) {
+        self.at(x, y)
+            .quantize_with_lookahead(source, x, y, error, next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::Decomposer;
+    use crate::dither::DecomposingDitherStrategy;
+    use crate::dither::diffuse::diffuse_dither;
+    use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+    use crate::dither::{ImageReader, ImageSize, ImageWriter};
+
+    /// Two inks at levels `dark` and `light`, mixed linearly.
+    struct TwoInks {
+        dark: f32,
+        light: f32,
+    }
+
+    impl Decomposer<f32> for TwoInks {
+        type Input = f32;
+        fn palette_size(&self) -> usize {
+            2
+        }
+        fn decompose_into(&self, input: &f32, out: &mut [f32]) {
+            let t = ((input - self.dark) / (self.light - self.dark)).clamp(0.0, 1.0);
+            out.copy_from_slice(&[1.0 - t, t]);
+        }
+    }
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 256;
+
+    /// Horizontal ramp from 0.3 to 0.7.
+    struct Ramp(Vec<usize>);
+
+    impl ImageSize for Ramp {
+        fn width(&self) -> usize {
+            WIDTH
+        }
+        fn height(&self) -> usize {
+            HEIGHT
+        }
+    }
+
+    impl ImageReader<f32> for Ramp {
+        fn get_pixel(&self, x: usize, _: usize) -> f32 {
+            0.3 + 0.4 * x as f32 / (WIDTH - 1) as f32
+        }
+    }
+
+    impl ImageWriter<usize> for Ramp {
+        fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+            self.0[y * WIDTH + x] = pixel;
+        }
+    }
+
+    #[test]
+    fn gradient_stays_continuous_across_the_seam() {
+        let (left, right) = (
+            TwoInks {
+                dark: 0.0,
+                light: 1.0,
+            },
+            TwoInks {
+                dark: 0.25,
+                light: 1.0,
+            },
+        );
+        let levels = |x: usize| {
+            let inks = if x < WIDTH / 2 { &left } else { &right };
+            [inks.dark, inks.light]
+        };
+        let strategy = TiledStrategy::new(
+            DecomposingDitherStrategy::new(&left, core::convert::identity),
+            alloc::vec![(
+                Region {
+                    x: WIDTH / 2,
+                    y: 0,
+                    width: WIDTH / 2,
+                    height: HEIGHT,
+                },
+                DecomposingDitherStrategy::new(&right, core::convert::identity),
+            )],
+        );
+        let mut image = Ramp(alloc::vec![0; WIDTH * HEIGHT]);
+        diffuse_dither(&strategy, &FLOYD_STEINBERG, &mut image, true);
+        // Mean shown level and light-ink share of 8 columns from `x`.
+        let band = |x: usize| {
+            let (mut level, mut light) = (0.0, 0);
+            for column in x..x + 8 {
+                for y in 0..HEIGHT {
+                    let index = image.0[y * WIDTH + column];
+                    level += levels(column)[index];
+                    light += index;
+                }
+            }
+            let count = (8 * HEIGHT) as f32;
+            (level / count, light as f32 / count)
+        };
+        // The first band is left out: error dropped past the left edge
+        // takes a few columns to settle.
+        for x in (8..WIDTH).step_by(8) {
+            let expected = (x..x + 8).map(|x| image.get_pixel(x, 0)).sum::<f32>() / 8.0;
+            let (level, _) = band(x);
+            assert!(
+                (level - expected).abs() < 0.02,
+                "columns {x}..: {level} vs {expected}"
+            );
+        }
+        // The tone is continuous, but the ink mix jumps: the right-hand
+        // inks need a smaller share of light ink for the same level.
+        let (_, before) = band(WIDTH / 2 - 8);
+        let (_, after) = band(WIDTH / 2);
+        assert!(before - after > 0.1, "{before} -> {after}");
+    }
+
+    #[test]
+    fn region_text_round_trips() {
+        let region: Region = "10, 0,200,480".parse().unwrap();
+        assert_eq!(
+            region,
+            Region {
+                x: 10,
+                y: 0,
+                width: 200,
+                height: 480
+            }
+        );
+        assert_eq!(alloc::format!("{region}").parse(), Ok(region));
+        assert!(region.contains(10, 479) && !region.contains(210, 0) && !region.contains(9, 0));
+        assert_eq!("1,2,3".parse::<Region>(), Err(InvalidRegion));
+        assert_eq!("1,2,3,4,5".parse::<Region>(), Err(InvalidRegion));
+    }
+}
@@ -0,0 +1,33 @@
+//! Per-ink pixel counts of a dithered image, e.g. for estimating refresh
+//! power before sending an image to the panel.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Count how often each palette index in `0..num_colors` occurs in
+/// `indices`. Indices outside the palette are not counted.
+pub fn palette_usage(indices: impl IntoIterator<Item = usize>, num_colors: usize) -> Vec<usize> {
+    let mut counts = vec![0; num_colors];
+    for index in indices {
+        if let Some(count) = counts.get_mut(index) {
+            *count += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_each_index() {
+        assert_eq!(palette_usage([0, 2, 2, 1, 2], 4), [1, 1, 3, 0]);
+    }
+
+    #[test]
+    fn ignores_out_of_palette_indices() {
+        assert_eq!(palette_usage([0, 5, 1], 2), [1, 1]);
+        assert!(palette_usage([0, 1], 0).is_empty());
+    }
+}
@@ -1,5 +1,9 @@
 use crate::Decomposer;
-use crate::dither::diffuse::PixelStrategy;
+use crate::dither::barrier::DiffuseMask;
+use crate::dither::diffuse::{Lookahead, PixelStrategy};
+use crate::dither::error_buffer::{ErrorBytes, read_u64};
+use crate::dither::mask::InkMask;
+use crate::dither::previous::PreviousFrame;
 use core::marker::PhantomData;
 use core::ops::{AddAssign, Div, Mul};
 use nalgebra::DVector;
@@ -19,6 +23,69 @@ use nalgebra::DVector;
 /// `n(x, y)` is sampled per pixel. Default after [`new`](Self::new) is
 /// `None`; use [`with_noise`](Self::with_noise) to plug one in.
 ///
+/// The noise pick walks the palette in index order, so on ties (and with
+/// ordered noise, which only takes a few distinct values) lower indices
+/// win slightly more often than their weight. `index_order_seed` (see
+/// [`with_index_order_seed`](Self::with_index_order_seed)) walks a
+/// per-pixel permutation instead, derived from a hash of `(x, y, seed)`:
+/// the output stays reproducible for a given seed, but the order no
+/// longer favours any index across the image.
+///
+/// [`with_noise_amplitude`](Self::with_noise_amplitude) scales the noise
+/// towards 0.5 before either pick (see [`crate::noise::scale_amplitude`]),
+/// trading dither strength for posterization.
+///
+/// [`with_tie_margin`](Self::with_tie_margin) has the noise pick evenly
+/// between the weights within a margin of the largest, instead of in
+/// proportion to them. Where two inks are nearly balanced, say 45/55, the
+/// region then comes out as an even 50/50 texture (e.g. a regular
+/// checkerboard with square Bayer noise) rather than one ink slightly
+/// clumping, at the cost of moving the local mix by up to half the margin.
+/// With error diffusion the diffused error pulls later pixels back
+/// towards the weights. It has no effect without noise, or with
+/// [`PickMode::DominantTexture`].
+///
+/// [`PickMode::DominantTexture`] (see [`with_pick`](Self::with_pick))
+/// replaces the cumulative walk with a two-colour choice; the index order
+/// plays no part there.
+///
+/// With a fallback (see [`with_fallback`](Self::with_fallback)), source
+/// pixels that aren't finite skip decomposition: they get the fallback
+/// index, and the error diffused into them is dropped rather than passed
+/// on.
+///
+/// Weights below [`NEGLIGIBLE_WEIGHT`] of the total are dropped before
+/// either pick, so float residue from the decomposer (around `1e-6` for a
+/// colour that is exactly a palette entry) neither wins a pixel at noise 0
+/// nor builds up in the diffused error. A flat palette colour therefore
+/// comes out solid. A flat 50/50 blend of two inks comes out as a
+/// checkerboard with square Bayer noise (half its thresholds lie below
+/// 0.5, in alternating cells), and likewise with Floyd-Steinberg
+/// diffusion and no noise, where each pixel's error tips its neighbours
+/// the other way. Non-ordered noise gives the right shares without the
+/// regular pattern.
+///
+/// With [`with_lookahead`](Self::with_lookahead), error diffusion also
+/// decomposes the next pixel in scan order and picks the index that,
+/// together with the best index there, leaves the least squared weight
+/// error over the two pixels, instead of the greedy pick's least error at
+/// the current pixel alone. The error is the diffused one, between
+/// weights, so it tracks the colour error best for inks spread evenly
+/// through the input space. This replaces the pick: noise, its amplitude,
+/// the pick mode and the index order don't apply. It costs a second
+/// decomposition per pixel (the next pixel is decomposed again when its
+/// turn comes) plus a pass over pairs of palette entries, so dithering
+/// takes two to three times as long.
+///
+/// With [`with_confidence_weighting`](Self::with_confidence_weighting),
+/// the error left at each pixel is scaled by the decomposer's
+/// [`confidence`](Decomposer::confidence) for the source colour, its depth
+/// inside the gamut. Near and outside the gamut boundary, where the
+/// decomposition only approximates the colour, less of the error spreads,
+/// so an out-of-gamut region no longer pushes a growing error into its
+/// neighbours; in-gamut tones deep inside keep all of theirs. Decomposers
+/// without a notion of depth report 1, leaving the error as is.
+///
 /// The strategy emits a `usize` palette index as its target and a
 /// per-component quantization error; whether and how that error is propagated
 /// is the caller's choice via the [`DiffusionMatrix`](crate::dither::diffusion_matrix::DiffusionMatrix)
@@ -29,6 +96,18 @@ pub struct DecomposingDitherStrategy<D, F, N, Src> {
     pub decomposer: D,
     pub convert: F,
     pub noise: Option<N>,
+    pub index_order_seed: Option<u64>,
+    pub previous: Option<PreviousFrame>,
+    pub mask: Option<InkMask>,
+    pub diffuse_mask: Option<DiffuseMask>,
+    pub pick: PickMode,
+    pub noise_amplitude: f32,
+    pub tie_margin: f32,
+    /// Index emitted for source pixels `is_valid` rejects.
+    pub fallback: Option<usize>,
+    pub lookahead: bool,
+    pub confidence_weighting: bool,
+    is_valid: fn(&Src) -> bool,
     _phantom: PhantomData<fn(Src)>,
 }
 
@@ -45,6 +124,17 @@ impl<D, F, Src> DecomposingDitherStrategy<D, F, DefaultNoiseFn, Src> {
             decomposer,
             convert,
             noise: None,
+            index_order_seed: None,
+            previous: None,
+            mask: None,
+            diffuse_mask: None,
+            pick: PickMode::Cumulative,
+            noise_amplitude: 1.0,
+            tie_margin: 0.0,
+            fallback: None,
+            lookahead: false,
+            confidence_weighting: false,
+            is_valid: |_| true,
             _phantom: PhantomData,
         }
     }
@@ -59,13 +149,279 @@ impl<D, F, N, Src> DecomposingDitherStrategy<D, F, N, Src> {
             decomposer: self.decomposer,
             convert: self.convert,
             noise: Some(noise),
+            index_order_seed: self.index_order_seed,
+            previous: self.previous,
+            mask: self.mask,
+            diffuse_mask: self.diffuse_mask,
+            pick: self.pick,
+            noise_amplitude: self.noise_amplitude,
+            tie_margin: self.tie_margin,
+            fallback: self.fallback,
+            lookahead: self.lookahead,
+            confidence_weighting: self.confidence_weighting,
+            is_valid: self.is_valid,
             _phantom: PhantomData,
         }
     }
+
+    /// Walk palette indices in a per-pixel permuted order (seeded by
+    /// `seed`) when picking with noise. `None` restores index order.
+    pub fn with_index_order_seed(mut self, seed: Option<u64>) -> Self {
+        self.index_order_seed = seed;
+        self
+    }
+
+    /// Prefer the index in `previous` where it is nearly as good as the
+    /// pick. `None` disables this.
+    pub fn with_previous(mut self, previous: Option<PreviousFrame>) -> Self {
+        self.previous = previous;
+        self
+    }
+
+    /// Scale weights per pixel by `mask`; see [`crate::dither::mask`].
+    /// `None` disables this.
+    pub fn with_mask(mut self, mask: Option<InkMask>) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Keep error diffusion out of the pixels `mask` masks out, and give
+    /// them its [`fill`](DiffuseMask::with_fill) if it has one; see
+    /// [`crate::dither::barrier`]. `None` diffuses everywhere.
+    pub fn with_diffuse_mask(mut self, mask: Option<DiffuseMask>) -> Self {
+        self.diffuse_mask = mask;
+        self
+    }
+
+    /// Scale the noise towards 0.5 by `amplitude` (1 = full dithering,
+    /// 0 = posterization to the weighted median).
+    pub fn with_noise_amplitude(mut self, amplitude: f32) -> Self {
+        self.noise_amplitude = amplitude;
+        self
+    }
+
+    /// Let the noise pick evenly between weights within `margin` of the
+    /// largest; see the type docs. 0 (the default) picks in proportion to
+    /// every weight.
+    pub fn with_tie_margin(mut self, margin: f32) -> Self {
+        self.tie_margin = margin;
+        self
+    }
+
+    /// How the noise value selects an index; see [`PickMode`].
+    pub fn with_pick(mut self, pick: PickMode) -> Self {
+        self.pick = pick;
+        self
+    }
+
+    /// Pick with one pixel of lookahead when diffusing; see the type docs.
+    pub fn with_lookahead(mut self, lookahead: bool) -> Self {
+        self.lookahead = lookahead;
+        self
+    }
+
+    /// Scale the diffused error by the decomposer's confidence; see the
+    /// type docs.
+    pub fn with_confidence_weighting(mut self, confidence_weighting: bool) -> Self {
+        self.confidence_weighting = confidence_weighting;
+        self
+    }
+
+    /// Emit palette index `fallback` for source pixels that aren't
+    /// [finite](crate::decompose::DecomposerInputColor::is_finite), such
+    /// as NaN samples from a corrupt source. `None` decomposes every
+    /// pixel as is.
+    pub fn with_fallback(mut self, fallback: Option<usize>) -> Self
+    where
+        Src: crate::decompose::DecomposerInputColor,
+    {
+        self.fallback = fallback;
+        self.is_valid = Src::is_finite;
+        self
+    }
+}
+
+/// How [`DecomposingDitherStrategy`] turns weights and a noise value into
+/// a palette index. Without noise both take the dominant component.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PickMode {
+    /// Walk the cumulative weights; any index with weight can be picked.
+    #[default]
+    Cumulative,
+    /// Emit the dominant index unless the noise falls below the share of
+    /// the second-largest weight, in which case emit that one. A blend of
+    /// two inks becomes a solid fill of the majority ink with the minority
+    /// scattered through it as a sparse texture, and no third colour ever
+    /// appears; whatever weight the other indices held goes into the
+    /// diffused error instead.
+    DominantTexture,
+}
+
+/// Pick a palette index from decomposition weights, as
+/// [`DecomposingDitherStrategy`] does with [`PickMode::Cumulative`] and
+/// nothing else configured:
+///
+/// * negative (and NaN) weights count as zero;
+/// * with `noise` (in `[0, 1)`) and a positive total, walk the cumulative
+///   weights in index order and return the index whose half-open interval
+///   contains `noise · total`, so a noise value exactly on a boundary goes
+///   to the later index. Only an index with positive weight is returned,
+///   even if rounding carries the noise past the last interval;
+/// * without noise, or if no weight is positive, return the index of the
+///   largest raw weight, the lowest such index on ties (0 if `weights` is
+///   empty).
+pub fn select_index(weights: &[f32], noise: Option<f32>) -> usize {
+    select_index_in_order(weights, noise, None, 0.0)
+}
+
+/// [`select_index`], walking the cumulative weights in `order` (index
+/// order if `None`). With noise, the positive weights within `tie_margin`
+/// of the largest count as equal: each walks the mean of their shares.
+fn select_index_in_order(
+    weights: &[f32],
+    noise: Option<f32>,
+    order: Option<IndexPermutation>,
+    tie_margin: f32,
+) -> usize {
+    let positive = |index: usize| {
+        let weight = weights[index];
+        if weight > 0.0 { weight } else { 0.0 }
+    };
+    let tie = if tie_margin > 0.0 {
+        tied_weights(weights, tie_margin)
+    } else {
+        None
+    };
+    let clipped = |index: usize| {
+        let weight = positive(index);
+        match tie {
+            Some((floor, mean)) if weight > 0.0 && weight >= floor => mean,
+            _ => weight,
+        }
+    };
+    let sum: f32 = (0..weights.len()).map(clipped).sum();
+    if let Some(noise) = noise
+        && sum > 0.0
+    {
+        let at = |k: usize| order.map_or(k, |order| order.apply(k));
+        let mut noise = noise * sum;
+        let mut last_positive = 0;
+        for k in 0..weights.len() {
+            let index = at(k);
+            let weight = clipped(index);
+            if weight > 0.0 {
+                if noise < weight {
+                    return index;
+                }
+                noise -= weight;
+                last_positive = index;
+            }
+        }
+        return last_positive;
+    }
+    weights
+        .iter()
+        .enumerate()
+        .fold((0, f32::NEG_INFINITY), |best, (index, &weight)| {
+            if weight > best.1 {
+                (index, weight)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+/// Share of the total weight below which [`DecomposingDitherStrategy`]
+/// treats a palette entry's weight as zero.
+pub const NEGLIGIBLE_WEIGHT: f32 = 1e-5;
+
+/// Zero weights whose magnitude is at most [`NEGLIGIBLE_WEIGHT`] of the
+/// positive total, scaling the rest so the sum is unchanged.
+fn drop_negligible_weights(weights: &mut [f32]) {
+    let positive: f32 = weights.iter().filter(|&&w| w > 0.0).sum();
+    if positive <= 0.0 {
+        return;
+    }
+    let total: f32 = weights.iter().sum();
+    let threshold = positive * NEGLIGIBLE_WEIGHT;
+    for weight in weights.iter_mut() {
+        if weight.abs() <= threshold {
+            *weight = 0.0;
+        }
+    }
+    let kept: f32 = weights.iter().sum();
+    if kept != 0.0 && kept != total {
+        let scale = total / kept;
+        weights.iter_mut().for_each(|w| *w *= scale);
+    }
+}
+
+/// Lowest weight and mean of the positive weights within `margin` of the
+/// largest, if there are at least two.
+fn tied_weights(weights: &[f32], margin: f32) -> Option<(f32, f32)> {
+    let top = weights.iter().copied().fold(0.0f32, f32::max);
+    let floor = top - margin;
+    let (count, sum) = weights
+        .iter()
+        .filter(|&&w| w > 0.0 && w >= floor)
+        .fold((0usize, 0.0), |(count, sum), &w| (count + 1, sum + w));
+    (count >= 2).then(|| (floor, sum / count as f32))
+}
+
+/// [`PickMode::DominantTexture`] on clipped weights summing to `sum > 0`.
+fn pick_dominant_texture(weights: &[f32], sum: f32, noise: f32) -> usize {
+    let mut dominant = (0, f32::NEG_INFINITY);
+    let mut minority = (0, f32::NEG_INFINITY);
+    for (index, &weight) in weights.iter().enumerate() {
+        if weight > dominant.1 {
+            minority = dominant;
+            dominant = (index, weight);
+        } else if weight > minority.1 {
+            minority = (index, weight);
+        }
+    }
+    if minority.1 > 0.0 && noise * sum < minority.1 {
+        minority.0
+    } else {
+        dominant.0
+    }
+}
+
+/// Affine permutation `k -> (step * k + offset) % len` of `0..len`, with
+/// `step` coprime to `len`. Cheap to build per pixel and needs no storage.
+#[derive(Clone, Copy)]
+struct IndexPermutation {
+    len: usize,
+    step: usize,
+    offset: usize,
+}
+
+impl IndexPermutation {
+    fn new(len: usize, hash: u64) -> Self {
+        let gcd = |mut a: usize, mut b: usize| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        let len = len.max(1);
+        let offset = (hash % len as u64) as usize;
+        // Terminates at the latest at `len + 1`, which is coprime to `len`.
+        let mut step = 1 + ((hash >> 32) % len as u64) as usize;
+        while gcd(step, len) != 1 {
+            step += 1;
+        }
+        Self { len, step, offset }
+    }
+
+    fn apply(&self, k: usize) -> usize {
+        (self.step * k + self.offset) % self.len
+    }
 }
 
 #[derive(Clone, Default)]
-pub struct DecomposedQuantizationError(Option<DVector<f32>>);
+pub struct DecomposedQuantizationError(pub(crate) Option<DVector<f32>>);
 
 impl Mul<usize> for DecomposedQuantizationError {
     type Output = Self;
@@ -91,6 +447,43 @@ impl AddAssign<DecomposedQuantizationError> for DecomposedQuantizationError {
     }
 }
 
+/// A tag byte (0 for no error, 1 for some), then for some the length as
+/// a little-endian `u64` and the weights as little-endian `f32`s.
+impl ErrorBytes for DecomposedQuantizationError {
+    fn write_bytes(&self, out: &mut alloc::vec::Vec<u8>) {
+        match &self.0 {
+            None => out.push(0),
+            Some(error) => {
+                out.push(1);
+                out.extend_from_slice(&(error.len() as u64).to_le_bytes());
+                for weight in error.iter() {
+                    weight.write_bytes(out);
+                }
+            }
+        }
+    }
+
+    fn read_bytes(bytes: &mut &[u8]) -> Option<Self> {
+        let (&tag, rest) = bytes.split_first()?;
+        *bytes = rest;
+        match tag {
+            0 => Some(Self(None)),
+            1 => {
+                let len = usize::try_from(read_u64(bytes)?).ok()?;
+                // Each weight takes four bytes, so a longer length is corrupt.
+                if len > bytes.len() / 4 {
+                    return None;
+                }
+                let weights = (0..len)
+                    .map(|_| f32::read_bytes(bytes))
+                    .collect::<Option<alloc::vec::Vec<_>>>()?;
+                Some(Self(Some(DVector::from_vec(weights))))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl<D, F, N, Src> PixelStrategy for DecomposingDitherStrategy<D, F, N, Src>
 where
     D: Decomposer<f32>,
@@ -108,35 +501,180 @@ where
         y: usize,
         error: Self::QuantizationError,
     ) -> (Self::Target, Self::QuantizationError) {
-        let noise = self.noise.as_ref().map(|n| n(x, y));
-        let mut decomposed = DVector::zeros(self.decomposer.palette_size());
-        self.decomposer
-            .decompose_into(&(self.convert)(source), decomposed.as_mut_slice());
-        let decomposed = match error.0 {
-            None => decomposed,
-            Some(error) => decomposed + error,
+        let (decomposed, confidence) = match self.target_weights(source, x, y, error) {
+            Ok(target) => target,
+            Err(index) => return (index, DecomposedQuantizationError(None)),
         };
+        let noise = self
+            .noise
+            .as_ref()
+            .map(|n| crate::noise::scale_amplitude(n(x, y), self.noise_amplitude));
         let decomposed_clipped = decomposed.map(|x| if x < 0.0 { 0.0 } else { x });
         let decomposed_clipped_sum = decomposed_clipped.sum();
-        let index = if let Some(noise) = noise
-            && decomposed_clipped_sum > 0.0
-        {
-            let mut noise = noise * decomposed_clipped_sum;
-            let mut index: usize = 0;
-            while index + 1 < decomposed_clipped.nrows() && noise >= decomposed_clipped[index] {
-                noise -= decomposed_clipped[index];
-                index += 1;
+        let index = match (self.pick, noise) {
+            (PickMode::DominantTexture, Some(noise)) if decomposed_clipped_sum > 0.0 => {
+                pick_dominant_texture(decomposed_clipped.as_slice(), decomposed_clipped_sum, noise)
             }
-            index
+            (PickMode::DominantTexture, _) => select_index(decomposed.as_slice(), None),
+            (PickMode::Cumulative, _) => {
+                let order = self.index_order_seed.map(|seed| {
+                    IndexPermutation::new(
+                        decomposed.nrows(),
+                        crate::noise::hash_coordinates(x, y, seed),
+                    )
+                });
+                select_index_in_order(decomposed.as_slice(), noise, order, self.tie_margin)
+            }
+        };
+        self.finish(decomposed, confidence, x, y, index)
+    }
+
+    fn diffuses(&self, x: usize, y: usize) -> bool {
+        self.diffuse_mask
+            .as_ref()
+            .is_none_or(|mask| mask.diffuses(x, y))
+    }
+
+    fn looks_ahead(&self) -> bool {
+        self.lookahead
+    }
+
+    fn quantize_with_lookahead(
+        &self,
+        source: Self::Source,
+        x: usize,
+        y: usize,
+        error: Self::QuantizationError,
+        next: Option<Lookahead<Self::Source, Self::QuantizationError>>,
+    ) -> (Self::Target, Self::QuantizationError) {
+        if !self.lookahead {
+            return self.quantize(source, x, y, error);
+        }
+        let (decomposed, confidence) = match self.target_weights(source, x, y, error) {
+            Ok(target) => target,
+            Err(index) => return (index, DecomposedQuantizationError(None)),
+        };
+        let next = next.and_then(|next| {
+            let share = next.share;
+            self.target_weights(next.source, next.x, y, next.error)
+                .ok()
+                .map(|(weights, _)| (weights, share))
+        });
+        let index = match next {
+            Some((next, share)) => pick_with_lookahead(&decomposed, &next, share),
+            None => select_index(decomposed.as_slice(), None),
+        };
+        self.finish(decomposed, confidence, x, y, index)
+    }
+}
+
+impl<D, F, N, Src> DecomposingDitherStrategy<D, F, N, Src>
+where
+    D: Decomposer<f32>,
+    F: Fn(Src) -> D::Input,
+{
+    /// Weights at `(x, y)` with the diffused `error` added, and the
+    /// confidence to scale the error left there by (1 unless
+    /// `confidence_weighting`), or `Err` with the fallback index for
+    /// sources `is_valid` rejects.
+    fn target_weights(
+        &self,
+        source: Src,
+        x: usize,
+        y: usize,
+        error: DecomposedQuantizationError,
+    ) -> Result<(DVector<f32>, f32), usize> {
+        if let Some(index) = self.fallback
+            && !(self.is_valid)(&source)
+        {
+            return Err(index);
+        }
+        if let Some(mask) = &self.diffuse_mask
+            && let Some(index) = mask.fill()
+            && !mask.diffuses(x, y)
+        {
+            return Err(index);
+        }
+        let input = (self.convert)(source);
+        let confidence = if self.confidence_weighting {
+            self.decomposer.confidence(&input)
         } else {
-            decomposed.argmax().0
+            1.0
+        };
+        let mut decomposed = DVector::zeros(self.decomposer.palette_size());
+        self.decomposer.decompose_into(&input, decomposed.as_mut_slice());
+        if let Some(mask) = &self.mask {
+            mask.apply(x, y, decomposed.as_mut_slice());
+        }
+        drop_negligible_weights(decomposed.as_mut_slice());
+        let mut decomposed = match error.0 {
+            None => decomposed,
+            Some(error) => decomposed + error,
+        };
+        if let Some(mask) = &self.mask {
+            mask.exclude(x, y, decomposed.as_mut_slice());
+        }
+        Ok((decomposed, confidence))
+    }
+
+    /// Apply the previous frame to the picked `index` and return it with
+    /// the error it leaves, scaled by `confidence`.
+    fn finish(
+        &self,
+        decomposed: DVector<f32>,
+        confidence: f32,
+        x: usize,
+        y: usize,
+        index: usize,
+    ) -> (usize, DecomposedQuantizationError) {
+        let index = match &self.previous {
+            Some(previous) => {
+                let clipped = decomposed.map(|x| if x < 0.0 { 0.0 } else { x });
+                previous.prefer(x, y, clipped.as_slice(), index)
+            }
+            None => index,
         };
         let mut error = decomposed;
         error[index] -= 1.0;
+        if confidence != 1.0 {
+            error *= confidence;
+        }
         (index, DecomposedQuantizationError(Some(error)))
     }
 }
 
+/// Index `i` for target weights `current` minimising the squared error
+/// `|current - e_i|²` plus the least `|next + share · (current - e_i) -
+/// e_j|²` over `j`, the error the next pixel is left with after its own
+/// best pick. Only indices with positive weight in `current` are tried;
+/// without any, this is [`select_index`] without noise. The lowest index
+/// wins ties.
+fn pick_with_lookahead(current: &DVector<f32>, next: &DVector<f32>, share: f32) -> usize {
+    // With u = next + share · current, the next pixel sees u - share · e_i,
+    // so its best j is the largest entry of that vector.
+    let ahead = next + current * share;
+    let (current_norm, ahead_norm) = (current.norm_squared(), ahead.norm_squared());
+    let mut best: Option<(usize, f32)> = None;
+    for (i, &weight) in current.iter().enumerate() {
+        if weight <= 0.0 {
+            continue;
+        }
+        let here = current_norm - 2.0 * weight + 1.0;
+        let lowered = ahead[i] - share;
+        let largest = ahead
+            .iter()
+            .enumerate()
+            .map(|(j, &v)| if j == i { lowered } else { v })
+            .fold(f32::NEG_INFINITY, f32::max);
+        let there = ahead_norm - 2.0 * share * ahead[i] + share * share - 2.0 * largest + 1.0;
+        let cost = here + there;
+        if best.is_none_or(|(_, best)| cost < best) {
+            best = Some((i, cost));
+        }
+    }
+    best.map_or_else(|| select_index(current.as_slice(), None), |(i, _)| i)
+}
+
 /// Library-grade enum equivalent of the binary's `--strategy` argument:
 /// names a built-in decomposition strategy. The registry layer (see
 /// [`crate::registry`], `image` feature) maps each variant to the
@@ -157,20 +695,37 @@ pub enum DecomposeStrategy {
     GrayPureSpread(f32),
     /// `OffsetBlendGrayDecomposer` with the given offset in input-space units.
     GrayOffsetBlend(f32),
+    /// Octahedron decomposition (closest axis) picked with
+    /// [`PickMode::DominantTexture`].
+    DominantTexture,
 }
 
 impl DecomposeStrategy {
     pub const LONG_HELP: &'static str = concat!(
         "Decomposition strategy.\n\n",
         "Accepted values:\n",
-        " octahedron-closest        Octahedron, pick closest axis (default)\n",
+        " octahedron-closest        Octahedron, pick closest axis (default\n",
+        "                           for 6-colour palettes)\n",
         " octahedron-furthest       Octahedron, pick furthest axis\n",
-        " naive-mix                 Naive, favour mixed weights\n",
+        " octahedron-blended        Octahedron, blend axes by closeness\n",
+        " naive-mix                 Naive, favour mixed weights (default for\n",
+        "                           other colour palettes, e.g. bwry)\n",
         " naive-dominant            Naive, favour dominant component\n",
         " naive-blend[:<p>]         Naive, smooth blend (default p=1)\n",
-        " grayscale                 1-D grayscale, no spread\n",
+        " naive-closest             Naive, closest reconstruction\n",
+        " grayscale                 1-D grayscale, no spread (default for\n",
+        "                           all-gray palettes)\n",
         " gray-pure-spread:<r>      Pure-spread grayscale, r in [0, 1]\n",
-        " gray-offset-blend:<r>     Offset-blend grayscale, r in [0, 1]\n\n",
+        " gray-offset-blend:<r>     Offset-blend grayscale, r in [0, 1]\n",
+        " dominant-texture          Octahedron, solid dominant ink with the\n",
+        "                           second ink dithered in as texture\n\n",
+        "Ordered dithering (a noise source without diffusion) picks straight\n",
+        "from the weights, with no diffused error to hide where they jump.\n",
+        "For 6-colour palettes, octahedron-blended and naive-blend change\n",
+        "inks smoothly across the gamut. octahedron-closest projects once\n",
+        "rather than three times, but switches inks where the closest axis\n",
+        "changes, as naive-mix and naive-dominant do where the chosen\n",
+        "tetrahedron does.\n\n",
         "Examples:\n",
         " --strategy octahedron-closest\n",
         " --strategy grayscale\n",
@@ -198,6 +753,20 @@ fn parse_unit_interval(s: &str) -> Result<f32, InvalidDecomposeStrategy> {
     Ok(v)
 }
 
+/// Inverse of `FromStr`. Float parameters print in Rust's shortest
+/// round-tripping form, so parsing the output gives back the same value.
+impl core::fmt::Display for DecomposeStrategy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Octahedron(strategy) => write!(f, "octahedron-{strategy}"),
+            Self::Naive(strategy) => write!(f, "naive-{strategy}"),
+            Self::GrayPureSpread(ratio) => write!(f, "gray-pure-spread:{ratio}"),
+            Self::GrayOffsetBlend(offset) => write!(f, "gray-offset-blend:{offset}"),
+            Self::DominantTexture => f.write_str("dominant-texture"),
+        }
+    }
+}
+
 impl core::str::FromStr for DecomposeStrategy {
     type Err = InvalidDecomposeStrategy;
 
@@ -205,6 +774,9 @@ impl core::str::FromStr for DecomposeStrategy {
         if s == "grayscale" {
             return Ok(Self::GrayOffsetBlend(0.0));
         }
+        if s == "dominant-texture" {
+            return Ok(Self::DominantTexture);
+        }
         if let Some(rest) = s.strip_prefix("octahedron-") {
             return Ok(Self::Octahedron(
                 rest.parse().map_err(|_| InvalidDecomposeStrategy)?,
@@ -224,3 +796,508 @@ impl core::str::FromStr for DecomposeStrategy {
         Err(InvalidDecomposeStrategy)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ignores its input and always returns equal thirds.
+    struct Thirds;
+
+    impl Decomposer<f32> for Thirds {
+        type Input = ();
+        fn palette_size(&self) -> usize {
+            3
+        }
+        fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+            out.fill(1.0 / 3.0);
+        }
+    }
+
+    fn max_frequency_error(seed: Option<u64>) -> f32 {
+        // 2x2 Bayer only yields thresholds 0, 1/4, 1/2, 3/4, so in index
+        // order the first palette entry wins half the pixels.
+        let strategy = DecomposingDitherStrategy::new(Thirds, |_: ()| ())
+            .with_noise(|x, y| crate::noise::bayer(x, y, 1))
+            .with_index_order_seed(seed);
+        let mut counts = [0usize; 3];
+        for y in 0..64 {
+            for x in 0..64 {
+                counts[strategy.quantize((), x, y, Default::default()).0] += 1;
+            }
+        }
+        counts
+            .iter()
+            .map(|&c| (c as f32 / 4096.0 - 1.0 / 3.0).abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// Flat blend of 70% index 0, 20% index 1 and 10% index 2.
+    struct Blend;
+
+    impl Decomposer<f32> for Blend {
+        type Input = ();
+        fn palette_size(&self) -> usize {
+            3
+        }
+        fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+            out.copy_from_slice(&[0.7, 0.2, 0.1]);
+        }
+    }
+
+    fn counts<D: Decomposer<f32, Input = ()>>(decomposer: D, pick: PickMode) -> [usize; 3] {
+        let strategy = DecomposingDitherStrategy::new(decomposer, |_: ()| ())
+            .with_noise(|x, y| crate::noise::bayer(x, y, 3))
+            .with_pick(pick);
+        let mut counts = [0usize; 3];
+        for y in 0..8 {
+            for x in 0..8 {
+                counts[strategy.quantize((), x, y, Default::default()).0] += 1;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn dominant_texture_flat_blend_keeps_minority_share() {
+        // 30% blend of two inks: index 1 should cover 30% of an 8x8 Bayer
+        // tile, rounded to a whole threshold step.
+        struct TwoInk;
+        impl Decomposer<f32> for TwoInk {
+            type Input = ();
+            fn palette_size(&self) -> usize {
+                3
+            }
+            fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+                out.copy_from_slice(&[0.7, 0.3, 0.0]);
+            }
+        }
+        let [dominant, minority, other] = counts(TwoInk, PickMode::DominantTexture);
+        assert_eq!(other, 0);
+        assert_eq!(dominant + minority, 64);
+        assert!(
+            (minority as f32 / 64.0 - 0.3).abs() <= 1.0 / 64.0,
+            "{minority}"
+        );
+    }
+
+    #[test]
+    fn dominant_texture_never_picks_third_colour() {
+        assert!(counts(Blend, PickMode::Cumulative)[2] > 0);
+        let [dominant, minority, other] = counts(Blend, PickMode::DominantTexture);
+        assert_eq!(other, 0);
+        assert!(dominant > minority && minority > 0);
+    }
+
+    #[test]
+    fn zero_amplitude_picks_weighted_median() {
+        struct Spread;
+        impl Decomposer<f32> for Spread {
+            type Input = ();
+            fn palette_size(&self) -> usize {
+                3
+            }
+            fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+                out.copy_from_slice(&[0.4, 0.3, 0.3]);
+            }
+        }
+        fn all_pick<D: Decomposer<f32, Input = ()>>(decomposer: D, index: usize) {
+            let strategy = DecomposingDitherStrategy::new(decomposer, |_: ()| ())
+                .with_noise(|x, y| crate::noise::bayer(x, y, 3))
+                .with_noise_amplitude(0.0);
+            for y in 0..8 {
+                for x in 0..8 {
+                    assert_eq!(strategy.quantize((), x, y, Default::default()).0, index);
+                }
+            }
+        }
+        // A majority weight is also the median...
+        all_pick(Blend, 0);
+        assert!(counts(Blend, PickMode::Cumulative)[0] < 64);
+        // ...but without one the cumulative weights cross one half later.
+        all_pick(Spread, 1);
+    }
+
+    #[test]
+    fn tie_margin_evens_out_near_equal_weights() {
+        struct NearTie;
+        impl Decomposer<f32> for NearTie {
+            type Input = ();
+            fn palette_size(&self) -> usize {
+                3
+            }
+            fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+                out.copy_from_slice(&[0.45, 0.55, 0.0]);
+            }
+        }
+        let first = |margin: f32| {
+            let strategy = DecomposingDitherStrategy::new(NearTie, |_: ()| ())
+                .with_noise(|x, y| crate::noise::bayer(x, y, 3))
+                .with_tie_margin(margin);
+            let mut first = 0;
+            for y in 0..8 {
+                for x in 0..8 {
+                    first += usize::from(strategy.quantize((), x, y, Default::default()).0 == 0);
+                }
+            }
+            first
+        };
+        // In proportion: 45% of an 8x8 Bayer tile, rounded to a step.
+        assert_eq!(first(0.0), 29);
+        assert_eq!(first(0.05), 29);
+        assert_eq!(first(0.15), 32);
+        assert_eq!(
+            select_index_in_order(&[0.45, 0.55, 0.0], Some(0.47), None, 0.15),
+            0
+        );
+        assert_eq!(
+            select_index_in_order(&[0.45, 0.55, 0.0], Some(0.5), None, 0.15),
+            1
+        );
+    }
+
+    #[test]
+    fn permutation_covers_all_indices() {
+        for len in 1..8 {
+            let order = IndexPermutation::new(len, 0x1234_5678_9ABC_DEF0);
+            let mut seen = [false; 8];
+            for k in 0..len {
+                seen[order.apply(k)] = true;
+            }
+            assert!(seen[..len].iter().all(|&s| s));
+        }
+    }
+
+    #[test]
+    fn permuted_order_tracks_weights_more_closely() {
+        let deterministic = max_frequency_error(None);
+        let permuted = max_frequency_error(Some(1));
+        assert!(permuted < deterministic, "{permuted} >= {deterministic}");
+        assert_eq!(max_frequency_error(Some(1)), permuted);
+    }
+
+    #[test]
+    fn select_index_clips_negative_weights() {
+        // Clipped to [0, 0.5, 0.5]: index 0 is never picked.
+        for noise in [0.0, 0.25, 0.49, 0.5, 0.99] {
+            let index = select_index(&[-0.5, 0.5, 0.5], Some(noise));
+            assert_eq!(index, if noise < 0.5 { 1 } else { 2 });
+        }
+    }
+
+    #[test]
+    fn select_index_boundary_goes_to_later_index() {
+        assert_eq!(select_index(&[0.25, 0.25, 0.5], Some(0.25)), 1);
+        assert_eq!(select_index(&[0.25, 0.25, 0.5], Some(0.5)), 2);
+    }
+
+    #[test]
+    fn residue_weights_never_win() {
+        struct Residue;
+        impl Decomposer<f32> for Residue {
+            type Input = ();
+            fn palette_size(&self) -> usize {
+                3
+            }
+            fn decompose_into(&self, _input: &(), out: &mut [f32]) {
+                out.copy_from_slice(&[2e-7, 0.9999997, 1e-7]);
+            }
+        }
+        for pick in [PickMode::Cumulative, PickMode::DominantTexture] {
+            let strategy = DecomposingDitherStrategy::new(Residue, |_: ()| ())
+                .with_noise(|_, _| 0.0)
+                .with_pick(pick);
+            let (index, error) = strategy.quantize((), 0, 0, Default::default());
+            assert_eq!(index, 1);
+            assert_eq!(error.0.unwrap().as_slice(), &[0.0; 3]);
+        }
+    }
+
+    #[cfg(feature = "image")]
+    fn dither_flat(
+        palette: &[[u8; 3]],
+        strategy: &str,
+        noise: crate::noise::NoiseSource,
+        matrix: crate::dither::diffusion_matrix::RefDiffusionMatrix,
+        color: [f32; 3],
+    ) -> crate::image::palette_image::PaletteImage {
+        use crate::dither::ImageCombinedRW;
+        use crate::image::palette_image::{PaletteImage, VerifiedPalette};
+        use image::{Rgb, Rgb32FImage};
+        let palette: alloc::vec::Vec<Rgb<u8>> = palette.iter().map(|&c| Rgb(c)).collect();
+        let ditherer = crate::registry::decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
+            strategy.parse::<DecomposeStrategy>().unwrap(),
+            noise,
+            &palette,
+            matrix,
+        )
+        .unwrap();
+        let input = Rgb32FImage::from_pixel(16, 16, Rgb(color));
+        let writer = PaletteImage::new(16, 16, VerifiedPalette::new(palette).unwrap());
+        let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+        ditherer.dyn_dither_into(&mut inout);
+        inout.writer
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn flat_palette_colours_dither_solid() {
+        use crate::dither::ImageReader;
+        use crate::dither::diffusion_matrix::{FLOYD_STEINBERG, NO_DIFFUSE};
+        use crate::noise::NoiseSource;
+        use crate::palette::SPECTRA6;
+        for strategy in ["octahedron-closest", "naive-mix", "dominant-texture"] {
+            for noise in [
+                NoiseSource::Bayer(Some(3)),
+                NoiseSource::InterleavedGradient,
+            ] {
+                for matrix in [NO_DIFFUSE, FLOYD_STEINBERG] {
+                    for (i, color) in SPECTRA6.iter().enumerate() {
+                        let color = color.map(|c| c as f32 / 255.0);
+                        let output = dither_flat(&SPECTRA6, strategy, noise.clone(), matrix, color);
+                        for y in 0..16 {
+                            for x in 0..16 {
+                                let index: usize = ImageReader::get_pixel(&output, x, y);
+                                assert_eq!(index, i, "{strategy} {noise} colour {i} at ({x}, {y})");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn flat_even_blend_is_checkerboard() {
+        use crate::dither::ImageReader;
+        use crate::dither::diffusion_matrix::{FLOYD_STEINBERG, NO_DIFFUSE};
+        use crate::noise::NoiseSource;
+        use crate::palette::BWRY;
+        // Halfway between yellow (2) and red (3).
+        for (noise, matrix) in [
+            (NoiseSource::Bayer(Some(3)), NO_DIFFUSE),
+            (NoiseSource::Bayer(Some(3)), FLOYD_STEINBERG),
+            (NoiseSource::None, FLOYD_STEINBERG),
+        ] {
+            let output = dither_flat(&BWRY, "naive-mix", noise.clone(), matrix, [1.0, 0.5, 0.0]);
+            let at = |x, y| -> usize { ImageReader::get_pixel(&output, x, y) };
+            for y in 0..16 {
+                for x in 0..16 {
+                    assert!(at(x, y) == 2 || at(x, y) == 3, "{noise} at ({x}, {y})");
+                    if x > 0 {
+                        assert_ne!(at(x, y), at(x - 1, y), "{noise} at ({x}, {y})");
+                    }
+                    if y > 0 {
+                        assert_ne!(at(x, y), at(x, y - 1), "{noise} at ({x}, {y})");
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn non_finite_pixels_get_the_fallback_index() {
+        use crate::dither::ImageCombinedRW;
+        use crate::dither::ImageReader;
+        use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+        use crate::image::palette_image::{PaletteImage, VerifiedPalette};
+        use crate::noise::NoiseSource;
+        use crate::palette::{GRAYSCALE4_RGB, SPECTRA6};
+        use crate::registry::{FactoryError, FactoryOptions, decompose_ditherer_with};
+        use image::{Rgb, Rgb32FImage};
+        let dither = |palette: &[[u8; 3]], strategy: &str, fallback| {
+            let palette: alloc::vec::Vec<Rgb<u8>> = palette.iter().map(|&c| Rgb(c)).collect();
+            let options = FactoryOptions {
+                non_finite_fallback: fallback,
+                ..Default::default()
+            };
+            let ditherer = decompose_ditherer_with::<Rgb<f32>, Rgb<u8>, _>(
+                strategy.parse::<DecomposeStrategy>().unwrap(),
+                NoiseSource::None,
+                &palette,
+                FLOYD_STEINBERG,
+                &options,
+            )?;
+            let input = Rgb32FImage::from_fn(8, 8, |x, y| match (x, y) {
+                (3, 3) => Rgb([f32::NAN, 0.5, 0.5]),
+                (4, 3) => Rgb([f32::INFINITY; 3]),
+                _ => Rgb([0.6; 3]),
+            });
+            let writer = PaletteImage::new(8, 8, VerifiedPalette::new(palette).unwrap());
+            let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+            ditherer.dyn_dither_into(&mut inout);
+            let at = |x, y| -> usize { ImageReader::get_pixel(&inout.writer, x, y) };
+            Ok([at(3, 3), at(4, 3)])
+        };
+        for (palette, strategy) in [
+            (&SPECTRA6[..], "naive-mix"),
+            (&SPECTRA6[..], "octahedron-closest"),
+            (&GRAYSCALE4_RGB[..], "gray-offset-blend:0"),
+        ] {
+            // Black is entry 0 of both palettes.
+            assert_eq!(dither(palette, strategy, None), Ok([0, 0]), "{strategy}");
+            assert_eq!(dither(palette, strategy, Some(2)), Ok([2, 2]), "{strategy}");
+        }
+        assert_eq!(
+            dither(&SPECTRA6, "naive-mix", Some(6)).err(),
+            Some(FactoryError::FallbackOutOfRange)
+        );
+    }
+
+    #[test]
+    fn select_index_skips_zero_weights() {
+        assert_eq!(select_index(&[0.0, 0.0, 1.0, 0.0], Some(0.0)), 2);
+        assert_eq!(select_index(&[0.5, 0.5, 0.0], Some(1.0)), 1);
+        assert_eq!(select_index(&[0.5, 0.5, 0.0], Some(0.999_999)), 1);
+    }
+
+    #[test]
+    fn select_index_without_noise_is_argmax() {
+        assert_eq!(select_index(&[0.2, 0.5, 0.3], None), 1);
+        // Ties go to the lowest index.
+        assert_eq!(select_index(&[0.4, 0.2, 0.4], None), 0);
+    }
+
+    #[test]
+    fn select_index_falls_back_to_argmax_without_positive_weight() {
+        assert_eq!(select_index(&[-0.3, -0.1, -0.2], Some(0.5)), 1);
+        assert_eq!(select_index(&[0.0, 0.0], Some(0.5)), 0);
+        assert_eq!(select_index(&[], Some(0.5)), 0);
+    }
+
+    #[test]
+    fn select_index_ignores_nan_weights() {
+        assert_eq!(select_index(&[f32::NAN, 1.0], Some(0.0)), 1);
+        assert_eq!(select_index(&[f32::NAN, 1.0], None), 1);
+    }
+
+    /// Inks at `LEVELS`, mixing the two around the input: a ramp over
+    /// them switches ink pairs halfway.
+    struct Bracket;
+
+    const LEVELS: [f32; 3] = [0.0, 0.5, 1.0];
+
+    impl Decomposer<f32> for Bracket {
+        type Input = f32;
+        fn palette_size(&self) -> usize {
+            3
+        }
+        fn decompose_into(&self, input: &f32, out: &mut [f32]) {
+            out.fill(0.0);
+            let upper = if *input < LEVELS[1] { 1 } else { 2 };
+            let t =
+                ((input - LEVELS[upper - 1]) / (LEVELS[upper] - LEVELS[upper - 1])).clamp(0.0, 1.0);
+            out[upper - 1] = 1.0 - t;
+            out[upper] = t;
+        }
+    }
+
+    /// Horizontal ramp over every level, collecting the output.
+    struct Gradient(alloc::vec::Vec<usize>);
+
+    const GRADIENT: [usize; 2] = [96, 32];
+
+    impl Gradient {
+        fn level(x: usize) -> f32 {
+            x as f32 / (GRADIENT[0] - 1) as f32
+        }
+    }
+
+    impl crate::dither::ImageSize for Gradient {
+        fn width(&self) -> usize {
+            GRADIENT[0]
+        }
+        fn height(&self) -> usize {
+            GRADIENT[1]
+        }
+    }
+
+    impl crate::dither::ImageReader<f32> for Gradient {
+        fn get_pixel(&self, x: usize, _: usize) -> f32 {
+            Self::level(x)
+        }
+    }
+
+    impl crate::dither::ImageWriter<usize> for Gradient {
+        fn put_pixel(&mut self, x: usize, y: usize, index: usize) {
+            self.0[y * GRADIENT[0] + x] = index;
+        }
+    }
+
+    /// Mean squared gap between the output and input levels, both
+    /// averaged over 3×3 windows.
+    fn reconstruction_error(lookahead: bool, serpentine: bool) -> f32 {
+        let strategy =
+            DecomposingDitherStrategy::new(Bracket, |v: f32| v).with_lookahead(lookahead);
+        let mut image = Gradient(alloc::vec![0; GRADIENT[0] * GRADIENT[1]]);
+        crate::dither::diffuse::diffuse_dither(
+            &strategy,
+            &crate::dither::diffusion_matrix::FLOYD_STEINBERG,
+            &mut image,
+            serpentine,
+        );
+        let [width, height] = GRADIENT;
+        let mut total = 0.0;
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let mut gap = 0.0;
+                for ny in y - 1..=y + 1 {
+                    for nx in x - 1..=x + 1 {
+                        gap += LEVELS[image.0[ny * width + nx]] - Gradient::level(nx);
+                    }
+                }
+                total += (gap / 9.0) * (gap / 9.0);
+            }
+        }
+        total / ((width - 2) * (height - 2)) as f32
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn confidence_weighting_shrinks_error_near_the_boundary() {
+        use crate::decompose::naive::NaiveDecomposer;
+        use nalgebra::Point3;
+        let palette = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+        ];
+        let decomposer = NaiveDecomposer::new(&palette).unwrap();
+        let error = |weighting: bool, input: Point3<f32>| {
+            let strategy = DecomposingDitherStrategy::new(&decomposer, |p: Point3<f32>| p)
+                .with_confidence_weighting(weighting);
+            strategy.quantize(input, 0, 0, Default::default()).1.0.unwrap()
+        };
+        // Deep inside: the error diffuses in full.
+        let deep = Point3::new(0.25, 0.25, 0.25);
+        assert_eq!(error(true, deep), error(false, deep));
+        // Just inside the face through inks 1, 2 and 3, and outside it.
+        for (input, confidence) in [
+            (Point3::new(0.33, 0.33, 0.32), 0.08),
+            (Point3::new(0.5, 0.5, 0.5), 0.0),
+        ] {
+            let plain = error(false, input);
+            let weighted = error(true, input);
+            assert!(plain.norm() > 0.5);
+            assert!(
+                (&weighted - &plain * confidence).norm() < 1e-5,
+                "{input}: {weighted} vs {plain}"
+            );
+        }
+    }
+
+    #[test]
+    fn lookahead_lowers_reconstruction_error() {
+        for serpentine in [false, true] {
+            let greedy = reconstruction_error(false, serpentine);
+            let lookahead = reconstruction_error(true, serpentine);
+            assert!(
+                lookahead < 0.9 * greedy,
+                "serpentine {serpentine}: {lookahead} vs {greedy}"
+            );
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Robustness checks for the projectors and decomposers on arbitrary
+//! palettes and inputs, for the cargo-fuzz targets in `fuzz/` and a seeded
+//! sweep in this crate's tests.
+//!
+//! [`check`] reads a palette and query points from raw bytes, builds every
+//! projector and decomposer that accepts the palette, and runs each query
+//! through them. Constructors may refuse a palette (duplicate colours,
+//! flat tetrahedra, the wrong number of entries); what they build must
+//! then never panic, and must return finite weights. Clipping projectors
+//! and decomposers must also return weights in `[0, 1]` summing to one,
+//! within [`TOLERANCE`]; plain projections only need to be finite, since
+//! they are negative outside the simplex by design.
+//!
+//! Byte layout: the first byte gives the palette size, 2 plus its low six
+//! bits modulo 7 (so 2 to 8). With its top bit set, palette coordinates
+//! snap to quarters of the unit cube, which makes collinear and coplanar
+//! palettes common; with the next bit set as well, they are then nudged
+//! off the grid by up to [`JITTER`] × 51, leaving them nearly flat instead.
+//! Then three bytes per palette entry, each scaled to `[0, 1]` unless
+//! snapped, and three bytes per query point, scaled to
+//! `[-0.25, 1.25]` so some land outside the gamut. Trailing bytes that
+//! don't make a whole point are ignored; input too short for a palette
+//! passes.
+//!
+//! Behind the `fuzz` Cargo feature. To fuzz (needs a nightly toolchain
+//! and `cargo install cargo-fuzz`):
+//!
+//! ```text
+//! cargo +nightly fuzz run decompose
+//! ```
+
+use crate::barycentric::line::LineProjector;
+use crate::barycentric::octahedron::OctahedronProjector;
+use crate::barycentric::tetrahedron::TetrahedronProjector;
+use crate::barycentric::triangle::ClippingTriangleProjector;
+use crate::decompose::Decomposer;
+use crate::decompose::approx::ApproxDecomposer;
+use crate::decompose::gray::{OffsetBlendGrayDecomposer, PureSpreadGrayDecomposer};
+use crate::decompose::grid::GridDecomposer;
+use crate::decompose::lut::LutDecomposer;
+use crate::decompose::max_inks::MaxInksDecomposer;
+use crate::decompose::naive::{NaiveDecomposer, NaiveDecomposerStrategy};
+use crate::decompose::octahedron::{OctahedronDecomposer, OctahedronDecomposerAxisStrategy};
+use alloc::vec;
+use alloc::vec::Vec;
+use nalgebra::geometry::Point3;
+
+/// How far weights may stray from `[0, 1]`, and their sum from one.
+pub const TOLERANCE: f32 = 1e-3;
+
+/// Step of the offsets [`check`] may add to snapped palette coordinates,
+/// up to 51 of them: small enough to leave palettes nearly flat.
+pub const JITTER: f32 = 2e-5;
+
+/// LUT resolution [`check`] samples the naive decomposer at.
+const LUT_RESOLUTION: usize = 3;
+
+/// What [`check`] found wrong, and where.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Failure {
+    /// The projector or decomposer that misbehaved.
+    pub subject: &'static str,
+    pub problem: Problem,
+    pub query: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    NonFinite,
+    OutOfRange,
+    Sum,
+}
+
+impl core::fmt::Display for Failure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let problem = match self.problem {
+            Problem::NonFinite => "non-finite weight",
+            Problem::OutOfRange => "weight outside [0, 1]",
+            Problem::Sum => "weights don't sum to one",
+        };
+        write!(f, "{}: {problem} for {:?}", self.subject, self.query)
+    }
+}
+
+impl core::error::Error for Failure {}
+
+/// `None` if all `weights` are finite and, for `clipped`, in range and
+/// summing to one.
+// The sum comparison is negated so NaN fails it.
+#[allow(clippy::neg_cmp_op_on_partial_ord)]
+fn problem(weights: &[f32], clipped: bool) -> Option<Problem> {
+    if !weights.iter().all(|w| w.is_finite()) {
+        return Some(Problem::NonFinite);
+    }
+    if !clipped {
+        return None;
+    }
+    let range = -TOLERANCE..=1.0 + TOLERANCE;
+    if !weights.iter().all(|w| range.contains(w)) {
+        return Some(Problem::OutOfRange);
+    }
+    if !((weights.iter().sum::<f32>() - 1.0).abs() <= TOLERANCE) {
+        return Some(Problem::Sum);
+    }
+    None
+}
+
+/// Run the checks in the module docs on the palette and queries encoded
+/// in `data`, stopping at the first failure.
+pub fn check(data: &[u8]) -> Result<(), Failure> {
+    let Some((&head, rest)) = data.split_first() else {
+        return Ok(());
+    };
+    let size = 2 + usize::from(head & 0x3F) % 7;
+    let (coarse, jitter) = (head & 0x80 != 0, head & 0x40 != 0);
+    if rest.len() < 3 * size {
+        return Ok(());
+    }
+    let (palette, queries) = rest.split_at(3 * size);
+    let palette: Vec<Point3<f32>> = palette
+        .chunks_exact(3)
+        .map(|c| {
+            Point3::from(core::array::from_fn(|i| match coarse {
+                true if jitter => f32::from(c[i] % 5) / 4.0 + f32::from(c[i] / 5) * JITTER,
+                true => f32::from(c[i] % 5) / 4.0,
+                false => f32::from(c[i]) / 255.0,
+            }))
+        })
+        .collect();
+    let queries: Vec<Point3<f32>> = queries
+        .chunks_exact(3)
+        .map(|c| {
+            Point3::from(core::array::from_fn(|i| {
+                f32::from(c[i]) / 255.0 * 1.5 - 0.25
+            }))
+        })
+        .collect();
+    Subjects::new(&palette).check(&queries)
+}
+
+/// Everything [`check`] builds from one palette.
+struct Subjects {
+    line: Option<LineProjector<f32>>,
+    triangle: Option<ClippingTriangleProjector<f32>>,
+    tetrahedron: Option<TetrahedronProjector<f32>>,
+    octahedron: Option<OctahedronProjector<f32>>,
+    decomposers: Vec<(&'static str, DynDecomposer)>,
+    gray: Vec<(&'static str, DynGrayDecomposer)>,
+}
+
+type DynDecomposer = alloc::boxed::Box<dyn Decomposer<f32, Input = Point3<f32>>>;
+type DynGrayDecomposer = alloc::boxed::Box<dyn Decomposer<f32, Input = f32>>;
+
+impl Subjects {
+    fn new(palette: &[Point3<f32>]) -> Self {
+        let first = |n: usize| palette.get(..n);
+        let mut decomposers: Vec<(&'static str, DynDecomposer)> = Vec::new();
+        if let Some(naive) = NaiveDecomposer::new(palette) {
+            if let Some(lut) = LutDecomposer::new(&naive, LUT_RESOLUTION) {
+                decomposers.push(("lut", alloc::boxed::Box::new(lut)));
+            }
+            for (name, max_inks) in [("max-inks-2", 2), ("max-inks-3", 3)] {
+                let capped = NaiveDecomposer::new(palette)
+                    .and_then(|d| MaxInksDecomposer::new(d, palette, max_inks));
+                decomposers.extend(capped.map(|d| (name, alloc::boxed::Box::new(d) as _)));
+            }
+            for (name, strategy) in [
+                ("naive-mix", NaiveDecomposerStrategy::FavorMix),
+                ("naive-dominant", NaiveDecomposerStrategy::FavorDominant),
+                ("naive-blend", NaiveDecomposerStrategy::TetraBlend(2)),
+                (
+                    "naive-closest",
+                    NaiveDecomposerStrategy::FavorClosestReconstruction,
+                ),
+            ] {
+                let naive = NaiveDecomposer::new(palette).map(|d| d.with_strategy(strategy));
+                decomposers.extend(naive.map(|d| (name, alloc::boxed::Box::new(d) as _)));
+            }
+        }
+        for (name, strategy) in [
+            (
+                "octahedron-closest",
+                OctahedronDecomposerAxisStrategy::Closest,
+            ),
+            (
+                "octahedron-furthest",
+                OctahedronDecomposerAxisStrategy::Furthest,
+            ),
+            (
+                "octahedron-average",
+                OctahedronDecomposerAxisStrategy::Average,
+            ),
+            (
+                "octahedron-blended",
+                OctahedronDecomposerAxisStrategy::Blended,
+            ),
+        ] {
+            let octahedron = OctahedronDecomposer::new(palette).map(|d| d.with_strategy(strategy));
+            decomposers.extend(octahedron.map(|d| (name, alloc::boxed::Box::new(d) as _)));
+        }
+        if let Some(approx) = ApproxDecomposer::new(palette) {
+            decomposers.push(("approx", alloc::boxed::Box::new(approx)));
+        }
+        if let Some(grid) = GridDecomposer::new(palette) {
+            decomposers.push(("grid", alloc::boxed::Box::new(grid)));
+        }
+        let mut levels: Vec<f32> = palette.iter().map(|p| p.coords.sum() / 3.0).collect();
+        levels.sort_by(f32::total_cmp);
+        let mut gray: Vec<(&'static str, DynGrayDecomposer)> = Vec::new();
+        if let Some(spread) = PureSpreadGrayDecomposer::new(levels.clone()) {
+            gray.push((
+                "gray-pure-spread",
+                alloc::boxed::Box::new(spread.with_spread_ratio(0.5)),
+            ));
+        }
+        if let Some(blend) = OffsetBlendGrayDecomposer::new(levels) {
+            gray.push((
+                "gray-offset-blend",
+                alloc::boxed::Box::new(blend.with_distance(0.1)),
+            ));
+        }
+        Self {
+            line: first(2).and_then(|p| LineProjector::new([p[0], p[1]])),
+            triangle: first(3).and_then(|p| ClippingTriangleProjector::new([p[0], p[1], p[2]])),
+            tetrahedron: first(4).and_then(|p| TetrahedronProjector::new([p[0], p[1], p[2], p[3]])),
+            octahedron: first(6)
+                .and_then(|p| OctahedronProjector::new(core::array::from_fn(|i| p[i]))),
+            decomposers,
+            gray,
+        }
+    }
+
+    fn check(&self, queries: &[Point3<f32>]) -> Result<(), Failure> {
+        let mut weights = vec![
+            0.0;
+            self.decomposers
+                .first()
+                .map_or(0, |(_, d)| d.palette_size())
+        ];
+        for query in queries {
+            let fail = |subject, problem| Failure {
+                subject,
+                problem,
+                query: (*query).into(),
+            };
+            let mut results: Vec<(&'static str, Option<Problem>)> = Vec::new();
+            if let Some(line) = &self.line {
+                results.push(("line", problem(line.project(query).as_slice(), false)));
+                let (w, _) = line.clipping_project(query);
+                results.push(("line-clipping", problem(w.as_slice(), true)));
+            }
+            if let Some(triangle) = &self.triangle {
+                results.push((
+                    "triangle",
+                    problem(triangle.project(query).0.as_slice(), false),
+                ));
+                let (w, _, _) = triangle.clipping_project(query);
+                results.push(("triangle-clipping", problem(w.as_slice(), true)));
+            }
+            if let Some(tetrahedron) = &self.tetrahedron {
+                let w = tetrahedron.project(query);
+                results.push(("tetrahedron", problem(w.as_slice(), false)));
+            }
+            if let Some(octahedron) = &self.octahedron {
+                let (w, _) = octahedron.project(query);
+                results.push(("octahedron", problem(w.as_slice(), true)));
+            }
+            for (name, decomposer) in &self.decomposers {
+                weights.resize(decomposer.palette_size(), 0.0);
+                decomposer.decompose_into(query, &mut weights);
+                results.push((name, problem(&weights, true)));
+            }
+            for (name, decomposer) in &self.gray {
+                weights.resize(decomposer.palette_size(), 0.0);
+                decomposer.decompose_into(&(query.coords.sum() / 3.0), &mut weights);
+                results.push((name, problem(&weights, true)));
+            }
+            if let Some((subject, Some(problem))) = results.into_iter().find(|(_, p)| p.is_some()) {
+                return Err(fail(subject, problem));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Xorshift64, so the sweep needs no `rand`.
+    struct Bytes(u64);
+
+    impl Iterator for Bytes {
+        type Item = u8;
+        fn next(&mut self) -> Option<u8> {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            Some((self.0 >> 24) as u8)
+        }
+    }
+
+    #[test]
+    fn random_palettes_and_queries() {
+        let mut bytes = Bytes(0x5EED_1234_ABCD_0001);
+        for case in 0..250 {
+            let data: Vec<u8> = bytes.by_ref().take(1 + 3 * 8 + 3 * 16).collect();
+            if let Err(failure) = check(&data) {
+                panic!("case {case} ({data:?}): {failure}");
+            }
+        }
+    }
+
+    #[test]
+    fn flags_bad_weights() {
+        assert_eq!(problem(&[0.25, 0.75], true), None);
+        assert_eq!(problem(&[-0.5, 1.5], false), None);
+        assert_eq!(problem(&[f32::NAN, 1.0], false), Some(Problem::NonFinite));
+        assert_eq!(problem(&[-0.5, 1.5], true), Some(Problem::OutOfRange));
+        assert_eq!(problem(&[0.5, 0.25], true), Some(Problem::Sum));
+    }
+
+    #[test]
+    fn degenerate_palettes() {
+        let coarse = 0x80;
+        for data in [
+            // Two identical colours, then all queries.
+            &[0, 10, 10, 10, 10, 10, 10, 0, 128, 255][..],
+            // Collinear grays, snapped to quarters.
+            &[
+                coarse | 2,
+                0,
+                0,
+                0,
+                1,
+                1,
+                1,
+                2,
+                2,
+                2,
+                3,
+                3,
+                3,
+                128,
+                128,
+                128,
+                0,
+                255,
+                0,
+            ],
+            // Four coplanar colours (blue is zero everywhere).
+            &[coarse | 2, 0, 0, 0, 4, 0, 0, 0, 4, 0, 4, 4, 0, 200, 30, 90],
+            // Short input: nothing to build.
+            &[6, 1, 2],
+            &[],
+        ] {
+            assert_eq!(check(data), Ok(()), "{data:?}");
+        }
+    }
+}
@@ -6,9 +6,14 @@
 //! [`ImageReader`] (and any [`image::GenericImage`] as both reader and
 //! [`ImageWriter`]) directly — no wrapper required for the in-place case.
 //! When the read side and write side need different concrete types, pair
-//! them with [`crate::dither::ImageCombinedRW`].
+//! them with [`crate::dither::ImageCombinedRW`]. [`DynamicImageIo`] wraps
+//! an arbitrary decoded [`image::DynamicImage`] as float RGB, optionally
+//! compositing its alpha over a background; [`DynamicImageIo::alpha_mask`]
+//! turns the alpha into a [`DiffuseMask`] instead.
 
+use crate::colorspace::{AlphaMode, composite_over};
 use crate::decompose::DecomposerInputColor;
+use crate::dither::barrier::DiffuseMask;
 use crate::dither::image_traits::{ImageReader, ImageSize, ImageWriter};
 use nalgebra::geometry::Point3;
 
@@ -74,3 +79,107 @@ where
         image::GenericImage::put_pixel(self, x as u32, y as u32, pixel)
     }
 }
+
+/// Owned `Rgb32FImage` that can be built from any [`image::DynamicImage`],
+/// for library users who load images with the `image` crate and want to
+/// hand them straight to a ditherer.
+///
+/// ```
+/// use epd_dither::dither::{DynDitherer, ImageCombinedRW};
+/// use epd_dither::image::adapter::DynamicImageIo;
+/// use epd_dither::image::palette_image::{PaletteImage, VerifiedPalette};
+/// use epd_dither::registry::parse_decompose_ditherer;
+/// use image::{DynamicImage, Rgb, RgbImage};
+///
+/// let gradient = RgbImage::from_fn(8, 8, |x, y| Rgb([(x * 32) as u8, (y * 32) as u8, 128]));
+/// let input = DynamicImageIo::new(DynamicImage::ImageRgb8(gradient));
+///
+/// let palette = epd_dither::palette::SPECTRA6.iter().map(|&c| Rgb(c)).collect();
+/// let output = PaletteImage::new(8, 8, VerifiedPalette::new(palette).unwrap());
+/// let mut inout = ImageCombinedRW::new(input, output).unwrap();
+///
+/// let ditherer = parse_decompose_ditherer::<Rgb<f32>, _>(
+///     "octahedron-closest",
+///     "bayer:2",
+///     "spectra6",
+///     "floyd-steinberg",
+/// )
+/// .unwrap();
+/// ditherer.dyn_dither_into(&mut inout);
+/// assert!(inout.writer.verify().is_ok());
+/// ```
+pub struct DynamicImageIo {
+    image: image::Rgb32FImage,
+}
+
+impl DynamicImageIo {
+    /// Convert `image` to 32-bit float RGB. 8- and 16-bit samples convert
+    /// exactly (`v / 255` or `v / 65535`), so smooth 16-bit gradients keep
+    /// every level.
+    pub fn new(image: image::DynamicImage) -> Self {
+        Self {
+            image: image.into_rgb32f(),
+        }
+    }
+
+    /// [`new`](Self::new), but an image with an alpha channel is
+    /// composited over the opaque sRGB `background` (see
+    /// [`composite_over`]) instead of having its alpha dropped.
+    pub fn composited(image: image::DynamicImage, background: [f32; 3], mode: AlphaMode) -> Self {
+        if !image.color().has_alpha() {
+            return Self::new(image);
+        }
+        let rgba = image.into_rgba32f();
+        Self {
+            image: image::Rgb32FImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                image::Rgb(composite_over(rgba.get_pixel(x, y).0, background, mode))
+            }),
+        }
+    }
+
+    /// Mask leaving out the pixels of `image` less than half opaque, or
+    /// `None` if it has no alpha channel.
+    pub fn alpha_mask(image: &image::DynamicImage) -> Option<DiffuseMask> {
+        if !image.color().has_alpha() {
+            return None;
+        }
+        let rgba = image.to_rgba32f();
+        let opaque = rgba.pixels().map(|pixel| pixel.0[3] >= 0.5).collect();
+        DiffuseMask::new(rgba.width() as usize, rgba.height() as usize, opaque)
+    }
+
+    pub fn as_image(&self) -> &image::Rgb32FImage {
+        &self.image
+    }
+
+    pub fn into_inner(self) -> image::Rgb32FImage {
+        self.image
+    }
+}
+
+impl From<image::Rgb32FImage> for DynamicImageIo {
+    fn from(image: image::Rgb32FImage) -> Self {
+        Self { image }
+    }
+}
+
+impl ImageSize for DynamicImageIo {
+    fn width(&self) -> usize {
+        self.image.width() as usize
+    }
+    fn height(&self) -> usize {
+        self.image.height() as usize
+    }
+}
+
+impl ImageReader<image::Rgb<f32>> for DynamicImageIo {
+    fn get_pixel(&self, x: usize, y: usize) -> image::Rgb<f32> {
+        *self.image.get_pixel(x as u32, y as u32)
+    }
+}
+
+impl ImageWriter<image::Rgb<f32>> for DynamicImageIo {
+    fn put_pixel(&mut self, x: usize, y: usize, pixel: image::Rgb<f32>) {
+        self.image.put_pixel(x as u32, y as u32, pixel)
+    }
+}
@@ -0,0 +1,81 @@
+//! Debug image of the octahedron decomposer's axis choice.
+//!
+//! [`OctahedronDecomposer`](crate::decompose::octahedron::OctahedronDecomposer)
+//! decomposes each input on one of its three pole-to-pole axes, and with
+//! the `Closest` strategy the choice switches abruptly where two axis
+//! lines are equally near: neighbouring inputs then use different inks,
+//! which can show as a seam in smooth gradients. [`axis_map`] paints each
+//! pixel in [`AXIS_COLORS`] by the axis chosen for it, so those switches
+//! show up as region borders.
+
+use crate::dither::{ImageReader, ImageSize};
+use image::{Rgb, RgbImage};
+
+/// Colour of axis `i` in an [`axis_map`].
+pub const AXIS_COLORS: [Rgb<u8>; 3] = [Rgb([230, 60, 50]), Rgb([60, 180, 75]), Rgb([50, 90, 230])];
+
+/// Colour of pixels no single axis was chosen for, as with the `Average`
+/// strategy.
+pub const NO_AXIS_COLOR: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// Paint every pixel of `input` by `axis`, its chosen axis index (e.g.
+/// from [`octahedron_axis_for`](crate::registry::octahedron_axis_for)):
+/// [`AXIS_COLORS`] for indices below 3, [`NO_AXIS_COLOR`] otherwise.
+pub fn axis_map<I, P, F>(input: &I, axis: F) -> RgbImage
+where
+    I: ImageSize + ImageReader<P>,
+    F: Fn(&P) -> Option<usize>,
+{
+    let (width, height) = (input.width(), input.height());
+    RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        axis(&input.get_pixel(x as usize, y as usize))
+            .and_then(|axis| AXIS_COLORS.get(axis).copied())
+            .unwrap_or(NO_AXIS_COLOR)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{FactoryOptions, octahedron_axis_for};
+    use alloc::vec::Vec;
+    use image::Rgb32FImage;
+
+    #[test]
+    fn one_colour_per_axis() {
+        let input = Rgb32FImage::from_fn(64, 64, |x, y| {
+            Rgb([x as f32 / 63.0, y as f32 / 63.0, (x + y) as f32 / 126.0])
+        });
+        let axis = octahedron_axis_for::<Rgb<f32>, _>(
+            "octahedron-closest".parse().unwrap(),
+            &crate::palette::SPECTRA6,
+            &FactoryOptions::default(),
+        )
+        .unwrap();
+        let map = axis_map(&input, axis);
+        let mut colours: Vec<Rgb<u8>> = map.pixels().copied().collect();
+        colours.sort_by_key(|c| c.0);
+        colours.dedup();
+        assert!(colours.len() > 1 && colours.len() <= 3, "{colours:?}");
+        assert!(colours.iter().all(|c| AXIS_COLORS.contains(c)));
+        let average = octahedron_axis_for::<Rgb<f32>, _>(
+            "octahedron-average".parse().unwrap(),
+            &crate::palette::SPECTRA6,
+            &FactoryOptions::default(),
+        )
+        .unwrap();
+        assert!(
+            axis_map(&input, average)
+                .pixels()
+                .all(|&c| c == NO_AXIS_COLOR)
+        );
+        assert!(
+            octahedron_axis_for::<Rgb<f32>, _>(
+                "naive-mix".parse().unwrap(),
+                &crate::palette::SPECTRA6,
+                &FactoryOptions::default(),
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Tone-preserving brightness match: re-dither with a small input gain so
+//! the output's mean luminance matches the input's.
+//!
+//! Clipping to the palette's gamut shifts mean brightness whenever part of
+//! the image lies outside it: with a palette whose darkest gray is well
+//! above black, every shadow pixel comes out lighter while the highlights
+//! inside the gamut come out right, so the whole picture drifts brighter.
+//! [`match_brightness`] measures that drift on a first dither and scales
+//! the input by a gain (clamped back into `[0, 1]`) to compensate, then
+//! dithers again. Only in-gamut regions respond to the gain, so the
+//! response isn't proportional; after the first correction, later ones
+//! are secant steps through the last two `(gain, luma)` samples.
+//!
+//! Luma is the BT.709-on-sRGB
+//! [`brightness`](crate::decompose::DecomposerInputColor::brightness) the
+//! grayscale decomposers use, averaged over all pixels. The output is
+//! measured through the palette the dither modelled, not whatever palette
+//! the PNG ends up labelled with.
+
+use crate::decompose::DecomposerInputColor;
+use crate::dither::ImageReader;
+use crate::image::palette_image::PaletteImage;
+use image::{Rgb, Rgb32FImage};
+
+/// Gain corrections [`match_brightness`] makes after the first dither when
+/// callers have no better choice.
+pub const DEFAULT_BRIGHTNESS_ITERATIONS: usize = 2;
+
+/// Result of [`match_brightness`]: the dither whose mean luma came closest
+/// to the target, and how it got there.
+pub struct BrightnessMatch {
+    pub output: PaletteImage,
+    /// Gain applied to the input for `output`; `1.0` if no correction
+    /// helped.
+    pub gain: f32,
+    /// Mean luma of the unmodified input.
+    pub target: f32,
+    /// Mean luma of `output`.
+    pub achieved: f32,
+}
+
+/// Mean BT.709 luma over all pixels; `0.0` for an empty image.
+pub fn mean_luma(image: &Rgb32FImage) -> f32 {
+    let count = image.width() as usize * image.height() as usize;
+    if count == 0 {
+        return 0.0;
+    }
+    let total: f64 = image.pixels().map(|p| p.brightness() as f64).sum();
+    (total / count as f64) as f32
+}
+
+/// Mean BT.709 luma of `image` with each index shown as `palette[index]`.
+/// Indices outside `palette` count as black.
+pub fn mean_palette_luma(image: &PaletteImage, palette: &[Rgb<u8>]) -> f32 {
+    let count = image.width as usize * image.height as usize;
+    if count == 0 {
+        return 0.0;
+    }
+    let mut total = 0.0f64;
+    for y in 0..image.height as usize {
+        for x in 0..image.width as usize {
+            let index: usize = ImageReader::get_pixel(image, x, y);
+            total += palette.get(index).map_or(0.0, |c| c.brightness() as f64);
+        }
+    }
+    (total / count as f64) as f32
+}
+
+/// `image` with every channel multiplied by `gain` and clamped to `[0, 1]`.
+pub fn scale_brightness(image: &Rgb32FImage, gain: f32) -> Rgb32FImage {
+    let mut scaled = image.clone();
+    for pixel in scaled.pixels_mut() {
+        pixel.0 = pixel.0.map(|c| (c * gain).clamp(0.0, 1.0));
+    }
+    scaled
+}
+
+/// Dither `input` once as is, then up to `iterations` more times with a
+/// corrected gain, and return the pass whose mean luma (through
+/// `palette`) is closest to the input's. `dither` runs one full dither of
+/// the image it's given.
+///
+/// Stops early once a pass hits the target exactly or the measured luma
+/// stops responding to the gain.
+pub fn match_brightness<F>(
+    input: &Rgb32FImage,
+    palette: &[Rgb<u8>],
+    iterations: usize,
+    mut dither: F,
+) -> BrightnessMatch
+where
+    F: FnMut(Rgb32FImage) -> PaletteImage,
+{
+    let target = mean_luma(input);
+    let output = dither(input.clone());
+    let achieved = mean_palette_luma(&output, palette);
+    let mut best = BrightnessMatch {
+        output,
+        gain: 1.0,
+        target,
+        achieved,
+    };
+    let (mut previous, mut last) = (None, (1.0f32, achieved));
+    for _ in 0..iterations {
+        let (gain, luma) = last;
+        if luma == target {
+            break;
+        }
+        let next = match previous {
+            Some((previous_gain, previous_luma)) if luma != previous_luma => {
+                gain + (target - luma) * (gain - previous_gain) / (luma - previous_luma)
+            }
+            Some(_) => break,
+            None if luma > 0.0 => gain * target / luma,
+            None => break,
+        };
+        if !next.is_finite() || next <= 0.0 {
+            break;
+        }
+        let output = dither(scale_brightness(input, next));
+        let achieved = mean_palette_luma(&output, palette);
+        if (achieved - target).abs() < (best.achieved - target).abs() {
+            best = BrightnessMatch {
+                output,
+                gain: next,
+                target,
+                achieved,
+            };
+        }
+        previous = Some(last);
+        last = (next, achieved);
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::diffusion_matrix::NO_DIFFUSE;
+    use crate::dither::{DecomposeStrategy, ImageCombinedRW};
+    use crate::image::palette_image::VerifiedPalette;
+    use crate::noise::NoiseSource;
+    use crate::registry::decompose_ditherer;
+    use alloc::vec;
+
+    #[test]
+    fn gain_pulls_clipped_image_back_to_input_luma() {
+        // Mean 0.3 lies between the grays at 0.2 and 0.8, but the black
+        // half clips up to 0.2, so a plain dither comes out near 0.4.
+        let input =
+            Rgb32FImage::from_fn(64, 64, |x, _| Rgb(if x < 32 { [0.0; 3] } else { [0.6; 3] }));
+        let palette = vec![Rgb([51, 51, 51]), Rgb([204, 204, 204])];
+        let ditherer = decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
+            "gray-offset-blend:0".parse::<DecomposeStrategy>().unwrap(),
+            NoiseSource::Bayer(None),
+            &palette,
+            NO_DIFFUSE,
+        )
+        .unwrap();
+        let verified = VerifiedPalette::new(palette.clone()).unwrap();
+        let dither = |image: Rgb32FImage| {
+            let writer = PaletteImage::new(image.width(), image.height(), verified.clone());
+            let mut inout = ImageCombinedRW::new(image, writer).unwrap();
+            ditherer.dyn_dither_into(&mut inout);
+            inout.writer
+        };
+        let matched = match_brightness(&input, &palette, DEFAULT_BRIGHTNESS_ITERATIONS, dither);
+        let plain = mean_palette_luma(&dither(input.clone()), &palette);
+        assert!((matched.target - 0.3).abs() < 1e-4);
+        assert!((plain - 0.4).abs() < 0.01, "{plain}");
+        assert!(
+            (matched.achieved - matched.target).abs() < 0.01,
+            "{} at gain {}",
+            matched.achieved,
+            matched.gain
+        );
+        assert!(matched.gain < 1.0);
+    }
+}
@@ -3,4 +3,13 @@
 //! [`image`](https://docs.rs/image) crate, plus a palette-indexed PNG sink.
 
 pub mod adapter;
+pub mod axes;
+pub mod brightness;
 pub mod palette_image;
+pub mod passes;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "serde")]
+pub mod sidecar;
+#[cfg(feature = "std")]
+pub mod weights;
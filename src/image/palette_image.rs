@@ -2,6 +2,12 @@
 //! sink and knows how to emit an indexed PNG. Picks the smallest legal
 //! indexed-PNG bit depth (1/2/4/8) for the palette size and packs indices
 //! MSB-first byte-aligned per scanline — exactly what the PNG spec wants.
+//! [`PngFormat::Indexed8`] writes one byte per pixel instead, for readers
+//! that only handle 8-bit indexed PNGs.
+//!
+//! [`to_reterminal_e1002`](PaletteImage::to_reterminal_e1002) skips PNG
+//! altogether and emits the raw frame buffer the reTerminal E1002's
+//! Spectra 6 panel takes, for writing straight to the device.
 //!
 //! Available behind the `image` Cargo feature, which also pulls in `png`.
 
@@ -38,6 +44,179 @@ impl core::fmt::Display for PaletteSizeOutOfRange {
 
 impl core::error::Error for PaletteSizeOutOfRange {}
 
+/// A stored index that doesn't name an entry of the image's palette.
+/// Indexed PNG stores `bit_depth` bits per pixel, so a 6-entry palette at
+/// 4 bpp can hold indices 6..=15 that decoders would reject or render as
+/// garbage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndexOutOfPalette {
+    pub x: usize,
+    pub y: usize,
+    pub index: usize,
+}
+
+impl core::fmt::Display for IndexOutOfPalette {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "pixel ({}, {}) has index {}, which is not in the palette",
+            self.x, self.y, self.index
+        )
+    }
+}
+
+impl core::error::Error for IndexOutOfPalette {}
+
+/// Width and height of the reTerminal E1002 panel, in pixels.
+pub const RETERMINAL_E1002_SIZE: (u32, u32) = (800, 480);
+
+/// Panel colour code for each entry of a palette in driver order (black,
+/// white, yellow, red, blue, green, as for
+/// [`SPECTRA6`](crate::palette::SPECTRA6)). The Spectra 6 controller
+/// skips code 4.
+pub const RETERMINAL_E1002_CODES: [u8; 6] = [0x0, 0x1, 0x2, 0x3, 0x5, 0x6];
+
+/// Why an image can't be sent to the reTerminal E1002 as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReTerminalE1002Error {
+    /// The image isn't [`RETERMINAL_E1002_SIZE`].
+    Size { width: u32, height: u32 },
+    /// The palette doesn't have the panel's six entries.
+    PaletteSize(usize),
+}
+
+impl core::fmt::Display for ReTerminalE1002Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (panel_width, panel_height) = RETERMINAL_E1002_SIZE;
+        match self {
+            Self::Size { width, height } => write!(
+                f,
+                "image is {width}x{height}, the reTerminal E1002 needs {panel_width}x{panel_height}"
+            ),
+            Self::PaletteSize(size) => write!(
+                f,
+                "output palette has {size} entries, the reTerminal E1002 needs 6 in driver order"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for ReTerminalE1002Error {}
+
+/// Layout of the indexed PNG [`PaletteImage::encode`] writes. Both carry
+/// the palette in `PLTE` and store each pixel's index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PngFormat {
+    /// The palette's [`bit_depth`](VerifiedPalette::bit_depth), packed as
+    /// stored.
+    #[default]
+    Packed,
+    /// 8 bits per pixel regardless of palette size.
+    Indexed8,
+}
+
+impl PngFormat {
+    pub const LONG_HELP: &'static str = concat!(
+        "Output PNG layout.\n\n",
+        "Accepted values:\n",
+        " packed-png   Indexed, smallest bit depth for the palette (default)\n",
+        " indexed-png  Indexed, 8 bits per pixel\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidPngFormat;
+
+impl core::fmt::Display for InvalidPngFormat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid PNG format name")
+    }
+}
+
+impl core::error::Error for InvalidPngFormat {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for PngFormat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Packed => "packed-png",
+            Self::Indexed8 => "indexed-png",
+        })
+    }
+}
+
+impl core::str::FromStr for PngFormat {
+    type Err = InvalidPngFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "packed-png" => Ok(Self::Packed),
+            "indexed-png" => Ok(Self::Indexed8),
+            _ => Err(InvalidPngFormat),
+        }
+    }
+}
+
+/// What to write a [`PaletteImage`] as: an indexed PNG, or the raw frame
+/// buffer of a panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png(PngFormat),
+    /// [`PaletteImage::to_reterminal_e1002`].
+    ReTerminalE1002,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Png(PngFormat::default())
+    }
+}
+
+impl OutputFormat {
+    pub const LONG_HELP: &'static str = concat!(
+        "Output file layout.\n\n",
+        "Accepted values:\n",
+        " packed-png        Indexed PNG, smallest bit depth for the palette\n",
+        "                   (default)\n",
+        " indexed-png       Indexed PNG, 8 bits per pixel\n",
+        " reterminal-e1002  Raw 800x480 frame buffer for the reTerminal\n",
+        "                   E1002, 4 bits per pixel; needs a 6-colour output\n",
+        "                   palette in driver order (K, W, Y, R, B, G)\n",
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidOutputFormat;
+
+impl core::fmt::Display for InvalidOutputFormat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid output format name")
+    }
+}
+
+impl core::error::Error for InvalidOutputFormat {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Png(format) => format.fmt(f),
+            Self::ReTerminalE1002 => f.write_str("reterminal-e1002"),
+        }
+    }
+}
+
+impl core::str::FromStr for OutputFormat {
+    type Err = InvalidOutputFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reterminal-e1002" => Ok(Self::ReTerminalE1002),
+            _ => s.parse().map(Self::Png).map_err(|_| InvalidOutputFormat),
+        }
+    }
+}
+
 /// A palette whose size has been checked to fit indexed-PNG's 1/2/4/8-bit
 /// layouts (1..=256 entries). Construct once via [`VerifiedPalette::new`];
 /// [`PaletteImage::new`] then takes one infallibly.
@@ -82,18 +261,93 @@ impl PaletteImage {
         }
     }
 
+    /// Check that every stored index names a palette entry. Returns the
+    /// first offending pixel in raster order.
+    pub fn verify(&self) -> Result<(), IndexOutOfPalette> {
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                let index = self.get_pixel(x, y);
+                if index >= self.palette.palette.len() {
+                    return Err(IndexOutOfPalette { x, y, index });
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        self.to_png_with_text(&[])
+    }
+
+    /// [`to_png`](Self::to_png) plus one UTF-8 (`iTXt`) text chunk per
+    /// `(keyword, text)` pair, e.g. a
+    /// [`DitherConfig`](crate::config::DitherConfig) under
+    /// [`CONFIG_PNG_KEYWORD`](crate::config::CONFIG_PNG_KEYWORD).
+    pub fn to_png_with_text(&self, text: &[(&str, &str)]) -> Result<Vec<u8>, png::EncodingError> {
+        self.encode(PngFormat::Packed, text)
+    }
+
+    /// [`to_png_with_text`](Self::to_png_with_text) in the given layout.
+    pub fn encode(
+        &self,
+        format: PngFormat,
+        text: &[(&str, &str)],
+    ) -> Result<Vec<u8>, png::EncodingError> {
+        let unpacked: Vec<u8>;
+        let (depth, data) = match format {
+            PngFormat::Packed => (self.palette.bit_depth, &self.data),
+            PngFormat::Indexed8 => {
+                unpacked = (0..self.height as usize)
+                    .flat_map(|y| (0..self.width as usize).map(move |x| (x, y)))
+                    .map(|(x, y)| self.get_pixel(x, y) as u8)
+                    .collect();
+                (BitDepth::Eight, &unpacked)
+            }
+        };
         let mut png_bytes: Vec<u8> = Vec::new();
         let mut encoder = png::Encoder::new(&mut png_bytes, self.width, self.height);
         encoder.set_color(png::ColorType::Indexed);
-        encoder.set_depth(self.palette.bit_depth);
+        encoder.set_depth(depth);
+        for (keyword, text) in text {
+            encoder.add_itxt_chunk((*keyword).into(), (*text).into())?;
+        }
         let palette_bytes: Vec<u8> = self.palette.palette.iter().flat_map(|rgb| rgb.0).collect();
         encoder.set_palette(palette_bytes);
         let mut writer = encoder.write_header()?;
-        writer.write_image_data(&self.data)?;
+        writer.write_image_data(data)?;
         drop(writer);
         Ok(png_bytes)
     }
+
+    /// The reTerminal E1002 frame buffer: rows top to bottom, two pixels
+    /// per byte with the left one in the high nibble, each pixel's index
+    /// mapped through [`RETERMINAL_E1002_CODES`]. 192000 bytes for the
+    /// 800×480 panel. The palette must list the panel's inks in driver
+    /// order; only its size is checked. Indices outside it (see
+    /// [`verify`](Self::verify)) come out white.
+    pub fn to_reterminal_e1002(&self) -> Result<Vec<u8>, ReTerminalE1002Error> {
+        if (self.width, self.height) != RETERMINAL_E1002_SIZE {
+            return Err(ReTerminalE1002Error::Size {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if self.palette.palette.len() != RETERMINAL_E1002_CODES.len() {
+            return Err(ReTerminalE1002Error::PaletteSize(
+                self.palette.palette.len(),
+            ));
+        }
+        let code = |x, y| {
+            RETERMINAL_E1002_CODES
+                .get(self.get_pixel(x, y))
+                .copied()
+                .unwrap_or(RETERMINAL_E1002_CODES[1])
+        };
+        Ok((0..self.height as usize)
+            .flat_map(|y| (0..self.width as usize).step_by(2).map(move |x| (x, y)))
+            .map(|(x, y)| code(x, y) << 4 | code(x + 1, y))
+            .collect())
+    }
 }
 
 impl ImageSize for PaletteImage {
@@ -194,6 +448,48 @@ mod tests {
         assert_eq!(w.data, vec![0b00_01_10_11, 0b01_00_00_00]);
     }
 
+    #[test]
+    fn reterminal_e1002_buffer() {
+        let (width, height) = RETERMINAL_E1002_SIZE;
+        let mut image = writer(width, height, 6);
+        image.put_pixel(0, 0, 4);
+        image.put_pixel(1, 0, 5);
+        image.put_pixel(2, 0, 1);
+        image.put_pixel(3, 0, 3);
+        image.put_pixel(799, 0, 2);
+        image.put_pixel(0, 1, 1);
+        image.put_pixel(798, 479, 5);
+        image.put_pixel(799, 479, 1);
+        let buffer = image.to_reterminal_e1002().unwrap();
+        assert_eq!(buffer.len(), 192_000);
+        // Blue and green, then white and red, in the first row.
+        assert_eq!(buffer[..2], [0x56, 0x13]);
+        assert_eq!(buffer[399], 0x02);
+        // Rows follow each other without padding.
+        assert_eq!(buffer[400], 0x10);
+        assert_eq!(buffer[191_999], 0x61);
+        assert!(buffer[401..191_999].iter().all(|&b| b == 0));
+        assert_eq!(
+            writer(480, 800, 6).to_reterminal_e1002(),
+            Err(ReTerminalE1002Error::Size {
+                width: 480,
+                height: 800
+            })
+        );
+        assert_eq!(
+            writer(width, height, 7).to_reterminal_e1002(),
+            Err(ReTerminalE1002Error::PaletteSize(7))
+        );
+        assert_eq!(
+            "reterminal-e1002".parse(),
+            Ok(OutputFormat::ReTerminalE1002)
+        );
+        assert_eq!(
+            "packed-png".parse(),
+            Ok(OutputFormat::Png(PngFormat::Packed))
+        );
+    }
+
     #[test]
     fn pack_1bit_msb_first() {
         let mut w = writer(9, 1, 2);
@@ -221,17 +517,75 @@ mod tests {
         assert_eq!(w.data, vec![0x00, 0x00]);
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn png_carries_text_chunks() {
+        let image = writer(3, 2, 2);
+        let png_bytes = image.to_png_with_text(&[("note", "héllo\nworld")]).unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(png_bytes));
+        let reader = decoder.read_info().unwrap();
+        let chunks = &reader.info().utf8_text;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].keyword, "note");
+        assert_eq!(chunks[0].get_text().unwrap(), "héllo\nworld");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn indexed_png_round_trips_indices_and_palette() {
+        let palette: Vec<Rgb<u8>> = (0..6).map(|i| Rgb([i * 40, 255 - i * 40, 7])).collect();
+        let mut image = PaletteImage::new(5, 3, VerifiedPalette::new(palette.clone()).unwrap());
+        for y in 0..3 {
+            for x in 0..5 {
+                image.put_pixel(x, y, (x + 2 * y) % 6);
+            }
+        }
+        let png_bytes = image.encode(PngFormat::Indexed8, &[]).unwrap();
+        let mut reader = png::Decoder::new(std::io::Cursor::new(png_bytes))
+            .read_info()
+            .unwrap();
+        let info = reader.info();
+        assert_eq!(info.color_type, png::ColorType::Indexed);
+        assert_eq!(info.bit_depth, BitDepth::Eight);
+        let plte: Vec<u8> = palette.iter().flat_map(|rgb| rgb.0).collect();
+        assert_eq!(info.palette.as_deref(), Some(&plte[..]));
+        let mut data = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut data).unwrap();
+        let expected: Vec<u8> = (0..3)
+            .flat_map(|y| (0..5).map(move |x| ((x + 2 * y) % 6) as u8))
+            .collect();
+        assert_eq!(data, expected);
+        assert_eq!("indexed-png".parse(), Ok(PngFormat::Indexed8));
+        assert_eq!(alloc::format!("{}", PngFormat::Indexed8), "indexed-png");
+    }
+
+    #[test]
+    fn verify_rejects_index_past_palette() {
+        let mut w = writer(3, 2, 6);
+        w.put_pixel(1, 1, 5);
+        assert_eq!(w.verify(), Ok(()));
+        w.put_pixel(2, 1, 6);
+        assert_eq!(
+            w.verify(),
+            Err(IndexOutOfPalette {
+                x: 2,
+                y: 1,
+                index: 6
+            })
+        );
+    }
+
     #[test]
     fn get_pixel_round_trips_writes() {
         let mut w = writer(7, 3, 16);
         for y in 0..3 {
             for x in 0..7 {
-                w.put_pixel(x, y, ((x * y + 1) & 0xF) as usize);
+                w.put_pixel(x, y, (x * y + 1) & 0xF);
             }
         }
         for y in 0..3 {
             for x in 0..7 {
-                assert_eq!(w.get_pixel(x, y), ((x * y + 1) & 0xF) as usize);
+                assert_eq!(w.get_pixel(x, y), (x * y + 1) & 0xF);
             }
         }
     }
@@ -0,0 +1,272 @@
+//! Multi-pass dithering: feed the residual of one dither back into the
+//! next.
+//!
+//! Error diffusion keeps each pixel's error local, but not all of it gets
+//! placed: error pushed past the image edges is lost, the dominant-texture
+//! pick sheds weight, and near colours the palette reproduces poorly the
+//! local tone drifts. [`multi_pass`] measures that drift after a dither as
+//! the difference between the input and the output, both blurred over
+//! a (2·[`RESIDUAL_BLUR_RADIUS`] + 1)² box so the dither pattern itself
+//! averages out. It then dithers again with [`RESIDUAL_GAIN`] times that
+//! residual added to the input. The residual image accumulates over
+//! passes, so each pass corrects what the previous corrected input still
+//! missed.
+//!
+//! Each pass is one full dither plus a blur over the image: `N` passes
+//! take about `N` times as long as one. The gain is small, since error
+//! diffusion already keeps most of the local tone. Feeding the full
+//! residual back, or blurring over a smaller window, does worse than a
+//! single pass, because the dither pattern then leaks into the next
+//! input. One pass is a plain dither.
+//!
+//! [`multi_pass_until`] stops before the cap once the
+//! [`reconstruction_error`] (that same blurred RMS error) is below a
+//! threshold, so images the first pass already gets right don't pay for
+//! the rest.
+
+use crate::dither::ImageReader;
+use crate::image::palette_image::PaletteImage;
+use image::{Rgb, Rgb32FImage};
+use nalgebra::ComplexField;
+
+/// Radius of the box blur [`multi_pass`] measures the residual over.
+pub const RESIDUAL_BLUR_RADIUS: u32 = 4;
+
+/// Share of each pass's blurred residual [`multi_pass`] feeds back.
+pub const RESIDUAL_GAIN: f32 = 0.25;
+
+/// Dither `input`, then `passes - 1` more times with the accumulated
+/// blurred residual (input minus output, the output shown through
+/// `palette`, scaled by [`RESIDUAL_GAIN`]) added to the input and clamped
+/// to `[0, 1]`; see the module
+/// docs. `dither` runs one full dither of the image it's given. Returns
+/// the last pass; `passes` below 1 count as 1.
+pub fn multi_pass<F>(
+    input: &Rgb32FImage,
+    palette: &[Rgb<u8>],
+    passes: usize,
+    dither: F,
+) -> PaletteImage
+where
+    F: FnMut(Rgb32FImage) -> PaletteImage,
+{
+    multi_pass_until(input, palette, passes, None, dither).output
+}
+
+/// Result of [`multi_pass_until`].
+pub struct MultiPass {
+    /// Output of the last pass run.
+    pub output: PaletteImage,
+    /// Number of passes run, between 1 and the cap.
+    pub passes: usize,
+    /// [`reconstruction_error`] of `output`, if a threshold was given.
+    pub error: Option<f32>,
+}
+
+/// [`multi_pass`] that also stops early, once the
+/// [`reconstruction_error`] of a pass drops below `max_error`. With
+/// `None` it runs all `passes`, like [`multi_pass`]. Checking costs
+/// nothing extra before a later pass, which needs the blurred residual
+/// anyway, but one blur after the last.
+pub fn multi_pass_until<F>(
+    input: &Rgb32FImage,
+    palette: &[Rgb<u8>],
+    passes: usize,
+    max_error: Option<f32>,
+    mut dither: F,
+) -> MultiPass
+where
+    F: FnMut(Rgb32FImage) -> PaletteImage,
+{
+    let mut output = dither(input.clone());
+    let mut run = 1;
+    let mut residual = Rgb32FImage::new(input.width(), input.height());
+    loop {
+        if run >= passes && max_error.is_none() {
+            return MultiPass {
+                output,
+                passes: run,
+                error: None,
+            };
+        }
+        let blurred = blurred_residual(input, &output, palette);
+        if let Some(max_error) = max_error {
+            let error = root_mean_square(&blurred);
+            if run >= passes || error < max_error {
+                return MultiPass {
+                    output,
+                    passes: run,
+                    error: Some(error),
+                };
+            }
+        }
+        for (total, step) in residual.pixels_mut().zip(blurred.pixels()) {
+            for (t, s) in total.0.iter_mut().zip(step.0) {
+                *t += RESIDUAL_GAIN * s;
+            }
+        }
+        let mut corrected = input.clone();
+        for (pixel, correction) in corrected.pixels_mut().zip(residual.pixels()) {
+            for (p, c) in pixel.0.iter_mut().zip(correction.0) {
+                *p = (*p + c).clamp(0.0, 1.0);
+            }
+        }
+        output = dither(corrected);
+        run += 1;
+    }
+}
+
+/// Root mean square, over pixels and channels, of the difference between
+/// `input` and `output` shown through `palette`, both blurred as
+/// [`multi_pass`] does: how far the dither's local tone is off, with the
+/// dither pattern itself averaged out. 0 for an exact match, in `[0, 1]`.
+pub fn reconstruction_error(
+    input: &Rgb32FImage,
+    output: &PaletteImage,
+    palette: &[Rgb<u8>],
+) -> f32 {
+    root_mean_square(&blurred_residual(input, output, palette))
+}
+
+/// `input` minus `output` shown through `palette`, blurred over
+/// [`RESIDUAL_BLUR_RADIUS`]. Indices outside `palette` show as black.
+pub(crate) fn blurred_residual(
+    input: &Rgb32FImage,
+    output: &PaletteImage,
+    palette: &[Rgb<u8>],
+) -> Rgb32FImage {
+    let difference = Rgb32FImage::from_fn(input.width(), input.height(), |x, y| {
+        let index: usize = ImageReader::get_pixel(output, x as usize, y as usize);
+        let shown = palette
+            .get(index)
+            .map_or([0.0; 3], |c| c.0.map(|c| c as f32 / 255.0));
+        let source = input.get_pixel(x, y).0;
+        Rgb(core::array::from_fn(|c| source[c] - shown[c]))
+    });
+    box_blur(&difference, RESIDUAL_BLUR_RADIUS)
+}
+
+fn root_mean_square(image: &Rgb32FImage) -> f32 {
+    let samples = image.as_raw();
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = samples.iter().map(|s| s * s).sum();
+    ComplexField::sqrt(sum / samples.len() as f32)
+}
+
+/// Mean over the `(2 * radius + 1)²` window around each pixel, shrunk to
+/// the part inside the image at the edges.
+fn box_blur(image: &Rgb32FImage, radius: u32) -> Rgb32FImage {
+    let (width, height) = image.dimensions();
+    let pass = |image: &Rgb32FImage, horizontal: bool| {
+        Rgb32FImage::from_fn(width, height, |x, y| {
+            let (center, limit) = if horizontal { (x, width) } else { (y, height) };
+            let range = center.saturating_sub(radius)..(center + radius + 1).min(limit);
+            let count = range.len() as f32;
+            let mut sum = [0.0; 3];
+            for i in range {
+                let (sx, sy) = if horizontal { (i, y) } else { (x, i) };
+                for (s, c) in sum.iter_mut().zip(image.get_pixel(sx, sy).0) {
+                    *s += c;
+                }
+            }
+            Rgb(sum.map(|s| s / count))
+        })
+    };
+    pass(&pass(image, true), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+    use crate::dither::{DecomposeStrategy, ImageCombinedRW};
+    use crate::image::palette_image::VerifiedPalette;
+    use crate::noise::NoiseSource;
+    use crate::registry::decompose_ditherer;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn indices(image: &PaletteImage) -> Vec<usize> {
+        let (width, height) = (image.width as usize, image.height as usize);
+        (0..width * height)
+            .map(|i| ImageReader::get_pixel(image, i % width, i / width))
+            .collect()
+    }
+
+    #[test]
+    fn one_pass_is_a_plain_dither() {
+        let input =
+            Rgb32FImage::from_fn(48, 32, |x, y| Rgb([x as f32 / 47.0, y as f32 / 31.0, 0.5]));
+        let palette: Vec<Rgb<u8>> = crate::palette::SPECTRA6.iter().map(|&c| Rgb(c)).collect();
+        let ditherer = decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
+            "naive-mix".parse::<DecomposeStrategy>().unwrap(),
+            NoiseSource::None,
+            &palette,
+            FLOYD_STEINBERG,
+        )
+        .unwrap();
+        let verified = VerifiedPalette::new(palette.clone()).unwrap();
+        let dither = |image: Rgb32FImage| {
+            let writer = PaletteImage::new(image.width(), image.height(), verified.clone());
+            let mut inout = ImageCombinedRW::new(image, writer).unwrap();
+            ditherer.dyn_dither_into(&mut inout);
+            inout.writer
+        };
+        let mut calls = 0;
+        let output = multi_pass(&input, &palette, 1, |image| {
+            calls += 1;
+            dither(image)
+        });
+        assert_eq!(calls, 1);
+        assert_eq!(indices(&output), indices(&dither(input.clone())));
+    }
+
+    #[test]
+    fn later_passes_add_the_accumulated_residual() {
+        let input = Rgb32FImage::from_pixel(8, 8, Rgb([0.25; 3]));
+        let palette = vec![Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+        let verified = VerifiedPalette::new(palette.clone()).unwrap();
+        let mut seen = Vec::new();
+        // Always black: each pass leaves the full 0.25 unplaced, a quarter
+        // of which adds up in the residual.
+        multi_pass(&input, &palette, 3, |image| {
+            seen.push(image.get_pixel(3, 3).0[0]);
+            PaletteImage::new(8, 8, verified.clone())
+        });
+        assert_eq!(seen, [0.25, 0.3125, 0.375]);
+    }
+
+    #[test]
+    fn max_error_stops_easy_images_early() {
+        let palette = vec![Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+        let verified = VerifiedPalette::new(palette.clone()).unwrap();
+        let ditherer = decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
+            "naive-mix".parse::<DecomposeStrategy>().unwrap(),
+            NoiseSource::None,
+            &palette,
+            FLOYD_STEINBERG,
+        )
+        .unwrap();
+        let dither = |image: Rgb32FImage| {
+            let writer = PaletteImage::new(image.width(), image.height(), verified.clone());
+            let mut inout = ImageCombinedRW::new(image, writer).unwrap();
+            ditherer.dyn_dither_into(&mut inout);
+            inout.writer
+        };
+        // Flat white is reproduced exactly by the first pass.
+        let easy = Rgb32FImage::from_pixel(16, 16, Rgb([1.0; 3]));
+        let result = multi_pass_until(&easy, &palette, 5, Some(1e-3), dither);
+        assert_eq!(result.passes, 1);
+        assert_eq!(result.error, Some(0.0));
+        // Saturated red is far outside a black and white gamut: no pass
+        // gets close, so it runs to the cap.
+        let hard = Rgb32FImage::from_pixel(16, 16, Rgb([1.0, 0.0, 0.0]));
+        let result = multi_pass_until(&hard, &palette, 5, Some(1e-3), dither);
+        assert_eq!(result.passes, 5);
+        let error = result.error.unwrap();
+        assert!(error > 1e-3);
+        assert_eq!(error, reconstruction_error(&hard, &result.output, &palette));
+    }
+}
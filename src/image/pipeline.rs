@@ -0,0 +1,454 @@
+//! Streaming PNG-in / indexed-PNG-out batch pipeline.
+//!
+//! [`dither_png_batch`] runs three stages connected by bounded channels:
+//! a decoder thread reads input rows, the calling thread dithers them, and
+//! an encoder thread packs and compresses output rows. Error diffusion is
+//! inherently serial, so the dither stage stays on one thread; what the
+//! pipeline buys is overlapping it with PNG inflate/deflate, within an
+//! image and across consecutive images of a batch.
+//!
+//! Each channel holds at most `buffered_rows` rows, so a slow stage
+//! applies backpressure instead of letting the others buffer whole
+//! images: the decoder blocks once it is that far ahead of the dither,
+//! and the dither once it is that far ahead of the encoder.
+//!
+//! Inputs are normalised to RGB (palette and low bit depths expanded,
+//! alpha dropped) and scaled to `0..1` at their own depth, so 16-bit
+//! samples keep their full precision; this matches what `image`'s
+//! `into_rgb32f` gives, which is exact for both 8- and 16-bit sources.
+//! Interlaced inputs are
+//! decoded whole before their first row is sent, so they still dither
+//! correctly but without decode/dither overlap.
+//!
+//! Available behind the `std` Cargo feature (together with `image`).
+
+use crate::dither::{DynDitherer, ImageReader, ImageSize, ImageWriter};
+use crate::image::palette_image::{PaletteImage, VerifiedPalette};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use image::Rgb;
+use std::io::{BufRead, Seek, Write};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+
+/// Rows buffered per channel when callers have no better choice.
+pub const DEFAULT_PIPELINE_ROWS: usize = 16;
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Decoding(png::DecodingError),
+    Encoding(png::EncodingError),
+}
+
+impl core::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Decoding(e) => write!(f, "decoding input PNG: {e}"),
+            Self::Encoding(e) => write!(f, "encoding output PNG: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Decoding(e) => Some(e),
+            Self::Encoding(e) => Some(e),
+        }
+    }
+}
+
+enum Decoded {
+    Header { width: usize, height: usize },
+    Row(Vec<Rgb<f32>>),
+}
+
+enum Encoded {
+    Header { width: u32, height: u32 },
+    Row(Vec<u8>),
+}
+
+/// The dither stage's view of one image: reads block until the decoder
+/// has delivered the requested row, and every completed output row is
+/// handed to the encoder as soon as the dither moves past it.
+///
+/// Rows must be *written* in non-decreasing order (as
+/// [`diffuse_dither`](crate::dither::diffuse::diffuse_dither) does, in
+/// either serpentine direction); a write to an earlier row than the
+/// current one is dropped. Reads may go anywhere, since decoded rows are
+/// kept until the image is done. If the decoder fails, reads of rows it
+/// never delivered return black.
+pub struct StreamingImage {
+    width: usize,
+    height: usize,
+    input: RefCell<StreamingInput>,
+    row: PaletteImage,
+    row_y: usize,
+    output: SyncSender<Encoded>,
+    encoder_alive: bool,
+}
+
+struct StreamingInput {
+    receiver: Receiver<Decoded>,
+    rows: Vec<Vec<Rgb<f32>>>,
+    exhausted: bool,
+}
+
+impl StreamingInput {
+    fn fill_to(&mut self, rows: usize) {
+        while !self.exhausted && self.rows.len() < rows {
+            match self.receiver.recv() {
+                Ok(Decoded::Row(row)) => self.rows.push(row),
+                Ok(Decoded::Header { .. }) | Err(_) => self.exhausted = true,
+            }
+        }
+    }
+}
+
+impl StreamingImage {
+    /// Send the current row and start row `y`, sending blank rows for any
+    /// the dither skipped in between.
+    fn advance_to(&mut self, y: usize) {
+        while self.row_y < y.min(self.height) {
+            let packed = core::mem::replace(&mut self.row.data, vec![0; self.row.bytes_per_row]);
+            self.encoder_alive &= self.output.send(Encoded::Row(packed)).is_ok();
+            self.row_y += 1;
+        }
+    }
+
+    /// Flush the remaining output rows and consume any input rows the
+    /// dither didn't read, leaving both channels at the next image.
+    fn finish(mut self) -> (Receiver<Decoded>, SyncSender<Encoded>, bool) {
+        self.advance_to(self.height);
+        let mut input = self.input.into_inner();
+        input.fill_to(self.height);
+        (
+            input.receiver,
+            self.output,
+            self.encoder_alive && !input.exhausted,
+        )
+    }
+}
+
+impl ImageSize for StreamingImage {
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl ImageReader<Rgb<f32>> for StreamingImage {
+    fn get_pixel(&self, x: usize, y: usize) -> Rgb<f32> {
+        let mut input = self.input.borrow_mut();
+        input.fill_to(y.saturating_add(1).min(self.height));
+        input
+            .rows
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(Rgb([0.0; 3]))
+    }
+}
+
+impl ImageWriter<usize> for StreamingImage {
+    fn put_pixel(&mut self, x: usize, y: usize, pixel: usize) {
+        if y < self.row_y || y >= self.height {
+            return;
+        }
+        self.advance_to(y);
+        self.row.put_pixel(x, 0, pixel);
+    }
+}
+
+/// Join a pipeline thread, re-raising its panic on the caller.
+fn join<T>(handle: std::thread::ScopedJoinHandle<'_, T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn decode_one<R: BufRead + Seek>(
+    input: R,
+    rows: &SyncSender<Decoded>,
+) -> Result<bool, png::DecodingError> {
+    let mut decoder = png::Decoder::new(input);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+    let (width, height) = reader.info().size();
+    let (width, height) = (width as usize, height as usize);
+    let interlaced = reader.info().interlaced;
+    let (color_type, depth) = reader.output_color_type();
+    let channels = color_type.samples();
+    let wide = depth == png::BitDepth::Sixteen;
+    let to_rgb = |data: &[u8]| -> Vec<Rgb<f32>> {
+        data.chunks_exact(if wide { 2 * channels } else { channels })
+            .take(width)
+            .map(|px| {
+                // 16-bit samples are big-endian.
+                let sample = |i: usize| {
+                    if wide {
+                        u16::from_be_bytes([px[2 * i], px[2 * i + 1]]) as f32 / 65535.0
+                    } else {
+                        px[i] as f32 / 255.0
+                    }
+                };
+                if channels >= 3 {
+                    Rgb([sample(0), sample(1), sample(2)])
+                } else {
+                    Rgb([sample(0); 3])
+                }
+            })
+            .collect()
+    };
+    if rows.send(Decoded::Header { width, height }).is_err() {
+        return Ok(false);
+    }
+    if interlaced {
+        let mut frame = vec![0; reader.output_buffer_size().unwrap_or(0)];
+        let info = reader.next_frame(&mut frame)?;
+        for row in frame.chunks_exact(info.line_size.max(1)).take(height) {
+            if rows.send(Decoded::Row(to_rgb(row))).is_err() {
+                return Ok(false);
+            }
+        }
+    } else {
+        while let Some(row) = reader.next_row()? {
+            if rows.send(Decoded::Row(to_rgb(row.data()))).is_err() {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn encode_one<W: Write>(
+    output: W,
+    rows: &Receiver<Encoded>,
+    palette: &VerifiedPalette,
+) -> Result<bool, png::EncodingError> {
+    let Ok(Encoded::Header { width, height }) = rows.recv() else {
+        return Ok(false);
+    };
+    let mut encoder = png::Encoder::new(output, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(palette.bit_depth);
+    encoder.set_palette(
+        palette
+            .palette
+            .iter()
+            .flat_map(|rgb| rgb.0)
+            .collect::<Vec<u8>>(),
+    );
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    for _ in 0..height {
+        let Ok(Encoded::Row(row)) = rows.recv() else {
+            return Ok(false);
+        };
+        stream.write_all(&row)?;
+    }
+    stream.finish()?;
+    writer.finish()?;
+    Ok(true)
+}
+
+/// Dither every input PNG of `jobs` with `ditherer` and write it to the
+/// paired output as an indexed PNG over `palette`, pipelining decode,
+/// dither and encode as described in the module docs. `buffered_rows`
+/// (clamped to at least 1) bounds each channel.
+///
+/// Stops at the first failing job and returns its error; outputs of
+/// earlier jobs are complete, later ones are left untouched.
+pub fn dither_png_batch<R, W, D>(
+    jobs: impl IntoIterator<Item = (R, W)>,
+    ditherer: &D,
+    palette: &VerifiedPalette,
+    buffered_rows: usize,
+) -> Result<(), PipelineError>
+where
+    R: BufRead + Seek + Send,
+    W: Write + Send,
+    D: DynDitherer<StreamingImage> + ?Sized,
+{
+    let (inputs, outputs): (Vec<R>, Vec<W>) = jobs.into_iter().unzip();
+    let buffered_rows = buffered_rows.max(1);
+    let (decoded_tx, decoded_rx) = sync_channel(buffered_rows);
+    let (encoded_tx, encoded_rx) = sync_channel(buffered_rows);
+    let (decoded, encoded) = std::thread::scope(|scope| {
+        let decoder = scope.spawn(move || {
+            for input in inputs {
+                if !decode_one(input, &decoded_tx)? {
+                    break;
+                }
+            }
+            Ok::<_, png::DecodingError>(())
+        });
+        let encoder = scope.spawn(move || {
+            for output in outputs {
+                if !encode_one(output, &encoded_rx, palette)? {
+                    break;
+                }
+            }
+            Ok::<_, png::EncodingError>(())
+        });
+        let (mut decoded_rx, mut encoded_tx) = (decoded_rx, encoded_tx);
+        while let Ok(Decoded::Header { width, height }) = decoded_rx.recv() {
+            let header = Encoded::Header {
+                width: width as u32,
+                height: height as u32,
+            };
+            if encoded_tx.send(header).is_err() {
+                break;
+            }
+            let mut image = StreamingImage {
+                width,
+                height,
+                input: RefCell::new(StreamingInput {
+                    receiver: decoded_rx,
+                    rows: Vec::with_capacity(height),
+                    exhausted: false,
+                }),
+                row: PaletteImage::new(width as u32, 1, palette.clone()),
+                row_y: 0,
+                output: encoded_tx,
+                encoder_alive: true,
+            };
+            ditherer.dyn_dither_into(&mut image);
+            let (rx, tx, ok) = image.finish();
+            (decoded_rx, encoded_tx) = (rx, tx);
+            if !ok {
+                break;
+            }
+        }
+        // Hang up both channels so a stage blocked on the other end
+        // returns instead of waiting for rows that will never come.
+        drop((decoded_rx, encoded_tx));
+        (join(decoder), join(encoder))
+    });
+    decoded.map_err(PipelineError::Decoding)?;
+    encoded.map_err(PipelineError::Encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+    use crate::dither::{DecomposeStrategy, ImageCombinedRW};
+    use crate::noise::NoiseSource;
+    use crate::palette::SPECTRA6;
+    use crate::registry::decompose_ditherer;
+    use alloc::boxed::Box;
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn gradient(width: u32, height: u32, seed: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            Rgb([
+                (x * 255 / width) as u8,
+                (y * 255 / height) as u8,
+                ((x + y + seed * 40) % 256) as u8,
+            ])
+        })
+    }
+
+    fn encode(image: &RgbImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn palette() -> VerifiedPalette {
+        VerifiedPalette::new(SPECTRA6.iter().map(|&c| Rgb(c)).collect()).unwrap()
+    }
+
+    fn ditherer<T>() -> Box<dyn DynDitherer<T> + Send + Sync>
+    where
+        T: ImageSize + ImageReader<Rgb<f32>> + ImageWriter<usize> + 'static,
+    {
+        decompose_ditherer::<Rgb<f32>, Rgb<u8>, T>(
+            "octahedron-closest".parse::<DecomposeStrategy>().unwrap(),
+            NoiseSource::InterleavedGradient,
+            &palette().palette,
+            FLOYD_STEINBERG,
+        )
+        .unwrap()
+    }
+
+    fn serial(image: &RgbImage) -> RgbImage {
+        let input = image::DynamicImage::ImageRgb8(image.clone()).into_rgb32f();
+        let writer = PaletteImage::new(image.width(), image.height(), palette());
+        let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+        ditherer().dyn_dither_into(&mut inout);
+        decode(&inout.writer.to_png().unwrap())
+    }
+
+    fn decode(png: &[u8]) -> RgbImage {
+        image::load_from_memory(png).unwrap().to_rgb8()
+    }
+
+    #[test]
+    fn batch_matches_serial_dither() {
+        let images = [gradient(37, 21, 0), gradient(16, 40, 1), gradient(50, 3, 2)];
+        let ditherer = ditherer();
+        for buffered_rows in [1, DEFAULT_PIPELINE_ROWS] {
+            let mut outputs = vec![Vec::new(); images.len()];
+            let jobs = images
+                .iter()
+                .map(|image| Cursor::new(encode(image)))
+                .zip(outputs.iter_mut());
+            dither_png_batch(jobs, ditherer.as_ref(), &palette(), buffered_rows).unwrap();
+            for (image, output) in images.iter().zip(&outputs) {
+                assert_eq!(decode(output), serial(image));
+            }
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_input_keeps_precision() {
+        use crate::image::adapter::DynamicImageIo;
+        // 16 distinct 16-bit levels per channel between two 8-bit codes.
+        let wide = image::ImageBuffer::<Rgb<u16>, _>::from_fn(64, 8, |x, y| {
+            Rgb([
+                0x8000 + x as u16 * 4,
+                0x4000 + y as u16,
+                0x8000 - x as u16 * 3,
+            ])
+        });
+        let mut png = Vec::new();
+        wide.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let input = DynamicImageIo::new(image::load_from_memory(&png).unwrap()).into_inner();
+        for (loaded, original) in input.pixels().zip(wide.pixels()) {
+            assert_eq!(loaded.0, original.0.map(|c| c as f32 / 65535.0));
+        }
+
+        let writer = PaletteImage::new(64, 8, palette());
+        let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+        ditherer().dyn_dither_into(&mut inout);
+        let serial = decode(&inout.writer.to_png().unwrap());
+        let mut output = Vec::new();
+        let jobs = [(Cursor::new(png), &mut output)];
+        dither_png_batch(jobs, ditherer().as_ref(), &palette(), 4).unwrap();
+        assert_eq!(decode(&output), serial);
+    }
+
+    #[test]
+    fn stops_at_corrupt_input() {
+        let ditherer = ditherer();
+        let inputs = [
+            encode(&gradient(8, 8, 0)),
+            b"not a png".to_vec(),
+            encode(&gradient(8, 8, 1)),
+        ];
+        let mut outputs = vec![Vec::new(); inputs.len()];
+        let jobs = inputs.iter().map(Cursor::new).zip(outputs.iter_mut());
+        let result = dither_png_batch(jobs, ditherer.as_ref(), &palette(), 2);
+        assert!(matches!(result, Err(PipelineError::Decoding(_))));
+        assert_eq!(decode(&outputs[0]), serial(&gradient(8, 8, 0)));
+        assert!(outputs[2].is_empty());
+    }
+}
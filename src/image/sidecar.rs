@@ -0,0 +1,301 @@
+//! Summary of a finished dither, written as JSON next to the image.
+//!
+//! A [`Sidecar`] records what a dither produced and how it was made, for
+//! tooling that tracks outputs over time (e.g. comparing quality across
+//! revisions without re-decoding every PNG). It (de)serializes with serde;
+//! its `Display` form is the JSON object, pretty-printed, and `FromStr`
+//! reads it back:
+//!
+//! ```text
+//! {
+//!   "schema": 1,
+//!   "width": 800,
+//!   "height": 480,
+//!   "mean_error": 0.0123,
+//!   "max_error": 0.0871,
+//!   "usage": [61440, 52013, 40110, 12877, 6071, 11489],
+//!   "gamut_coverage": 0.9634,
+//!   "config": {
+//!     "palette": [[25, 30, 33], ...],
+//!     "strategy": "octahedron-closest",
+//!     ...
+//!   }
+//! }
+//! ```
+//!
+//! - `schema`: [`SIDECAR_SCHEMA`]. Fields are only ever added under the
+//!   same number; renaming or reinterpreting one bumps it.
+//! - `width`, `height`: output size in pixels.
+//! - `mean_error`, `max_error`: mean and maximum over pixels of the
+//!   Euclidean RGB distance, in `[0, 1]` per channel, between the input and
+//!   the output shown through the dither palette, both blurred as for
+//!   [`reconstruction_error`](crate::image::passes::reconstruction_error)
+//!   so the dither pattern itself averages out.
+//! - `usage`: pixel count of each dither-palette entry, as
+//!   [`palette_usage`].
+//! - `gamut_coverage`: share of input pixels the decomposer reproduces
+//!   exactly, see [`gamut_coverage`].
+//! - `config`: the full [`DitherConfig`], in its serde form.
+//!
+//! Numbers that aren't finite are written as `null`. Reading ignores keys
+//! it doesn't know, so older readers accept newer files of the same schema.
+
+use crate::config::DitherConfig;
+use crate::decompose::{Decomposer, DecomposerInputColor, assert_valid};
+use crate::dither::ImageReader;
+use crate::dither::usage::palette_usage;
+use crate::image::palette_image::PaletteImage;
+use crate::image::passes::blurred_residual;
+use alloc::vec;
+use alloc::vec::Vec;
+use image::{Rgb, Rgb32FImage};
+use nalgebra::ComplexField;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Version of the sidecar layout; see the module docs.
+pub const SIDECAR_SCHEMA: u32 = 1;
+
+/// How far, in the decomposer's input space, a decomposition may mix back
+/// from its input and still count towards [`gamut_coverage`]: one 8-bit
+/// step.
+pub const GAMUT_TOLERANCE: f32 = 1.0 / 255.0;
+
+/// Share of `input`'s pixels that `decomposer` decomposes into weights
+/// over `palette` mixing back (additively) to the pixel within
+/// [`GAMUT_TOLERANCE`]: those inside the reachable gamut. Non-finite
+/// pixels count as outside. 1 for an empty image.
+pub fn gamut_coverage<D>(input: &Rgb32FImage, decomposer: &D, palette: &[Rgb<u8>]) -> f32
+where
+    D: Decomposer<f32, Input = Rgb<f32>> + ?Sized,
+{
+    let pixels = input.width() as usize * input.height() as usize;
+    if pixels == 0 {
+        return 1.0;
+    }
+    let points: Vec<_> = palette.iter().map(|c| c.to_point()).collect();
+    let mut weights = vec![0.0; decomposer.palette_size()];
+    let inside = input
+        .pixels()
+        .filter(|pixel| {
+            if !pixel.0.iter().all(|c| c.is_finite()) {
+                return false;
+            }
+            decomposer.decompose_into(pixel, &mut weights);
+            assert_valid(&weights, &points, Some(&pixel.to_point()), GAMUT_TOLERANCE).is_ok()
+        })
+        .count();
+    inside as f32 / pixels as f32
+}
+
+/// What a dither produced and how; see the module docs for the JSON form.
+///
+/// The serde form leaves out `schema`; `Display` and `FromStr` add and
+/// check it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub width: u32,
+    pub height: u32,
+    #[serde(deserialize_with = "null_as_nan")]
+    pub mean_error: f32,
+    #[serde(deserialize_with = "null_as_nan")]
+    pub max_error: f32,
+    pub usage: Vec<usize>,
+    #[serde(deserialize_with = "null_as_nan")]
+    pub gamut_coverage: f32,
+    pub config: DitherConfig,
+}
+
+/// Reads back the `null` that serde_json writes for a non-finite number.
+fn null_as_nan<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    Ok(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::NAN))
+}
+
+/// A [`Sidecar`] with the [`SIDECAR_SCHEMA`] it was written under.
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    schema: u32,
+    #[serde(flatten)]
+    sidecar: T,
+}
+
+impl Sidecar {
+    /// Measure `output`, dithered from `input` with `decomposer` over
+    /// `palette` (the dither palette) as described by `config`. `None` if
+    /// `input` and `output` differ in size.
+    pub fn measure<D>(
+        input: &Rgb32FImage,
+        output: &PaletteImage,
+        palette: &[Rgb<u8>],
+        decomposer: &D,
+        config: DitherConfig,
+    ) -> Option<Self>
+    where
+        D: Decomposer<f32, Input = Rgb<f32>> + ?Sized,
+    {
+        if input.dimensions() != (output.width, output.height) {
+            return None;
+        }
+        let residual = blurred_residual(input, output, palette);
+        let errors = residual
+            .pixels()
+            .map(|p| ComplexField::sqrt(p.0.iter().map(|c| c * c).sum::<f32>()));
+        let (sum, max) = errors.fold((0.0, 0.0f32), |(sum, max), e| (sum + e, max.max(e)));
+        let pixels = (output.width as usize * output.height as usize).max(1);
+        let (width, height) = (output.width as usize, output.height as usize);
+        let indices =
+            (0..width * height).map(|i| ImageReader::get_pixel(output, i % width, i / width));
+        Some(Self {
+            width: output.width,
+            height: output.height,
+            mean_error: sum / pixels as f32,
+            max_error: max,
+            usage: palette_usage(indices, palette.len()),
+            gamut_coverage: gamut_coverage(input, decomposer, palette),
+            config,
+        })
+    }
+}
+
+/// Text that isn't a [`Sidecar`]'s JSON form: malformed JSON, a missing
+/// or mistyped field, an unparsable config or another schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidSidecar;
+
+impl core::fmt::Display for InvalidSidecar {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid sidecar JSON")
+    }
+}
+
+impl core::error::Error for InvalidSidecar {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for Sidecar {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let versioned = Versioned {
+            schema: SIDECAR_SCHEMA,
+            sidecar: self,
+        };
+        let json = serde_json::to_string_pretty(&versioned).map_err(|_| core::fmt::Error)?;
+        writeln!(f, "{json}")
+    }
+}
+
+impl core::str::FromStr for Sidecar {
+    type Err = InvalidSidecar;
+
+    /// Expects one JSON object with every field of the current schema;
+    /// unknown keys are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let versioned: Versioned<Sidecar> = serde_json::from_str(s).map_err(|_| InvalidSidecar)?;
+        if versioned.schema != SIDECAR_SCHEMA {
+            return Err(InvalidSidecar);
+        }
+        Ok(versioned.sidecar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DiffusionCoefficients, DiffusionSetting};
+    use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+    use crate::dither::{DecomposeStrategy, ImageCombinedRW};
+    use crate::image::palette_image::VerifiedPalette;
+    use crate::noise::NoiseSource;
+    use crate::registry::{FactoryOptions, decompose_ditherer, decomposer_for};
+
+    fn config() -> DitherConfig {
+        DitherConfig::new(
+            crate::palette::SPECTRA6.to_vec(),
+            "naive-mix".parse().unwrap(),
+            DiffusionSetting::Fixed(DiffusionCoefficients::of(&FLOYD_STEINBERG)),
+            NoiseSource::None,
+        )
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let sidecar = Sidecar {
+            width: 3,
+            height: 2,
+            mean_error: 0.125,
+            max_error: 0.5,
+            usage: alloc::vec![4, 0, 2],
+            gamut_coverage: 1.0,
+            config: config(),
+        };
+        let json = alloc::format!("{sidecar}");
+        assert!(json.contains("\"strategy\": \"naive-mix\""), "{json}");
+        assert_eq!(json.parse::<Sidecar>(), Ok(sidecar.clone()));
+        let unmeasured = Sidecar {
+            max_error: f32::NAN,
+            ..sidecar.clone()
+        };
+        let with_null = alloc::format!("{unmeasured}");
+        assert!(with_null.contains("\"max_error\": null"), "{with_null}");
+        assert!(with_null.parse::<Sidecar>().unwrap().max_error.is_nan());
+        // Unknown keys are skipped, missing ones and other schemas refused.
+        let extended = json.replacen('{', "{\"extra\": {\"nested\": [true, \"\\\"\"]},", 1);
+        assert_eq!(extended.parse::<Sidecar>().unwrap().usage, sidecar.usage);
+        assert_eq!(
+            json.replace("\"width\"", "\"breadth\"").parse::<Sidecar>(),
+            Err(InvalidSidecar)
+        );
+        assert_eq!(
+            json.replace("\"schema\": 1", "\"schema\": 2")
+                .parse::<Sidecar>(),
+            Err(InvalidSidecar)
+        );
+        assert_eq!(
+            alloc::format!("{json}]").parse::<Sidecar>(),
+            Err(InvalidSidecar)
+        );
+    }
+
+    #[test]
+    fn measures_a_dither() {
+        let palette = crate::palette::SPECTRA6;
+        let palette_rgb: Vec<Rgb<u8>> = palette.iter().map(|&c| Rgb(c)).collect();
+        let input = Rgb32FImage::from_fn(16, 8, |x, y| Rgb([x as f32 / 15.0, y as f32 / 7.0, 0.5]));
+        let strategy: DecomposeStrategy = "naive-mix".parse().unwrap();
+        let ditherer = decompose_ditherer::<
+            Rgb<f32>,
+            Rgb<u8>,
+            ImageCombinedRW<Rgb32FImage, PaletteImage>,
+        >(strategy, NoiseSource::None, &palette_rgb, FLOYD_STEINBERG)
+        .unwrap();
+        let writer = PaletteImage::new(16, 8, VerifiedPalette::new(palette_rgb.clone()).unwrap());
+        let mut image = ImageCombinedRW::new(input, writer).unwrap();
+        ditherer.dyn_dither_into(&mut image);
+        let decomposer =
+            decomposer_for::<Rgb<f32>, Rgb<u8>>(strategy, &palette_rgb, &FactoryOptions::default())
+                .unwrap();
+        let sidecar = Sidecar::measure(
+            &image.reader,
+            &image.writer,
+            &palette_rgb,
+            decomposer.as_ref(),
+            config(),
+        )
+        .unwrap();
+        let parsed: Sidecar = alloc::format!("{sidecar}").parse().unwrap();
+        assert_eq!(parsed, sidecar);
+        assert_eq!((parsed.width, parsed.height), (16, 8));
+        assert_eq!(parsed.usage.len(), palette.len());
+        assert_eq!(parsed.usage.iter().sum::<usize>(), 16 * 8);
+        assert!(parsed.mean_error > 0.0 && parsed.mean_error <= parsed.max_error);
+        // A plane through the RGB cube leaves Spectra 6's gamut in places.
+        assert!(parsed.gamut_coverage > 0.0 && parsed.gamut_coverage < 1.0);
+        assert!(
+            Sidecar::measure(
+                &Rgb32FImage::new(4, 4),
+                &image.writer,
+                &palette_rgb,
+                decomposer.as_ref(),
+                config(),
+            )
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Raw decomposition weights as a multi-channel float TIFF, for
+//! processing outside this crate.
+//!
+//! [`encode_weights_tiff`] writes one 32-bit float sample per palette
+//! entry per pixel, in palette order: channel `i` is the weight of
+//! palette entry `i`. The weights are those the decomposer gives, before
+//! noise and error diffusion, so for inputs inside the palette's gamut
+//! each pixel's channels sum to one. The first channel is tagged as gray
+//! and the rest as unspecified extra samples, which is how TIFF spells an
+//! arbitrary number of channels; readers that insist on a standard colour
+//! type see the first channel only.
+//!
+//! Available behind the `std` Cargo feature (together with `image`).
+
+use crate::decompose::Decomposer;
+use crate::dither::{ImageReader, ImageSize};
+use alloc::vec;
+use alloc::vec::Vec;
+use tiff::encoder::{TiffEncoder, colortype::Gray32Float};
+use tiff::tags::ExtraSamples;
+
+/// Encode the decomposition of every pixel of `input` as an uncompressed
+/// TIFF with [`Decomposer::palette_size`] float channels; see the module
+/// docs.
+pub fn encode_weights_tiff<D, I>(input: &I, decomposer: &D) -> tiff::TiffResult<Vec<u8>>
+where
+    D: Decomposer<f32> + ?Sized,
+    I: ImageSize + ImageReader<D::Input>,
+{
+    let (width, height) = (input.width(), input.height());
+    let channels = decomposer.palette_size();
+    let mut samples = vec![0.0; width * height * channels];
+    for (i, pixel) in samples.chunks_exact_mut(channels.max(1)).enumerate() {
+        decomposer.decompose_into(&input.get_pixel(i % width, i / width), pixel);
+    }
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    let mut encoder = TiffEncoder::new(&mut bytes)?;
+    let mut image =
+        encoder.new_image::<Gray32Float>(u32::try_from(width)?, u32::try_from(height)?)?;
+    image.extra_samples(&vec![ExtraSamples::Unspecified; channels.saturating_sub(1)])?;
+    image.write_data(&samples)?;
+    Ok(bytes.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompose::naive::NaiveDecomposerStrategy;
+    use crate::dither::DecomposeStrategy;
+    use crate::palette::SPECTRA6;
+    use crate::registry::{FactoryOptions, decomposer_for};
+    use image::{Rgb, Rgb32FImage};
+    use tiff::ColorType;
+    use tiff::decoder::{Decoder, DecodingResult};
+
+    #[test]
+    fn one_channel_per_ink_summing_to_one() {
+        let palette: Vec<Rgb<u8>> = SPECTRA6.iter().map(|&c| Rgb(c)).collect();
+        let decomposer = decomposer_for::<Rgb<f32>, Rgb<u8>>(
+            DecomposeStrategy::Naive(NaiveDecomposerStrategy::default()),
+            &palette,
+            &FactoryOptions::default(),
+        )
+        .unwrap();
+        // Mixes of black, white and red: inside the gamut.
+        let input = Rgb32FImage::from_fn(12, 5, |x, y| {
+            let (red, white) = (x as f32 / 11.0 * 0.5, y as f32 / 4.0 * 0.5);
+            let shares = [1.0 - red - white, white, red];
+            let ink = |c: usize| {
+                (0..3)
+                    .map(|i| SPECTRA6[i][c] as f32 / 255.0 * shares[i])
+                    .sum::<f32>()
+            };
+            Rgb([ink(0), ink(1), ink(2)])
+        });
+        let bytes = encode_weights_tiff(&input, decomposer.as_ref()).unwrap();
+        let mut decoder = Decoder::new(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(decoder.dimensions().unwrap(), (12, 5));
+        assert_eq!(
+            decoder.colortype().unwrap(),
+            ColorType::Multiband {
+                bit_depth: 32,
+                num_samples: 6
+            }
+        );
+        let DecodingResult::F32(samples) = decoder.read_image().unwrap() else {
+            panic!("expected float samples");
+        };
+        assert_eq!(samples.len(), 12 * 5 * 6);
+        let mut expected = [0.0; 6];
+        for (i, pixel) in samples.chunks_exact(6).enumerate() {
+            let sum: f32 = pixel.iter().sum();
+            assert!((sum - 1.0).abs() < 1e-4, "pixel {i}: {pixel:?}");
+            decomposer.decompose_into(input.get_pixel(i as u32 % 12, i as u32 / 12), &mut expected);
+            assert_eq!(pixel, expected);
+        }
+    }
+}
@@ -2,16 +2,28 @@
 #![deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)]
 #[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 pub mod barycentric;
+pub mod colorspace;
+#[cfg(feature = "alloc")]
+pub mod config;
 pub mod decompose;
 pub mod dither;
+#[cfg(all(feature = "alloc", any(test, feature = "fuzz")))]
+pub mod fuzz;
 #[cfg(feature = "alloc")]
 pub mod registry;
 mod array_util;
+mod bytes;
 #[cfg(feature = "image")]
 pub mod image;
+#[cfg(feature = "image")]
+pub mod metrics;
 pub mod noise;
 pub mod palette;
+#[cfg(feature = "image")]
+pub mod preset;
 
 pub use decompose::{Decomposer, DecomposerInputColor};
 pub use palette::Palette;
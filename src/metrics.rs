@@ -0,0 +1,277 @@
+//! Image-quality metrics for comparing dither outputs, e.g. two revisions
+//! of an algorithm against the same original.
+//!
+//! Every metric takes two `image::RgbImage`s and returns `None` if their
+//! dimensions differ. Dithered palette PNGs decode to RGB, so outputs and
+//! originals compare directly.
+//!
+//! [`ssim`] runs on luma, not per RGB channel: SSIM models perceived
+//! structure, and the eye reads structure mostly from lightness. Averaging
+//! per-channel scores would weigh a blue-channel pattern the same as an
+//! equally strong green one. Luma here is the same BT.709-on-sRGB
+//! [`brightness`](crate::decompose::DecomposerInputColor::brightness) the
+//! grayscale decomposers use.
+//!
+//! A dither is mostly high-frequency texture, so both PSNR and SSIM against
+//! the original are low in absolute terms; they are meant for ranking
+//! variants of the same image, not as an absolute quality scale.
+//!
+//! [`worm_score`] needs no original: it looks only at the texture of one
+//! dither, for the "worms" error diffusion leaves in smooth areas, chains
+//! of same-coloured pixels running along one direction.
+
+use crate::decompose::DecomposerInputColor;
+use alloc::vec::Vec;
+use image::RgbImage;
+use nalgebra::ComplexField;
+
+/// Side of the square SSIM window, in pixels.
+pub const SSIM_WINDOW: u32 = 8;
+
+/// Offset between neighbouring SSIM windows, in pixels.
+pub const SSIM_STRIDE: u32 = 4;
+
+/// Longest offset, in pixels along each direction, that [`worm_score`]
+/// correlates over.
+pub const WORM_LAGS: usize = 3;
+
+fn same_size(a: &RgbImage, b: &RgbImage) -> bool {
+    a.dimensions() == b.dimensions()
+}
+
+/// Peak signal-to-noise ratio in dB over all RGB channels, with a peak of
+/// 255. Identical images give `f32::INFINITY`.
+pub fn psnr(a: &RgbImage, b: &RgbImage) -> Option<f32> {
+    if !same_size(a, b) {
+        return None;
+    }
+    let samples = a.as_raw().len();
+    if samples == 0 {
+        return Some(f32::INFINITY);
+    }
+    let squared_error: u64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&x, &y)| {
+            let d = x.abs_diff(y) as u64;
+            d * d
+        })
+        .sum();
+    if squared_error == 0 {
+        return Some(f32::INFINITY);
+    }
+    let mse = squared_error as f64 / samples as f64;
+    Some((10.0 * ComplexField::log10(255.0 * 255.0 / mse)) as f32)
+}
+
+/// Mean structural similarity of the two images' luma, over
+/// [`SSIM_WINDOW`]² windows spaced [`SSIM_STRIDE`] apart with uniform
+/// weighting and the usual constants `C1 = (0.01 L)²`, `C2 = (0.03 L)²`
+/// (luma scaled to `L = 255`). `1.0` means identical; images smaller than
+/// one window are scored as a single window covering all of them. Where
+/// the stride doesn't land a window on the right or bottom edge, one more
+/// window is placed flush against it, so every pixel is scored.
+pub fn ssim(a: &RgbImage, b: &RgbImage) -> Option<f32> {
+    if !same_size(a, b) {
+        return None;
+    }
+    let (width, height) = a.dimensions();
+    if width == 0 || height == 0 {
+        return Some(1.0);
+    }
+    let luma = |image: &RgbImage| -> Vec<f64> {
+        image
+            .pixels()
+            .map(|p| p.brightness() as f64 * 255.0)
+            .collect()
+    };
+    let (la, lb) = (luma(a), luma(b));
+    let starts = |len: u32| {
+        let window = SSIM_WINDOW.min(len);
+        let last = len - window;
+        (0..=last)
+            .step_by(SSIM_STRIDE as usize)
+            .chain((!last.is_multiple_of(SSIM_STRIDE)).then_some(last))
+            .map(move |s| (s, window))
+    };
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (mut total, mut windows) = (0.0, 0usize);
+    for (y0, window_height) in starts(height) {
+        for (x0, window_width) in starts(width) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0..y0 + window_height {
+                for x in x0..x0 + window_width {
+                    let index = (y * width + x) as usize;
+                    let (va, vb) = (la[index], lb[index]);
+                    sa += va;
+                    sb += vb;
+                    saa += va * va;
+                    sbb += vb * vb;
+                    sab += va * vb;
+                }
+            }
+            let n = (window_width * window_height) as f64;
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+            total += ((2.0 * ma * mb + C1) * (2.0 * cov + C2))
+                / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+            windows += 1;
+        }
+    }
+    Some((total / windows as f64) as f32)
+}
+
+/// Directional texture of a dithered image: `0.0` for a pattern that looks
+/// the same along every direction, up to `1.0` for parallel lines.
+///
+/// The texture is the luma minus its 3×3 local mean, which keeps the
+/// dither pattern and drops the image content underneath. Its normalised
+/// autocorrelation is averaged over offsets `1..=`[`WORM_LAGS`] along four
+/// directions: horizontal, vertical and the two diagonals. Worms are
+/// chains along one direction, so they correlate strongly along it and
+/// weakly or negatively across it; the score is half the larger of the
+/// diagonal and the horizontal/vertical differences. Raw diagonal
+/// correlation alone isn't used, as a clean checkerboard correlates
+/// perfectly along both diagonals.
+///
+/// Real edges in the image, which are directional too, add to the score;
+/// compare scores of dithers of the same image.
+pub fn worm_score(image: &RgbImage) -> f32 {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let luma: Vec<f64> = image.pixels().map(|p| p.brightness() as f64).collect();
+    let mut texture = Vec::with_capacity(luma.len());
+    for y in 0..height {
+        for x in 0..width {
+            let (mut sum, mut count) = (0.0, 0.0);
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    sum += luma[ny * width + nx];
+                    count += 1.0;
+                }
+            }
+            texture.push(luma[y * width + x] - sum / count);
+        }
+    }
+    let correlation = |dx: isize, dy: usize| -> f64 {
+        let mut total = 0.0;
+        for lag in 1..=WORM_LAGS {
+            let (mut sab, mut saa, mut sbb) = (0.0, 0.0, 0.0);
+            for y in 0..height.saturating_sub(dy * lag) {
+                for x in 0..width {
+                    let Some(nx) = x
+                        .checked_add_signed(dx * lag as isize)
+                        .filter(|&nx| nx < width)
+                    else {
+                        continue;
+                    };
+                    let (a, b) = (texture[y * width + x], texture[(y + dy * lag) * width + nx]);
+                    sab += a * b;
+                    saa += a * a;
+                    sbb += b * b;
+                }
+            }
+            if saa > 0.0 && sbb > 0.0 {
+                total += sab / ComplexField::sqrt(saa * sbb);
+            }
+        }
+        total / WORM_LAGS as f64
+    };
+    let straight = (correlation(1, 0) - correlation(0, 1)).abs();
+    let diagonal = (correlation(1, 1) - correlation(-1, 1)).abs();
+    (straight.max(diagonal) / 2.0).min(1.0) as f32
+}
+
+/// Number of pixels whose RGB value differs between the two images.
+pub fn pixel_diff_count(a: &RgbImage, b: &RgbImage) -> Option<usize> {
+    if !same_size(a, b) {
+        return None;
+    }
+    Some(a.pixels().zip(b.pixels()).filter(|(x, y)| x != y).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn checkerboard(size: u32, dark: [u8; 3], light: [u8; 3]) -> RgbImage {
+        RgbImage::from_fn(size, size, |x, y| {
+            Rgb(if (x + y) % 2 == 0 { dark } else { light })
+        })
+    }
+
+    #[test]
+    fn identical_images_are_perfect() {
+        let image = checkerboard(16, [0, 0, 0], [255, 255, 255]);
+        assert_eq!(psnr(&image, &image), Some(f32::INFINITY));
+        assert!((ssim(&image, &image).unwrap() - 1.0).abs() < 1e-6);
+        assert_eq!(pixel_diff_count(&image, &image), Some(0));
+    }
+
+    #[test]
+    fn size_mismatch_is_none() {
+        let (a, b) = (RgbImage::new(4, 4), RgbImage::new(4, 5));
+        assert_eq!(psnr(&a, &b), None);
+        assert_eq!(ssim(&a, &b), None);
+        assert_eq!(pixel_diff_count(&a, &b), None);
+    }
+
+    #[test]
+    fn psnr_of_uniform_offset() {
+        let a = RgbImage::from_pixel(4, 4, Rgb([100, 100, 100]));
+        let b = RgbImage::from_pixel(4, 4, Rgb([110, 100, 100]));
+        // MSE = 100 / 3 per sample.
+        let expected = 10.0 * ComplexField::log10(255.0f32 * 255.0 / (100.0 / 3.0));
+        assert!((psnr(&a, &b).unwrap() - expected).abs() < 1e-3);
+        assert_eq!(pixel_diff_count(&a, &b), Some(16));
+    }
+
+    #[test]
+    fn ssim_prefers_matching_structure() {
+        let original = checkerboard(32, [64, 64, 64], [192, 192, 192]);
+        let stronger = checkerboard(32, [0, 0, 0], [255, 255, 255]);
+        let inverted = checkerboard(32, [192, 192, 192], [64, 64, 64]);
+        let matching = ssim(&original, &stronger).unwrap();
+        let opposite = ssim(&original, &inverted).unwrap();
+        assert!(matching > 0.5 && opposite < 0.0, "{matching} / {opposite}");
+    }
+
+    #[test]
+    fn ssim_scores_trailing_pixels() {
+        // 10 isn't a multiple of the stride, so the strided windows stop
+        // two pixels short of the far corner.
+        let original = checkerboard(10, [0, 0, 0], [255, 255, 255]);
+        let mut corner = original.clone();
+        corner.put_pixel(9, 9, Rgb([128, 128, 128]));
+        assert!(ssim(&original, &corner).unwrap() < 1.0 - 1e-3);
+    }
+
+    #[test]
+    fn worm_score_flags_directional_chains() {
+        // Chains running down-left, two pixels apart, as worms form in a
+        // light flat tone.
+        let worms = RgbImage::from_fn(64, 64, |x, y| {
+            Rgb(if (x + y) % 3 == 0 { [0; 3] } else { [255; 3] })
+        });
+        let checker = checkerboard(64, [0, 0, 0], [255, 255, 255]);
+        let mut rng = crate::noise::Pcg32::new(5, 0);
+        let scattered = RgbImage::from_fn(64, 64, |_, _| {
+            Rgb(if rng.next_f32() < 1.0 / 3.0 {
+                [0; 3]
+            } else {
+                [255; 3]
+            })
+        });
+        let (worms, checker, scattered) = (
+            worm_score(&worms),
+            worm_score(&checker),
+            worm_score(&scattered),
+        );
+        assert!(worms > 0.4, "{worms}");
+        assert!(checker < 0.01, "{checker}");
+        assert!(scattered < 0.1, "{scattered}");
+        assert_eq!(worm_score(&RgbImage::from_pixel(8, 8, Rgb([9; 3]))), 0.0);
+    }
+}
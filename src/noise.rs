@@ -8,6 +8,335 @@ use num_traits::zero;
 #[cfg(feature = "image")]
 pub(crate) const BLUE_NOISE_PNG: &[u8] = include_bytes!("../assets/HDR_L_0.png");
 
+/// Stateless 64-bit hash of a pixel coordinate and seed (SplitMix64
+/// finaliser over the packed coordinate). Used wherever a per-pixel
+/// pseudo-random choice must be reproducible for a given seed.
+pub fn hash_coordinates(x: usize, y: usize, seed: u64) -> u64 {
+    let mut z = seed ^ (((x as u64) << 32) | (y as u64 & 0xFFFF_FFFF));
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// PCG32 (XSH-RR variant) pseudo-random generator: 64-bit state, 32-bit
+/// output, period 2^64 per stream. Small, `no_std` and deterministic for a
+/// given `(seed, stream)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Uniform in `[0, 1)`, with 24 bits of precision.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+/// Seeded white noise in `[0, 1)`, built once and sampled per pixel.
+///
+/// Each sample comes from a [`Pcg32`] keyed by [`hash_coordinates`] rather
+/// than from one shared stream: noise functions are called through `&self`
+/// and must be `Send + Sync` for the registry, and keying by coordinate
+/// makes the output independent of traversal order (serpentine or not).
+/// The same seed always yields the same image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WhiteNoise {
+    pub seed: u64,
+}
+
+impl WhiteNoise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn sample(&self, x: usize, y: usize) -> f32 {
+        Pcg32::new(hash_coordinates(x, y, self.seed), 0).next_f32()
+    }
+}
+
+/// Spatial and temporal standard deviation, in pixels and frames, of the
+/// Gaussian energy [`SpatioTemporal::generate`] spreads samples with.
+pub const SPATIOTEMPORAL_SIGMA: f32 = 1.9;
+
+/// Width, height and frame count of the stack behind
+/// [`NoiseSource::SpatioTemporal`].
+pub const STBN_SIZE: [usize; 3] = [32, 32, 8];
+
+/// Stack of blue-noise slices for dithering frame sequences.
+///
+/// A single blue-noise texture reused every frame puts the same threshold
+/// on a pixel each time, so a static region dithers identically in every
+/// frame and any error stays put. Spatiotemporal blue noise (STBN) makes
+/// each slice a blue-noise texture and also each pixel's values across
+/// slices a blue-noise sequence, so thresholds at a pixel cycle through
+/// low and high values over consecutive frames and its average over
+/// frames stays close to 0.5. [`sample`](Self::sample) picks slice
+/// `t % frames` and tiles each slice over the image.
+///
+/// [`generate`](Self::generate) builds the stack with void-and-cluster;
+/// [`new`](Self::new) takes a precomputed one, e.g. a published STBN
+/// texture set.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpatioTemporal {
+    width: usize,
+    height: usize,
+    frames: usize,
+    // Row-major slices, one after the other.
+    values: alloc::vec::Vec<f32>,
+}
+
+#[cfg(feature = "alloc")]
+impl SpatioTemporal {
+    /// Stack of `frames` slices of `width × height`, slice by slice in
+    /// row-major order. `None` unless there are exactly that many values,
+    /// all in `[0, 1]`, and at least one of each dimension.
+    pub fn new(
+        width: usize,
+        height: usize,
+        frames: usize,
+        values: alloc::vec::Vec<f32>,
+    ) -> Option<Self> {
+        if width == 0
+            || height == 0
+            || frames == 0
+            || values.len() != width * height * frames
+            || !values.iter().all(|v| (0.0..=1.0).contains(v))
+        {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            frames,
+            values,
+        })
+    }
+
+    /// Void-and-cluster over the `width × height × frames` torus with an
+    /// energy that joins samples within a slice (by spatial distance) and
+    /// at the same pixel (by frame distance), both Gaussian with
+    /// [`SPATIOTEMPORAL_SIGMA`], as in STBN: ranks go to the emptiest
+    /// spot in that combined sense. Within each slice the values are then
+    /// the ranks `(k + 0.5) / (width · height)`, so every slice has a flat
+    /// histogram. `seed` picks the initial pattern. Time and memory are
+    /// quadratic and linear in the sample count `width · height ·
+    /// frames`: a 32 × 32 × 8 stack takes a fraction of a second. `None`
+    /// if any dimension is 0.
+    pub fn generate(width: usize, height: usize, frames: usize, seed: u64) -> Option<Self> {
+        use alloc::vec;
+        use nalgebra::ComplexField;
+        let slice = width * height;
+        let count = slice * frames;
+        if count == 0 {
+            return None;
+        }
+        let gaussian = |d: usize, period: usize| {
+            let d = d.min(period - d) as f32;
+            ComplexField::exp(-d * d / (2.0 * SPATIOTEMPORAL_SIGMA * SPATIOTEMPORAL_SIGMA))
+        };
+        let across: alloc::vec::Vec<f32> = (0..width).map(|d| gaussian(d, width)).collect();
+        let down: alloc::vec::Vec<f32> = (0..height).map(|d| gaussian(d, height)).collect();
+        let over: alloc::vec::Vec<f32> = (0..frames).map(|d| gaussian(d, frames)).collect();
+        // Add (or with `-1`, remove) the energy of a sample at `index`.
+        let splat = |energy: &mut [f32], index: usize, sign: f32| {
+            let (t, pixel) = (index / slice, index % slice);
+            let (x, y) = (pixel % width, pixel / width);
+            for (i, e) in energy[t * slice..(t + 1) * slice].iter_mut().enumerate() {
+                let (dx, dy) = ((i % width).abs_diff(x), (i / width).abs_diff(y));
+                *e += sign * across[dx] * down[dy];
+            }
+            for (other, factor) in over.iter().enumerate().skip(1) {
+                energy[(t + other) % frames * slice + pixel] += sign * factor;
+            }
+        };
+        // Extreme of `energy` over the samples that are (`ones`) or
+        // aren't set; the first on ties.
+        let extreme = |energy: &[f32], set: &[bool], ones: bool| {
+            (0..count).filter(|&i| set[i] == ones).reduce(|best, i| {
+                let better = if ones {
+                    energy[i] > energy[best]
+                } else {
+                    energy[i] < energy[best]
+                };
+                if better { i } else { best }
+            })
+        };
+        // Initial pattern: a tenth of the samples, at random, then moved
+        // from the tightest cluster to the largest void until stable.
+        let mut rng = Pcg32::new(seed, 0);
+        let initial = (count / 10).max(1);
+        let mut set = vec![false; count];
+        let mut energy = vec![0.0; count];
+        let mut placed = 0;
+        while placed < initial {
+            let index = rng.next_u32() as usize % count;
+            if !set[index] {
+                set[index] = true;
+                splat(&mut energy, index, 1.0);
+                placed += 1;
+            }
+        }
+        for _ in 0..count {
+            let Some(cluster) = extreme(&energy, &set, true) else {
+                break;
+            };
+            set[cluster] = false;
+            splat(&mut energy, cluster, -1.0);
+            let Some(void) = extreme(&energy, &set, false) else {
+                break;
+            };
+            set[void] = true;
+            splat(&mut energy, void, 1.0);
+            if void == cluster {
+                break;
+            }
+        }
+        let mut rank = vec![0; count];
+        // Below the initial pattern: remove tightest clusters, last first.
+        let (mut fewer, mut fewer_energy) = (set.clone(), energy.clone());
+        for k in (0..initial).rev() {
+            let Some(cluster) = extreme(&fewer_energy, &fewer, true) else {
+                break;
+            };
+            fewer[cluster] = false;
+            splat(&mut fewer_energy, cluster, -1.0);
+            rank[cluster] = k;
+        }
+        // Above it: fill largest voids.
+        for k in initial..count {
+            let Some(void) = extreme(&energy, &set, false) else {
+                break;
+            };
+            set[void] = true;
+            splat(&mut energy, void, 1.0);
+            rank[void] = k;
+        }
+        // Rank again within each slice so every slice is uniform.
+        let mut values = vec![0.0; count];
+        let mut order: alloc::vec::Vec<usize> = (0..slice).collect();
+        for t in 0..frames {
+            let base = t * slice;
+            order.sort_by_key(|&i| rank[base + i]);
+            for (k, &i) in order.iter().enumerate() {
+                values[base + i] = (k as f32 + 0.5) / slice as f32;
+            }
+        }
+        Self::new(width, height, frames, values)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Threshold at pixel `(x, y)` of frame `t`: slice `t % frames`,
+    /// tiled.
+    pub fn sample(&self, x: usize, y: usize, t: usize) -> f32 {
+        let index =
+            ((t % self.frames) * self.height + y % self.height) * self.width + x % self.width;
+        self.values[index]
+    }
+}
+
+/// Tiled ordered-dither threshold matrix, e.g. a hand-tuned one loaded
+/// with [`NoiseSource::Matrix`].
+///
+/// Only the order of the given levels counts: the cell with the `k`-th
+/// lowest of `n` gets threshold `k / n`, ties going to the earlier cell in
+/// row-major order. The thresholds are thus spread evenly over `[0, 1)`
+/// whatever range or gamma the levels were stored with, and a classic
+/// Bayer matrix comes out exactly as [`bayer`] does.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdMatrix {
+    width: usize,
+    height: usize,
+    // Row-major.
+    thresholds: alloc::vec::Vec<f32>,
+}
+
+#[cfg(feature = "alloc")]
+impl ThresholdMatrix {
+    /// Matrix ranking row-major `levels`. `None` unless there are exactly
+    /// `width * height` of them, all not NaN, and both are at least 1.
+    pub fn new(width: usize, height: usize, levels: &[f32]) -> Option<Self> {
+        if width == 0 || height == 0 || levels.len() != width * height {
+            return None;
+        }
+        if levels.iter().any(|level| level.is_nan()) {
+            return None;
+        }
+        let mut order: alloc::vec::Vec<usize> = (0..levels.len()).collect();
+        // Stable, so ties keep row-major order.
+        order.sort_by(|&a, &b| levels[a].total_cmp(&levels[b]));
+        let mut thresholds = alloc::vec![0.0; levels.len()];
+        for (rank, index) in order.into_iter().enumerate() {
+            thresholds[index] = rank as f32 / levels.len() as f32;
+        }
+        Some(Self {
+            width,
+            height,
+            thresholds,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Threshold at pixel `(x, y)`, tiled.
+    pub fn sample(&self, x: usize, y: usize) -> f32 {
+        self.thresholds[(y % self.height) * self.width + x % self.width]
+    }
+}
+
+/// Pull a noise value towards 0.5: `0.5 + amplitude * (noise - 0.5)`.
+/// Amplitude 1 leaves the noise as is; 0 makes every pixel pick at the
+/// middle of the cumulative weights, i.e. posterization to the weighted
+/// median. That is the largest weight only when it is at least one half:
+/// `[0.4, 0.3, 0.3]` picks the second entry.
+pub fn scale_amplitude(noise: f32, amplitude: f32) -> f32 {
+    0.5 + amplitude * (noise - 0.5)
+}
+
 pub fn interleaved_gradient_noise<F>(x: F, y: F) -> F
 where
     F: FloatCore + From<f32>,
@@ -20,40 +349,165 @@ where
 
 const BAYER_MATRIX: [[f32; 2]; 2] = [[0.0, 2.0], [3.0, 1.0]];
 
+/// Threshold of the infinite Bayer pattern recursing the usual 2×2 base;
+/// see [`bayer_inf_with`]. Computed with [`bayer_rank`] rather than digit
+/// by digit: the same thresholds in constant time however large the
+/// coordinates get, keeping the top 48 bits of the rank. Both agree
+/// exactly wherever the threshold fits `F` (for `f32`, coordinates below
+/// 4096).
 pub fn bayer_inf<F>(x: usize, y: usize) -> F
 where
     F: From<f32> + FloatCore + Zero,
 {
-    let base_multiplier: F = (0.25).into();
+    let rank = bayer_rank(x, y, usize::BITS);
+    // Two f32-sized pieces, each exact, so F = f64 keeps what f32 can't.
+    // The rank has `2 * usize::BITS` bits, fewer than 48 on 16-bit targets.
+    let bits = 2 * usize::BITS;
+    let top = if bits >= 48 {
+        rank >> (bits - 48)
+    } else {
+        rank << (48 - bits)
+    } as u64;
+    let high: F = ((top >> 24) as f32 / (1u32 << 24) as f32).into();
+    let low: F = ((top & 0xFF_FFFF) as f32 / (1u32 << 24) as f32).into();
+    let value = high + low * (1.0 / (1u32 << 24) as f32).into();
+    // Rounding the sum to F can reach 1; the thresholds stay below it.
+    if value < F::one() { value } else { high }
+}
+
+/// Rank of `(x, y)` in the `2^depth × 2^depth` Bayer matrix, in
+/// `0..4^depth`: [`bayer`] is this divided by `4^depth`. `depth` is capped
+/// at 64.
+///
+/// The recursion over the 2×2 base `[[0, 2], [3, 1]]` has a closed form:
+/// the cell at `(x0, y0)` (one bit each) has rank `2·(x0 ⊕ y0) + y0`, so
+/// level `k` contributes the bit pair `((x ⊕ y)_k, y_k)`, and with the
+/// first level the most significant, the rank is those pairs interleaved
+/// in bit-reversed order. That is a bit interleave and a bit reversal
+/// rather than a loop over the digits.
+pub fn bayer_rank(x: usize, y: usize, depth: u32) -> u128 {
+    let depth = depth.min(64);
+    if depth == 0 {
+        return 0;
+    }
+    // Spread the low 64 bits of `v` to the even bits of a u128.
+    fn spread(v: u64) -> u128 {
+        let mut v = u128::from(v);
+        v = (v | v << 32) & 0x0000_0000_FFFF_FFFF_0000_0000_FFFF_FFFF;
+        v = (v | v << 16) & 0x0000_FFFF_0000_FFFF_0000_FFFF_0000_FFFF;
+        v = (v | v << 8) & 0x00FF_00FF_00FF_00FF_00FF_00FF_00FF_00FF;
+        v = (v | v << 4) & 0x0F0F_0F0F_0F0F_0F0F_0F0F_0F0F_0F0F_0F0F;
+        v = (v | v << 2) & 0x3333_3333_3333_3333_3333_3333_3333_3333;
+        (v | v << 1) & 0x5555_5555_5555_5555_5555_5555_5555_5555
+    }
+    let mask = u64::MAX >> (64 - depth);
+    let (x, y) = (x as u64 & mask, y as u64 & mask);
+    // Reversed, bit 2k lands above bit 2k + 1: (x ⊕ y)_k is the high bit
+    // of level k's pair.
+    let interleaved = spread(x ^ y) | spread(y) << 1;
+    interleaved.reverse_bits() >> (128 - 2 * depth)
+}
+
+/// [`bayer`] with the usual 2×2 base; see [`bayer_with`].
+pub fn bayer<F>(x: usize, y: usize, max_depth: usize) -> F
+where
+    F: From<f32> + FloatCore + Zero,
+{
+    bayer_with(&BAYER_MATRIX, x, y, max_depth)
+}
+
+/// [`bayer_with`] without a depth limit: the pattern never repeats, and
+/// successive levels get finer without bound.
+pub fn bayer_inf_with<F, const W: usize, const H: usize>(
+    base: &[[f32; W]; H],
+    x: usize,
+    y: usize,
+) -> F
+where
+    F: From<f32> + FloatCore + Zero,
+{
+    bayer_with(base, x, y, usize::MAX)
+}
+
+/// Bayer threshold for the tile that repeats the `W × H` `base` recursively
+/// `max_depth` times, i.e. a `W^max_depth × H^max_depth` matrix.
+///
+/// `base` holds each of the ranks `0..W * H` once, indexed `[y][x]`. Each
+/// level of recursion takes the next base-`W` digit of `x` and base-`H`
+/// digit of `y` (lowest first) and adds its rank scaled by `1 / (W·H)` of
+/// the previous level's step, so the first level decides the coarsest
+/// threshold split and output lies in `[0, 1)` in steps of
+/// `(W·H)^-max_depth`. A `W` or `H` of 1 leaves that coordinate unused.
+pub fn bayer_with<F, const W: usize, const H: usize>(
+    base: &[[f32; W]; H],
+    x: usize,
+    y: usize,
+    max_depth: usize,
+) -> F
+where
+    F: From<f32> + FloatCore + Zero,
+{
+    if W * H < 2 {
+        return zero();
+    }
+    let base_multiplier: F = (1.0 / (W * H) as f32).into();
     let mut ret: F = zero();
-    let mut x = x;
-    let mut y = y;
+    // A single column (or row) has no digits to consume along that axis.
+    let mut x = if W > 1 { x } else { 0 };
+    let mut y = if H > 1 { y } else { 0 };
+    let mut max_depth = max_depth;
     let mut multiplier = base_multiplier;
-    while x > 0 || y > 0 {
-        ret = ret + (multiplier * BAYER_MATRIX[y % 2][x % 2].into());
-        x /= 2;
-        y /= 2;
+    while max_depth > 0 && (x > 0 || y > 0) {
+        ret = ret + (multiplier * base[y % H][x % W].into());
+        x /= W;
+        y /= H;
+        max_depth -= 1;
         multiplier = multiplier * base_multiplier;
     }
     ret
 }
 
-pub fn bayer<F>(x: usize, y: usize, max_depth: usize) -> F
+/// Bayer threshold for a `2^depth_x × 2^depth_y` matrix, recursing the x
+/// and y axes independently so horizontal and vertical dither frequencies
+/// can differ. The first `min(depth_x, depth_y)` levels use the usual 2×2
+/// base (matching [`bayer`] exactly when both depths are equal); the
+/// remaining levels of the deeper axis split each threshold in two along
+/// that axis alone. Output lies in `[0, 1)` in steps of
+/// `1 / 2^(depth_x + depth_y)`.
+pub fn bayer_rect<F>(x: usize, y: usize, depth_x: usize, depth_y: usize) -> F
 where
     F: From<f32> + FloatCore + Zero,
 {
-    let base_multiplier: F = (0.25).into();
     let mut ret: F = zero();
     let mut x = x;
     let mut y = y;
-    let mut max_depth = max_depth;
-    let mut multiplier = base_multiplier;
-    while max_depth > 0 && (x > 0 || y > 0) {
+    let mut depth_x = depth_x;
+    let mut depth_y = depth_y;
+    let mut multiplier: F = (0.25).into();
+    while depth_x > 0 && depth_y > 0 && (x > 0 || y > 0) {
         ret = ret + (multiplier * BAYER_MATRIX[y % 2][x % 2].into());
         x /= 2;
         y /= 2;
-        max_depth -= 1;
-        multiplier = multiplier * base_multiplier;
+        depth_x -= 1;
+        depth_y -= 1;
+        multiplier = multiplier * (0.25).into();
+    }
+    // Only one axis has levels left (or both are exhausted). Scale the
+    // multiplier back up by 2: each remaining level contributes a single
+    // bit rather than a 2×2 cell.
+    let (mut rest, mut depth) = if depth_x > 0 {
+        (x, depth_x)
+    } else {
+        (y, depth_y)
+    };
+    multiplier = multiplier * (2.0).into();
+    while depth > 0 && rest > 0 {
+        if rest % 2 == 1 {
+            ret = ret + multiplier;
+        }
+        rest /= 2;
+        depth -= 1;
+        multiplier = multiplier * (0.5).into();
     }
     ret
 }
@@ -70,15 +524,28 @@ pub enum NoiseSource {
     /// Deterministic Bayer matrix. `Some(n)` selects the 2^n × 2^n matrix;
     /// `None` is the infinite (recursively-extended) variant.
     Bayer(Option<usize>),
+    /// Rectangular Bayer matrix of `2^x × 2^y`; see [`bayer_rect`].
+    BayerRect(usize, usize),
     InterleavedGradient,
+    /// [`WhiteNoise`] with a random seed picked once per run.
     #[cfg(feature = "rand")]
     White,
+    /// [`WhiteNoise`] with the given seed.
+    WhiteSeeded(u64),
     /// External noise image at the given path. Loaded by the registry.
     #[cfg(feature = "image")]
     File(alloc::string::String),
+    /// Threshold-matrix image at the given path, ranked into a
+    /// [`ThresholdMatrix`]. Loaded by the registry.
+    #[cfg(feature = "image")]
+    Matrix(alloc::string::String),
     /// Built-in blue-noise tile bundled with the crate.
     #[cfg(feature = "image")]
     Blue,
+    /// Frame `t` of a [`SpatioTemporal`] stack generated at
+    /// [`STBN_SIZE`]; dither frame `t` of a sequence with `stbn:<t>`.
+    #[cfg(feature = "alloc")]
+    SpatioTemporal(usize),
 }
 
 impl NoiseSource {
@@ -87,11 +554,18 @@ impl NoiseSource {
         "Accepted values:\n",
         " none           No noise\n",
         " bayer:<N>      Bayer matrix of size 2^N\n",
+        " bayer:<X>x<Y>  Rectangular Bayer matrix of size 2^X by 2^Y\n",
         " bayer          Infinite Bayer pattern\n",
         " ign            Interleaved Gradient Noise\n",
-        " white          White noise (requires `rand` feature)\n",
+        " white          White noise, random seed (requires `rand` feature)\n",
+        " white:<SEED>   White noise with a fixed seed\n",
         " file:<PATH>    External noise image (requires `image` feature)\n",
+        " matrix:<PATH>  Threshold-matrix image, e.g. a hand-tuned Bayer\n",
+        "                matrix; levels are ranked, so thresholds spread\n",
+        "                evenly over [0, 1) (requires `image` feature)\n",
         " blue           Built-in blue-noise tile (requires `image` feature)\n",
+        " stbn:<T>       Frame T of spatiotemporal blue noise, for sequences\n",
+        "                (requires `alloc` feature)\n",
     );
 }
 
@@ -106,6 +580,32 @@ impl core::fmt::Display for InvalidNoiseSource {
 
 impl core::error::Error for InvalidNoiseSource {}
 
+/// Inverse of `FromStr`. [`White`](Self::White) prints as `white`, which
+/// parses back but picks a new seed; resolve it to
+/// [`WhiteSeeded`](Self::WhiteSeeded) first where the seed matters.
+impl core::fmt::Display for NoiseSource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Bayer(None) => f.write_str("bayer"),
+            Self::Bayer(Some(n)) => write!(f, "bayer:{n}"),
+            Self::BayerRect(x, y) => write!(f, "bayer:{x}x{y}"),
+            Self::InterleavedGradient => f.write_str("ign"),
+            #[cfg(feature = "rand")]
+            Self::White => f.write_str("white"),
+            Self::WhiteSeeded(seed) => write!(f, "white:{seed}"),
+            #[cfg(feature = "image")]
+            Self::File(path) => write!(f, "file:{path}"),
+            #[cfg(feature = "image")]
+            Self::Matrix(path) => write!(f, "matrix:{path}"),
+            #[cfg(feature = "image")]
+            Self::Blue => f.write_str("blue"),
+            #[cfg(feature = "alloc")]
+            Self::SpatioTemporal(t) => write!(f, "stbn:{t}"),
+        }
+    }
+}
+
 impl core::str::FromStr for NoiseSource {
     type Err = InvalidNoiseSource;
 
@@ -119,16 +619,245 @@ impl core::str::FromStr for NoiseSource {
             #[cfg(feature = "image")]
             "blue" => Ok(Self::Blue),
             _ if s.starts_with("bayer:") => {
-                let n = s["bayer:".len()..]
+                let rest = &s["bayer:".len()..];
+                if let Some((x, y)) = rest.split_once('x') {
+                    let x = x.parse::<usize>().map_err(|_| InvalidNoiseSource)?;
+                    let y = y.parse::<usize>().map_err(|_| InvalidNoiseSource)?;
+                    return Ok(Self::BayerRect(x, y));
+                }
+                let n = rest.parse::<usize>().map_err(|_| InvalidNoiseSource)?;
+                Ok(Self::Bayer(Some(n)))
+            }
+            _ if s.starts_with("white:") => {
+                let seed = s["white:".len()..]
+                    .parse::<u64>()
+                    .map_err(|_| InvalidNoiseSource)?;
+                Ok(Self::WhiteSeeded(seed))
+            }
+            #[cfg(feature = "alloc")]
+            _ if s.starts_with("stbn:") => {
+                let t = s["stbn:".len()..]
                     .parse::<usize>()
                     .map_err(|_| InvalidNoiseSource)?;
-                Ok(Self::Bayer(Some(n)))
+                Ok(Self::SpatioTemporal(t))
             }
             #[cfg(feature = "image")]
             _ if s.starts_with("file:") => {
                 Ok(Self::File(alloc::string::String::from(&s["file:".len()..])))
             }
+            #[cfg(feature = "image")]
+            _ if s.starts_with("matrix:") => Ok(Self::Matrix(alloc::string::String::from(
+                &s["matrix:".len()..],
+            ))),
             _ => Err(InvalidNoiseSource),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn threshold_matrix_ranks_levels_evenly() {
+        // The classic 4x4 Bayer matrix, stored as 8-bit grays.
+        let levels: alloc::vec::Vec<f32> = (0..16)
+            .map(|i| (bayer_rank(i % 4, i / 4, 2) * 17) as f32 / 255.0)
+            .collect();
+        let matrix = ThresholdMatrix::new(4, 4, &levels).unwrap();
+        for y in 0..12 {
+            for x in 0..12 {
+                assert_eq!(matrix.sample(x, y), bayer::<f32>(x, y, 2));
+            }
+        }
+        // Any monotone re-encoding ranks the same; ties go in scan order.
+        let squared: alloc::vec::Vec<f32> = levels.iter().map(|l| l * l).collect();
+        assert_eq!(ThresholdMatrix::new(4, 4, &squared), Some(matrix));
+        let flat = ThresholdMatrix::new(2, 1, &[0.5, 0.5]).unwrap();
+        assert_eq!([flat.sample(0, 0), flat.sample(1, 0)], [0.0, 0.5]);
+        assert!(ThresholdMatrix::new(2, 2, &[0.0; 3]).is_none());
+        assert!(ThresholdMatrix::new(1, 1, &[f32::NAN]).is_none());
+    }
+
+    #[test]
+    fn bayer_rank_matches_the_recursion() {
+        for n in 0..=8 {
+            let scale = (1u32 << (2 * n)) as f32;
+            for y in 0..300 {
+                for x in 0..300 {
+                    let rank = bayer_rank(x, y, n);
+                    assert_eq!(rank as f32 / scale, bayer::<f32>(x, y, n as usize));
+                    assert_eq!(
+                        bayer_inf::<f32>(x, y),
+                        bayer_inf_with::<f32, _, _>(&BAYER_MATRIX, x, y)
+                    );
+                }
+            }
+        }
+        // Past the coordinates the test above covers, and where the
+        // recursion's multiplier runs out of f32 range.
+        for (x, y) in [
+            (usize::MAX, 0),
+            (0, usize::MAX),
+            (usize::MAX, usize::MAX - 1),
+        ] {
+            let expected: f64 = bayer_inf_with(&BAYER_MATRIX, x, y);
+            assert!((bayer_inf::<f64>(x, y) - expected).abs() < 1e-12);
+            assert!(bayer_inf::<f32>(x, y) < 1.0);
+        }
+        assert_eq!(bayer_rank(1, 0, 200), 2 << 126);
+    }
+
+    #[test]
+    fn bayer_rect_square_matches_bayer() {
+        for n in 0..4 {
+            for y in 0..20 {
+                for x in 0..20 {
+                    assert_eq!(bayer_rect::<f32>(x, y, n, n), bayer::<f32>(x, y, n));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bayer_rect_covers_every_threshold_once() {
+        // 2^2 × 2^1 = 8 cells, thresholds k/8 for k in 0..8.
+        let mut seen = [false; 8];
+        for y in 0..2 {
+            for x in 0..4 {
+                let v = bayer_rect::<f32>(x, y, 2, 1);
+                assert!((0.0..1.0).contains(&v));
+                let k = (v * 8.0) as usize;
+                assert_eq!(k as f32 / 8.0, v);
+                assert!(!seen[k]);
+                seen[k] = true;
+            }
+        }
+        assert_eq!(bayer_rect::<f32>(4, 2, 2, 1), bayer_rect::<f32>(0, 0, 2, 1));
+    }
+
+    #[test]
+    fn four_by_four_base_reproduces_classic_bayer() {
+        const CLASSIC: [[f32; 4]; 4] = [
+            [0.0, 8.0, 2.0, 10.0],
+            [12.0, 4.0, 14.0, 6.0],
+            [3.0, 11.0, 1.0, 9.0],
+            [15.0, 7.0, 13.0, 5.0],
+        ];
+        for y in 0..32 {
+            for x in 0..32 {
+                let classic = bayer_with::<f32, 4, 4>(&CLASSIC, x, y, 1);
+                assert_eq!(classic, CLASSIC[y % 4][x % 4] / 16.0);
+                assert_eq!(classic, bayer::<f32>(x, y, 2));
+                // One 4×4 level is two 2×2 levels, all the way down.
+                assert_eq!(
+                    bayer_inf_with::<f32, 4, 4>(&CLASSIC, x, y),
+                    bayer_inf::<f32>(x, y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rectangular_base_covers_every_threshold_once() {
+        // 3×2 base, two levels: a 9×4 tile with thresholds k/36.
+        let base = [[0.0, 4.0, 2.0], [3.0, 1.0, 5.0]];
+        let mut seen = [false; 36];
+        for y in 0..4 {
+            for x in 0..9 {
+                let v = bayer_with::<f32, 3, 2>(&base, x, y, 2);
+                let k = (v * 36.0).round() as usize;
+                assert!(
+                    (v * 36.0 - k as f32).abs() < 1e-4 && !seen[k],
+                    "{x},{y}: {v}"
+                );
+                seen[k] = true;
+            }
+        }
+        assert_eq!(
+            bayer_with::<f32, 3, 2>(&base, 9, 4, 2),
+            bayer_with::<f32, 3, 2>(&base, 0, 0, 2)
+        );
+        assert_eq!(bayer_inf_with::<f32, 1, 2>(&[[0.0], [1.0]], 7, 1), 0.5);
+    }
+
+    #[test]
+    fn pcg32_is_deterministic_per_seed() {
+        let mut a = Pcg32::new(42, 54);
+        let mut b = Pcg32::new(42, 54);
+        let mut c = Pcg32::new(43, 54);
+        let first: [u32; 4] = core::array::from_fn(|_| a.next_u32());
+        assert_eq!(first, core::array::from_fn(|_| b.next_u32()));
+        assert_ne!(first, core::array::from_fn(|_| c.next_u32()));
+    }
+
+    #[test]
+    fn pcg32_state_does_not_cycle_early_and_is_uniform() {
+        let mut rng = Pcg32::new(7, 0);
+        let start = rng.clone();
+        let mut buckets = [0usize; 16];
+        const SAMPLES: usize = 1 << 16;
+        for _ in 0..SAMPLES {
+            let v = rng.next_f32();
+            assert!((0.0..1.0).contains(&v));
+            buckets[(v * 16.0) as usize] += 1;
+            assert_ne!(rng, start);
+        }
+        let expected = SAMPLES / 16;
+        for count in buckets {
+            assert!(count.abs_diff(expected) < expected / 10, "{buckets:?}");
+        }
+    }
+
+    #[test]
+    fn white_noise_is_reproducible() {
+        let noise = WhiteNoise::new(3);
+        assert_eq!(noise.sample(10, 20), WhiteNoise::new(3).sample(10, 20));
+        assert_ne!(noise.sample(10, 20), noise.sample(11, 20));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn spatiotemporal_slices_are_uniform_and_pixels_average_flat() {
+        let (width, height, frames) = (16, 16, 8);
+        let noise = SpatioTemporal::generate(width, height, frames, 1).unwrap();
+        for t in 0..frames {
+            let mut slice: alloc::vec::Vec<f32> = (0..width * height)
+                .map(|i| noise.sample(i % width, i / width, t))
+                .collect();
+            slice.sort_by(f32::total_cmp);
+            for (k, v) in slice.iter().enumerate() {
+                assert_eq!(*v, (k as f32 + 0.5) / (width * height) as f32);
+            }
+        }
+        // Independent uniform thresholds would give each pixel's mean over
+        // the frames a variance of 1 / (12 · frames).
+        let variance = (0..width * height)
+            .map(|i| {
+                let mean = (0..frames)
+                    .map(|t| noise.sample(i % width, i / width, t))
+                    .sum::<f32>()
+                    / frames as f32;
+                (mean - 0.5) * (mean - 0.5)
+            })
+            .sum::<f32>()
+            / (width * height) as f32;
+        let white = 1.0 / (12.0 * frames as f32);
+        assert!(variance < white / 10.0, "{variance} vs {white}");
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn spatiotemporal_sample_wraps() {
+        let values = (0..2 * 2 * 3).map(|i| i as f32 / 11.0).collect();
+        let noise = SpatioTemporal::new(2, 2, 3, values).unwrap();
+        assert_eq!(noise.sample(1, 0, 2), 9.0 / 11.0);
+        assert_eq!(noise.sample(3, 2, 5), 9.0 / 11.0);
+        assert!(SpatioTemporal::new(2, 2, 3, alloc::vec![0.5; 11]).is_none());
+        assert!(SpatioTemporal::new(1, 1, 1, alloc::vec![1.5]).is_none());
+        assert!(SpatioTemporal::generate(4, 0, 2, 1).is_none());
+        assert_eq!("stbn:3".parse(), Ok(NoiseSource::SpatioTemporal(3)));
+        assert!("stbn:".parse::<NoiseSource>().is_err());
+    }
+}
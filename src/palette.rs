@@ -13,6 +13,11 @@
 //! `Display` impl. Callers that want a formatted error string get one
 //! automatically via the `ToString` blanket impl when they enable
 //! `alloc`.
+//!
+//! The exceptions are the `alloc`-gated swatch importers ([`from_ase`],
+//! [`from_aco`]), which turn designer-supplied Adobe swatch files into an
+//! owned colour table, and [`analyze`], which tetrahedralizes a palette to
+//! report on its gamut.
 
 // ============================================================================
 // 6-colour palettes
@@ -254,6 +259,24 @@ pub const SPECTRA6_D65_BPC100_ADJUSTED: [[u8; 3]; 6] = [
     [ 90, 175, 100], // G
 ];
 
+// ============================================================================
+// 4-colour palettes — too few inks for an octahedron; the four points span
+// a single tetrahedron, so use the naive decomposer.
+// ============================================================================
+
+/// Black/White/Red/Yellow 4-ink panel palette (placeholder; idealised
+/// primaries — replace with measured panel reflectance values). Ordered
+/// like the first four [`SPECTRA6`] inks.
+///
+/// Valid decomposers: [`NaiveDecomposer`](crate::decompose::naive::NaiveDecomposer).
+#[rustfmt::skip]
+pub const BWRY: [[u8; 3]; 4] = [
+    [  0,   0,   0], // K
+    [255, 255, 255], // W
+    [255, 255,   0], // Y
+    [255,   0,   0], // R
+];
+
 // ============================================================================
 // 1-D grayscale palettes — RGB-widened so the same `&[[u8; 3]]` interface
 // as the chromatic palettes works without reaching for `alloc`.
@@ -316,25 +339,39 @@ pub enum Palette {
     Spectra6D65Bpc90Adjusted,
     Spectra6D65Bpc100Adjusted,
     Epdoptimize,
+    Bwry,
     Grayscale2,
     Grayscale4,
     Grayscale16,
 }
 
+macro_rules! builtin_names {
+    () => {
+        concat!(
+            "  naive\n",
+            "  spectra6\n",
+            "  spectra6-d50, spectra6-d50-adjusted\n",
+            "  spectra6-d50-bpc{50,75,80,90,100}-adjusted\n",
+            "  spectra6-d65, spectra6-d65-adjusted\n",
+            "  spectra6-d65-bpc{50,75,80,90,100}-adjusted\n",
+            "  epdoptimize\n",
+            "  bwry\n",
+            "  grayscale2, grayscale4, grayscale16\n",
+        )
+    };
+}
+
 impl Palette {
     pub const LONG_HELP: &'static str = concat!(
         "Built-in palette to use.\n\n",
         "Accepted values:\n",
-        "  naive\n",
-        "  spectra6\n",
-        "  spectra6-d50, spectra6-d50-adjusted\n",
-        "  spectra6-d50-bpc{50,75,80,90,100}-adjusted\n",
-        "  spectra6-d65, spectra6-d65-adjusted\n",
-        "  spectra6-d65-bpc{50,75,80,90,100}-adjusted\n",
-        "  epdoptimize\n",
-        "  grayscale2, grayscale4, grayscale16\n",
+        builtin_names!(),
     );
 
+    /// The built-in palette names from [`Self::LONG_HELP`], one indented
+    /// group per line, for help texts that accept more than a name.
+    pub const NAMES_HELP: &'static str = builtin_names!();
+
     pub fn as_rgb_slice(&self) -> &'static [[u8; 3]] {
         match self {
             Self::Naive => &NAIVE_RGB6,
@@ -354,6 +391,7 @@ impl Palette {
             Self::Spectra6D65Bpc90Adjusted => &SPECTRA6_D65_BPC90_ADJUSTED,
             Self::Spectra6D65Bpc100Adjusted => &SPECTRA6_D65_BPC100_ADJUSTED,
             Self::Epdoptimize => &EPDOPTIMIZE,
+            Self::Bwry => &BWRY,
             Self::Grayscale2 => &GRAYSCALE2_RGB,
             Self::Grayscale4 => &GRAYSCALE4_RGB,
             Self::Grayscale16 => &GRAYSCALE16_RGB,
@@ -394,6 +432,7 @@ impl core::str::FromStr for Palette {
             "spectra6-d65-bpc90-adjusted" => Ok(Self::Spectra6D65Bpc90Adjusted),
             "spectra6-d65-bpc100-adjusted" => Ok(Self::Spectra6D65Bpc100Adjusted),
             "epdoptimize" => Ok(Self::Epdoptimize),
+            "bwry" => Ok(Self::Bwry),
             "grayscale2" => Ok(Self::Grayscale2),
             "grayscale4" => Ok(Self::Grayscale4),
             "grayscale16" => Ok(Self::Grayscale16),
@@ -401,3 +440,578 @@ impl core::str::FromStr for Palette {
         }
     }
 }
+
+/// Parse a `#RRGGBB` (or `RRGGBB`) hex colour. Lets callers accept
+/// panel-specific palettes of any size alongside the built-in [`Palette`]
+/// names without this module allocating.
+pub fn parse_hex_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+/// `line` without its comment, for the line-based text files the binaries
+/// read: a comment is a `#` at the start of the line or after whitespace,
+/// through the end of the line, unless the `#` begins a `#RRGGBB` colour
+/// followed by whitespace or the end of the line. `#facade, the front` is
+/// a comment, so comma-separated colours are best written without `#`.
+pub fn strip_comment(line: &str) -> &str {
+    let mut previous = None;
+    for (i, c) in line.char_indices() {
+        if c == '#' && previous.is_none_or(char::is_whitespace) {
+            let rest = &line[i..];
+            let word = rest
+                .find(char::is_whitespace)
+                .map_or(rest, |end| &rest[..end]);
+            if parse_hex_color(word).is_none() {
+                return &line[..i];
+            }
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+/// Index of the first `palette` entry equal to `color`, the inverse of
+/// `palette[index]` for an image that was dithered to `palette`.
+pub fn index_of(color: [u8; 3], palette: &[[u8; 3]]) -> Option<usize> {
+    palette.iter().position(|&entry| entry == color)
+}
+
+/// Index of the `palette` entry nearest `color` by squared RGB distance,
+/// the first on ties; `0` for an empty palette. For images that were
+/// dithered to `palette` but may have been altered since, e.g. by lossy
+/// compression.
+pub fn nearest_index(color: [u8; 3], palette: &[[u8; 3]]) -> usize {
+    let distance = |entry: &[u8; 3]| {
+        color
+            .iter()
+            .zip(entry)
+            .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0)
+}
+
+/// Nominal colours of the inks e-paper panels use, for naming a palette
+/// entry by colour rather than by index; see [`InkKey`].
+pub const INK_NAMES: &[(&str, [u8; 3])] = &[
+    ("black", [0, 0, 0]),
+    ("white", [255, 255, 255]),
+    ("red", [255, 0, 0]),
+    ("green", [0, 255, 0]),
+    ("blue", [0, 0, 255]),
+    ("yellow", [255, 255, 0]),
+    ("orange", [255, 128, 0]),
+];
+
+/// A palette entry as an argument names it: by index, by exact
+/// `#RRGGBB` (or `RRGGBB`) colour, or by one of the [`INK_NAMES`], which
+/// stands for the entry nearest its nominal colour. Resolve it against a
+/// palette with [`InkKey::index_in`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InkKey {
+    Index(usize),
+    Color([u8; 3]),
+    Named(&'static str),
+}
+
+impl InkKey {
+    /// Index of the entry this names in `palette`; `None` for an index
+    /// past its end, a colour it lacks, or an empty palette.
+    pub fn index_in(self, palette: &[[u8; 3]]) -> Option<usize> {
+        match self {
+            Self::Index(index) => (index < palette.len()).then_some(index),
+            Self::Color(color) => index_of(color, palette),
+            Self::Named(name) => {
+                let &(_, color) = INK_NAMES.iter().find(|&&(ink, _)| ink == name)?;
+                (!palette.is_empty()).then(|| nearest_index(color, palette))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidInkKey;
+
+impl core::fmt::Display for InvalidInkKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("expected a palette index, #RRGGBB colour or ink name (")?;
+        for (i, (name, _)) in INK_NAMES.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(name)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl core::error::Error for InvalidInkKey {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for InkKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "{index}"),
+            Self::Color([r, g, b]) => write!(f, "#{r:02X}{g:02X}{b:02X}"),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl core::str::FromStr for InkKey {
+    type Err = InvalidInkKey;
+
+    /// Ink names are matched case-insensitively; six hex digits are a
+    /// colour even if they are also a number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(&(name, _)) = INK_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(Self::Named(name));
+        }
+        if let Some(color) = parse_hex_color(s) {
+            return Ok(Self::Color(color));
+        }
+        s.parse().map(Self::Index).map_err(|_| InvalidInkKey)
+    }
+}
+
+// ============================================================================
+// Adobe swatch import
+// ============================================================================
+
+/// Unreadable or unsupported swatch file: truncated data, a bad signature,
+/// or a colour model with no RGB conversion here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSwatchFile;
+
+impl core::fmt::Display for InvalidSwatchFile {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid or unsupported swatch file")
+    }
+}
+
+impl core::error::Error for InvalidSwatchFile {}
+
+/// Big-endian cursor over a swatch file.
+#[cfg(feature = "alloc")]
+struct SwatchReader<'a> {
+    buf: &'a [u8],
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> SwatchReader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], InvalidSwatchFile> {
+        let (head, rest) = self.buf.split_first_chunk::<N>().ok_or(InvalidSwatchFile)?;
+        self.buf = rest;
+        Ok(*head)
+    }
+
+    fn u16(&mut self) -> Result<u16, InvalidSwatchFile> {
+        self.bytes().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, InvalidSwatchFile> {
+        self.bytes().map(u32::from_be_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, InvalidSwatchFile> {
+        self.bytes().map(f32::from_be_bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<&'a [u8], InvalidSwatchFile> {
+        let (head, rest) = self.buf.split_at_checked(len).ok_or(InvalidSwatchFile)?;
+        self.buf = rest;
+        Ok(head)
+    }
+}
+
+/// Round a `[0, 1]` channel (clamped) to 8 bits.
+#[cfg(feature = "alloc")]
+fn unit_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+/// Naive CMYK (each `0..1` ink coverage) to RGB: `(1 - c)(1 - k)` per
+/// channel. No ICC profile is applied, so press-calibrated CMYK swatches
+/// come out only approximately right.
+#[cfg(feature = "alloc")]
+fn cmyk_to_rgb(c: f32, m: f32, y: f32, k: f32) -> [u8; 3] {
+    [c, m, y].map(|ink| unit_to_u8((1.0 - ink) * (1.0 - k)))
+}
+
+/// CIE L\*a\*b\* (`l` in `0..100`) to sRGB, taking Adobe's D50 reference
+/// white and Bradford-adapting to sRGB's D65. Out-of-gamut colours are
+/// clamped per channel.
+#[cfg(feature = "alloc")]
+fn lab_to_rgb(l: f32, a: f32, b: f32) -> [u8; 3] {
+    use nalgebra::ComplexField;
+    const D50_WHITE: [f32; 3] = [0.96422, 1.0, 0.82521];
+    #[rustfmt::skip]
+    const XYZ_D50_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+        [ 3.133_856, -1.616_867, -0.490_615],
+        [-0.978_768,  1.916_142,  0.033_454],
+        [ 0.071_945, -0.228_991,  1.405_243],
+    ];
+    let fy = (l + 16.0) / 116.0;
+    let f = [fy + a / 500.0, fy, fy - b / 200.0];
+    let xyz: [f32; 3] = core::array::from_fn(|i| {
+        let t = f[i];
+        let cube = t * t * t;
+        let linear = if cube > 216.0 / 24389.0 {
+            cube
+        } else {
+            (116.0 * t - 16.0) * 27.0 / 24389.0
+        };
+        linear * D50_WHITE[i]
+    });
+    XYZ_D50_TO_LINEAR_SRGB.map(|row| {
+        let linear = row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2];
+        let encoded = if linear <= 0.003_130_8 {
+            12.92 * linear
+        } else {
+            1.055 * ComplexField::powf(linear, 1.0 / 2.4) - 0.055
+        };
+        unit_to_u8(encoded)
+    })
+}
+
+/// Parse an Adobe Swatch Exchange (`.ase`) file into a colour table, in
+/// file order. Group markers are skipped, so grouped swatches are
+/// flattened.
+///
+/// Colour models: `RGB ` is taken as sRGB, `Gray` as a `0..1` lightness
+/// (0 black), `CMYK` as `(1 - c)(1 - k)` per channel
+/// (no ICC profile, so press-calibrated swatches come out approximate), and `LAB `
+/// (L stored as a `0..1` fraction) via D50 → sRGB. Anything else is an
+/// error rather than a silently dropped entry, since dropping one would
+/// shift every later palette index.
+#[cfg(feature = "alloc")]
+pub fn from_ase(bytes: &[u8]) -> Result<alloc::vec::Vec<[u8; 3]>, InvalidSwatchFile> {
+    const COLOR_ENTRY: u16 = 0x0001;
+    let mut reader = SwatchReader { buf: bytes };
+    if &reader.bytes::<4>()? != b"ASEF" {
+        return Err(InvalidSwatchFile);
+    }
+    let _version = reader.u32()?;
+    let block_count = reader.u32()?;
+    let mut colors = alloc::vec::Vec::new();
+    for _ in 0..block_count {
+        let block_type = reader.u16()?;
+        let block_len = reader.u32()? as usize;
+        let mut block = SwatchReader {
+            buf: reader.skip(block_len)?,
+        };
+        if block_type != COLOR_ENTRY {
+            continue;
+        }
+        let name_len = block.u16()? as usize;
+        block.skip(name_len * 2)?;
+        let color = match &block.bytes::<4>()? {
+            b"RGB " => [block.f32()?, block.f32()?, block.f32()?].map(unit_to_u8),
+            b"Gray" => [unit_to_u8(block.f32()?); 3],
+            b"CMYK" => cmyk_to_rgb(block.f32()?, block.f32()?, block.f32()?, block.f32()?),
+            b"LAB " => lab_to_rgb(block.f32()? * 100.0, block.f32()?, block.f32()?),
+            _ => return Err(InvalidSwatchFile),
+        };
+        colors.push(color);
+    }
+    Ok(colors)
+}
+
+/// Parse a Photoshop colour swatch (`.aco`) file into a colour table, in
+/// file order. Only the version 1 section, which every file starts with,
+/// is read; the optional version 2 section repeats the same colours with
+/// names.
+///
+/// Colour spaces: RGB and HSB are taken as sRGB; CMYK (stored inverted,
+/// 0 = full ink) as `(1 - c)(1 - k)` per channel; Lab (L in
+/// hundredths, a/b signed hundredths) via D50 → sRGB; grayscale as a
+/// `0..10000` lightness (0 black). Other spaces are an error.
+#[cfg(feature = "alloc")]
+pub fn from_aco(bytes: &[u8]) -> Result<alloc::vec::Vec<[u8; 3]>, InvalidSwatchFile> {
+    let mut reader = SwatchReader { buf: bytes };
+    if reader.u16()? != 1 {
+        return Err(InvalidSwatchFile);
+    }
+    let count = reader.u16()?;
+    let mut colors = alloc::vec::Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let space = reader.u16()?;
+        let [w, x, y, z] = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+        let unit = |v: u16| v as f32 / 65535.0;
+        let signed = |v: u16| v as i16 as f32 / 100.0;
+        let color = match space {
+            0 => [w, x, y].map(|v| unit_to_u8(unit(v))),
+            1 => crate::colorspace::hsv_to_rgb([unit(w), unit(x), unit(y)]).map(unit_to_u8),
+            2 => cmyk_to_rgb(1.0 - unit(w), 1.0 - unit(x), 1.0 - unit(y), 1.0 - unit(z)),
+            7 => lab_to_rgb(w as f32 / 100.0, signed(x), signed(y)),
+            8 => [unit_to_u8(w as f32 / 10000.0); 3],
+            _ => return Err(InvalidSwatchFile),
+        };
+        colors.push(color);
+    }
+    Ok(colors)
+}
+
+// ============================================================================
+// Palette analysis
+// ============================================================================
+
+/// Gamut diagnostics for a palette, from [`analyze`]. Distances and volumes
+/// are in the `[0, 1]` RGB cube (see
+/// [`to_point`](crate::DecomposerInputColor::to_point)), so `hull_volume`
+/// is the fraction of the cube the inks can mix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteReport {
+    /// Volume of the colours' convex hull; 0 if they are coplanar, as for
+    /// grayscale palettes.
+    pub hull_volume: f32,
+    /// Distance between the two closest colours; 0 for duplicates.
+    pub min_distance: f32,
+    /// Indices of those two colours, `None` for fewer than two.
+    pub closest_pair: Option<[usize; 2]>,
+    /// Opposite pairs found by
+    /// [`find_opposites`](crate::barycentric::octahedron::OctahedronProjector::find_opposites).
+    pub opposites: Option<[(usize, usize); 3]>,
+    /// Whether the colours form an octahedron the
+    /// [`OctahedronDecomposer`](crate::decompose::octahedron::OctahedronDecomposer)
+    /// accepts.
+    pub is_octahedral: bool,
+}
+
+/// Measure `colors`' gamut, see [`PaletteReport`].
+#[cfg(feature = "alloc")]
+pub fn analyze(colors: &[[u8; 3]]) -> PaletteReport {
+    use crate::DecomposerInputColor;
+    use crate::barycentric::octahedron::OctahedronProjector;
+    use crate::decompose::octahedron::OctahedronDecomposer;
+    use nalgebra::geometry::Point3;
+    let points: alloc::vec::Vec<Point3<f32>> = colors.iter().map(|c| c.to_point()).collect();
+    let hull_volume = crate::decompose::hull::tetrahedralize(&points)
+        .iter()
+        .map(|&[a, b, c, d]| {
+            let [a, b, c, d] = [a, b, c, d].map(|i| points[i]);
+            (b - a).cross(&(c - a)).dot(&(d - a)).abs() / 6.0
+        })
+        // Not `sum`, which is -0 when empty.
+        .fold(0.0, |total, volume| total + volume);
+    let closest = (0..points.len())
+        .flat_map(|a| (a + 1..points.len()).map(move |b| [a, b]))
+        .map(|[a, b]| ([a, b], nalgebra::distance(&points[a], &points[b])))
+        .reduce(|best, pair| if pair.1 < best.1 { pair } else { best });
+    let opposites = <&[Point3<f32>; 6]>::try_from(points.as_slice())
+        .ok()
+        .filter(|points| crate::barycentric::find_duplicate(*points).is_none())
+        .and_then(OctahedronProjector::find_opposites);
+    PaletteReport {
+        hull_volume,
+        min_distance: closest.map_or(0.0, |(_, distance)| distance),
+        closest_pair: closest.map(|(pair, _)| pair),
+        opposites,
+        is_octahedral: OctahedronDecomposer::new(&points).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_comments_but_not_colours() {
+        assert_eq!(
+            strip_comment("0,0,400,480 000000,FFFFFF,00FF00 # left module"),
+            "0,0,400,480 000000,FFFFFF,00FF00 "
+        );
+        // A colour only if the word ends with it.
+        assert_eq!(strip_comment("#facade, the front"), "");
+        assert_eq!(strip_comment("0,0,8,8 #000000,#FFFFFF"), "0,0,8,8 ");
+        assert_eq!(strip_comment("#FF0000 1.2"), "#FF0000 1.2");
+        assert_eq!(strip_comment("#FF0000\t1.2\t#red"), "#FF0000\t1.2\t");
+        assert_eq!(strip_comment("FF0000 1.2 # measured"), "FF0000 1.2 ");
+        assert_eq!(strip_comment("# whole line"), "");
+        assert_eq!(
+            strip_comment("spectra6#not-a-comment"),
+            "spectra6#not-a-comment"
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn analyzes_built_in_palettes() {
+        let spectra6 = analyze(&SPECTRA6);
+        assert!(spectra6.hull_volume > 0.0 && spectra6.hull_volume < 1.0);
+        assert!(spectra6.is_octahedral && spectra6.opposites.is_some());
+        assert!(spectra6.min_distance > 0.1, "{spectra6:?}");
+        // The cube without its cyan and magenta corners.
+        let naive = analyze(&NAIVE_RGB6);
+        assert!((naive.hull_volume - 2.0 / 3.0).abs() < 1e-5, "{naive:?}");
+        assert_eq!(naive.min_distance, 1.0);
+        let bwry = analyze(&BWRY);
+        assert!(bwry.hull_volume > 0.0 && !bwry.is_octahedral);
+        assert_eq!(bwry.opposites, None);
+        let gray = analyze(&GRAYSCALE4_RGB);
+        assert_eq!(gray.hull_volume, 0.0);
+        assert!(!gray.is_octahedral);
+        let black_white = analyze(&GRAYSCALE2_RGB);
+        assert!((black_white.min_distance - 3f32.sqrt()).abs() < 1e-6);
+        assert_eq!(black_white.closest_pair, Some([0, 1]));
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_hex_color("#161D31"), Some([0x16, 0x1D, 0x31]));
+        assert_eq!(parse_hex_color("ead400"), Some([0xEA, 0xD4, 0x00]));
+        assert_eq!(parse_hex_color("#12345"), None);
+        assert_eq!(parse_hex_color("#12345G"), None);
+    }
+
+    #[test]
+    fn maps_colors_back_to_indices() {
+        for (i, &color) in SPECTRA6.iter().enumerate() {
+            assert_eq!(index_of(color, &SPECTRA6), Some(i));
+            assert_eq!(nearest_index(color, &SPECTRA6), i);
+        }
+        let off_white = [250, 251, 249];
+        assert_eq!(index_of(off_white, &GRAYSCALE2_RGB), None);
+        assert_eq!(nearest_index(off_white, &GRAYSCALE2_RGB), 1);
+        // Equally far from both: the first wins.
+        assert_eq!(nearest_index([10, 0, 0], &[[0; 3], [20, 0, 0]]), 0);
+        assert_eq!(index_of([0; 3], &[]), None);
+        assert_eq!(nearest_index([0; 3], &[]), 0);
+    }
+
+    #[test]
+    fn resolves_ink_keys() {
+        let index = |key: &str| key.parse::<InkKey>().unwrap().index_in(&SPECTRA6);
+        assert_eq!(index("3"), Some(3));
+        assert_eq!(index("6"), None);
+        assert_eq!(index("#9F0000"), Some(3));
+        assert_eq!(index("FF0000"), None);
+        // Names pick the nearest entry, so they survive calibration.
+        assert_eq!(index("Red"), Some(3));
+        assert_eq!(index("green"), Some(5));
+        assert_eq!(InkKey::Named("orange").index_in(&[]), None);
+        assert_eq!("magenta".parse::<InkKey>(), Err(InvalidInkKey));
+        assert_eq!(
+            alloc::string::ToString::to_string(&InkKey::Color([159, 0, 0])),
+            "#9F0000"
+        );
+    }
+
+    /// `ASEF` v1.0 file: a group holding a red RGB swatch named "R", then a
+    /// CMYK swatch with full cyan and half black, then a Lab white.
+    #[cfg(feature = "alloc")]
+    #[rustfmt::skip]
+    const MINIMAL_ASE: &[u8] = &[
+        b'A', b'S', b'E', b'F', 0, 1, 0, 0, 0, 0, 0, 5,
+        // Group start "G": name length 2 (incl. NUL), UTF-16BE.
+        0xC0, 0x01, 0, 0, 0, 6, 0, 2, 0, b'G', 0, 0,
+        // Colour "R": RGB 1.0, 0.0, 0.0, global.
+        0x00, 0x01, 0, 0, 0, 24, 0, 2, 0, b'R', 0, 0, b'R', b'G', b'B', b' ',
+        0x3F, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // Group end.
+        0xC0, 0x02, 0, 0, 0, 0,
+        // Unnamed CMYK 1.0, 0.0, 0.0, 0.5, spot.
+        0x00, 0x01, 0, 0, 0, 24, 0, 0, b'C', b'M', b'Y', b'K',
+        0x3F, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x3F, 0, 0, 0, 0, 1,
+        // Unnamed Lab 1.0 (L = 100), 0.0, 0.0, normal.
+        0x00, 0x01, 0, 0, 0, 20, 0, 0, b'L', b'A', b'B', b' ',
+        0x3F, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+    ];
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn parses_minimal_ase() {
+        assert_eq!(
+            from_ase(MINIMAL_ASE),
+            Ok(alloc::vec![[255, 0, 0], [0, 128, 128], [255, 255, 255]])
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rejects_truncated_or_foreign_ase() {
+        assert_eq!(
+            from_ase(&MINIMAL_ASE[..MINIMAL_ASE.len() - 1]),
+            Err(InvalidSwatchFile)
+        );
+        assert_eq!(from_ase(b"PNG\0\0\0\0\0\0\0\0\0"), Err(InvalidSwatchFile));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn parses_aco_v1() {
+        #[rustfmt::skip]
+        let aco: &[u8] = &[
+            0, 1, 0, 3,
+            // RGB blue.
+            0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0,
+            // CMYK: inverted, so 0xFFFF is no ink; full magenta.
+            0, 2, 0xFF, 0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF,
+            // Grayscale 50%.
+            0, 8, 0x13, 0x88, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(
+            from_aco(aco),
+            Ok(alloc::vec![[0, 0, 255], [255, 0, 255], [128, 128, 128]])
+        );
+        assert_eq!(from_aco(&aco[..aco.len() - 2]), Err(InvalidSwatchFile));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn bwry_dithers_red_to_yellow_gradient() {
+        use crate::dither::diffusion_matrix::FLOYD_STEINBERG;
+        use crate::dither::{DecomposeStrategy, ImageCombinedRW, ImageReader};
+        use crate::image::palette_image::{PaletteImage, VerifiedPalette};
+        use crate::noise::NoiseSource;
+        use crate::registry::decompose_ditherer;
+        use image::{Rgb, Rgb32FImage};
+
+        const WIDTH: u32 = 64;
+        let palette: alloc::vec::Vec<Rgb<u8>> = BWRY.iter().map(|&c| Rgb(c)).collect();
+        let ditherer = decompose_ditherer::<Rgb<f32>, Rgb<u8>, _>(
+            "naive-mix".parse::<DecomposeStrategy>().unwrap(),
+            NoiseSource::InterleavedGradient,
+            &palette,
+            FLOYD_STEINBERG,
+        )
+        .unwrap();
+        let input = Rgb32FImage::from_fn(WIDTH, 16, |x, _| {
+            Rgb([1.0, x as f32 / (WIDTH - 1) as f32, 0.0])
+        });
+        let writer = PaletteImage::new(WIDTH, 16, VerifiedPalette::new(palette).unwrap());
+        let mut inout = ImageCombinedRW::new(input, writer).unwrap();
+        ditherer.dyn_dither_into(&mut inout);
+        // Palette order is K, W, Y, R: only yellow and red appear, and the
+        // share of yellow follows the gradient.
+        let yellow_share = |columns: core::ops::Range<usize>| {
+            let mut yellow = 0;
+            let mut total = 0;
+            for y in 0..16 {
+                for x in columns.clone() {
+                    let index: usize = ImageReader::get_pixel(&inout.writer, x, y);
+                    assert!(index == 2 || index == 3, "({x}, {y}): {index}");
+                    yellow += usize::from(index == 2);
+                    total += 1;
+                }
+            }
+            yellow as f32 / total as f32
+        };
+        let quarter = WIDTH as usize / 4;
+        let shares: alloc::vec::Vec<f32> = (0..4)
+            .map(|q| yellow_share(q * quarter..(q + 1) * quarter))
+            .collect();
+        assert!(shares.windows(2).all(|w| w[0] < w[1]), "{shares:?}");
+        assert!(shares[0] < 0.25 && shares[3] > 0.75, "{shares:?}");
+    }
+}
@@ -0,0 +1,162 @@
+//! Named combinations of strategy, diffusion and noise, the binary's
+//! `--preset` argument.
+//!
+//! A [`Preset`] only suggests the three components; callers apply each one
+//! the user hasn't chosen explicitly.
+
+use crate::decompose::naive::NaiveDecomposerStrategy;
+use crate::decompose::octahedron::OctahedronDecomposerAxisStrategy;
+use crate::dither::DecomposeStrategy;
+use crate::dither::diffusion_matrix::DiffuseMethod;
+use crate::noise::NoiseSource;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// Octahedron (closest axis), Floyd-Steinberg and blue noise: fine,
+    /// even grain for photographs. 6-colour dither palettes only.
+    Photo,
+    /// Naive (dominant component), no diffusion, no noise: solid glyphs
+    /// with crisp edges and no speckle.
+    Text,
+    /// The palette's default strategy, no diffusion, no noise: each pixel
+    /// takes its largest-weight ink, for flat posterized areas.
+    Poster,
+    /// Octahedron (blended axes), Jarvis-Judice-Ninke and blue noise: inks
+    /// change smoothly across the gamut and error spreads wide, for soft
+    /// gradients. 6-colour dither palettes only.
+    Smooth,
+}
+
+impl Preset {
+    pub const LONG_HELP: &'static str = concat!(
+        "Sensible strategy, diffusion and noise for a kind of image.\n",
+        "Explicit --strategy, --diffuse and --noise take precedence.\n\n",
+        "Accepted values:\n",
+        " photo   octahedron-closest, floyd-steinberg, blue\n",
+        " text    naive-dominant, no diffusion, no noise\n",
+        " poster  the palette's default strategy, no diffusion, no noise\n",
+        "         (every pixel takes its largest-weight ink)\n",
+        " smooth  octahedron-blended, jarvis-judice-and-ninke, blue\n\n",
+        "photo and smooth need a 6-colour dither palette.\n",
+    );
+
+    /// Decomposition strategy, `None` for the palette's default.
+    pub fn strategy(self) -> Option<DecomposeStrategy> {
+        match self {
+            Self::Photo => Some(DecomposeStrategy::Octahedron(
+                OctahedronDecomposerAxisStrategy::Closest,
+            )),
+            Self::Text => Some(DecomposeStrategy::Naive(
+                NaiveDecomposerStrategy::FavorDominant,
+            )),
+            Self::Poster => None,
+            Self::Smooth => Some(DecomposeStrategy::Octahedron(
+                OctahedronDecomposerAxisStrategy::Blended,
+            )),
+        }
+    }
+
+    pub fn diffuse(self) -> DiffuseMethod {
+        match self {
+            Self::Photo => DiffuseMethod::FloydSteinberg,
+            Self::Text | Self::Poster => DiffuseMethod::None,
+            Self::Smooth => DiffuseMethod::JarvisJudiceAndNinke,
+        }
+    }
+
+    pub fn noise(self) -> NoiseSource {
+        match self {
+            Self::Photo | Self::Smooth => NoiseSource::Blue,
+            Self::Text | Self::Poster => NoiseSource::None,
+        }
+    }
+
+    /// Strategy, diffusion and noise to use given the ones chosen
+    /// explicitly: each `Some` wins over the preset's suggestion. The
+    /// strategy is still `None` for the palette's default.
+    pub fn resolve(
+        self,
+        strategy: Option<DecomposeStrategy>,
+        diffuse: Option<DiffuseMethod>,
+        noise: Option<NoiseSource>,
+    ) -> (Option<DecomposeStrategy>, DiffuseMethod, NoiseSource) {
+        (
+            strategy.or(self.strategy()),
+            diffuse.unwrap_or(self.diffuse()),
+            noise.unwrap_or_else(|| self.noise()),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidPreset;
+
+impl core::fmt::Display for InvalidPreset {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("invalid preset name")
+    }
+}
+
+impl core::error::Error for InvalidPreset {}
+
+/// Inverse of `FromStr`.
+impl core::fmt::Display for Preset {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Photo => "photo",
+            Self::Text => "text",
+            Self::Poster => "poster",
+            Self::Smooth => "smooth",
+        })
+    }
+}
+
+impl core::str::FromStr for Preset {
+    type Err = InvalidPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "photo" => Ok(Self::Photo),
+            "text" => Ok(Self::Text),
+            "poster" => Ok(Self::Poster),
+            "smooth" => Ok(Self::Smooth),
+            _ => Err(InvalidPreset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn photo_is_octahedron_floyd_steinberg_blue() {
+        let photo: Preset = "photo".parse().unwrap();
+        assert_eq!(
+            photo.strategy(),
+            Some("octahedron-closest".parse().unwrap())
+        );
+        assert_eq!(photo.diffuse(), "floyd-steinberg".parse().unwrap());
+        assert_eq!(photo.noise(), "blue".parse().unwrap());
+        for preset in [Preset::Photo, Preset::Text, Preset::Poster, Preset::Smooth] {
+            assert_eq!(alloc::format!("{preset}").parse(), Ok(preset));
+        }
+        assert_eq!("comic".parse::<Preset>(), Err(InvalidPreset));
+    }
+
+    #[test]
+    fn explicit_choices_override_the_preset() {
+        let naive = Some("naive-mix".parse().unwrap());
+        let (strategy, diffuse, noise) =
+            Preset::Photo.resolve(naive, Some(DiffuseMethod::Atkinson), None);
+        assert_eq!(strategy, naive);
+        assert_eq!(diffuse, DiffuseMethod::Atkinson);
+        assert_eq!(noise, NoiseSource::Blue);
+
+        let (strategy, diffuse, noise) =
+            Preset::Text.resolve(None, None, Some(NoiseSource::InterleavedGradient));
+        assert_eq!(strategy, Preset::Text.strategy());
+        assert_eq!(diffuse, DiffuseMethod::None);
+        assert_eq!(noise, NoiseSource::InterleavedGradient);
+    }
+}
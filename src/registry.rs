@@ -1,4 +1,4 @@
-//! Convenience layer: build a `Box<dyn DynDitherer<T>>` from typed
+//! Convenience layer: build a `Box<dyn DynResumableDitherer<T>>` from typed
 //! configuration enums (via [`decompose_ditherer`]) or from strings (via
 //! [`parse_decompose_ditherer`], which parses then delegates).
 //!
@@ -10,25 +10,46 @@
 //! [`[u8; 3]`](DecomposerInputColor) impl in [`crate::decompose::input`] covers the
 //! image-free case.
 //!
-//! [`NoiseSource::File`] and [`NoiseSource::Blue`] arms are gated on the
-//! `image` feature — they decode an image — but the rest of the registry
-//! works without it, including the all-strings entry
+//! [`NoiseSource::File`], [`NoiseSource::Matrix`] and [`NoiseSource::Blue`]
+//! arms are gated on the `image` feature — they decode an image — but the
+//! rest of the registry works without it, including the all-strings entry
 //! [`parse_decompose_ditherer`].
+//!
+//! [`tiled_ditherer_with`] builds the same pipeline with a different
+//! palette per image region; see [`crate::dither::tiles`].
 
 use crate::Decomposer;
+use crate::barycentric::find_duplicate;
 use crate::decompose::DecomposerInputColor;
+use crate::decompose::bias::{BiasedDecomposer, InkBias};
 use crate::decompose::gray::{OffsetBlendGrayDecomposer, PureSpreadGrayDecomposer};
+use crate::decompose::max_inks::MaxInksDecomposer;
 use crate::decompose::naive::NaiveDecomposer;
+use crate::decompose::neutral::NeutralLockDecomposer;
 use crate::decompose::octahedron::OctahedronDecomposer;
-use crate::dither::diffusion_matrix::{DiffuseMethod, DiffusionMatrix, InvalidDiffuseMethod};
+use crate::decompose::response::GammaDecomposer;
+use crate::decompose::subtractive::{MixingModel, SubtractiveDecomposer};
+use crate::dither::barrier::DiffuseMask;
+use crate::dither::diffuse::{EdgeMode, PixelStrategy};
+use crate::dither::diffusion_matrix::{
+    DiffuseMethod, DiffusionMatrix, InvalidDiffuseMethod, ScanOrder,
+};
+use crate::dither::error_buffer::ErrorBytes;
+use crate::dither::lab::LabDiffusionStrategy;
+use crate::dither::mask::InkMask;
+use crate::dither::previous::PreviousFrame;
+use crate::dither::rgb::{ErrorSpace, RgbDiffusionStrategy};
+use crate::dither::tiles::{Region, TiledStrategy};
 use crate::dither::{
-    BundledDitherer, DecomposeStrategy, DecomposingDitherStrategy, DynDitherer, ImageReader,
-    ImageSize, ImageWriter, InvalidDecomposeStrategy,
+    BundledDitherer, DecomposeStrategy, DecomposingDitherStrategy, DynResumableDitherer,
+    ImageReader, ImageSize, ImageWriter, InvalidDecomposeStrategy, PickMode,
 };
 use crate::noise::{InvalidNoiseSource, NoiseSource};
 use crate::palette::{InvalidPalette, Palette};
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::marker::PhantomData;
 use nalgebra::geometry::Point3;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -43,9 +64,41 @@ pub enum FactoryError {
     /// Decomposer construction returned `None` (e.g. the octahedron
     /// palette doesn't form a valid octahedron).
     DecomposerBuildFailed,
+    /// Two palette entries are the same colour (see
+    /// [`find_duplicate`](crate::barycentric::find_duplicate)), which no
+    /// decomposer can tell apart.
+    DuplicateColors {
+        indices: [usize; 2],
+    },
+    /// [`FactoryOptions::non_finite_fallback`] or the
+    /// [`fill`](DiffuseMask::with_fill) of [`FactoryOptions::diffuse_mask`]
+    /// is not a palette index.
+    FallbackOutOfRange,
+    /// A [`tiled_ditherer_with`] region palette has a different number
+    /// of entries than the base palette.
+    TilePaletteSize,
     /// Failed to load or decode an external noise image.
     #[cfg(feature = "image")]
     NoiseImageError,
+    /// An octahedron-only query ([`octahedron_axis_for`]) for another
+    /// strategy.
+    NotOctahedron,
+    /// [`FactoryOptions::octahedron_axes`] rejected by
+    /// [`OctahedronDecomposer::new_with_axes`].
+    InvalidAxes,
+    /// The diffusion matrix aims error at pixels already dithered; see
+    /// [`DiffusionMatrix::is_causal`].
+    NonCausalMatrix,
+    /// [`DiffuseMethod::Adaptive`] picks its kernels from the input image,
+    /// which the string entry point doesn't have; build an
+    /// [`AdaptiveDiffusion`](crate::dither::diffusion_matrix::AdaptiveDiffusion)
+    /// and call [`decompose_ditherer`] instead.
+    ImageDependentDiffusion,
+    /// [`FactoryOptions::compactness`] is negative or not finite.
+    InvalidCompactness,
+    /// [`FactoryOptions::compactness`] is set for a strategy other than
+    /// the naive ones, which would ignore it.
+    CompactnessUnused,
 }
 
 impl core::fmt::Display for FactoryError {
@@ -59,14 +112,169 @@ impl core::fmt::Display for FactoryError {
                 "grayscale strategy requires an achromatic, strictly-ascending palette",
             ),
             Self::DecomposerBuildFailed => f.write_str("decomposer construction failed"),
+            Self::DuplicateColors { indices: [a, b] } => {
+                write!(f, "palette entries {a} and {b} are the same colour")
+            }
+            Self::FallbackOutOfRange => f.write_str("fallback index is outside the palette"),
+            Self::TilePaletteSize => {
+                f.write_str("every region palette needs as many entries as the base palette")
+            }
             #[cfg(feature = "image")]
             Self::NoiseImageError => f.write_str("failed to load or decode noise image"),
+            Self::NotOctahedron => f.write_str("strategy doesn't use the octahedron decomposer"),
+            Self::InvalidAxes => f.write_str(
+                "octahedron axes must pair every palette entry with a roughly opposite one",
+            ),
+            Self::NonCausalMatrix => {
+                f.write_str("diffusion matrix diffuses error to pixels already dithered")
+            }
+            Self::ImageDependentDiffusion => {
+                f.write_str("adaptive diffusion needs the input image to build its kernels")
+            }
+            Self::InvalidCompactness => f.write_str("compactness must be a finite number >= 0"),
+            Self::CompactnessUnused => {
+                f.write_str("compactness only applies to the naive strategies")
+            }
         }
     }
 }
 
 impl core::error::Error for FactoryError {}
 
+/// Pipeline knobs that apply across strategies and noise sources. Passed
+/// to [`decompose_ditherer_with`]; [`Default`] reproduces
+/// [`decompose_ditherer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactoryOptions {
+    /// Colour mixing model; see [`crate::decompose::subtractive`].
+    pub mixing: MixingModel,
+    /// Per-ink weight scaling applied to every decomposition; see
+    /// [`crate::decompose::bias`]. Empty means no bias.
+    pub ink_bias: Vec<InkBias>,
+    /// Per-ink response exponents, by palette index, applied to every
+    /// decomposition before `ink_bias`; see [`crate::decompose::response`].
+    /// Empty means every ink responds linearly.
+    pub ink_gamma: Vec<f32>,
+    /// Seed for per-pixel palette index order in the noise pick; see
+    /// [`DecomposingDitherStrategy`]. `None` walks indices in order.
+    pub index_order_seed: Option<u64>,
+    /// Indices currently on the panel, to keep where nearly as good; see
+    /// [`crate::dither::previous`].
+    pub previous: Option<PreviousFrame>,
+    /// Per-pixel ink weighting; see [`crate::dither::mask`]. `None`
+    /// weights every pixel alike.
+    pub mask: Option<InkMask>,
+    /// Pixels error diffusion doesn't cross; see
+    /// [`crate::dither::barrier`]. `None` diffuses everywhere.
+    pub diffuse_mask: Option<DiffuseMask>,
+    /// How the noise value picks an index; see [`PickMode`].
+    /// [`DecomposeStrategy::DominantTexture`] overrides it.
+    pub pick: PickMode,
+    /// Ordered-dither strength in `[0, 1]`; see
+    /// [`crate::noise::scale_amplitude`]. Defaults to 1.
+    pub noise_amplitude: f32,
+    /// Weights within this of the largest share the noise pick evenly;
+    /// see [`DecomposingDitherStrategy::with_tie_margin`]. 0 picks in
+    /// proportion. Not for `lab_diffusion`.
+    pub tie_margin: f32,
+    /// `[x, y]` added to pixel coordinates before sampling the noise, to
+    /// align its pattern with a panel origin or shift it between runs.
+    /// Offsets by a multiple of a periodic pattern's size (8 for
+    /// `bayer:3`) change nothing.
+    pub noise_offset: [usize; 2],
+    /// Sample the noise half its tile width further right on odd rows, so
+    /// consecutive rows of a tiled pattern don't line up in phase, which
+    /// can show as faint horizontal banding in raster-order dithering.
+    /// This decorrelates the rows of blue-noise and file tiles; a Bayer
+    /// tile shifted by half its width only swaps its finest threshold
+    /// steps, so square Bayer patterns barely change. Noise without a tile
+    /// (`ign`, `white`, infinite `bayer`) is unaffected.
+    pub noise_row_phase: bool,
+    /// Penalty on wide tetrahedra for the naive strategies; see
+    /// [`NaiveDecomposer::with_compactness`]. 0 disables it; anything
+    /// else is an error for the other strategies.
+    pub compactness: f32,
+    /// Most inks mixed per pixel for the RGB strategies; see
+    /// [`MaxInksDecomposer`]. `None` leaves decompositions unrestricted.
+    pub max_inks: Option<usize>,
+    /// Chroma at or below which the RGB strategies mix only the darkest
+    /// and brightest inks; see [`NeutralLockDecomposer`]. `None` leaves
+    /// grays to the decomposer.
+    pub neutral_lock: Option<f32>,
+    /// Palette index emitted for non-finite source pixels; see
+    /// [`DecomposingDitherStrategy::with_fallback`]. `None` picks the
+    /// darkest palette entry (black for the built-in palettes).
+    pub non_finite_fallback: Option<usize>,
+    /// Handling of diffusion error aimed past the side edges.
+    pub edges: EdgeMode,
+    /// Row order; [`ScanOrder::Auto`] follows the matrix's preference.
+    pub scan: ScanOrder,
+    /// Diffuse colour error in CIELAB instead of weight error, for the RGB
+    /// strategies; see [`crate::dither::lab`]. Index order, previous
+    /// frame, masks and pick mode don't apply there.
+    pub lab_diffusion: bool,
+    /// Space the diffused error is measured in, for the RGB strategies;
+    /// [`ErrorSpace::Rgb`] bypasses the decomposer, see
+    /// [`crate::dither::rgb`], so noise, ink bias and gamma, masks, the
+    /// previous frame and `lab_diffusion` don't apply there.
+    pub error_space: ErrorSpace,
+    /// Pick each index with one pixel of lookahead; see
+    /// [`DecomposingDitherStrategy::with_lookahead`]. Noise, pick mode and
+    /// index order don't apply then, nor does it to `lab_diffusion`.
+    pub lookahead: bool,
+    /// Scale the diffused error by how deep inside the gamut each pixel's
+    /// colour lies; see
+    /// [`DecomposingDitherStrategy::with_confidence_weighting`]. Not for
+    /// `lab_diffusion`.
+    pub confidence_weighting: bool,
+    /// Palette index pairs to use as the octahedron's axes; see
+    /// [`OctahedronDecomposer::new_with_axes`]. `None` finds them from the
+    /// palette.
+    pub octahedron_axes: Option<[(usize, usize); 3]>,
+}
+
+impl Default for FactoryOptions {
+    fn default() -> Self {
+        Self {
+            mixing: MixingModel::default(),
+            ink_bias: Vec::new(),
+            ink_gamma: Vec::new(),
+            index_order_seed: None,
+            previous: None,
+            mask: None,
+            diffuse_mask: None,
+            pick: PickMode::default(),
+            noise_amplitude: 1.0,
+            tie_margin: 0.0,
+            noise_offset: [0, 0],
+            noise_row_phase: false,
+            compactness: 0.0,
+            max_inks: None,
+            neutral_lock: None,
+            non_finite_fallback: None,
+            edges: EdgeMode::Drop,
+            scan: ScanOrder::Auto,
+            lab_diffusion: false,
+            error_space: ErrorSpace::default(),
+            lookahead: false,
+            confidence_weighting: false,
+            octahedron_axes: None,
+        }
+    }
+}
+
+/// `strategy` and `matrix` with the diffusion settings of `options`.
+fn bundled<S, M: DiffusionMatrix>(
+    strategy: S,
+    matrix: M,
+    options: &FactoryOptions,
+) -> BundledDitherer<S, M> {
+    let serpentine = options.scan.is_serpentine(&matrix);
+    BundledDitherer::new(strategy, matrix)
+        .with_serpentine(serpentine)
+        .with_edges(options.edges)
+}
+
 /// True iff every entry is achromatic and the entries are strictly
 /// ascending in brightness.
 fn verify_grayscale_palette<Q: DecomposerInputColor>(p: &[Q]) -> bool {
@@ -74,94 +282,440 @@ fn verify_grayscale_palette<Q: DecomposerInputColor>(p: &[Q]) -> bool {
         && p.windows(2).all(|w| w[0].brightness() < w[1].brightness())
 }
 
-fn build_decomposing<D, F, Src, N, T>(
+/// `decomposer` as a [`DecomposingDitherStrategy`] with `options` and
+/// `noise_fn`, bundled with `matrix`.
+fn build_decomposing<D, Src, N, T>(
     decomposer: D,
-    convert: F,
     noise_fn: Option<N>,
     matrix: impl DiffusionMatrix + Send + Sync + 'static,
-) -> Box<dyn DynDitherer<T> + Send + Sync>
+    options: &FactoryOptions,
+) -> Box<dyn DynResumableDitherer<T> + Send + Sync>
 where
-    D: Decomposer<f32> + Send + Sync + 'static,
-    F: Fn(Src) -> D::Input + Send + Sync + 'static,
-    Src: 'static,
+    D: Decomposer<f32, Input = Src> + Send + Sync + 'static,
+    Src: DecomposerInputColor + 'static,
     N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
     T: ImageSize + ImageReader<Src> + ImageWriter<usize> + ?Sized + 'static,
 {
-    let strategy = DecomposingDitherStrategy::new(decomposer, convert);
+    let strategy = configured(decomposer, |p: Src| p, options);
     match noise_fn {
-        Some(n) => Box::new(BundledDitherer::new(strategy.with_noise(n), matrix)),
-        None => Box::new(BundledDitherer::new(strategy, matrix)),
+        Some(n) => Box::new(bundled(strategy.with_noise(n), matrix, options)),
+        None => Box::new(bundled(strategy, matrix, options)),
+    }
+}
+
+/// [`DecomposingDitherStrategy`] before [`with_noise`](DecomposingDitherStrategy::with_noise).
+type Noiseless<D, F, Src> = DecomposingDitherStrategy<D, F, fn(usize, usize) -> f32, Src>;
+
+/// `decomposer` with `options.ink_gamma`, then `options.ink_bias`, applied
+/// to its weights.
+fn biased<D>(
+    decomposer: D,
+    options: &FactoryOptions,
+) -> BiasedDecomposer<GammaDecomposer<D, Vec<f32>>, Vec<InkBias>> {
+    BiasedDecomposer::new(
+        GammaDecomposer::new(decomposer, options.ink_gamma.clone()),
+        options.ink_bias.clone(),
+    )
+}
+
+/// Noise-less [`DecomposingDitherStrategy`] with `options` applied.
+fn configured<D, F, Src>(
+    decomposer: D,
+    convert: F,
+    options: &FactoryOptions,
+) -> Noiseless<D, F, Src>
+where
+    Src: DecomposerInputColor,
+{
+    DecomposingDitherStrategy::new(decomposer, convert)
+        .with_fallback(options.non_finite_fallback)
+        .with_index_order_seed(options.index_order_seed)
+        .with_previous(options.previous.clone())
+        .with_mask(options.mask.clone())
+        .with_diffuse_mask(options.diffuse_mask.clone())
+        .with_pick(options.pick)
+        .with_noise_amplitude(options.noise_amplitude)
+        .with_tie_margin(options.tie_margin)
+        .with_lookahead(options.lookahead)
+        .with_confidence_weighting(options.confidence_weighting)
+}
+
+/// Rejects palettes listing the same colour twice.
+fn check_distinct<Q: DecomposerInputColor>(palette: &[Q]) -> Result<(), FactoryError> {
+    let points: Vec<_> = palette.iter().map(|q| q.to_point()).collect();
+    match find_duplicate(&points) {
+        Some(indices) => Err(FactoryError::DuplicateColors { indices }),
+        None => Ok(()),
+    }
+}
+
+/// Palette points in the RGB decomposers' input space under `mixing`,
+/// after checking the palette has no duplicates.
+fn rgb_palette_points<Q: DecomposerInputColor>(
+    palette: &[Q],
+    mixing: MixingModel,
+) -> Result<Vec<Point3<f32>>, FactoryError> {
+    check_distinct(palette)?;
+    Ok(palette
+        .iter()
+        .map(|q| match mixing {
+            MixingModel::Additive => q.to_point(),
+            MixingModel::Subtractive => crate::decompose::subtractive::to_density(&q.to_point()),
+        })
+        .collect())
+}
+
+/// 1-D counterpart of [`rgb_palette_points`]. Subtractive uses negated
+/// density so the levels stay ascending; barycentric weights are
+/// unaffected by the sign flip.
+fn gray_level(brightness: f32, mixing: MixingModel) -> f32 {
+    match mixing {
+        MixingModel::Additive => brightness,
+        MixingModel::Subtractive => -crate::decompose::subtractive::to_density_scalar(brightness),
     }
 }
 
+/// Grayscale decomposer levels for `palette`, after checking it suits a
+/// grayscale strategy.
+fn gray_levels<Q: DecomposerInputColor>(
+    palette: &[Q],
+    mixing: MixingModel,
+) -> Result<Vec<f32>, FactoryError> {
+    check_distinct(palette)?;
+    if !verify_grayscale_palette(palette) {
+        return Err(FactoryError::NonGrayscalePalette);
+    }
+    Ok(palette
+        .iter()
+        .map(|q| gray_level(q.brightness(), mixing))
+        .collect())
+}
+
+/// `decomposer` behind a [`NeutralLockDecomposer`], with the darkest and
+/// brightest of `inks` as poles.
+fn neutral_locked<D>(
+    decomposer: D,
+    points: &[Point3<f32>],
+    inks: &[Point3<f32>],
+    threshold: f32,
+) -> Result<NeutralLockDecomposer<D>, FactoryError>
+where
+    D: Decomposer<f32, Input = Point3<f32>>,
+{
+    let by_lightness =
+        |a: &usize, b: &usize| inks[*a].coords.sum().total_cmp(&inks[*b].coords.sum());
+    let black = (0..inks.len()).min_by(by_lightness);
+    let white = (0..inks.len()).max_by(by_lightness);
+    black
+        .zip(white)
+        .and_then(|(black, white)| {
+            NeutralLockDecomposer::new(decomposer, points, [black, white], threshold)
+        })
+        .ok_or(FactoryError::DecomposerBuildFailed)
+}
+
+fn build_lab<D, P, N, T>(
+    decomposer: D,
+    inks: &[Point3<f32>],
+    options: &FactoryOptions,
+    noise_fn: Option<N>,
+    matrix: impl DiffusionMatrix + Send + Sync + 'static,
+) -> Result<Box<dyn DynResumableDitherer<T> + Send + Sync>, FactoryError>
+where
+    D: Decomposer<f32, Input = Point3<f32>> + Send + Sync + 'static,
+    P: DecomposerInputColor + 'static,
+    N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
+    T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
+{
+    let strategy = LabDiffusionStrategy::<D, _, P>::new(decomposer, inks)
+        .ok_or(FactoryError::DecomposerBuildFailed)?
+        .with_noise_amplitude(options.noise_amplitude)
+        .with_fallback(options.non_finite_fallback.unwrap_or(0));
+    Ok(match noise_fn {
+        Some(n) => Box::new(bundled(strategy.with_noise(n), matrix, options)),
+        None => Box::new(bundled(strategy, matrix, options)),
+    })
+}
+
+/// Octahedron decomposer over `points`, on
+/// [`FactoryOptions::octahedron_axes`] if given.
+fn octahedron(
+    points: &[Point3<f32>],
+    options: &FactoryOptions,
+) -> Result<OctahedronDecomposer<f32>, FactoryError> {
+    match options.octahedron_axes {
+        Some(axes) => {
+            OctahedronDecomposer::new_with_axes(points, axes).ok_or(FactoryError::InvalidAxes)
+        }
+        None => OctahedronDecomposer::new(points).ok_or(FactoryError::DecomposerBuildFailed),
+    }
+}
+
+/// Index of the lowest-brightness entry of `palette` (the first on ties).
+fn darkest_entry<Q: DecomposerInputColor>(palette: &[Q]) -> Option<usize> {
+    (0..palette.len()).reduce(|darkest, index| {
+        if palette[index].brightness() < palette[darkest].brightness() {
+            index
+        } else {
+            darkest
+        }
+    })
+}
+
+/// `options` with [`FactoryOptions::non_finite_fallback`] checked against
+/// `palette`, or resolved to its darkest entry, and the diffuse mask's
+/// fill checked too.
+fn with_resolved_fallback<Q: DecomposerInputColor>(
+    palette: &[Q],
+    options: &FactoryOptions,
+) -> Result<FactoryOptions, FactoryError> {
+    if let Some(fill) = options.diffuse_mask.as_ref().and_then(DiffuseMask::fill)
+        && fill >= palette.len()
+    {
+        return Err(FactoryError::FallbackOutOfRange);
+    }
+    let fallback = match options.non_finite_fallback {
+        Some(index) if index >= palette.len() => return Err(FactoryError::FallbackOutOfRange),
+        Some(index) => Some(index),
+        None => darkest_entry(palette),
+    };
+    Ok(FactoryOptions {
+        non_finite_fallback: fallback,
+        ..options.clone()
+    })
+}
+
 fn build_with_noise<P, Q, N, T>(
     strategy: DecomposeStrategy,
     palette: &[Q],
     noise_fn: Option<N>,
     matrix: impl DiffusionMatrix + Send + Sync + 'static,
-) -> Result<Box<dyn DynDitherer<T> + Send + Sync>, FactoryError>
+    options: &FactoryOptions,
+) -> Result<Box<dyn DynResumableDitherer<T> + Send + Sync>, FactoryError>
 where
     P: DecomposerInputColor + 'static,
     Q: DecomposerInputColor,
     N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
     T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
 {
-    match strategy {
+    let mut options = with_resolved_fallback(palette, options)?;
+    if strategy == DecomposeStrategy::DominantTexture {
+        options.pick = PickMode::DominantTexture;
+    }
+    let options = &options;
+    let rgb = !matches!(
+        strategy,
+        DecomposeStrategy::GrayPureSpread(_) | DecomposeStrategy::GrayOffsetBlend(_)
+    );
+    if rgb && options.error_space == ErrorSpace::Rgb {
+        // Unused, but building it still vets the palette.
+        decomposer_for::<P, Q>(strategy, palette, options)?;
+        let inks: Vec<Point3<f32>> = palette.iter().map(Q::to_point).collect();
+        let strategy = RgbDiffusionStrategy::<P>::new(&inks)
+            .ok_or(FactoryError::DecomposerBuildFailed)?
+            .with_fallback(options.non_finite_fallback.unwrap_or(0));
+        return Ok(Box::new(bundled(strategy, matrix, options)));
+    }
+    if rgb && options.lab_diffusion {
+        let decomposer = decomposer_for::<Point3<f32>, Q>(strategy, palette, options)?;
+        let inks: Vec<Point3<f32>> = palette.iter().map(Q::to_point).collect();
+        return build_lab(decomposer, &inks, options, noise_fn, matrix);
+    }
+    let decomposer = decomposer_for::<P, Q>(strategy, palette, options)?;
+    Ok(build_decomposing(decomposer, noise_fn, matrix, options))
+}
+
+/// Decomposer plus the conversion from source pixels into its input, so
+/// [`decomposer_for`] can hand back a single source-pixel decomposer.
+struct Converted<D, F, P> {
+    decomposer: D,
+    convert: F,
+    _phantom: core::marker::PhantomData<fn(&P)>,
+}
+
+impl<D, F, P> Decomposer<f32> for Converted<D, F, P>
+where
+    D: Decomposer<f32>,
+    F: Fn(&P) -> D::Input,
+{
+    type Input = P;
+
+    fn palette_size(&self) -> usize {
+        self.decomposer.palette_size()
+    }
+
+    fn decompose_into(&self, input: &P, out: &mut [f32]) {
+        self.decomposer.decompose_into(&(self.convert)(input), out);
+    }
+
+    fn confidence(&self, input: &P) -> f32 {
+        self.decomposer.confidence(&(self.convert)(input))
+    }
+}
+
+fn boxed_decomposer<D, F, P>(
+    decomposer: D,
+    convert: F,
+    options: &FactoryOptions,
+) -> Box<dyn Decomposer<f32, Input = P> + Send + Sync>
+where
+    D: Decomposer<f32> + Send + Sync + 'static,
+    F: Fn(&P) -> D::Input + Send + Sync + 'static,
+    P: 'static,
+{
+    let converted = Converted {
+        decomposer,
+        convert,
+        _phantom: core::marker::PhantomData,
+    };
+    if options.ink_bias.is_empty() && options.ink_gamma.is_empty() {
+        Box::new(converted)
+    } else {
+        Box::new(biased(converted, options))
+    }
+}
+
+/// [`boxed_decomposer`] counterpart of [`build_rgb`].
+fn boxed_rgb_decomposer<D, P>(
+    decomposer: D,
+    points: &[Point3<f32>],
+    inks: &[Point3<f32>],
+    options: &FactoryOptions,
+) -> Result<Box<dyn Decomposer<f32, Input = P> + Send + Sync>, FactoryError>
+where
+    D: Decomposer<f32, Input = Point3<f32>> + Send + Sync + 'static,
+    P: DecomposerInputColor + 'static,
+{
+    if let Some(threshold) = options.neutral_lock {
+        let locked = neutral_locked(decomposer, points, inks, threshold)?;
+        return boxed_rgb_capped(locked, points, options);
+    }
+    boxed_rgb_capped(decomposer, points, options)
+}
+
+fn boxed_rgb_capped<D, P>(
+    decomposer: D,
+    points: &[Point3<f32>],
+    options: &FactoryOptions,
+) -> Result<Box<dyn Decomposer<f32, Input = P> + Send + Sync>, FactoryError>
+where
+    D: Decomposer<f32, Input = Point3<f32>> + Send + Sync + 'static,
+    P: DecomposerInputColor + 'static,
+{
+    if let Some(max_inks) = options.max_inks {
+        let limited = MaxInksDecomposer::new(decomposer, points, max_inks)
+            .ok_or(FactoryError::DecomposerBuildFailed)?;
+        return Ok(boxed_rgb_mixing(limited, options));
+    }
+    Ok(boxed_rgb_mixing(decomposer, options))
+}
+
+fn boxed_rgb_mixing<D, P>(
+    decomposer: D,
+    options: &FactoryOptions,
+) -> Box<dyn Decomposer<f32, Input = P> + Send + Sync>
+where
+    D: Decomposer<f32, Input = Point3<f32>> + Send + Sync + 'static,
+    P: DecomposerInputColor + 'static,
+{
+    let rgb = |p: &P| p.to_point();
+    match options.mixing {
+        MixingModel::Additive => boxed_decomposer(decomposer, rgb, options),
+        MixingModel::Subtractive => {
+            boxed_decomposer(SubtractiveDecomposer::new(decomposer), rgb, options)
+        }
+    }
+}
+
+/// The octahedron axis the decomposer of [`decomposer_for`] picks for
+/// each source pixel, as [`OctahedronDecomposer::chosen_axis`]; only the
+/// mixing model of `options` matters. Fails with
+/// [`FactoryError::NotOctahedron`] for strategies that don't decompose on
+/// an octahedron.
+pub fn octahedron_axis_for<P, Q>(
+    strategy: DecomposeStrategy,
+    palette: &[Q],
+    options: &FactoryOptions,
+) -> Result<impl Fn(&P) -> Option<usize> + Send + Sync + use<P, Q>, FactoryError>
+where
+    P: DecomposerInputColor,
+    Q: DecomposerInputColor,
+{
+    let axis = match strategy {
+        DecomposeStrategy::Octahedron(axis) => axis,
+        DecomposeStrategy::DominantTexture => Default::default(),
+        _ => return Err(FactoryError::NotOctahedron),
+    };
+    let points = rgb_palette_points(palette, options.mixing)?;
+    let decomposer = octahedron(&points, options)?.with_strategy(axis);
+    let mixing = options.mixing;
+    Ok(move |p: &P| {
+        let point = match mixing {
+            MixingModel::Additive => p.to_point(),
+            MixingModel::Subtractive => crate::decompose::subtractive::to_density(&p.to_point()),
+        };
+        decomposer.chosen_axis(&point)
+    })
+}
+
+/// The decomposer [`decompose_ditherer_with`] uses for `strategy`,
+/// `palette` and `options` (mixing model and ink bias included), taking
+/// source pixels directly. The factory builds every decomposing ditherer
+/// through here, so inspecting the weights, e.g. dumping them as images,
+/// sees exactly what the ditherer does.
+pub fn decomposer_for<P, Q>(
+    strategy: DecomposeStrategy,
+    palette: &[Q],
+    options: &FactoryOptions,
+) -> Result<Box<dyn Decomposer<f32, Input = P> + Send + Sync>, FactoryError>
+where
+    P: DecomposerInputColor + 'static,
+    Q: DecomposerInputColor,
+{
+    if !(options.compactness.is_finite() && options.compactness >= 0.0) {
+        return Err(FactoryError::InvalidCompactness);
+    }
+    if options.compactness != 0.0 && !matches!(strategy, DecomposeStrategy::Naive(_)) {
+        return Err(FactoryError::CompactnessUnused);
+    }
+    let mixing = options.mixing;
+    let gray = move |p: &P| gray_level(p.brightness(), mixing);
+    let inks: Vec<Point3<f32>> = palette.iter().map(Q::to_point).collect();
+    Ok(match strategy {
+        // The pick mode doesn't affect the weights.
+        DecomposeStrategy::DominantTexture => {
+            return decomposer_for(
+                DecomposeStrategy::Octahedron(Default::default()),
+                palette,
+                options,
+            );
+        }
         DecomposeStrategy::Octahedron(axis) => {
-            let palette_points: Vec<Point3<f32>> = palette.iter().map(|q| q.to_point()).collect();
-            let decomposer = OctahedronDecomposer::new(&palette_points)
-                .ok_or(FactoryError::DecomposerBuildFailed)?
-                .with_strategy(axis);
-            Ok(build_decomposing(
-                decomposer,
-                |p: P| p.to_point(),
-                noise_fn,
-                matrix,
-            ))
+            let points = rgb_palette_points(palette, mixing)?;
+            let decomposer = octahedron(&points, options)?.with_strategy(axis);
+            boxed_rgb_decomposer(decomposer, &points, &inks, options)?
         }
         DecomposeStrategy::Naive(naive) => {
-            let palette_points: Vec<Point3<f32>> = palette.iter().map(|q| q.to_point()).collect();
-            let decomposer = NaiveDecomposer::new(&palette_points)
+            let points = rgb_palette_points(palette, mixing)?;
+            let decomposer = NaiveDecomposer::new(&points)
                 .ok_or(FactoryError::DecomposerBuildFailed)?
-                .with_strategy(naive);
-            Ok(build_decomposing(
-                decomposer,
-                |p: P| p.to_point(),
-                noise_fn,
-                matrix,
-            ))
+                .with_strategy(naive)
+                .with_compactness(options.compactness);
+            boxed_rgb_decomposer(decomposer, &points, &inks, options)?
         }
         DecomposeStrategy::GrayPureSpread(spread) => {
-            if !verify_grayscale_palette(palette) {
-                return Err(FactoryError::NonGrayscalePalette);
-            }
-            let levels: Vec<f32> = palette.iter().map(|q| q.brightness()).collect();
-            let decomposer = PureSpreadGrayDecomposer::new(levels)
+            let decomposer = PureSpreadGrayDecomposer::new(gray_levels(palette, mixing)?)
                 .ok_or(FactoryError::DecomposerBuildFailed)?
                 .with_spread_ratio(spread);
-            Ok(build_decomposing(
-                decomposer,
-                |p: P| p.brightness(),
-                noise_fn,
-                matrix,
-            ))
+            boxed_decomposer(decomposer, gray, options)
         }
         DecomposeStrategy::GrayOffsetBlend(distance) => {
-            if !verify_grayscale_palette(palette) {
-                return Err(FactoryError::NonGrayscalePalette);
-            }
-            let levels: Vec<f32> = palette.iter().map(|q| q.brightness()).collect();
-            let decomposer = OffsetBlendGrayDecomposer::new(levels)
+            let decomposer = OffsetBlendGrayDecomposer::new(gray_levels(palette, mixing)?)
                 .ok_or(FactoryError::DecomposerBuildFailed)?
                 .with_distance(distance);
-            Ok(build_decomposing(
-                decomposer,
-                |p: P| p.brightness(),
-                noise_fn,
-                matrix,
-            ))
+            boxed_decomposer(decomposer, gray, options)
         }
-    }
+    })
 }
 
 #[cfg(feature = "image")]
@@ -174,7 +728,7 @@ fn sample_luma_image(
         .0[0]
 }
 
-/// Build a `Box<dyn DynDitherer<T> + Send + Sync>` from already-parsed
+/// Build a `Box<dyn DynResumableDitherer<T> + Send + Sync>` from already-parsed
 /// configuration. Use [`parse_decompose_ditherer`] for the all-strings
 /// entry point.
 ///
@@ -188,44 +742,103 @@ pub fn decompose_ditherer<P, Q, T>(
     noise: NoiseSource,
     palette: &[Q],
     matrix: impl DiffusionMatrix + Send + Sync + 'static,
-) -> Result<Box<dyn DynDitherer<T> + Send + Sync>, FactoryError>
+) -> Result<Box<dyn DynResumableDitherer<T> + Send + Sync>, FactoryError>
 where
     P: DecomposerInputColor + 'static,
     Q: DecomposerInputColor,
     T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
 {
-    match noise {
-        NoiseSource::None => build_with_noise::<P, Q, fn(usize, usize) -> f32, T>(
-            strategy, palette, None, matrix,
-        ),
-        NoiseSource::Bayer(Some(n)) => build_with_noise(
-            strategy,
-            palette,
-            Some(move |x, y| crate::noise::bayer(x, y, n)),
-            matrix,
-        ),
-        NoiseSource::Bayer(None) => build_with_noise(
-            strategy,
-            palette,
-            Some(|x, y| crate::noise::bayer_inf(x, y)),
-            matrix,
-        ),
-        NoiseSource::InterleavedGradient => build_with_noise(
+    decompose_ditherer_with(strategy, noise, palette, matrix, &FactoryOptions::default())
+}
+
+/// [`decompose_ditherer`] with explicit [`FactoryOptions`].
+pub fn decompose_ditherer_with<P, Q, T>(
+    strategy: DecomposeStrategy,
+    noise: NoiseSource,
+    palette: &[Q],
+    matrix: impl DiffusionMatrix + Send + Sync + 'static,
+    options: &FactoryOptions,
+) -> Result<Box<dyn DynResumableDitherer<T> + Send + Sync>, FactoryError>
+where
+    P: DecomposerInputColor + 'static,
+    Q: DecomposerInputColor,
+    T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
+{
+    if !matrix.is_causal() {
+        return Err(FactoryError::NonCausalMatrix);
+    }
+    resolve_noise(
+        noise,
+        options.noise_offset,
+        options.noise_row_phase,
+        Untiled {
             strategy,
             palette,
-            Some(|x, y| crate::noise::interleaved_gradient_noise(x as f32, y as f32)),
             matrix,
-        ),
+            options,
+            _phantom: PhantomData::<fn(P, &T)>,
+        },
+    )
+}
+
+/// Something built around the noise function, so [`resolve_noise`] can
+/// hand every [`NoiseSource`] over as its own concrete closure type.
+trait WithNoise {
+    type Output;
+
+    fn build<N>(self, noise_fn: Option<N>) -> Result<Self::Output, FactoryError>
+    where
+        N: Fn(usize, usize) -> f32 + Send + Sync + 'static;
+}
+
+/// `noise` as a closure, sampled `offset` further on, handed to `with`.
+/// With `row_phase`, odd rows of tiled noise are sampled half a tile
+/// further right; see [`FactoryOptions::noise_row_phase`].
+fn resolve_noise<W: WithNoise>(
+    noise: NoiseSource,
+    offset: [usize; 2],
+    row_phase: bool,
+    with: W,
+) -> Result<W::Output, FactoryError> {
+    let with = Offset {
+        inner: with,
+        offset,
+        row_shift: 0,
+    };
+    // `with`, shifting odd rows by half of a `width` wide tile.
+    let tiled = |with: Offset<W>, width: usize| Offset {
+        row_shift: if row_phase { width / 2 } else { 0 },
+        ..with
+    };
+    // Width of a `2^depth` Bayer tile, 0 if it doesn't fit.
+    let bayer_width = |depth: usize| {
+        u32::try_from(depth)
+            .ok()
+            .and_then(|depth| 1usize.checked_shl(depth))
+            .unwrap_or(0)
+    };
+    match noise {
+        NoiseSource::None => with.build::<fn(usize, usize) -> f32>(None),
+        NoiseSource::Bayer(Some(n)) => {
+            tiled(with, bayer_width(n)).build(Some(move |x, y| crate::noise::bayer(x, y, n)))
+        }
+        NoiseSource::BayerRect(depth_x, depth_y) => {
+            tiled(with, bayer_width(depth_x)).build(Some(move |x, y| {
+                crate::noise::bayer_rect(x, y, depth_x, depth_y)
+            }))
+        }
+        NoiseSource::Bayer(None) => with.build(Some(crate::noise::bayer_inf)),
+        NoiseSource::InterleavedGradient => with.build(Some(|x, y| {
+            crate::noise::interleaved_gradient_noise(x as f32, y as f32)
+        })),
         #[cfg(feature = "rand")]
         NoiseSource::White => {
-            use rand::Rng;
-            use rand::distr::StandardUniform;
-            build_with_noise(
-                strategy,
-                palette,
-                Some(|_x, _y| rand::rng().sample::<f32, _>(StandardUniform)),
-                matrix,
-            )
+            let noise = crate::noise::WhiteNoise::new(rand::random());
+            with.build(Some(move |x, y| noise.sample(x, y)))
+        }
+        NoiseSource::WhiteSeeded(seed) => {
+            let noise = crate::noise::WhiteNoise::new(seed);
+            with.build(Some(move |x, y| noise.sample(x, y)))
         }
         #[cfg(feature = "image")]
         NoiseSource::File(path) => {
@@ -234,25 +847,213 @@ where
                 .decode()
                 .map_err(|_| FactoryError::NoiseImageError)?
                 .to_luma32f();
-            build_with_noise(
-                strategy,
-                palette,
-                Some(move |x, y| sample_luma_image(&img, x, y)),
-                matrix,
-            )
+            let width = img.width() as usize;
+            tiled(with, width).build(Some(move |x, y| sample_luma_image(&img, x, y)))
+        }
+        #[cfg(feature = "image")]
+        NoiseSource::Matrix(path) => {
+            let img = image::ImageReader::open(&path)
+                .map_err(|_| FactoryError::NoiseImageError)?
+                .decode()
+                .map_err(|_| FactoryError::NoiseImageError)?
+                .to_luma32f();
+            let (width, height) = (img.width() as usize, img.height() as usize);
+            let matrix = crate::noise::ThresholdMatrix::new(width, height, img.as_raw())
+                .ok_or(FactoryError::NoiseImageError)?;
+            tiled(with, width).build(Some(move |x, y| matrix.sample(x, y)))
         }
         #[cfg(feature = "image")]
         NoiseSource::Blue => {
             let img = image::load_from_memory(crate::noise::BLUE_NOISE_PNG)
                 .map_err(|_| FactoryError::NoiseImageError)?
                 .to_luma32f();
-            build_with_noise(
-                strategy,
-                palette,
-                Some(move |x, y| sample_luma_image(&img, x, y)),
-                matrix,
-            )
+            let width = img.width() as usize;
+            tiled(with, width).build(Some(move |x, y| sample_luma_image(&img, x, y)))
         }
+        #[cfg(feature = "alloc")]
+        NoiseSource::SpatioTemporal(t) => {
+            let [width, height, frames] = crate::noise::STBN_SIZE;
+            let noise = crate::noise::SpatioTemporal::generate(width, height, frames, 0);
+            tiled(with, width).build(noise.map(|noise| move |x, y| noise.sample(x, y, t)))
+        }
+    }
+}
+
+/// [`WithNoise`] passing the noise on with its coordinates shifted by
+/// `offset`, and odd rows by `row_shift` more; see
+/// [`FactoryOptions::noise_offset`] and [`FactoryOptions::noise_row_phase`].
+struct Offset<W> {
+    inner: W,
+    offset: [usize; 2],
+    row_shift: usize,
+}
+
+impl<W: WithNoise> WithNoise for Offset<W> {
+    type Output = W::Output;
+
+    fn build<N>(self, noise_fn: Option<N>) -> Result<Self::Output, FactoryError>
+    where
+        N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
+    {
+        let ([dx, dy], row_shift) = (self.offset, self.row_shift);
+        self.inner.build(noise_fn.map(|noise| {
+            move |x: usize, y: usize| {
+                let y = y.wrapping_add(dy);
+                let shift = if y % 2 == 1 { row_shift } else { 0 };
+                noise(x.wrapping_add(dx).wrapping_add(shift), y)
+            }
+        }))
+    }
+}
+
+/// [`WithNoise`] for [`decompose_ditherer_with`].
+struct Untiled<'a, Q, M, Marker> {
+    strategy: DecomposeStrategy,
+    palette: &'a [Q],
+    matrix: M,
+    options: &'a FactoryOptions,
+    _phantom: PhantomData<Marker>,
+}
+
+impl<P, Q, M, T> WithNoise for Untiled<'_, Q, M, fn(P, &T)>
+where
+    P: DecomposerInputColor + 'static,
+    Q: DecomposerInputColor,
+    M: DiffusionMatrix + Send + Sync + 'static,
+    T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
+{
+    type Output = Box<dyn DynResumableDitherer<T> + Send + Sync>;
+
+    fn build<N>(self, noise_fn: Option<N>) -> Result<Self::Output, FactoryError>
+    where
+        N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
+    {
+        build_with_noise::<P, Q, N, T>(
+            self.strategy,
+            self.palette,
+            noise_fn,
+            self.matrix,
+            self.options,
+        )
+    }
+}
+
+/// [`decompose_ditherer_with`] with a palette per image region: pixels in
+/// one of `tiles` are decomposed against that region's palette (the first
+/// listed where regions overlap), the rest against `palette`. Error
+/// diffusion runs across the whole image, seams included; see
+/// [`crate::dither::tiles`].
+///
+/// Every region palette must list the same inks as `palette`, in the same
+/// order: the output indices and the diffused per-ink error are shared.
+/// Only the entry count is checked ([`FactoryError::TilePaletteSize`]).
+/// The non-finite fallback is resolved against `palette`, and
+/// [`FactoryOptions::lab_diffusion`] is not supported here.
+pub fn tiled_ditherer_with<P, Q, T>(
+    strategy: DecomposeStrategy,
+    noise: NoiseSource,
+    palette: &[Q],
+    tiles: &[(Region, Vec<Q>)],
+    matrix: impl DiffusionMatrix + Send + Sync + 'static,
+    options: &FactoryOptions,
+) -> Result<Box<dyn DynResumableDitherer<T> + Send + Sync>, FactoryError>
+where
+    P: DecomposerInputColor + 'static,
+    Q: DecomposerInputColor,
+    T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
+{
+    if tiles.iter().any(|(_, tile)| tile.len() != palette.len()) {
+        return Err(FactoryError::TilePaletteSize);
+    }
+    if !matrix.is_causal() {
+        return Err(FactoryError::NonCausalMatrix);
+    }
+    let mut options = with_resolved_fallback(palette, options)?;
+    if strategy == DecomposeStrategy::DominantTexture {
+        options.pick = PickMode::DominantTexture;
+    }
+    let tiles = tiles
+        .iter()
+        .map(|(region, tile)| Ok((*region, decomposer_for(strategy, tile, &options)?)))
+        .collect::<Result<_, FactoryError>>()?;
+    resolve_noise(
+        noise,
+        options.noise_offset,
+        options.noise_row_phase,
+        Tiled {
+            base: decomposer_for(strategy, palette, &options)?,
+            tiles,
+            matrix,
+            options,
+            _phantom: PhantomData::<fn(&T)>,
+        },
+    )
+}
+
+type BoxedDecomposer<P> = Box<dyn Decomposer<f32, Input = P> + Send + Sync>;
+
+/// Strategy of one [`Tiled`] region, before noise.
+type TileStrategy<P> = Noiseless<BoxedDecomposer<P>, fn(P) -> P, P>;
+
+/// [`WithNoise`] for [`tiled_ditherer_with`].
+struct Tiled<P, M, Marker> {
+    base: BoxedDecomposer<P>,
+    tiles: Vec<(Region, BoxedDecomposer<P>)>,
+    matrix: M,
+    options: FactoryOptions,
+    _phantom: PhantomData<Marker>,
+}
+
+impl<P, M, T> Tiled<P, M, fn(&T)>
+where
+    P: DecomposerInputColor + 'static,
+    M: DiffusionMatrix + Send + Sync + 'static,
+    T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
+{
+    /// Bundle the regions' strategies, each passed through `finish`.
+    fn bundle<S>(
+        self,
+        finish: impl Fn(TileStrategy<P>) -> S,
+    ) -> Box<dyn DynResumableDitherer<T> + Send + Sync>
+    where
+        S: PixelStrategy<Source = P, Target = usize> + Send + Sync + 'static,
+        S::QuantizationError: ErrorBytes,
+    {
+        let options = &self.options;
+        let strategy =
+            |decomposer| finish(configured(decomposer, core::convert::identity, options));
+        let tiles = self
+            .tiles
+            .into_iter()
+            .map(|(region, decomposer)| (region, strategy(decomposer)))
+            .collect();
+        let tiled = TiledStrategy::new(strategy(self.base), tiles);
+        Box::new(bundled(tiled, self.matrix, options))
+    }
+}
+
+impl<P, M, T> WithNoise for Tiled<P, M, fn(&T)>
+where
+    P: DecomposerInputColor + 'static,
+    M: DiffusionMatrix + Send + Sync + 'static,
+    T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
+{
+    type Output = Box<dyn DynResumableDitherer<T> + Send + Sync>;
+
+    fn build<N>(self, noise_fn: Option<N>) -> Result<Self::Output, FactoryError>
+    where
+        N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
+    {
+        Ok(match noise_fn {
+            Some(noise) => {
+                let noise = Arc::new(noise);
+                self.bundle(|strategy| {
+                    let noise = Arc::clone(&noise);
+                    strategy.with_noise(move |x, y| noise(x, y))
+                })
+            }
+            None => self.bundle(|strategy| strategy),
+        })
     }
 }
 
@@ -261,14 +1062,14 @@ where
 /// pixel type `P`; the palette entry type is fixed to `[u8; 3]` since
 /// that's what the [`Palette`] enum's slice accessor returns.
 ///
-/// Returns `Box<dyn DynDitherer<T> + Send + Sync>` — see
+/// Returns `Box<dyn DynResumableDitherer<T> + Send + Sync>` — see
 /// [`decompose_ditherer`] for the rationale.
 pub fn parse_decompose_ditherer<P, T>(
     strategy: &str,
     noise: &str,
     palette: &str,
     diffuse: &str,
-) -> Result<Box<dyn DynDitherer<T> + Send + Sync>, FactoryError>
+) -> Result<Box<dyn DynResumableDitherer<T> + Send + Sync>, FactoryError>
 where
     P: DecomposerInputColor + 'static,
     T: ImageSize + ImageReader<P> + ImageWriter<usize> + ?Sized + 'static,
@@ -277,10 +1078,290 @@ where
     let noise: NoiseSource = noise.parse().map_err(FactoryError::InvalidNoise)?;
     let palette: Palette = palette.parse().map_err(FactoryError::InvalidPalette)?;
     let diffuse: DiffuseMethod = diffuse.parse().map_err(FactoryError::InvalidDiffuse)?;
-    decompose_ditherer::<P, [u8; 3], T>(
-        strategy,
-        noise,
-        palette.as_rgb_slice(),
-        diffuse.to_matrix(),
-    )
+    let matrix = diffuse
+        .try_to_matrix()
+        .ok_or(FactoryError::ImageDependentDiffusion)?;
+    decompose_ditherer::<P, [u8; 3], T>(strategy, noise, palette.as_rgb_slice(), matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// [`WithNoise`] that samples the noise over a 32×32 block.
+    struct Sample;
+
+    impl WithNoise for Sample {
+        type Output = Vec<f32>;
+
+        fn build<N>(self, noise_fn: Option<N>) -> Result<Vec<f32>, FactoryError>
+        where
+            N: Fn(usize, usize) -> f32 + Send + Sync + 'static,
+        {
+            let noise = noise_fn.ok_or(FactoryError::DecomposerBuildFailed)?;
+            Ok((0..32 * 32).map(|i| noise(i % 32, i / 32)).collect())
+        }
+    }
+
+    #[test]
+    fn noise_offset_by_a_bayer_tile_repeats_the_pattern() {
+        let sample = |noise: &str, offset| {
+            resolve_noise(noise.parse().unwrap(), offset, false, Sample).unwrap()
+        };
+        let plain = sample("bayer:3", [0, 0]);
+        assert_eq!(sample("bayer:3", [8, 0]), plain);
+        assert_eq!(sample("bayer:3", [16, 24]), plain);
+        assert_ne!(sample("bayer:3", [3, 0]), plain);
+        assert_ne!(sample("bayer:3", [0, 4]), plain);
+        // Non-periodic noise shifts by exactly the offset.
+        let ign = sample("ign", [0, 0]);
+        let shifted = sample("ign", [1, 2]);
+        assert_eq!(shifted[0], ign[2 * 32 + 1]);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn matrix_noise_loads_a_bayer_image() {
+        extern crate std;
+        let bayer = image::GrayImage::from_fn(4, 4, |x, y| {
+            image::Luma([(crate::noise::bayer_rank(x as usize, y as usize, 2) * 17) as u8])
+        });
+        let path = std::env::temp_dir().join(alloc::format!(
+            "epd-dither-bayer-{}.png",
+            std::process::id()
+        ));
+        bayer.save(&path).unwrap();
+        let noise = alloc::format!("matrix:{}", path.display());
+        let matrix = resolve_noise(noise.parse().unwrap(), [0, 0], false, Sample);
+        std::fs::remove_file(&path).unwrap();
+        let expected = resolve_noise(NoiseSource::Bayer(Some(2)), [0, 0], false, Sample);
+        assert_eq!(matrix.unwrap(), expected.unwrap());
+    }
+
+    /// A 32×32 image of one colour, recording the indices written.
+    struct Flat([u8; 3], Vec<usize>);
+
+    impl ImageSize for Flat {
+        fn width(&self) -> usize {
+            32
+        }
+        fn height(&self) -> usize {
+            32
+        }
+    }
+
+    impl ImageReader<[u8; 3]> for Flat {
+        fn get_pixel(&self, _: usize, _: usize) -> [u8; 3] {
+            self.0
+        }
+    }
+
+    impl ImageWriter<usize> for Flat {
+        fn put_pixel(&mut self, x: usize, y: usize, index: usize) {
+            self.1[y * 32 + x] = index;
+        }
+    }
+
+    #[test]
+    fn rejects_non_causal_matrices() {
+        use crate::dither::diffusion_matrix::{FLOYD_STEINBERG, RefDiffusionMatrix};
+        let build = |matrix| {
+            decompose_ditherer::<[u8; 3], _, Flat>(
+                "naive-mix".parse().unwrap(),
+                NoiseSource::None,
+                &crate::palette::SPECTRA6,
+                matrix,
+            )
+            .err()
+        };
+        // Half the error back to the pixel on the left.
+        let backwards = RefDiffusionMatrix(2, &[(1, 0, 1), (-1, 0, 1)], true);
+        assert_eq!(build(backwards), Some(FactoryError::NonCausalMatrix));
+        assert_eq!(build(FLOYD_STEINBERG), None);
+    }
+
+    #[test]
+    fn compactness_needs_a_naive_strategy_and_a_valid_value() {
+        let build = |strategy: &str, compactness| {
+            let options = FactoryOptions {
+                compactness,
+                ..FactoryOptions::default()
+            };
+            decomposer_for::<[u8; 3], _>(
+                strategy.parse().unwrap(),
+                &crate::palette::SPECTRA6,
+                &options,
+            )
+            .err()
+        };
+        assert_eq!(build("naive-mix", 0.5), None);
+        assert_eq!(build("octahedron-closest", 0.0), None);
+        assert_eq!(
+            build("octahedron-closest", 0.5),
+            Some(FactoryError::CompactnessUnused)
+        );
+        assert_eq!(
+            build("naive-mix", -0.5),
+            Some(FactoryError::InvalidCompactness)
+        );
+        assert_eq!(
+            build("naive-mix", f32::NAN),
+            Some(FactoryError::InvalidCompactness)
+        );
+    }
+
+    #[test]
+    fn string_entry_point_refuses_adaptive_diffusion() {
+        let build = |diffuse| {
+            parse_decompose_ditherer::<[u8; 3], Flat>("naive-mix", "none", "spectra6", diffuse)
+                .err()
+        };
+        assert_eq!(
+            build("adaptive"),
+            Some(FactoryError::ImageDependentDiffusion)
+        );
+        assert_eq!(build("sierra"), None);
+    }
+
+    #[test]
+    fn row_phase_shifts_odd_rows_by_half_a_tile() {
+        let sample = |noise: &str, row_phase| {
+            resolve_noise(noise.parse().unwrap(), [0, 0], row_phase, Sample).unwrap()
+        };
+        let (plain, shifted) = (sample("bayer:3", false), sample("bayer:3", true));
+        for y in 0..32 {
+            for x in 0..28 {
+                let expected = if y % 2 == 1 { x + 4 } else { x };
+                assert_eq!(shifted[y * 32 + x], plain[y * 32 + expected]);
+            }
+        }
+        // No tile, no shift.
+        assert_eq!(sample("ign", true), sample("ign", false));
+    }
+
+    /// Correlation between vertically adjacent pixels of a flat `level`
+    /// dithered in black and white with ordered `noise` alone.
+    #[cfg(feature = "image")]
+    fn row_correlation(noise: &str, level: u8, noise_row_phase: bool) -> f32 {
+        let ditherer = decompose_ditherer_with::<[u8; 3], _, Flat>(
+            "grayscale".parse().unwrap(),
+            noise.parse().unwrap(),
+            &[[0, 0, 0], [255, 255, 255]],
+            crate::dither::diffusion_matrix::NO_DIFFUSE,
+            &FactoryOptions {
+                noise_row_phase,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut image = Flat([level; 3], alloc::vec![0; 32 * 32]);
+        ditherer.dyn_dither_into(&mut image);
+        let values: Vec<f32> = image.1.iter().map(|&i| i as f32).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance =
+            values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / values.len() as f32;
+        let covariance = (32..32 * 32)
+            .map(|i| (values[i] - mean) * (values[i - 32] - mean))
+            .sum::<f32>()
+            / (32 * 31) as f32;
+        covariance / variance
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn row_phase_decorrelates_rows_of_a_tile() {
+        for level in [80, 128, 170] {
+            let plain = row_correlation("blue", level, false);
+            let shifted = row_correlation("blue", level, true);
+            assert!(
+                shifted.abs() < plain.abs() / 2.0,
+                "level {level}: {shifted} vs {plain}"
+            );
+        }
+    }
+
+    #[test]
+    fn neutral_lock_dithers_gray_with_black_and_white() {
+        let used = |neutral_lock| {
+            let ditherer = decompose_ditherer_with::<[u8; 3], _, Flat>(
+                "naive-mix".parse().unwrap(),
+                NoiseSource::InterleavedGradient,
+                &crate::palette::SPECTRA6,
+                crate::dither::diffusion_matrix::FLOYD_STEINBERG,
+                &FactoryOptions {
+                    neutral_lock,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let mut used = [false; 6];
+            for level in [64, 128, 192] {
+                let mut image = Flat([level; 3], alloc::vec![0; 32 * 32]);
+                ditherer.dyn_dither_into(&mut image);
+                for index in image.1 {
+                    used[index] = true;
+                }
+            }
+            used
+        };
+        assert!(used(None)[2..].iter().any(|&u| u));
+        assert_eq!(used(Some(0.02)), [true, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn identity_ink_gamma_reproduces_the_default() {
+        let indices = |ink_gamma: Vec<f32>| {
+            let ditherer = decompose_ditherer_with::<[u8; 3], _, Flat>(
+                "naive-mix".parse().unwrap(),
+                NoiseSource::InterleavedGradient,
+                &crate::palette::SPECTRA6,
+                crate::dither::diffusion_matrix::FLOYD_STEINBERG,
+                &FactoryOptions {
+                    ink_gamma,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let mut image = Flat([150, 90, 40], alloc::vec![0; 32 * 32]);
+            ditherer.dyn_dither_into(&mut image);
+            image.1
+        };
+        let plain = indices(Vec::new());
+        assert_eq!(indices(alloc::vec![1.0; 6]), plain);
+        assert_ne!(indices(alloc::vec![3.0, 1.0, 1.0, 1.0, 1.0, 1.0]), plain);
+    }
+
+    #[test]
+    fn duplicate_palette_entries_are_reported() {
+        let mut palette = crate::palette::SPECTRA6;
+        palette[5] = palette[2];
+        for strategy in ["octahedron-closest", "naive-mix", "dominant-texture"] {
+            let result = decomposer_for::<[u8; 3], _>(
+                strategy.parse().unwrap(),
+                &palette,
+                &FactoryOptions::default(),
+            );
+            assert_eq!(
+                result.err(),
+                Some(FactoryError::DuplicateColors { indices: [2, 5] }),
+                "{strategy}"
+            );
+        }
+        let gray = [[0, 0, 0], [128, 128, 128], [128, 128, 128], [255, 255, 255]];
+        let result = decomposer_for::<[u8; 3], _>(
+            DecomposeStrategy::GrayPureSpread(0.0),
+            &gray,
+            &FactoryOptions::default(),
+        );
+        assert_eq!(
+            result.err(),
+            Some(FactoryError::DuplicateColors { indices: [1, 2] })
+        );
+        // The decomposers refuse nearly coincident colours on their own.
+        let mut points = crate::palette::SPECTRA6.map(|c| c.to_point());
+        points[5] = points[2] + nalgebra::Vector3::new(1e-5, 0.0, 0.0);
+        assert_eq!(find_duplicate(&points), Some([2, 5]));
+        assert!(OctahedronDecomposer::new(&points).is_none());
+        assert!(NaiveDecomposer::new(&points).is_none());
+    }
 }